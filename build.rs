@@ -13,6 +13,8 @@ fn main() -> Result<()> {
   let manifest_dir =
     env::var_os("CARGO_MANIFEST_DIR").context("CARGO_MANIFEST_DIR variable not set")?;
   let pkg_version = env::var("CARGO_PKG_VERSION").context("CARGO_PKG_VERSION variable not set")?;
+  let target = env::var("TARGET").context("TARGET variable not set")?;
+  println!("cargo:rustc-env=TARGET={target}");
 
   if let Some(git_rev) = git_revision_auto(manifest_dir)? {
     println!("cargo:rustc-env=VERSION={pkg_version} ({git_rev})");
@@ -0,0 +1,258 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Renaming of the members of zip and tar archives.
+//!
+//! Archives are never extracted. Instead, they are rewritten with the
+//! renamed members, copying the data of all members verbatim.
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::fs::metadata;
+use std::fs::File;
+use std::path::Path;
+use std::path::PathBuf;
+
+use tar::Archive as TarArchive;
+use tar::Builder as TarBuilder;
+use tar::EntryType;
+
+use tempfile::Builder;
+use tempfile::NamedTempFile;
+
+use zip::ZipArchive;
+use zip::ZipWriter;
+
+use crate::error::Context as _;
+use crate::error::Error;
+use crate::error::Result;
+
+
+/// The kind of an archive.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Kind {
+  /// A zip archive.
+  Zip,
+  /// An uncompressed tar archive.
+  Tar,
+}
+
+impl Kind {
+  /// Determine the kind of the archive at `path` based on its
+  /// extension.
+  pub fn detect(path: &Path) -> Result<Self> {
+    let ext = path
+      .extension()
+      .map(|ext| ext.to_ascii_lowercase())
+      .unwrap_or_default();
+    match ext.to_str() {
+      Some("zip") => Ok(Self::Zip),
+      Some("tar") => Ok(Self::Tar),
+      _ => Err(Error::Invalid(format!(
+        "`{}` is not a supported archive; expected a `.zip` or `.tar` file",
+        path.display()
+      ))),
+    }
+  }
+}
+
+
+/// Open the archive at `path` for reading.
+fn open(path: &Path) -> Result<File> {
+  File::open(path).with_context(|| format!("failed to open archive `{}`", path.display()))
+}
+
+
+/// Retrieve the paths of all regular file members of the archive at
+/// `path`, in archive order.
+///
+/// This function blocks.
+pub fn members(path: &Path) -> Result<Vec<PathBuf>> {
+  let file = open(path)?;
+  let context = || format!("failed to read archive `{}`", path.display());
+
+  let mut members = Vec::new();
+  match Kind::detect(path)? {
+    Kind::Zip => {
+      let mut archive = ZipArchive::new(file).with_context(context)?;
+      for idx in 0..archive.len() {
+        let member = archive.by_index_raw(idx).with_context(context)?;
+        if member.is_file() {
+          let () = members.push(PathBuf::from(member.name()));
+        }
+      }
+    },
+    Kind::Tar => {
+      let mut archive = TarArchive::new(file);
+      for entry in archive.entries().with_context(context)? {
+        let entry = entry.with_context(context)?;
+        if entry.header().entry_type().is_file() {
+          let path = entry.path().with_context(context)?;
+          let () = members.push(path.into_owned());
+        }
+      }
+    },
+  }
+  Ok(members)
+}
+
+
+/// Determine the name of the zip member `name` after renaming it
+/// according to `renames`, if it is to be renamed.
+///
+/// The result is derived from the original name directly, so as not
+/// to introduce platform specific path separators.
+fn zip_name(name: &str, renames: &HashMap<PathBuf, PathBuf>) -> Result<Option<String>> {
+  let Some(dst) = renames.get(Path::new(name)) else {
+    return Ok(None)
+  };
+  let file = dst
+    .file_name()
+    .and_then(|file| file.to_str())
+    .with_context(|| format!("`{}` is not a valid zip member name", dst.display()))?;
+  let dir = name.rfind('/').map(|idx| &name[..=idx]).unwrap_or_default();
+  Ok(Some(format!("{dir}{file}")))
+}
+
+
+/// Copy the zip archive `src` to `dst`, renaming members along the way.
+fn rewrite_zip(
+  src: File,
+  dst: &mut NamedTempFile,
+  renames: &HashMap<PathBuf, PathBuf>,
+) -> Result<()> {
+  let mut archive = ZipArchive::new(src).context("failed to read zip archive")?;
+  let mut writer = ZipWriter::new(dst);
+  let mut names = HashSet::with_capacity(archive.len());
+
+  for idx in 0..archive.len() {
+    let member = archive
+      .by_index_raw(idx)
+      .context("failed to read zip archive member")?;
+    let name = zip_name(member.name(), renames)?;
+    let final_name = name.as_deref().unwrap_or(member.name()).to_string();
+    if !names.insert(final_name.clone()) {
+      return Err(Error::Invalid(format!(
+        "archive would contain multiple members named `{final_name}`"
+      )))
+    }
+
+    let () = match name {
+      Some(name) => writer
+        .raw_copy_file_rename(member, name)
+        .context("failed to write zip archive member")?,
+      None => writer
+        .raw_copy_file(member)
+        .context("failed to write zip archive member")?,
+    };
+  }
+
+  let () = writer.set_raw_comment(archive.comment().into());
+  let _dst = writer.finish().context("failed to finish zip archive")?;
+  Ok(())
+}
+
+
+/// Copy the tar archive `src` to `dst`, renaming members along the way.
+fn rewrite_tar(
+  src: File,
+  dst: &mut NamedTempFile,
+  renames: &HashMap<PathBuf, PathBuf>,
+) -> Result<()> {
+  let mut archive = TarArchive::new(src);
+  let mut builder = TarBuilder::new(dst);
+  let mut names = HashSet::new();
+
+  for entry in archive.entries().context("failed to read tar archive")? {
+    let mut entry = entry.context("failed to read tar archive member")?;
+    let mut header = entry.header().clone();
+    let path = entry
+      .path()
+      .context("tar archive member has an invalid path")?
+      .into_owned();
+    let path = renames.get(&path).cloned().unwrap_or(path);
+    if !header.entry_type().is_dir() && !names.insert(path.clone()) {
+      return Err(Error::Invalid(format!(
+        "archive would contain multiple members named `{}`",
+        path.display()
+      )))
+    }
+
+    // Paths are written anew in all cases, because long paths are not
+    // stored in the header itself.
+    let () = match header.entry_type() {
+      EntryType::Symlink | EntryType::Link => {
+        let target = entry
+          .link_name()
+          .context("link member has an invalid target")?
+          .context("link member lacks a target")?
+          .into_owned();
+        builder
+          .append_link(&mut header, &path, target)
+          .context("failed to write tar archive member")?
+      },
+      EntryType::XGlobalHeader => builder
+        .append(&header, &mut entry)
+        .context("failed to write tar archive member")?,
+      _ => builder
+        .append_data(&mut header, &path, &mut entry)
+        .context("failed to write tar archive member")?,
+    };
+  }
+
+  let () = builder.finish().context("failed to finish tar archive")?;
+  Ok(())
+}
+
+
+/// Rewrite the archive at `path`, renaming the members in `renames` to
+/// their respective destinations.
+///
+/// Keys of `renames` are member paths as reported by [`members`]. Only
+/// the file name of a member may change. The archive is rewritten to a
+/// temporary file first, which atomically replaces the original once
+/// complete.
+///
+/// This function blocks.
+pub fn rewrite(path: &Path, renames: &HashMap<PathBuf, PathBuf>) -> Result<()> {
+  for (src, dst) in renames {
+    if src.parent() != dst.parent() {
+      return Err(Error::Invalid(format!(
+        "`{}` must not move member `{}` to a different directory",
+        dst.display(),
+        src.display()
+      )))
+    }
+  }
+
+  let kind = Kind::detect(path)?;
+  let src = open(path)?;
+  let dir = path
+    .parent()
+    .filter(|dir| !dir.as_os_str().is_empty())
+    .unwrap_or_else(|| Path::new("."));
+  let mut tmp = Builder::new()
+    .prefix(".batch-rename-")
+    .tempfile_in(dir)
+    .with_context(|| format!("failed to create temporary file in `{}`", dir.display()))?;
+
+  let context = || format!("failed to rewrite archive `{}`", path.display());
+  let () = match kind {
+    Kind::Zip => rewrite_zip(src, &mut tmp, renames),
+    Kind::Tar => rewrite_tar(src, &mut tmp, renames),
+  }
+  .with_context(context)?;
+
+  let permissions = metadata(path)
+    .with_context(|| format!("failed to retrieve metadata of `{}`", path.display()))?
+    .permissions();
+  let () = tmp
+    .as_file()
+    .set_permissions(permissions)
+    .with_context(context)?;
+  let () = tmp.as_file().sync_all().with_context(context)?;
+  let _file = tmp
+    .persist(path)
+    .with_context(|| format!("failed to replace archive `{}`", path.display()))?;
+  Ok(())
+}
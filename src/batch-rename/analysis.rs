@@ -0,0 +1,92 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Reporting of statistics about the new names of files.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use batch_renamer::output::Format;
+use batch_renamer::plan::analyze;
+use batch_renamer::plan::LONG_NAME;
+
+use crate::tell;
+
+
+/// The number of characters covered by each bucket of the histogram.
+const BUCKET: usize = 10;
+/// The width of the longest bar of the histogram.
+const BAR_WIDTH: usize = 40;
+/// The line standing in for empty buckets of the histogram.
+const GAP: &str = "          …";
+
+
+/// Render the distribution of name lengths in `lengths` as a histogram,
+/// one line per bucket.
+fn histogram(lengths: &BTreeMap<usize, usize>) -> Vec<String> {
+  let mut buckets = BTreeMap::<usize, usize>::new();
+  for (length, count) in lengths {
+    // Lengths start at one, so that buckets cover 1-10, 11-20, ...
+    *buckets
+      .entry(length.saturating_sub(1) / BUCKET)
+      .or_default() += count;
+  }
+  let max = buckets.values().copied().max().unwrap_or(0);
+
+  let mut lines = Vec::new();
+  let mut next = None;
+  for (bucket, count) in buckets {
+    // Runs of empty buckets are collapsed into a single line.
+    if next.is_some_and(|next| next != bucket) {
+      let () = lines.push(GAP.to_string());
+    }
+    let range = format!("{}-{}", bucket * BUCKET + 1, (bucket + 1) * BUCKET);
+    // Any non-empty bucket gets a bar, no matter how short.
+    let width = (count * BAR_WIDTH).div_ceil(max);
+    let () = lines.push(format!("  {range:>9} {} {count}", "#".repeat(width)));
+    next = Some(bucket + 1);
+  }
+  lines
+}
+
+
+/// Tell the user about the `paths` exhibiting an anomaly described by
+/// `what`, if any.
+fn anomaly<'p, I>(output: Format, what: &str, paths: I)
+where
+  I: ExactSizeIterator<Item = &'p PathBuf>,
+{
+  if paths.len() > 0 {
+    let () = tell(output, &format!("{} new name(s) {what}:", paths.len()));
+    for path in paths {
+      let () = tell(output, &format!("  {}", path.display()));
+    }
+  }
+}
+
+
+/// Analyze the new names of the files in `plan` and report the outcome
+/// to the user, as a histogram of their lengths followed by the
+/// anomalies found.
+pub(crate) fn report(plan: &[(PathBuf, PathBuf, PathBuf)], output: Format) {
+  let renames = plan
+    .iter()
+    .map(|(_, abs_src, dst)| (abs_src.clone(), dst.clone()))
+    .collect::<Vec<_>>();
+  let analysis = analyze(&renames);
+  let count = analysis.lengths.values().sum::<usize>();
+  let () = tell(output, &format!("Lengths of {count} new name(s):"));
+  for line in histogram(&analysis.lengths) {
+    let () = tell(output, &line);
+  }
+
+  let () = anomaly(output, "with an empty stem", analysis.empty_stems.iter());
+  let () = anomaly(
+    output,
+    &format!("with {LONG_NAME} or more characters"),
+    analysis.long_names.iter(),
+  );
+  for group in &analysis.duplicate_stems {
+    let () = anomaly(output, "sharing a stem", group.iter());
+  }
+}
@@ -0,0 +1,182 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Renaming of the members of an archive.
+
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::path::Path;
+use std::process::exit;
+use std::sync::Mutex;
+
+use anyhow::Context as _;
+use anyhow::Error;
+use anyhow::Result;
+
+use batch_renamer::archive::members;
+use batch_renamer::archive::rewrite;
+use batch_renamer::concurrency::buffered;
+use batch_renamer::output::Format;
+use batch_renamer::output::Status;
+use batch_renamer::output::Summary;
+use batch_renamer::propose;
+use batch_renamer::RenameOpts;
+
+use futures::stream;
+use futures::stream::StreamExt as _;
+use futures::TryStreamExt as _;
+
+use tokio::task::spawn_blocking;
+
+use crate::ensure_case_distinct;
+use crate::lock;
+use crate::prompt;
+use crate::report;
+use crate::review;
+use crate::review::Edit;
+use crate::show_summary;
+use crate::term::Input;
+use crate::theme::Theme;
+use crate::Response;
+use crate::Root;
+
+
+/// Rename the members of the archive at `path` using `command`.
+///
+/// Members are presented for confirmation (or, if `edit` is `true`,
+/// for review in a text editor) just like regular files, with responses
+/// read from the [`Input`] in `input` and prompts styled using its
+/// [`Theme`]; without `input` all renames are accepted.
+/// Accepted renames are only applied once all members have been
+/// processed, by rewriting the archive as a whole. If `check_case` is
+/// `true`, renames are refused if any two members would end up with
+/// names differing only in case. New names are communicated by
+/// `command` as per `opts.protocol`.
+pub(crate) async fn rename(
+  path: &Path,
+  command: &[OsString],
+  edit: bool,
+  check_case: bool,
+  opts: &RenameOpts,
+  input: Option<(&Input, Theme)>,
+  output: Format,
+) -> Result<()> {
+  let members = {
+    let path = path.to_path_buf();
+    spawn_blocking(move || members(&path)).await??
+  };
+
+  let planned = stream::iter(members).map(|member| async move {
+    let name = member
+      .file_name()
+      .with_context(|| format!("member `{}` does not have file name", member.display()))?;
+    let new = propose(name, command, opts).await?;
+    let dst = member.with_file_name(new);
+    Result::<_, Error>::Ok((member, dst))
+  });
+  let mut plan = buffered(planned, opts.plan_concurrency).boxed_local();
+  if check_case {
+    // All new names have to be known before they can be checked.
+    let members = plan.try_collect::<Vec<_>>().await?;
+    let () = ensure_case_distinct(members.iter().map(|(_, dst)| dst.as_path()))?;
+    plan = stream::iter(members.into_iter().map(Ok)).boxed_local();
+  }
+
+  let mut renames = Vec::new();
+  let mut interrupted = false;
+  let summary = Mutex::new(Summary::default());
+
+  if edit {
+    // Member paths are not subject to canonicalization and archives
+    // validate the new paths themselves.
+    let plan = plan
+      .map_ok(|(member, dst)| (member.clone(), member, dst))
+      .try_collect::<Vec<_>>()
+      .await?;
+    let () = renames.extend(
+      review::edit(plan, &Root::Anywhere)
+        .await?
+        .into_iter()
+        .map(|Edit { src, edited, .. }| (src, edited)),
+    );
+  } else {
+    let mut accept_all = input.is_none();
+
+    while let Some(result) = plan.next().await {
+      let (src, mut dst) = result?;
+      if src == dst {
+        let () = report(output, &summary, &src, &dst, Status::Unchanged, None)?;
+        continue
+      }
+
+      let accept = match input.filter(|_| !accept_all) {
+        None => true,
+        Some((input, theme)) => {
+          match prompt(output, &src, &[], &dst, None, None, input, theme).await? {
+            Response::Accept => true,
+            Response::Decline => false,
+            Response::AcceptAll => {
+              accept_all = true;
+              true
+            },
+            // Archives validate the new paths themselves.
+            Response::Edit(path) => {
+              dst = path;
+              true
+            },
+            // Members can't conflict with existing files.
+            Response::Resolve(_) | Response::Discard => unreachable!(),
+            Response::Quit => break,
+            // Nothing got written yet, so there is nothing to revert.
+            Response::Abort => {
+              let () = renames.clear();
+              break
+            },
+            Response::Interrupt => {
+              interrupted = true;
+              break
+            },
+          }
+        },
+      };
+
+      if accept {
+        let () = renames.push((src, dst));
+      } else {
+        let () = report(output, &summary, &src, &dst, Status::Declined, None)?;
+      }
+    }
+  }
+
+  if !renames.is_empty() {
+    let result = {
+      let path = path.to_path_buf();
+      let renames = renames.iter().cloned().collect::<HashMap<_, _>>();
+      spawn_blocking(move || rewrite(&path, &renames).map_err(Error::from)).await?
+    };
+
+    for (src, dst) in &renames {
+      let () = report(
+        output,
+        &summary,
+        src,
+        dst,
+        if result.is_ok() {
+          Status::Renamed
+        } else {
+          Status::Failed
+        },
+        result.as_ref().err(),
+      )?;
+    }
+    let () = result?;
+  }
+
+  let () = show_summary(output, &lock(&summary), false, false)?;
+  if interrupted {
+    // Mirror the conventional exit status of a process terminated by
+    // SIGINT.
+    exit(130)
+  }
+  Ok(())
+}
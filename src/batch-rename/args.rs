@@ -0,0 +1,1198 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::io::Write;
+use std::num::NonZeroU64;
+use std::num::NonZeroUsize;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use batch_renamer::change::Change;
+use batch_renamer::config::Config;
+use batch_renamer::config::Preset;
+use batch_renamer::conflict::ConflictPolicy;
+use batch_renamer::lint::Warning;
+use batch_renamer::locale::Locale;
+use batch_renamer::manifest::Direction;
+use batch_renamer::manifest::Identity;
+use batch_renamer::output::Format;
+use batch_renamer::sanitize::Normalization;
+use batch_renamer::schedule::Schedule;
+use batch_renamer::template::Template;
+use batch_renamer::traverse::age;
+#[cfg(unix)]
+use batch_renamer::traverse::group_id;
+#[cfg(unix)]
+use batch_renamer::traverse::user_id;
+use batch_renamer::Protocol;
+
+use clap::builder::PossibleValuesParser;
+use clap::error::ErrorKind;
+use clap::parser::ValueSource;
+use clap::ArgAction;
+use clap::CommandFactory as _;
+use clap::Error;
+use clap::FromArgMatches as _;
+use clap::Parser;
+use clap_complete::generate;
+use clap_complete::Shell;
+
+use crate::order::Order;
+use crate::theme::Theme;
+
+
+/// The arguments that do without a rename command.
+const COMMAND_ALTERNATIVES: [&str; 14] = [
+  "undo",
+  "verify",
+  "audit",
+  "recover",
+  "suggest",
+  "since",
+  "editor_protocol",
+  "template",
+  "sanitize",
+  "manifest",
+  "diff_names",
+  "apply_plan",
+  "wizard",
+  "check",
+];
+
+
+#[derive(Debug, Parser)]
+#[clap(version = env!("VERSION"))]
+pub struct Args {
+  /// The command (and arguments) to use for renaming the file(s).
+  ///
+  /// The name of the file is passed as last argument, unless arguments
+  /// contain `{}` placeholders, which are replaced with it instead, as
+  /// in `mv {} prefix_{}`.
+  ///
+  /// The command's environment contains the file's 1-based position in
+  /// the batch (BR_INDEX), the batch's size (BR_TOTAL), the file's
+  /// original path (BR_ORIG_PATH), and its directory (BR_PARENT_DIR).
+  ///
+  /// Defaults to the command configured in the closest
+  /// `.batch-renamer.toml` file found in the current directory or its
+  /// ancestors, if any. Said file may also provide the --protocol to
+  /// use with it as well as --include and --exclude patterns.
+  #[clap(required_unless_present_any = COMMAND_ALTERNATIVES)]
+  pub command: Vec<OsString>,
+  /// The files to rename.
+  #[clap(last = true)]
+  pub files: Vec<PathBuf>,
+  /// Read the files to rename from the given file, one per line, in
+  /// addition to those provided on the command line.
+  ///
+  /// Use `-` to read from stdin. Prompts are then read from the
+  /// controlling terminal.
+  #[clap(long, value_name = "FILE")]
+  pub files_from: Option<PathBuf>,
+  /// Expect files read via --files-from to be separated by NUL
+  /// characters instead of newlines, as produced by `find -print0`.
+  #[clap(short = '0', long, requires = "files_from")]
+  pub null: bool,
+  /// Read the responses to prompts from the given file instead of
+  /// asking the user, for driving the interactive flow from scripts.
+  ///
+  /// Use `-` to read from stdin. Each line holds one response, with its
+  /// first character being the key pressed (e.g., `y`, `n`, or `e`) and
+  /// an empty line standing for the enter key. The replacement for the
+  /// path being edited may follow an `e` on the same line, as in
+  /// `e new-name.txt`, or be provided on the next line. Running out of
+  /// responses is treated as an interruption.
+  #[clap(long, value_name = "FILE", conflicts_with = "yes")]
+  pub answers_from: Option<PathBuf>,
+  /// Rename the files newly added to the index of the git repository
+  /// containing the current directory, including those staged under a
+  /// new name, in addition to those provided on the command line.
+  ///
+  /// Meant for usage in pre-commit hooks, as in `batch-rename --staged
+  /// --check`, checking the names of files before they are committed.
+  /// Renamed files are not staged again.
+  #[clap(long, conflicts_with_all = ["manifest", "undo", "verify", "editor_protocol"])]
+  pub staged: bool,
+  /// Recursively rename the files inside of provided directories.
+  #[clap(short, long)]
+  pub recursive: bool,
+  /// Rename provided directories themselves, as `mv` would, instead of
+  /// refusing to work with them.
+  ///
+  /// The rename command sees the name of the directory and, while
+  /// determining its new name, operates on an empty stand-in
+  /// directory. Directories cannot be backed up via --backup, as that
+  /// would mean copying their entire contents.
+  #[clap(
+    short,
+    long,
+    conflicts_with_all = ["recursive", "batch_preview", "backup", "editor_protocol"]
+  )]
+  pub directory: bool,
+  /// Only rename files matching the given glob pattern during
+  /// recursive traversal.
+  ///
+  /// Patterns containing a path separator are matched against paths
+  /// relative to the directory being traversed, while all others are
+  /// matched against file names. May be provided multiple times.
+  #[clap(long, value_name = "GLOB", requires = "recursive")]
+  pub include: Vec<String>,
+  /// Exclude files and directories matching the given glob pattern
+  /// during recursive traversal.
+  ///
+  /// May be provided multiple times.
+  #[clap(long, value_name = "GLOB", requires = "recursive")]
+  pub exclude: Vec<String>,
+  /// The maximum depth to descend to during recursive traversal.
+  #[clap(long, value_name = "DEPTH", requires = "recursive")]
+  pub max_depth: Option<usize>,
+  /// Only rename files owned by the given user, by name or numeric ID,
+  /// during recursive traversal.
+  #[cfg(unix)]
+  #[clap(long, value_name = "USER", value_parser = user_id, requires = "recursive")]
+  pub owner: Option<u32>,
+  /// Only rename files belonging to the given group, by name or numeric
+  /// ID, during recursive traversal.
+  #[cfg(unix)]
+  #[clap(long, value_name = "GROUP", value_parser = group_id, requires = "recursive")]
+  pub group: Option<u32>,
+  /// Only rename files last modified longer ago than the given age
+  /// during recursive traversal.
+  ///
+  /// The age is a number followed by one of the units `s`, `m`, `h`,
+  /// `d`, and `w`, as in `30d` for 30 days.
+  #[clap(long, value_name = "AGE", value_parser = age, requires = "recursive")]
+  pub older_than: Option<Duration>,
+  /// Rename the directories encountered during recursive traversal as
+  /// well, after the files and directories inside of them.
+  ///
+  /// Renames are applied deepest first, so that renaming a directory
+  /// never invalidates the path of a file yet to be renamed. The
+  /// provided directories themselves are not renamed.
+  #[clap(long, requires = "recursive", conflicts_with = "transactional")]
+  pub dirs: bool,
+  /// The order in which to process files: `name`, `mtime` (oldest
+  /// first), `size` (smallest first), or `none`, keeping the order in
+  /// which they were provided.
+  ///
+  /// The order is reflected in previews and prompts as well as in the
+  /// numbering of files by the `{counter}` template token.
+  #[clap(
+    long,
+    value_name = "ORDER",
+    default_value = "none",
+    conflicts_with_all = ["manifest", "undo", "verify", "editor_protocol"]
+  )]
+  pub sort: Order,
+  /// Reverse the order in which files are processed.
+  #[clap(long, conflicts_with_all = ["manifest", "undo", "verify", "editor_protocol"])]
+  pub reverse: bool,
+  /// Rename the members of the given zip or tar archive instead of
+  /// files.
+  ///
+  /// The archive is not extracted, but rewritten with the accepted
+  /// renames applied once all members have been processed. Renames of
+  /// archive members are not journaled.
+  #[cfg(feature = "archive")]
+  #[clap(
+    long,
+    value_name = "ARCHIVE",
+    conflicts_with_all = ["files", "files_from", "recursive", "template", "sanitize", "wizard", "undo", "editor_protocol", "direct", "tui", "dry_run", "staged", "then_args", "changed_only", "batch"]
+  )]
+  pub archive: Option<PathBuf>,
+  /// Rename files according to the given template instead of using a
+  /// rename command.
+  ///
+  /// Tokens enclosed in curly braces are replaced with information
+  /// about each file: `{stem}`, `{ext}` (including the dot), `{name}`,
+  /// `{size}`, `{mtime}` (optionally with a strftime style format, as
+  /// in `{mtime:%Y-%m-%d}`), and `{counter}` (numbering the files of
+  /// the batch starting at one, optionally with a width, as in
+  /// `{counter:03}`). `{f2}` refers to the second number in the file
+  /// stem. Integer arithmetic may be used on `{counter}`, `{size}`,
+  /// and numbers of the stem, as in `{counter + 100}` or `{f1 * 2:03}`.
+  /// Appending `|upper` or `|lower` to a token changes the case of its
+  /// value, as in `{stem|lower}`. `|date` and `|number` normalize the
+  /// first date or number in a value, as in `{stem|date}` turning
+  /// `14. März 2023` into `2023-03-14` or `{stem|number}` turning
+  /// `1.234,5` into `1234.5`. If built with the `metadata` feature,
+  /// `{exif.date}`, `{exif.model}`, `{id3.title}`, `{id3.artist}`, and
+  /// `{id3.track}` are available as well. Functions defined in the
+  /// `[functions]` table of `.batch-renamer.toml` may be called, as in
+  /// `{shortdate()}` or `{tag(raw, 03)}`. Renames are applied directly.
+  #[clap(
+    long,
+    value_name = "TEMPLATE",
+    conflicts_with_all = ["command", "editor_protocol"]
+  )]
+  pub template: Option<Template>,
+  /// Clean up file names instead of using a rename command.
+  ///
+  /// Runs of whitespace are replaced with a single underscore, and
+  /// control characters and characters with special meaning to shells
+  /// (such as `$`, `*`, or quotes) are removed, as are leading dashes
+  /// and trailing dots. Names reserved for devices on Windows (such as
+  /// `con`) get an underscore appended to their stem. Names are brought
+  /// into the Unicode normalization form selected via --normalize.
+  /// Renames are applied directly.
+  #[clap(long, conflicts_with_all = ["command", "template", "editor_protocol"])]
+  pub sanitize: bool,
+  /// Lowercase names when sanitizing them.
+  #[clap(
+    long,
+    requires = "sanitize",
+    conflicts_with_all = ["command", "template", "editor_protocol"]
+  )]
+  pub lowercase: bool,
+  /// The Unicode normalization form to bring sanitized names into:
+  /// `nfc` (composed, as common on Linux and Windows) or `nfd`
+  /// (decomposed, as used by macOS).
+  ///
+  /// Latin letters with diacritics are normalized; other characters
+  /// are left alone.
+  #[clap(
+    long,
+    value_name = "FORM",
+    default_value = "nfc",
+    requires = "sanitize",
+    conflicts_with_all = ["command", "template", "editor_protocol"]
+  )]
+  pub normalize: Normalization,
+  /// Interactively build a template for renaming the files, based on
+  /// the structure detected in a sample of them.
+  ///
+  /// Once the template is accepted, files are renamed as with
+  /// --template.
+  #[clap(
+    long,
+    conflicts_with_all = ["command", "template", "sanitize", "editor_protocol", "undo", "verify", "manifest"]
+  )]
+  pub wizard: bool,
+  /// The locale to use for changing the case of template tokens, for
+  /// interpreting ambiguous dates and numbers (such as `03/04/2023` and
+  /// `1,234`), and for the names of months and days in time formats,
+  /// such as `de-DE`.
+  ///
+  /// Defaults to the POSIX locale, independent of the environment.
+  #[clap(
+    long,
+    value_name = "LOCALE",
+    requires = "template",
+    conflicts_with = "command"
+  )]
+  pub locale: Option<Locale>,
+  /// Continue the numbering of files already present in destination
+  /// directories, starting `{counter}` after the highest value found
+  /// among files matching the template.
+  #[clap(long, requires = "template", conflicts_with = "command")]
+  pub continue_numbering: bool,
+  /// Rename symbolic links themselves instead of the files they refer
+  /// to.
+  #[clap(short = 'P', long)]
+  pub no_dereference: bool,
+  /// Restrict renamed files to the given directory and its
+  /// subdirectories.
+  ///
+  /// By default, files may only be moved within (subdirectories of)
+  /// the directory they reside in.
+  #[clap(long, value_name = "DIR")]
+  pub root: Option<PathBuf>,
+  /// Allow files to be moved to arbitrary directories.
+  #[clap(long, conflicts_with = "root")]
+  pub no_confine: bool,
+  /// Make it impossible for the program and the processes it spawns to
+  /// modify files outside of the directories files get renamed in, by
+  /// means of Landlock.
+  ///
+  /// Besides said directories (or the one provided via --root), only
+  /// the directory for temporary files, the journal, as well as the
+  /// directories containing the --log, --state, --manifest, and
+  /// --save-plan files stay writable, also for the rename command and
+  /// hooks.
+  #[cfg(all(target_os = "linux", feature = "landlock"))]
+  #[clap(long, conflicts_with_all = ["no_confine", "editor_protocol"])]
+  pub landlock: bool,
+  /// Take a read-only snapshot of the file systems files get renamed
+  /// on before applying any renames, as a way of rolling back the
+  /// session as a whole should --undo not suffice.
+  ///
+  /// Only Btrfs subvolumes and ZFS datasets are supported. Btrfs
+  /// snapshots are created in the `.snapshots` directory of the
+  /// respective subvolume. Snapshots are named after the session and
+  /// recorded in its journal, but never removed.
+  #[cfg(target_os = "linux")]
+  #[clap(long, conflicts_with_all = ["no_journal", "dry_run", "editor_protocol"])]
+  pub snapshot: bool,
+  /// Synchronize the names of files with the given manifest, listing
+  /// the authoritative paths of all files below the directory
+  /// containing it.
+  ///
+  /// Files are identified by their contents. Differences between the
+  /// manifest and the directory are reported and resolved as per
+  /// --sync.
+  #[clap(
+    long,
+    value_name = "FILE",
+    conflicts_with_all = ["command", "files", "files_from", "recursive", "template", "sanitize", "undo", "editor_protocol", "batch_preview"]
+  )]
+  pub manifest: Option<PathBuf>,
+  /// How to synchronize files with the manifest: `check` (only report
+  /// differences, failing if there are any), `files` (rename files to
+  /// match the manifest), `manifest` (update the manifest from the
+  /// directory), or `both` (rename files, then update the manifest).
+  #[clap(
+    long,
+    value_name = "DIRECTION",
+    default_value = "check",
+    requires = "manifest"
+  )]
+  pub sync: Direction,
+  /// Compare the names of the files below two directories, e.g., a
+  /// directory and a backup of it renamed on only one side, reporting
+  /// the files named differently below the second one.
+  ///
+  /// Files are paired up as per --pair-by. Use --reconcile for
+  /// renaming the files below the second directory to match the names
+  /// of their counterparts below the first.
+  #[clap(
+    long,
+    num_args = 2,
+    value_names = ["DIR_A", "DIR_B"],
+    conflicts_with_all = ["command", "files", "files_from", "recursive", "template", "sanitize", "manifest", "apply_plan", "undo", "editor_protocol", "batch_preview"]
+  )]
+  pub diff_names: Option<Vec<PathBuf>>,
+  /// How to pair up files compared via --diff-names: by their
+  /// `content` or by their `metadata`, i.e., size and modification
+  /// time, which is quicker but less reliable.
+  #[clap(
+    long,
+    value_name = "IDENTITY",
+    default_value = "content",
+    requires = "diff_names"
+  )]
+  pub pair_by: Identity,
+  /// Rename the files below the second directory compared via
+  /// --diff-names to match the names of their counterparts below the
+  /// first one.
+  ///
+  /// Renames are applied directly, but prompted for unless --yes is
+  /// provided. Combined with --dry-run, the plan can be saved via
+  /// --save-plan instead.
+  #[clap(long, requires = "diff_names")]
+  pub reconcile: bool,
+  /// Apply the renames of a plan saved via --save-plan instead of
+  /// determining new names.
+  ///
+  /// Renames are applied directly, but are otherwise treated like
+  /// those proposed by a rename command, i.e., they are prompted for
+  /// unless --yes is provided.
+  #[clap(
+    long,
+    value_name = "FILE",
+    conflicts_with_all = ["command", "files", "files_from", "staged", "recursive", "template", "sanitize", "wizard", "manifest", "undo", "editor_protocol", "batch_preview"]
+  )]
+  pub apply_plan: Option<PathBuf>,
+  /// Refuse to apply the plan provided via --apply-plan unless it
+  /// carries a valid signature made with the secret key belonging to
+  /// the given minisign public key.
+  ///
+  /// The signature is expected next to the plan, with `.minisig`
+  /// appended to its name, as created via --sign-plan or minisign
+  /// directly. Requires minisign.
+  #[clap(long, value_name = "PUBKEY", requires = "apply_plan")]
+  pub require_signed: Option<PathBuf>,
+  /// Revert the renames of a previous session instead of renaming
+  /// files.
+  ///
+  /// If no session ID is provided, the most recent session that has
+  /// not yet been undone is reverted.
+  #[clap(long, value_name = "SESSION", conflicts_with_all = ["command", "files", "files_from"])]
+  pub undo: Option<Option<String>>,
+  /// Verify that the files renamed in a previous session are still
+  /// present with unchanged contents, instead of renaming files.
+  ///
+  /// Only sessions recorded with --checksums can be verified. If no
+  /// session ID is provided, the most recent session that has not been
+  /// undone is verified.
+  #[clap(
+    long,
+    value_name = "SESSION",
+    conflicts_with_all = ["command", "files", "files_from", "undo", "manifest"]
+  )]
+  pub verify: Option<Option<String>>,
+  /// Check that the files renamed in a previous session are still
+  /// present at their new paths and absent from their old ones, instead
+  /// of renaming files.
+  ///
+  /// Unlike --verify, contents are not inspected, making this suitable
+  /// for spotting drift after restoring from a backup or syncing a
+  /// replica. If no session ID is provided, the most recent session
+  /// that has not been undone is audited.
+  #[clap(
+    long,
+    value_name = "SESSION",
+    conflicts_with_all = ["command", "files", "files_from", "undo", "verify", "manifest"]
+  )]
+  pub audit: Option<Option<String>>,
+  /// Recover a previous session that got interrupted while renaming
+  /// files, e.g., by a crash, instead of renaming files.
+  ///
+  /// Whether the renames in flight at the time got applied is
+  /// determined from where the files reside, after which the session
+  /// is reverted as a whole. If no session ID is provided, the most
+  /// recent interrupted session is recovered.
+  #[clap(
+    long,
+    value_name = "SESSION",
+    conflicts_with_all = ["command", "files", "files_from", "undo", "verify", "audit", "manifest"]
+  )]
+  pub recover: Option<Option<String>>,
+  /// Offer to repeat the most recent session that renamed files in the
+  /// current directory on the files that newly appeared in it since,
+  /// e.g., for renaming each import the same way.
+  ///
+  /// Files count as new if they changed after the session started and
+  /// are not the result of a recorded rename. The session's command or
+  /// template is repeated as if provided anew.
+  #[clap(
+    long,
+    conflicts_with_all = ["command", "files", "files_from", "undo", "verify", "audit", "recover", "manifest", "no_journal"]
+  )]
+  pub suggest: bool,
+  /// Replace this program with the most recent release, if it is newer,
+  /// instead of renaming files.
+  ///
+  /// The release's static binary is downloaded and verified against
+  /// its published checksum and minisign signature. Only batch-rename
+  /// is updated; print-rename is not part of releases and stays as is.
+  /// Requires `curl`, `sha256sum`, and `minisign`.
+  #[cfg(feature = "self-update")]
+  #[clap(long, exclusive = true)]
+  pub self_update: bool,
+  /// Print a script for completing arguments in the given shell
+  /// (`bash`, `zsh`, `fish`, `elvish`, or `powershell`) instead of
+  /// renaming files.
+  ///
+  /// Names of the built-in presets and of those configured at the time
+  /// are completed as well.
+  #[clap(long, value_name = "SHELL", exclusive = true)]
+  pub completions: Option<Shell>,
+  /// Instead of renaming, print systemd user units with the given name
+  /// for running this invocation unattended (as per --batch) whenever
+  /// the contents of the provided directories change.
+  ///
+  /// A `.path` unit watches the directories and triggers a `.service`
+  /// unit, which runs sandboxed, with write access limited to the
+  /// directories, the journal, and the --log and --state files. As the
+  /// service also gets triggered by the renames it applied itself,
+  /// the rename command should leave names it produced alone.
+  #[clap(
+    long,
+    value_name = "NAME",
+    requires_all = ["files", "recursive"],
+    conflicts_with_all = ["files_from", "staged", "answers_from", "dry_run", "edit", "tui", "editor_protocol", "wizard", "check", "manifest", "undo", "verify", "audit", "recover"]
+  )]
+  pub service: Option<String>,
+  /// Install the units generated as per --service for the current
+  /// user, instead of printing them.
+  #[clap(long, requires = "service")]
+  pub install_service: bool,
+  /// Run the units generated as per --service on the given cron-style
+  /// schedule, as in `0 3 * * *` for every night at 3am, instead of
+  /// whenever the contents of the directories change.
+  ///
+  /// The schedule consists of minute, hour, day of month, month, and
+  /// day of week. Defaults to the `schedule` of the --preset, if any.
+  #[clap(long, value_name = "SCHEDULE", requires = "service")]
+  pub schedule: Option<Schedule>,
+  /// Do not record applied renames in the journal.
+  #[clap(long)]
+  pub no_journal: bool,
+  /// Record a checksum of each renamed file in the journal, for later
+  /// verification with --verify.
+  #[clap(long, conflicts_with = "no_journal")]
+  pub checksums: bool,
+  /// Append a record of each applied rename to the given file, for
+  /// auditing purposes.
+  ///
+  /// Each record is a JSON object on a line of its own, comprising a
+  /// sequence number (`seq`), the time of the rename, the old and new
+  /// path, the command used, and its exit status (absent if new paths
+  /// were not determined by a command). Sequence numbers increase
+  /// monotonically across invocations, also with multiple processes
+  /// appending to the same log. The log is kept independently of the
+  /// journal.
+  #[clap(long, value_name = "FILE")]
+  pub log: Option<PathBuf>,
+  /// Print the records of the --log following the one with the given
+  /// sequence number instead of renaming files.
+  ///
+  /// Meant for consumers processing the log incrementally, which pass
+  /// the sequence number of the last record they have seen. The log is
+  /// not read in full for finding the first record to print. Use zero
+  /// for printing all records.
+  #[clap(
+    long,
+    value_name = "CURSOR",
+    requires = "log",
+    conflicts_with_all = ["command", "files", "files_from", "undo", "verify", "audit", "recover", "manifest"]
+  )]
+  pub since: Option<u64>,
+  /// Persist which files got renamed or declined in the given file, so
+  /// that re-running the same invocation after an interruption skips
+  /// them.
+  ///
+  /// Files renamed previously are skipped by their old as well as by
+  /// their new path, so that the rename command is not run on new
+  /// names. The file is removed once the invocation completed
+  /// successfully.
+  #[clap(long, value_name = "FILE", conflicts_with_all = ["manifest", "editor_protocol"])]
+  pub state: Option<PathBuf>,
+  /// Preserve each file under its original name with the given suffix
+  /// appended (`~` by default) right before renaming it, as `mv
+  /// --backup` does.
+  ///
+  /// The backup is a hard link to the file where possible and a copy
+  /// otherwise. Existing backups are replaced. Renames of archive
+  /// members and those reverted via --undo are not backed up, nor are
+  /// directories.
+  #[clap(
+    long,
+    value_name = "SUFFIX",
+    num_args = 0..=1,
+    require_equals = true,
+    default_missing_value = "~"
+  )]
+  pub backup: Option<OsString>,
+  /// A command to run before each applied rename, receiving the old and
+  /// new path of the file via the BR_OLD and BR_NEW environment
+  /// variables.
+  ///
+  /// The command is interpreted by the shell. A failing command
+  /// prevents the rename. Renames of archive members and those
+  /// reverted via --undo do not run hooks.
+  #[clap(long, value_name = "COMMAND")]
+  pub pre_hook: Option<OsString>,
+  /// A command to run after each applied rename, receiving the old and
+  /// new path of the file via the BR_OLD and BR_NEW environment
+  /// variables.
+  ///
+  /// The command is interpreted by the shell. A failing command is
+  /// reported as an error, but the rename stays in place.
+  #[clap(long, value_name = "COMMAND")]
+  pub post_hook: Option<OsString>,
+  /// A command to run once renames got applied, if any file got
+  /// renamed, for notifying about changes, e.g., via `notify-send`.
+  ///
+  /// The command is interpreted by the shell and receives the number
+  /// of files renamed, skipped, and failed via the BR_RENAMED,
+  /// BR_SKIPPED, and BR_FAILED environment variables.
+  #[clap(long, value_name = "COMMAND", conflicts_with_all = ["dry_run", "check", "editor_protocol"])]
+  pub notify: Option<OsString>,
+  /// A URL to send an HTTP POST request to once renames got applied,
+  /// e.g., for telling a media server to rescan its library when
+  /// running as a service. May be provided multiple times.
+  ///
+  /// The request carries a JSON object with the summary of the batch
+  /// and reports about the files renamed, as with `--output json`. It
+  /// is only sent if any file got renamed. Requires `curl`. Failing
+  /// requests are reported as warnings and don't affect renames.
+  #[clap(long, value_name = "URL", conflicts_with_all = ["dry_run", "check", "editor_protocol"])]
+  pub webhook: Vec<String>,
+  /// Send a request to each --webhook per applied rename, carrying the
+  /// report about the file renamed, instead of one per batch.
+  #[clap(long, requires = "webhook")]
+  pub webhook_per_rename: bool,
+  /// Do not refresh the libraries of the media servers configured in
+  /// the `[jellyfin]` and `[plex]` tables of `.batch-renamer.toml` or
+  /// of the user-wide configuration file once renames got applied.
+  ///
+  /// By default, each configured server is told about the directories
+  /// touched by renames that belong to any of its libraries.
+  #[clap(long)]
+  pub no_library_refresh: bool,
+  /// A playlist, desktop entry, or similar file referencing other files
+  /// by path, whose references to renamed files to update once renames
+  /// got applied. May be provided multiple times.
+  ///
+  /// M3U and PLS playlists, XSPF playlists, desktop entries, and plain
+  /// lists with one path per line are understood, as told by the file's
+  /// extension. Relative paths are kept relative and `file://` URIs are
+  /// kept as URIs.
+  #[clap(long, value_name = "FILE", conflicts_with_all = ["dry_run", "check", "editor_protocol"])]
+  pub update_refs: Vec<PathBuf>,
+  /// Update the links to renamed files in the Markdown and HTML
+  /// documents next to them once renames got applied, e.g., for keeping
+  /// the notes of a note-taking vault linked.
+  ///
+  /// Relative links of the form `[text](target)`, `[[wiki links]]`,
+  /// and `href` and `src` attributes are understood.
+  #[clap(long, conflicts_with_all = ["dry_run", "check", "editor_protocol"])]
+  pub update_links: bool,
+  /// Do not report gaps and duplicates in the numbering of files once
+  /// renames changing numbers in file names got applied.
+  ///
+  /// By default, the files numbered alike next to each renamed one are
+  /// checked for forming a complete sequence, e.g., of episodes or
+  /// tracks, without any number missing or present more than once.
+  #[clap(long)]
+  pub no_sequence_check: bool,
+  /// Print the proposed new names of all files that would be renamed
+  /// and exit, without prompting or renaming anything.
+  #[clap(
+    short = 'n',
+    long,
+    conflicts_with_all = ["yes", "edit", "tui", "editor_protocol", "undo", "verify"]
+  )]
+  pub dry_run: bool,
+  /// Save the plan printed via --dry-run to the given file, for
+  /// applying it later via --apply-plan.
+  #[clap(long, value_name = "FILE", requires = "dry_run")]
+  pub save_plan: Option<PathBuf>,
+  /// Sign the plan saved via --save-plan with the given minisign secret
+  /// key, for checking it via --require-signed before it gets applied.
+  ///
+  /// The signature is stored next to the plan, with `.minisig`
+  /// appended to its name. Requires minisign.
+  #[clap(long, value_name = "SECKEY", requires = "save_plan")]
+  pub sign_plan: Option<PathBuf>,
+  /// Accept all proposed renames without prompting.
+  #[clap(short, long)]
+  pub yes: bool,
+  /// Make quitting at a prompt via `q` revert the renames applied so
+  /// far in the session, as `Q` does, instead of keeping them.
+  ///
+  /// Renames get reverted by means of the journal, as for --undo.
+  #[clap(long, conflicts_with_all = ["yes", "no_journal", "edit", "tui", "editor_protocol"])]
+  pub abort_reverts: bool,
+  /// Run unattended, as in containers and CI pipelines.
+  ///
+  /// All proposed renames are accepted without prompting, with
+  /// conflicts resolved as per --on-conflict and --discard-identical.
+  /// The outcome for each file is reported as a line of JSON (as with
+  /// `--output json`), concluded by a JSON object summarizing all
+  /// outcomes. Messages go to stderr, without colors or progress
+  /// reporting. Combined with --dry-run or --check nothing is renamed.
+  #[clap(
+    long,
+    conflicts_with_all = ["answers_from", "edit", "tui", "editor_protocol", "wizard", "output"]
+  )]
+  pub batch: bool,
+  /// Speak a line based JSON protocol over stdin and stdout, for usage
+  /// as a backend by editor plugins.
+  #[clap(long, conflicts_with_all = ["files", "files_from", "yes"])]
+  pub editor_protocol: bool,
+  /// Review and edit all proposed renames at once in a text editor
+  /// instead of confirming them one by one.
+  ///
+  /// The editor is taken from the VISUAL or EDITOR environment
+  /// variables. A note may be added to each line after a tab, to be
+  /// recorded in the journal along with the rename or its rejection.
+  #[clap(short, long, conflicts_with = "yes")]
+  pub edit: bool,
+  /// Review all proposed renames in a full screen terminal interface
+  /// instead of confirming them one by one.
+  ///
+  /// Renames can be selected and deselected individually, their new
+  /// paths edited, and notes attached to them for the journal, before
+  /// the selection gets applied at once.
+  #[clap(long, conflicts_with_all = ["yes", "edit", "editor_protocol"])]
+  pub tui: bool,
+  /// The number of times to retry applying a rename that failed with
+  /// a transient error, such as reported sporadically by network file
+  /// systems. Defaults to not retrying.
+  #[clap(long, value_name = "COUNT")]
+  pub retries: Option<u32>,
+  /// The delay before the first retry, in milliseconds. The delay
+  /// doubles with each subsequent retry.
+  #[clap(long, value_name = "MS", default_value_t = 100, requires = "retries")]
+  pub retry_backoff: u64,
+  /// Adapt the number of files processed concurrently to observed
+  /// latencies instead of using a fixed number.
+  ///
+  /// Concurrency is increased for as long as throughput improves and
+  /// reduced when latencies spike, as is typical for network file
+  /// systems under load.
+  #[clap(long)]
+  pub adaptive: bool,
+  /// The number of files to process concurrently, both when
+  /// determining new names and when applying renames.
+  ///
+  /// Use `--jobs 1` for commands that are not safe to run concurrently.
+  /// Defaults to 32.
+  #[clap(short, long, value_name = "N", conflicts_with = "adaptive")]
+  pub jobs: Option<NonZeroUsize>,
+  /// The number of files for which to determine new names
+  /// concurrently, overriding `--jobs` and `--adaptive` for planning.
+  ///
+  /// Planning only runs the rename command in a sandbox, so it can
+  /// typically use a higher level of concurrency than applying.
+  #[clap(long, value_name = "N")]
+  pub plan_jobs: Option<NonZeroUsize>,
+  /// The number of renames to apply concurrently, overriding `--jobs`
+  /// and `--adaptive` for applying.
+  ///
+  /// Applying touches the actual files, which may be subject to rate
+  /// limits, for example on network file systems.
+  #[clap(long, value_name = "N")]
+  pub apply_jobs: Option<NonZeroUsize>,
+  /// Keep memory usage and the number of processes spawned at once to
+  /// a minimum, for constrained devices such as routers, NAS boxes, or
+  /// tiny containers.
+  ///
+  /// Files are processed one at a time and no progress is reported.
+  #[clap(long, conflicts_with_all = ["adaptive", "jobs", "plan_jobs", "apply_jobs"])]
+  pub low_resource: bool,
+  /// The number of seconds after which a rename command gets killed,
+  /// failing the rename of the file it was run for.
+  ///
+  /// Applies to each invocation of the rename command, both when
+  /// determining new names and when applying renames. By default,
+  /// commands may run indefinitely.
+  #[clap(long, value_name = "SECS")]
+  pub timeout: Option<NonZeroU64>,
+  /// Run the rename command in a sandbox, for commands that are not
+  /// trusted, such as one-liners copied from the internet.
+  ///
+  /// The command sees a read-only view of the system and lacks network
+  /// access. It may only write to the directory it operates in: a
+  /// temporary one while determining new names and the one containing
+  /// the file when applying a rename. Requires bubblewrap (`bwrap`).
+  #[clap(long)]
+  pub sandbox: bool,
+  /// Apply accepted renames directly instead of running the rename
+  /// command on the actual files.
+  ///
+  /// The command is still used for determining new names, but renames
+  /// are applied in a single batch once all files have been processed.
+  /// Use this mode only with commands that do nothing but rename.
+  #[clap(long, conflicts_with_all = ["edit", "tui", "editor_protocol"])]
+  pub direct: bool,
+  /// Determine new names by running the rename command once for many
+  /// files, passing them as arguments, instead of once per file.
+  ///
+  /// Only use this mode with commands accepting multiple files that
+  /// leave the contents of files alone. Implies --direct.
+  #[clap(long, conflicts_with_all = ["template", "sanitize", "wizard", "edit", "tui", "editor_protocol"])]
+  pub batch_preview: bool,
+  /// A further command to apply to the name proposed by the rename
+  /// command, or by the previous --then command, when determining new
+  /// names, as in `batch-rename cmd1 --then cmd2 arg -- files`.
+  ///
+  /// May be provided multiple times, for composing a rename out of
+  /// several commands. Each command communicates names as per
+  /// --protocol. Intermediate names are shown along with the final
+  /// one. Implies --direct.
+  #[clap(
+    long = "then",
+    value_name = "COMMAND",
+    num_args = 1..,
+    action = ArgAction::Append,
+    conflicts_with_all = ["template", "sanitize", "wizard", "manifest", "batch_preview", "editor_protocol"]
+  )]
+  pub then_args: Vec<OsString>,
+  /// The commands provided via --then, each along with its arguments.
+  #[clap(skip)]
+  pub then: Vec<Vec<OsString>>,
+  /// The way the rename command communicates new names: `rename` (the
+  /// command renames the file passed to it), `stdout` (the command
+  /// receives the name as argument and prints the new one), or `stdin`
+  /// (the command reads the name from stdin and prints the new one, as
+  /// `sed` does).
+  ///
+  /// With `stdout` and `stdin`, the command is never run on actual
+  /// files and accepted renames are applied directly.
+  #[clap(
+    long,
+    value_name = "PROTOCOL",
+    default_value = "rename",
+    conflicts_with_all = ["template", "sanitize", "wizard"]
+  )]
+  pub protocol: Protocol,
+  /// Apply all accepted renames as a single transaction, reverting
+  /// those already applied should any of them fail.
+  ///
+  /// Implies --direct.
+  #[clap(long, conflicts_with_all = ["edit", "tui", "editor_protocol"])]
+  pub transactional: bool,
+  /// Keep processing the remaining files when determining the new path
+  /// of or renaming a file fails, reporting all failures at the end.
+  ///
+  /// The exit status still indicates failure if any file failed.
+  #[clap(long, conflicts_with_all = ["transactional", "editor_protocol"])]
+  pub keep_going: bool,
+  /// Refuse to rename if any two files would end up with names that
+  /// differ only in case.
+  ///
+  /// Such files can't coexist on case-insensitive file systems, as
+  /// commonly used on macOS and Windows. New names of all files are
+  /// determined before the first prompt.
+  #[clap(long, conflicts_with_all = ["undo", "editor_protocol"])]
+  pub check_case: bool,
+  /// Report the given warning about proposed renames as an error,
+  /// refusing to rename any file.
+  ///
+  /// Warnings are `collision` (multiple files get the same new path),
+  /// `big-change` (the new name retains little of the old one),
+  /// `extension-change`, `hidden-change` (a file becomes hidden or
+  /// visible), and `cross-device` (a file would move to another file
+  /// system). If any warning is denied, new names of all files are
+  /// determined before the first prompt. May be provided multiple
+  /// times.
+  #[clap(long, value_name = "WARNING")]
+  pub deny: Vec<Warning>,
+  /// Do not report the given warning about proposed renames.
+  ///
+  /// May be provided multiple times.
+  #[clap(long, value_name = "WARNING")]
+  pub allow: Vec<Warning>,
+  /// Only rename files if the change made to their names is of the
+  /// given kind, declining all other renames.
+  ///
+  /// Changes are classified heuristically as `case-only`, `extension`
+  /// (only the extension changes), `reorder` (words are put into a
+  /// different order), `date` (a date gets added), `numbering` (numbers
+  /// get added or changed), or `free-form`. The kind of change is
+  /// reported in JSON output and summaries. May be provided multiple
+  /// times.
+  #[clap(long, value_name = "CHANGE")]
+  pub only_change: Vec<Change>,
+  /// Accept renames making the given kind of change to file names
+  /// without prompting, as classified for --only-change.
+  ///
+  /// May be provided multiple times.
+  #[clap(long, value_name = "CHANGE", conflicts_with_all = ["yes", "edit", "tui", "editor_protocol"])]
+  pub accept_change: Vec<Change>,
+  /// Validate new paths against the naming policy in the given file.
+  ///
+  /// Defaults to `naming-policy.toml` in the current directory, if
+  /// present. The policy may restrict the length of file names, the
+  /// characters they consist of, and the glob pattern they have to
+  /// match, for all files or those matching a glob pattern. If any
+  /// violation is denied, new names of all files are determined before
+  /// the first prompt.
+  #[clap(long, value_name = "FILE")]
+  pub policy: Option<PathBuf>,
+  /// Only check the new paths of the files against the naming policy
+  /// and for warnings, failing if any is denied, without renaming.
+  ///
+  /// Without a rename command, the current paths of the files are
+  /// checked against the naming policy.
+  #[clap(
+    long,
+    conflicts_with_all = ["yes", "edit", "tui", "editor_protocol", "undo", "verify", "dry_run", "wizard", "manifest"]
+  )]
+  pub check: bool,
+  /// How to deal with new paths that exist already: `abort` the
+  /// rename, `skip` the file, `overwrite` the existing file, or append
+  /// a numbered `suffix`, as in `photo (1).jpg`.
+  ///
+  /// When prompting, a conflict with an existing file is shown along
+  /// with the option to overwrite, skip, or suffix, or to edit the new
+  /// path, with the policy applying to plain acceptance only. Renames
+  /// accepted without prompting, as with --yes or --batch, are subject
+  /// to the policy throughout.
+  #[clap(long, value_name = "POLICY", default_value = "abort")]
+  pub on_conflict: ConflictPolicy,
+  /// Remove files whose new path exists already with identical
+  /// contents, instead of renaming them.
+  ///
+  /// Without this option, removal is offered as a resolution when
+  /// prompting.
+  #[clap(long, conflicts_with_all = ["edit", "tui", "editor_protocol"])]
+  pub discard_identical: bool,
+  /// The format in which to report the outcome for each file: `human`,
+  /// `json`, or `null` (old and new path of each renamed file, each
+  /// terminated by NUL).
+  ///
+  /// In formats other than `human`, prompts are printed to stderr.
+  #[clap(
+    long,
+    value_name = "FORMAT",
+    default_value = "human",
+    conflicts_with_all = ["edit", "tui", "editor_protocol"]
+  )]
+  pub output: Format,
+  /// Only report files whose name changes, omitting those the rename
+  /// command left alone. They are still accounted for in the summary
+  /// printed at the end.
+  ///
+  /// Previews shown with --dry-run always omit unchanged files.
+  #[clap(long, conflicts_with_all = ["edit", "tui", "editor_protocol"])]
+  pub changed_only: bool,
+  /// Report statistics about the new names of files before the first
+  /// rename gets prompted for or applied, surfacing misbehaving rename
+  /// commands: a histogram of the lengths of new names, along with new
+  /// names that have an empty stem, are 200 or more characters long,
+  /// or share a stem with another one in the same directory.
+  #[clap(long, conflicts_with = "editor_protocol")]
+  pub analyze: bool,
+  /// Report what is going on in more detail on stderr, such as the
+  /// commands run and the directories they are run in.
+  ///
+  /// Provide twice for including the output of commands as well.
+  #[clap(short, long, action = ArgAction::Count, conflicts_with = "quiet")]
+  pub verbose: u8,
+  /// Only report errors on stderr, omitting diagnostic warnings.
+  #[clap(long)]
+  pub quiet: bool,
+  /// The colors used for highlighting changes, in prompts, previews,
+  /// and the terminal interface: `default`, `high-contrast`, or
+  /// `monochrome` (text attributes only).
+  ///
+  /// Defaults to the theme configured in the closest
+  /// `.batch-renamer.toml` file, if any.
+  #[clap(long, value_name = "THEME", default_value = "default")]
+  pub theme: Theme,
+  /// Apply the named preset from the configuration, providing the
+  /// rename command, --protocol, --on-conflict, and additional flags.
+  ///
+  /// The built-in `archive` preset moves files last modified more than
+  /// 30 days ago below the provided directories into an `archive`
+  /// hierarchy by year and month, prefixing their names with the date
+  /// of modification, as in `archive/2023/07/2023-07-14_notes.txt`.
+  ///
+  /// Presets are defined in `[presets.<name>]` tables of the closest
+  /// `.batch-renamer.toml` file or of the user-wide configuration file,
+  /// `batch-renamer/config.toml` in the user's configuration directory.
+  /// Options provided on the command line take precedence.
+  #[clap(long, value_name = "NAME")]
+  pub preset: Option<String>,
+}
+
+
+/// Collect the built-in presets along with those in `config`, which
+/// take precedence.
+fn presets(config: Option<&Config>) -> BTreeMap<String, Preset> {
+  let mut presets = Preset::builtin();
+  if let Some(config) = config {
+    let () = presets.extend(
+      config
+        .presets
+        .iter()
+        .map(|(name, preset)| (name.clone(), preset.clone())),
+    );
+  }
+  presets
+}
+
+
+/// Find the name of the preset selected in `args`, if any.
+fn find_preset(args: &[OsString]) -> Option<String> {
+  let mut args = args.iter().skip(1).take_while(|arg| *arg != "--");
+  while let Some(arg) = args.next() {
+    if arg == "--preset" {
+      return args.next().map(|name| name.to_string_lossy().into_owned())
+    }
+    if let Some(name) = arg.to_str().and_then(|arg| arg.strip_prefix("--preset=")) {
+      return Some(name.to_string())
+    }
+  }
+  None
+}
+
+impl Args {
+  /// Write a script for completing arguments in `shell` to `writer`,
+  /// including the names of the built-in presets and of those in
+  /// `config`.
+  pub fn write_completions<W>(shell: Shell, config: Option<&Config>, writer: &mut W)
+  where
+    W: Write,
+  {
+    let mut command = Self::command();
+    let presets = presets(config);
+    command = command.mut_args(|arg| {
+      if arg.get_id() == "preset" {
+        arg.value_parser(PossibleValuesParser::new(presets.keys().cloned()))
+      } else {
+        arg
+      }
+    });
+    let name = command.get_name().to_string();
+    let () = generate(shell, &mut command, name, writer);
+  }
+
+  /// Parse the given arguments, as [`Parser::try_parse_from`] does,
+  /// but keeping the commands provided via --then apart and falling
+  /// back to the defaults in `config` for those not provided.
+  pub fn try_parse_grouped<I, T>(args: I, config: Option<&Config>) -> Result<Self, Error>
+  where
+    I: IntoIterator<Item = T>,
+    T: Into<OsString> + Clone,
+  {
+    let mut args = args.into_iter().map(Into::into).collect::<Vec<_>>();
+    let mut command = Self::command();
+    let preset = if let Some(name) = find_preset(&args) {
+      let mut presets = presets(config);
+      let preset = presets.remove(&name).ok_or_else(|| {
+        let names = presets
+          .keys()
+          .map(|name| format!("`{name}`"))
+          .collect::<Vec<_>>();
+        Error::raw(
+          ErrorKind::InvalidValue,
+          format!(
+            "unknown preset `{name}`; expected one of {}\n",
+            names.join(", ")
+          ),
+        )
+      })?;
+      // The preset's flags go first, so that those provided on the
+      // command line override them.
+      let rest = args.split_off(args.len().min(1));
+      args = args
+        .into_iter()
+        .chain(preset.flags.iter().map(OsString::from))
+        .chain(rest)
+        .collect();
+      command = command.args_override_self(true);
+      Some(preset)
+    } else {
+      None
+    };
+
+    if config.is_some_and(|config| !config.command.is_empty())
+      || preset
+        .as_ref()
+        .is_some_and(|preset| !preset.command.is_empty())
+    {
+      // With a default command at hand, files suffice. Note that
+      // `Command::mut_arg` would move the argument to the end,
+      // changing positional indices.
+      command = command.mut_args(|arg| {
+        if arg.get_id() == "command" {
+          arg.required_unless_present_any(["files", "files_from", "staged"])
+        } else {
+          arg
+        }
+      });
+    }
+    if let Some(config) = config.filter(|config| !config.functions.is_empty()) {
+      command = command.mut_args(|arg| {
+        if arg.get_id() == "template" {
+          let functions = config.functions.clone();
+          arg.value_parser(move |s: &str| Template::parse_with_functions(s, &functions))
+        } else {
+          arg
+        }
+      });
+    }
+
+    let mut matches = command.try_get_matches_from(args)?;
+    let provided = |id| matches.value_source(id) == Some(ValueSource::CommandLine);
+    let default_command = !provided("command") && !COMMAND_ALTERNATIVES.into_iter().any(provided);
+    let default_protocol = !provided("protocol");
+    let default_on_conflict = !provided("on_conflict");
+    let default_theme = !provided("theme");
+    let then = matches
+      .get_occurrences::<OsString>("then_args")
+      .map(|occurrences| {
+        occurrences
+          .map(|command| command.cloned().collect())
+          .collect()
+      })
+      .unwrap_or_default();
+    let mut args = Self::from_arg_matches_mut(&mut matches)?;
+    args.then = then;
+
+    let (default_command, default_protocol) = match &preset {
+      Some(preset) => {
+        if default_command && !preset.command.is_empty() {
+          args.command = preset.command.clone();
+        }
+        if let (true, Some(protocol)) = (default_protocol, preset.protocol) {
+          args.protocol = protocol;
+        }
+        if let (true, Some(policy)) = (default_on_conflict, preset.on_conflict) {
+          args.on_conflict = policy;
+        }
+        if args.schedule.is_none() {
+          args.schedule = preset.schedule.clone();
+        }
+        (
+          default_command && preset.command.is_empty(),
+          default_protocol && preset.protocol.is_none(),
+        )
+      },
+      None => (default_command, default_protocol),
+    };
+
+    if let Some(config) = config {
+      if default_command && !config.command.is_empty() {
+        args.command = config.command.clone();
+        if let (true, Some(protocol)) = (default_protocol, config.protocol) {
+          args.protocol = protocol;
+        }
+      }
+      if args.include.is_empty() {
+        args.include = config.include.clone();
+      }
+      if args.exclude.is_empty() {
+        args.exclude = config.exclude.clone();
+      }
+      if let (true, Some(theme)) = (default_theme, &config.theme) {
+        args.theme = theme.parse().map_err(|err| {
+          Error::raw(
+            ErrorKind::ValueValidation,
+            format!("invalid theme in `{}`: {err}\n", config.path.display()),
+          )
+        })?;
+      }
+    }
+    Ok(args)
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+
+  /// Check that --retry-backoff is only accepted along with --retries.
+  #[test]
+  fn retry_backoff() {
+    let args = Args::try_parse_grouped(["batch-rename", "cmd"], None).unwrap();
+    assert_eq!(args.retries, None);
+    assert_eq!(args.retry_backoff, 100);
+
+    let args = Args::try_parse_grouped(
+      [
+        "batch-rename",
+        "--retries",
+        "3",
+        "--retry-backoff",
+        "50",
+        "cmd",
+      ],
+      None,
+    )
+    .unwrap();
+    assert_eq!(args.retries, Some(3));
+    assert_eq!(args.retry_backoff, 50);
+
+    let err =
+      Args::try_parse_grouped(["batch-rename", "--retry-backoff", "50", "cmd"], None).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::MissingRequiredArgument);
+  }
+
+  /// Check that --directory and --backup cannot be combined.
+  #[test]
+  fn directory_backup() {
+    let err = Args::try_parse_grouped(
+      [
+        "batch-rename",
+        "--directory",
+        "--backup",
+        "cmd",
+        "--",
+        "dir",
+      ],
+      None,
+    )
+    .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::ArgumentConflict);
+  }
+}
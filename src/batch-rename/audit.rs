@@ -0,0 +1,220 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Auditing of applied renames.
+
+use std::ffi::OsString;
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::io::copy;
+use std::io::stdout;
+use std::io::BufRead as _;
+use std::io::BufReader;
+use std::io::Read as _;
+use std::io::Seek as _;
+use std::io::SeekFrom;
+use std::io::Write as _;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::sync::PoisonError;
+
+use anyhow::Context as _;
+use anyhow::Result;
+
+use batch_renamer::output::Audit;
+
+use chrono::SecondsFormat;
+use chrono::Utc;
+
+use serde::Deserialize;
+
+
+/// The size of the chunks in which the log is read backwards when
+/// looking for its last record.
+const CHUNK_SIZE: u64 = 4096;
+
+
+/// The part of a record in the log relevant for finding records.
+#[derive(Deserialize)]
+struct Seq {
+  /// The sequence number of the record. Records written before
+  /// sequence numbers got introduced lack it.
+  #[serde(default)]
+  seq: u64,
+}
+
+
+/// Parse the sequence number of the record on `line`.
+fn parse_seq(line: &[u8]) -> Result<u64> {
+  let record = serde_json::from_slice::<Seq>(line).with_context(|| {
+    format!(
+      "failed to parse audit record `{}`",
+      String::from_utf8_lossy(line).trim_end()
+    )
+  })?;
+  Ok(record.seq)
+}
+
+
+/// Read the sequence number of the last record in `file`, or zero if
+/// there is none.
+fn last_seq(file: &mut File) -> Result<u64> {
+  let len = file.seek(SeekFrom::End(0))?;
+  let mut line = Vec::new();
+  let mut pos = len;
+  while pos > 0 {
+    let size = pos.min(CHUNK_SIZE);
+    pos -= size;
+    let mut chunk = vec![0; size as usize];
+    let () = file.seek(SeekFrom::Start(pos)).map(|_pos| ())?;
+    let () = file.read_exact(&mut chunk)?;
+    let () = chunk.extend_from_slice(&line);
+    line = chunk;
+
+    // The last line is terminated by a newline, which does not start a
+    // line of its own.
+    let content = line.strip_suffix(b"\n").unwrap_or(&line);
+    if let Some(idx) = content.iter().rposition(|byte| *byte == b'\n') {
+      let _removed = line.drain(..=idx);
+      break
+    }
+  }
+
+  if line.iter().all(u8::is_ascii_whitespace) {
+    return Ok(0)
+  }
+  parse_seq(&line)
+}
+
+
+/// Find the first line starting at or after the byte offset `pos` in
+/// `file`, reporting its start, its end, and the sequence number of the
+/// record on it.
+fn line_at(file: &File, pos: u64) -> Result<Option<(u64, u64, u64)>> {
+  let mut reader = BufReader::new(file);
+  let mut start = pos;
+  if pos > 0 {
+    // Skip the remainder of the line containing the preceding byte, so
+    // that we end up at the start of a line.
+    let _pos = reader.seek(SeekFrom::Start(pos - 1))?;
+    let mut skipped = Vec::new();
+    let count = reader.read_until(b'\n', &mut skipped)?;
+    start = pos - 1 + count as u64;
+  } else {
+    let _pos = reader.seek(SeekFrom::Start(0))?;
+  }
+
+  let mut line = Vec::new();
+  let count = reader.read_until(b'\n', &mut line)?;
+  if count == 0 {
+    return Ok(None)
+  }
+  let seq = parse_seq(&line)?;
+  Ok(Some((start, start + count as u64, seq)))
+}
+
+
+/// Write the records of the audit log at `path` following the one with
+/// the sequence number `cursor` to stdout, as they appear in the log.
+///
+/// Sequence numbers increase monotonically, so the first record of
+/// interest is found by bisecting the log, without reading all of it.
+/// Records written before sequence numbers got introduced are only
+/// included for a `cursor` of zero.
+pub(crate) fn print_since(path: &Path, cursor: u64) -> Result<()> {
+  let context = || format!("failed to read audit log `{}`", path.display());
+  let file = File::open(path).with_context(context)?;
+  let len = file.metadata().with_context(context)?.len();
+
+  let (mut lo, mut hi) = (0, len);
+  if cursor > 0 {
+    while lo < hi {
+      let mid = lo + (hi - lo) / 2;
+      match line_at(&file, mid).with_context(context)? {
+        Some((_start, end, seq)) if seq <= cursor => lo = end,
+        _ => hi = mid,
+      }
+    }
+  }
+
+  let start = match line_at(&file, lo).with_context(context)? {
+    Some((start, ..)) => start,
+    None => return Ok(()),
+  };
+  let mut reader = BufReader::new(&file);
+  let _pos = reader.seek(SeekFrom::Start(start)).with_context(context)?;
+  let mut stdout = stdout().lock();
+  let _count = copy(&mut reader, &mut stdout).context("failed to write audit records")?;
+  Ok(())
+}
+
+
+/// A log recording each applied rename, for auditing purposes.
+///
+/// Unlike the journal, the log is meant to be kept around: records are
+/// appended to it across invocations.
+#[derive(Debug)]
+pub(crate) struct AuditLog {
+  /// The path to the log file.
+  path: PathBuf,
+  /// The log file, opened for appending.
+  file: Mutex<File>,
+  /// The command used for renaming.
+  command: Vec<OsString>,
+  /// The exit status of the rename command for applied renames, if
+  /// new paths are determined by one.
+  status: Option<i32>,
+}
+
+impl AuditLog {
+  /// Open the audit log at `path` for recording renames performed
+  /// using `command`, creating the log if necessary.
+  pub(crate) fn open(path: &Path, command: &[OsString], status: Option<i32>) -> Result<Self> {
+    let file = OpenOptions::new()
+      .create(true)
+      .read(true)
+      .append(true)
+      .open(path)
+      .with_context(|| format!("failed to open audit log `{}`", path.display()))?;
+
+    let slf = Self {
+      path: path.to_path_buf(),
+      file: Mutex::new(file),
+      command: command.to_vec(),
+      status,
+    };
+    Ok(slf)
+  }
+
+  /// Record the rename of `src` to `dst`.
+  pub(crate) fn record(&self, src: &Path, dst: &Path) -> Result<()> {
+    let mut file = self.file.lock().unwrap_or_else(PoisonError::into_inner);
+    // Other processes may append to a shared log as well, so it stays
+    // locked while determining the sequence number of the record and
+    // writing it.
+    let () = file
+      .lock()
+      .with_context(|| format!("failed to lock audit log `{}`", self.path.display()))?;
+    let result = (|| {
+      let audit = Audit {
+        seq: last_seq(&mut file)? + 1,
+        time: Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+        src: src.to_path_buf(),
+        dst: dst.to_path_buf(),
+        command: self.command.clone(),
+        status: self.status,
+      };
+      // Each record is written in one go, so that it is never torn.
+      let mut line = Vec::new();
+      let () = audit.write(&mut line)?;
+      let () = file.write_all(&line)?;
+      let () = file.sync_data()?;
+      Result::<_>::Ok(())
+    })();
+    let _result = file.unlock();
+    let () =
+      result.with_context(|| format!("failed to write audit log `{}`", self.path.display()))?;
+    Ok(())
+  }
+}
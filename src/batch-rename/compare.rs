@@ -0,0 +1,72 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Comparison of the names of the same files in two directories.
+
+use std::path::Path;
+use std::path::PathBuf;
+
+use anyhow::Result;
+
+use batch_renamer::manifest::Drift;
+use batch_renamer::manifest::Identity;
+use batch_renamer::manifest::Manifest;
+use batch_renamer::output::Format;
+use batch_renamer::resolve;
+use batch_renamer::SymlinkPolicy;
+
+use crate::tell;
+
+
+/// Compare the names of the files below `dir_b` with those of the
+/// same files below `dir_a`, pairing files up as per `identity`, and
+/// report each difference.
+///
+/// Returned are the renames necessary for making the names of files
+/// below `dir_b` match those below `dir_a`, as the paths of the files
+/// as well as their resolved forms, along with their new paths. Files
+/// present on only one side or with contents differing between both
+/// are not reported.
+pub(crate) async fn diff_names(
+  dir_a: &Path,
+  dir_b: &Path,
+  identity: Identity,
+  output: Format,
+) -> Result<Vec<(PathBuf, PathBuf, PathBuf)>> {
+  let dir_a = resolve(dir_a, SymlinkPolicy::Follow).await?;
+  let dir_b = resolve(dir_b, SymlinkPolicy::Follow).await?;
+  let files_a = Manifest::scan_by(&dir_a, None, identity).await?;
+  let files_b = Manifest::scan_by(&dir_b, None, identity).await?;
+
+  let plan = files_a
+    .drift(&files_b)
+    .into_iter()
+    .filter_map(|drift| match drift {
+      Drift::Moved { path, recorded } => {
+        let () = tell(
+          output,
+          &format!(
+            "`{}` is named `{}` in `{}`",
+            path.display(),
+            recorded.display(),
+            dir_a.display()
+          ),
+        );
+        let src = dir_b.join(path);
+        Some((src.clone(), src, dir_b.join(recorded)))
+      },
+      Drift::Modified(..) | Drift::Untracked(..) | Drift::Missing(..) => None,
+    })
+    .collect::<Vec<_>>();
+
+  let () = tell(
+    output,
+    &format!(
+      "{} file(s) in `{}` named differently than in `{}`",
+      plan.len(),
+      dir_b.display(),
+      dir_a.display()
+    ),
+  );
+  Ok(plan)
+}
@@ -0,0 +1,197 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Highlighting of the differences between old and new names.
+
+use std::ops::Range;
+
+use batch_renamer::grapheme::graphemes;
+
+use crate::theme::Theme;
+
+/// The character shown in place of elided parts of a name.
+const ELLIPSIS: &str = "…";
+/// The maximum number of character pairs compared, to keep the effort
+/// bounded for pathological inputs.
+const MAX_CELLS: usize = 1 << 20;
+
+
+/// Find the characters of `old` and `new` that are part of a longest
+/// common subsequence of both.
+///
+/// Characters are grapheme clusters, so that combined characters and
+/// emoji sequences are never split. Returned is one flag per character
+/// of each string, telling whether it is shared.
+fn common(old: &[&str], new: &[&str]) -> (Vec<bool>, Vec<bool>) {
+  let width = new.len() + 1;
+  // `lengths[i * width + j]` is the length of the longest common
+  // subsequence of `old[i..]` and `new[j..]`.
+  let mut lengths = vec![0usize; (old.len() + 1) * width];
+  for i in (0..old.len()).rev() {
+    for j in (0..new.len()).rev() {
+      lengths[i * width + j] = if old[i] == new[j] {
+        lengths[(i + 1) * width + j + 1] + 1
+      } else {
+        lengths[(i + 1) * width + j].max(lengths[i * width + j + 1])
+      };
+    }
+  }
+
+  let mut old_shared = vec![false; old.len()];
+  let mut new_shared = vec![false; new.len()];
+  let (mut i, mut j) = (0, 0);
+  while i < old.len() && j < new.len() {
+    if old[i] == new[j] {
+      old_shared[i] = true;
+      new_shared[j] = true;
+      i += 1;
+      j += 1;
+    } else if lengths[(i + 1) * width + j] >= lengths[i * width + j + 1] {
+      i += 1;
+    } else {
+      j += 1;
+    }
+  }
+  (old_shared, new_shared)
+}
+
+
+/// Determine the parts of a name of `len` characters to show in
+/// `width` columns, keeping the changed characters, between the indices
+/// in `changed` (inclusive), visible where possible.
+///
+/// Names are truncated in the middle, as they tend to be recognizable
+/// by their beginning and end, but only if the changes are not located
+/// there. Otherwise the unchanged parts around the changes are elided
+/// instead. Returned are the two ranges of characters to show, the
+/// latter always ending with the name. Each gap before or between them
+/// takes up one column, for an ellipsis.
+fn fit(len: usize, changed: Option<(usize, usize)>, width: Option<usize>) -> [Range<usize>; 2] {
+  let width = match width {
+    Some(width) if len > width && width >= 3 => width,
+    _ => return [0..len, len..len],
+  };
+
+  // The number of characters shown alongside a single ellipsis.
+  let shown = width - 1;
+  let head = shown.div_ceil(2);
+  match changed {
+    None => [0..head, len - (shown - head)..len],
+    Some((_, last)) if last < shown => {
+      let head = head.max(last + 1);
+      [0..head, len - (shown - head)..len]
+    },
+    Some((first, _)) if len - first <= shown => {
+      let tail = (shown - head).max(len - first);
+      [0..shown - tail, len - tail..len]
+    },
+    Some((first, last)) => {
+      // The changes are in the middle, so show them with an ellipsis
+      // on either side.
+      let shown = width - 2;
+      let span = last + 1 - first;
+      let start = first
+        .saturating_sub(shown.saturating_sub(span) / 2)
+        .clamp(1, len - shown - 1);
+      [start..start + shown, len..len]
+    },
+  }
+}
+
+
+/// Render the parts of `chars` in `ranges`, styling shared characters
+/// as unchanged and all others using `changed`.
+fn render(
+  chars: &[&str],
+  shared: &[bool],
+  ranges: &[Range<usize>],
+  changed: &str,
+  theme: Theme,
+) -> String {
+  let mut visible = Vec::with_capacity(chars.len());
+  let mut end = 0;
+  for range in ranges {
+    if range.start > end {
+      let () = visible.push((ELLIPSIS, true));
+    }
+    let () = visible.extend(range.clone().map(|idx| (chars[idx], shared[idx])));
+    end = range.end;
+  }
+
+  let mut s = String::with_capacity(visible.len() * 2);
+  let mut current = None;
+  for (c, shared) in visible {
+    let style = if shared { theme.unchanged() } else { changed };
+    if current != Some(style) {
+      // Styles may set different attributes, so reset the previous one
+      // first.
+      let () = s.push_str(theme.reset());
+      let () = s.push_str(style);
+      current = Some(style);
+    }
+    let () = s.push_str(c);
+  }
+  let () = s.push_str(theme.reset());
+  s
+}
+
+
+/// Determine the indices of the first and last of the characters not
+/// `shared`, if any.
+fn changed(shared: &[bool]) -> Option<(usize, usize)> {
+  let first = shared.iter().position(|shared| !shared)?;
+  // SANITY: There is at least one character not shared.
+  let last = shared.iter().rposition(|shared| !shared).unwrap();
+  Some((first, last))
+}
+
+
+/// Style `old` and `new` for display, highlighting the characters
+/// removed from `old` and those inserted into `new`.
+///
+/// Each name is truncated to the respective width in `widths`, if any,
+/// keeping the highlighted characters visible where possible.
+fn highlight(
+  old: &str,
+  new: &str,
+  widths: (Option<usize>, Option<usize>),
+  theme: Theme,
+) -> (String, String) {
+  let old = graphemes(old).collect::<Vec<_>>();
+  let new = graphemes(new).collect::<Vec<_>>();
+  let (old_shared, new_shared) = if (old.len() + 1).saturating_mul(new.len() + 1) <= MAX_CELLS {
+    common(&old, &new)
+  } else {
+    (vec![true; old.len()], vec![true; new.len()])
+  };
+  let old_ranges = fit(old.len(), changed(&old_shared), widths.0);
+  let new_ranges = fit(new.len(), changed(&new_shared), widths.1);
+  (
+    render(&old, &old_shared, &old_ranges, theme.removed(), theme),
+    render(&new, &new_shared, &new_ranges, theme.inserted(), theme),
+  )
+}
+
+
+/// Style the names of a file along a chain of renames for display,
+/// highlighting in each name the characters inserted compared to the
+/// previous one, and in the first those removed by the first rename.
+///
+/// Each name is truncated to the respective width in `widths`, if any.
+/// `names` has to contain at least two names and `widths` as many
+/// widths.
+pub(crate) fn highlight_chain(
+  names: &[String],
+  widths: &[Option<usize>],
+  theme: Theme,
+) -> Vec<String> {
+  let mut styled = Vec::with_capacity(names.len());
+  for (pair, widths) in names.windows(2).zip(widths.windows(2)) {
+    let (old, new) = highlight(&pair[0], &pair[1], (widths[0], widths[1]), theme);
+    if styled.is_empty() {
+      let () = styled.push(old);
+    }
+    let () = styled.push(new);
+  }
+  styled
+}
@@ -0,0 +1,284 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! A line based JSON protocol over stdio, allowing editors to use the
+//! program as a backend for renaming files.
+//!
+//! Each request is a single line containing a JSON object with an `id`
+//! (echoed back verbatim) and a `method`. Supported methods are:
+//! - `plan`: compute new names for `files`, optionally using `command`
+//!   instead of the one provided on the command line
+//! - `decide`: accept or decline planned entries via `decisions`, a
+//!   list of `{"src": ..., "accept": ...}` objects
+//! - `apply`: apply all accepted entries of the current plan
+//!
+//! Each response is a single line containing a JSON object with the
+//! request's `id` and either a `result` or an `error`.
+
+use std::ffi::OsString;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use anyhow::bail;
+use anyhow::Context as _;
+use anyhow::Error;
+use anyhow::Result;
+
+use batch_renamer::concurrency::buffered;
+use batch_renamer::journal::Journal;
+use batch_renamer::rename_with;
+use batch_renamer::resolve;
+use batch_renamer::RenameOpts;
+
+use futures::stream;
+use futures::stream::StreamExt as _;
+
+use serde::Deserialize;
+use serde::Serialize;
+use serde_json::json;
+use serde_json::Value;
+
+use tokio::io::stdin;
+use tokio::io::stdout;
+use tokio::io::AsyncBufReadExt as _;
+use tokio::io::AsyncWriteExt as _;
+use tokio::io::BufReader;
+
+use crate::apply;
+use crate::hook::Hooks;
+
+
+/// A method invoked by a request.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "method", rename_all = "snake_case")]
+enum Method {
+  Plan {
+    files: Vec<PathBuf>,
+    #[serde(default)]
+    command: Option<Vec<String>>,
+  },
+  Decide {
+    decisions: Vec<Decision>,
+  },
+  Apply,
+}
+
+
+/// A request as sent by the editor.
+#[derive(Debug, Deserialize)]
+struct Request {
+  #[serde(default)]
+  id: Value,
+  #[serde(flatten)]
+  method: Method,
+}
+
+
+/// A decision about a planned entry.
+#[derive(Debug, Deserialize)]
+struct Decision {
+  src: PathBuf,
+  accept: bool,
+}
+
+
+/// The outcome of a request.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum Outcome {
+  Result(Value),
+  Error(String),
+}
+
+
+/// A response as sent to the editor.
+#[derive(Debug, Serialize)]
+struct Response {
+  id: Value,
+  #[serde(flatten)]
+  outcome: Outcome,
+}
+
+
+/// An entry of the current plan.
+#[derive(Debug)]
+struct Entry {
+  src: PathBuf,
+  dst: PathBuf,
+  command: Vec<OsString>,
+  accept: bool,
+}
+
+
+/// The state of a protocol session.
+struct State<'cmd> {
+  /// The command provided on the command line, if any.
+  command: &'cmd [OsString],
+  /// The options to use for renaming.
+  opts: &'cmd RenameOpts,
+  /// The hooks to run around each applied rename.
+  hooks: &'cmd Hooks,
+  /// The journal to record applied renames in.
+  journal: Option<&'cmd Mutex<Journal>>,
+  /// The current plan.
+  plan: Vec<Entry>,
+}
+
+impl State<'_> {
+  async fn plan(&mut self, files: Vec<PathBuf>, command: Option<Vec<String>>) -> Result<Value> {
+    let command = command
+      .map(|command| command.into_iter().map(OsString::from).collect())
+      .unwrap_or_else(|| self.command.to_vec());
+    if command.is_empty() {
+      bail!("no rename command provided")
+    }
+
+    let mut opts = self.opts.clone();
+    opts.dry_run = true;
+    let results = stream::iter(files).map(|file| {
+      let (command, opts) = (&command, &opts);
+      async move {
+        let result = async {
+          let abs_src = resolve(&file, opts.symlinks).await?;
+          let dst = rename_with(&abs_src, command, opts).await?;
+          Result::<_, Error>::Ok((abs_src, dst))
+        }
+        .await;
+        (file, result)
+      }
+    });
+    let results = buffered(results, self.opts.plan_concurrency)
+      .collect::<Vec<_>>()
+      .await;
+
+    let mut plan = Vec::with_capacity(results.len());
+    let mut entries = Vec::with_capacity(results.len());
+    for (src, result) in results {
+      match result {
+        Ok((abs_src, dst)) => {
+          let changed = abs_src != dst;
+          let () = entries.push(json!({"src": src, "dst": dst, "changed": changed}));
+          let () = plan.push(Entry {
+            src,
+            dst,
+            command: command.clone(),
+            accept: changed,
+          });
+        },
+        Err(err) => {
+          let () = entries.push(json!({"src": src, "error": format!("{err:#}")}));
+        },
+      }
+    }
+
+    self.plan = plan;
+    Ok(json!({ "entries": entries }))
+  }
+
+  fn decide(&mut self, decisions: Vec<Decision>) -> Result<Value> {
+    for Decision { src, accept } in decisions {
+      let entry = self
+        .plan
+        .iter_mut()
+        .find(|entry| entry.src == src)
+        .with_context(|| format!("`{}` is not part of the plan", src.display()))?;
+      entry.accept = accept;
+    }
+
+    let accepted = self.plan.iter().filter(|entry| entry.accept).count();
+    Ok(json!({ "accepted": accepted }))
+  }
+
+  async fn apply(&mut self) -> Result<Value> {
+    let (accepted, declined) = self
+      .plan
+      .drain(..)
+      .partition::<Vec<_>, _>(|entry| entry.accept);
+    self.plan = declined;
+
+    let opts = self.opts;
+    let hooks = self.hooks;
+    let journal = self.journal;
+    let results = stream::iter(accepted).map(|entry| async move {
+      let result = apply(&entry.src, &entry.dst, &entry.command, opts, hooks, journal).await;
+      (entry, result)
+    });
+    let results = buffered(results, opts.apply_concurrency)
+      .collect::<Vec<_>>()
+      .await;
+
+    let mut applied = Vec::new();
+    let mut failed = Vec::new();
+    for (entry, result) in results {
+      match result {
+        Ok(dst) => applied.push(json!({"src": entry.src, "dst": dst})),
+        Err(err) => failed.push(json!({"src": entry.src, "error": format!("{err:#}")})),
+      }
+    }
+
+    Ok(json!({"applied": applied, "failed": failed}))
+  }
+
+  async fn handle(&mut self, line: &str) -> Response {
+    let request = match serde_json::from_str::<Request>(line) {
+      Ok(request) => request,
+      Err(err) => {
+        return Response {
+          id: Value::Null,
+          outcome: Outcome::Error(format!("invalid request: {err}")),
+        }
+      },
+    };
+
+    let result = match request.method {
+      Method::Plan { files, command } => self.plan(files, command).await,
+      Method::Decide { decisions } => self.decide(decisions),
+      Method::Apply => self.apply().await,
+    };
+
+    let outcome = match result {
+      Ok(value) => Outcome::Result(value),
+      Err(err) => Outcome::Error(format!("{err:#}")),
+    };
+
+    Response {
+      id: request.id,
+      outcome,
+    }
+  }
+}
+
+
+/// Serve requests read from stdin until it is closed.
+pub(crate) async fn serve(
+  command: &[OsString],
+  opts: &RenameOpts,
+  hooks: &Hooks,
+  journal: Option<&Mutex<Journal>>,
+) -> Result<()> {
+  let mut state = State {
+    command,
+    opts,
+    hooks,
+    journal,
+    plan: Vec::new(),
+  };
+  let mut lines = BufReader::new(stdin()).lines();
+  let mut stdout = stdout();
+
+  while let Some(line) = lines.next_line().await.context("failed to read request")? {
+    if line.trim().is_empty() {
+      continue
+    }
+
+    let response = state.handle(&line).await;
+    let mut response = serde_json::to_vec(&response).context("failed to serialize response")?;
+    let () = response.push(b'\n');
+    let () = stdout
+      .write_all(&response)
+      .await
+      .context("failed to write response")?;
+    let () = stdout.flush().await.context("failed to flush stdout")?;
+  }
+  Ok(())
+}
@@ -0,0 +1,48 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Integration with git repositories.
+
+use std::path::PathBuf;
+use std::process::Stdio;
+
+use anyhow::Context as _;
+use anyhow::Result;
+
+use batch_renamer::evaluate;
+use batch_renamer::os_string_from_bytes;
+
+use tokio::process::Command;
+
+
+/// Run git with the given arguments in the current directory,
+/// returning its output.
+async fn git(args: &[&str]) -> Result<Vec<u8>> {
+  let output = Command::new("git")
+    .args(args)
+    .stdin(Stdio::null())
+    .stderr(Stdio::piped())
+    .output()
+    .await
+    .context("failed to run `git`")?;
+  let () = evaluate(&output, "git", args)?;
+  Ok(output.stdout)
+}
+
+
+/// Retrieve the files added to the index of the repository containing
+/// the current directory, including those staged with a new name.
+///
+/// Returned paths are absolute.
+pub(crate) async fn staged_files() -> Result<Vec<PathBuf>> {
+  let toplevel = git(&["rev-parse", "--show-toplevel"]).await?;
+  let toplevel = toplevel.strip_suffix(b"\n").unwrap_or(&toplevel);
+  let toplevel = PathBuf::from(os_string_from_bytes(toplevel)?);
+
+  let staged = git(&["diff", "--cached", "--name-only", "--diff-filter=AR", "-z"]).await?;
+  staged
+    .split(|b| *b == b'\0')
+    .filter(|file| !file.is_empty())
+    .map(|file| Ok(toplevel.join(os_string_from_bytes(file)?)))
+    .collect()
+}
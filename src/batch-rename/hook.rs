@@ -0,0 +1,133 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! User provided commands run before and after each applied rename.
+
+use std::ffi::OsStr;
+use std::ffi::OsString;
+use std::path::Path;
+use std::process::Stdio;
+use std::sync::Arc;
+
+use anyhow::Context as _;
+use anyhow::Result;
+
+use batch_renamer::evaluate;
+use batch_renamer::output::Summary;
+
+use tokio::process::Command;
+
+use crate::audit::AuditLog;
+use crate::notifier::Notifiers;
+use crate::state::State;
+
+
+/// The shell used for interpreting hooks, along with the arguments
+/// preceding the hook.
+#[cfg(not(windows))]
+const SHELL: (&str, &[&str]) = ("sh", &["-c"]);
+#[cfg(windows)]
+const SHELL: (&str, &[&str]) = ("cmd", &["/C"]);
+
+
+/// Run `hook` using the shell, with the variables in `env` added to
+/// its environment.
+async fn run(hook: &OsString, env: &[(&str, &OsStr)]) -> Result<()> {
+  let (shell, args) = SHELL;
+  let args = args
+    .iter()
+    .map(OsString::from)
+    .chain([hook.clone()])
+    .collect::<Vec<_>>();
+  let output = Command::new(shell)
+    .args(&args)
+    .envs(env.iter().copied())
+    .stdin(Stdio::null())
+    .stdout(Stdio::null())
+    .stderr(Stdio::piped())
+    .output()
+    .await
+    .with_context(|| format!("failed to run hook `{}`", hook.to_string_lossy()))?;
+  let () = evaluate(&output, shell, &args)?;
+  Ok(())
+}
+
+
+/// The environment passing `old` and `new` to a hook via the `BR_OLD`
+/// and `BR_NEW` variables.
+fn env<'p>(old: &'p Path, new: &'p Path) -> [(&'static str, &'p OsStr); 2] {
+  [("BR_OLD", old.as_os_str()), ("BR_NEW", new.as_os_str())]
+}
+
+
+/// Run the `notify` command, if any file got renamed as per `summary`,
+/// passing the counts of files renamed, skipped, and failed via the
+/// `BR_RENAMED`, `BR_SKIPPED`, and `BR_FAILED` environment variables.
+pub(crate) async fn notify(notify: &OsString, summary: &Summary) -> Result<()> {
+  if summary.renamed == 0 {
+    return Ok(())
+  }
+
+  let renamed = OsString::from(summary.renamed.to_string());
+  let skipped = OsString::from(summary.skipped.to_string());
+  let failed = OsString::from(summary.failed.to_string());
+  let env = [
+    ("BR_RENAMED", renamed.as_os_str()),
+    ("BR_SKIPPED", skipped.as_os_str()),
+    ("BR_FAILED", failed.as_os_str()),
+  ];
+  run(notify, &env)
+    .await
+    .context("notification command failed")
+}
+
+
+/// The hooks to run around each applied rename.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Hooks {
+  /// The command to run before a rename.
+  pub pre: Option<OsString>,
+  /// The command to run after a rename.
+  pub post: Option<OsString>,
+  /// The audit log to record each applied rename in.
+  pub log: Option<Arc<AuditLog>>,
+  /// The state to record each applied rename in, for resuming an
+  /// interrupted invocation.
+  pub state: Option<Arc<State>>,
+  /// The parties to notify about each applied rename.
+  pub notifiers: Notifiers,
+}
+
+impl Hooks {
+  /// Run the pre-rename hook, if any, for the rename of `old` to
+  /// `new`.
+  ///
+  /// A failing hook is meant to prevent the rename.
+  pub(crate) async fn pre(&self, old: &Path, new: &Path) -> Result<()> {
+    match &self.pre {
+      Some(hook) => run(hook, &env(old, new))
+        .await
+        .with_context(|| format!("pre-hook failed for `{}`", old.display())),
+      None => Ok(()),
+    }
+  }
+
+  /// Record the rename of `old` to `new` in the audit log and state,
+  /// notify interested parties, and run the post-rename hook, if any.
+  pub(crate) async fn post(&self, old: &Path, new: &Path) -> Result<()> {
+    if let Some(log) = &self.log {
+      let () = log.record(old, new)?;
+    }
+    if let Some(state) = &self.state {
+      let () = state.renamed(old, new)?;
+    }
+    let () = self.notifiers.renamed(old, new).await;
+
+    match &self.post {
+      Some(hook) => run(hook, &env(old, new))
+        .await
+        .with_context(|| format!("post-hook failed for `{}`", new.display())),
+      None => Ok(()),
+    }
+  }
+}
@@ -0,0 +1,96 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Requests to web services, made by means of curl.
+
+use std::process::Stdio;
+
+use anyhow::Context as _;
+use anyhow::Result;
+
+use batch_renamer::evaluate;
+
+use tokio::io::AsyncWriteExt as _;
+use tokio::process::Command;
+
+
+/// The program used for making requests.
+const CURL: &str = "curl";
+/// The maximum number of seconds a request may take.
+const MAX_TIME: &str = "30";
+
+
+/// Quote `s` for usage as a value in a curl configuration.
+fn quote(s: &str) -> String {
+  let mut quoted = String::with_capacity(s.len() + 2);
+  let () = quoted.push('"');
+  for c in s.chars() {
+    let () = match c {
+      '"' => quoted.push_str("\\\""),
+      '\\' => quoted.push_str("\\\\"),
+      '\n' => quoted.push_str("\\n"),
+      '\r' => quoted.push_str("\\r"),
+      '\t' => quoted.push_str("\\t"),
+      c => quoted.push(c),
+    };
+  }
+  let () = quoted.push('"');
+  quoted
+}
+
+
+/// Make an HTTP request with the given `method` to `url`, with
+/// `headers` and an optional `body`, returning the response body.
+///
+/// The request is passed to curl via its standard input, so that
+/// secrets, such as access tokens in headers, do not show up in the
+/// list of processes.
+pub(crate) async fn request(
+  method: &str,
+  url: &str,
+  headers: &[String],
+  body: Option<&str>,
+) -> Result<Vec<u8>> {
+  let mut config = format!("url = {}\nrequest = {}\n", quote(url), quote(method));
+  for header in headers {
+    let () = config.push_str(&format!("header = {}\n", quote(header)));
+  }
+  if let Some(body) = body {
+    let () = config.push_str(&format!("data-raw = {}\n", quote(body)));
+  }
+
+  let args = [
+    "--fail",
+    "--silent",
+    "--show-error",
+    "--proto",
+    "=http,https",
+    "--max-time",
+    MAX_TIME,
+    "--config",
+    "-",
+  ];
+  let mut child = Command::new(CURL)
+    .args(args)
+    .stdin(Stdio::piped())
+    .stdout(Stdio::piped())
+    .stderr(Stdio::piped())
+    .spawn()
+    .with_context(|| format!("failed to run `{CURL}`"))?;
+  // SANITY: The child's stdin got piped above.
+  let mut stdin = child.stdin.take().unwrap();
+  let () = stdin
+    .write_all(config.as_bytes())
+    .await
+    .with_context(|| format!("failed to pass request to `{CURL}`"))?;
+  // Close stdin, so that curl sees the end of its configuration.
+  let () = drop(stdin);
+
+  let output = child
+    .wait_with_output()
+    .await
+    .with_context(|| format!("failed to wait for `{CURL}`"))?;
+  let () =
+    evaluate(&output, CURL, args).with_context(|| format!("{method} request to `{url}` failed"))?;
+  Ok(output.stdout)
+}
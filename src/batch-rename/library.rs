@@ -0,0 +1,194 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Refreshing of the libraries of media servers for the directories
+//! touched by renames.
+
+use std::collections::BTreeSet;
+use std::mem::take;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::sync::PoisonError;
+
+use anyhow::Context as _;
+use anyhow::Result;
+
+use batch_renamer::config::MediaServer;
+use batch_renamer::config::MediaServerKind;
+
+use serde_json::json;
+use serde_json::Value;
+
+use tracing::debug;
+
+use crate::http::request;
+
+
+/// Percent-encode `s` for usage in the query of a URL.
+fn encode(s: &str) -> String {
+  s.bytes()
+    .map(|b| match b {
+      b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+        char::from(b).to_string()
+      },
+      b => format!("%{b:02X}"),
+    })
+    .collect()
+}
+
+
+/// Parse the JSON response of `server` to a request for `what`.
+fn parse(response: &[u8], server: &MediaServer, what: &str) -> Result<Value> {
+  serde_json::from_slice(response).with_context(|| {
+    format!(
+      "failed to parse {what} reported by {} server `{}`",
+      server.kind, server.url
+    )
+  })
+}
+
+
+/// Check whether any of the library `locations` contains `dir`.
+fn contains<'v, I>(locations: I, dir: &Path) -> bool
+where
+  I: IntoIterator<Item = &'v Value>,
+{
+  locations
+    .into_iter()
+    .filter_map(Value::as_str)
+    .any(|location| dir.starts_with(location))
+}
+
+
+/// Tell the Jellyfin `server` about changes to the directories in
+/// `dirs` belonging to any of its libraries.
+async fn refresh_jellyfin(server: &MediaServer, dirs: &BTreeSet<PathBuf>) -> Result<()> {
+  let headers = [
+    format!("Authorization: MediaBrowser Token=\"{}\"", server.token),
+    "Content-Type: application/json".to_string(),
+  ];
+  let url = format!("{}/Library/VirtualFolders", server.url);
+  // The content type only applies to requests with a body.
+  let response = request("GET", &url, &headers[..1], None).await?;
+  let folders = parse(&response, server, "libraries")?;
+  let folders = folders.as_array().map(Vec::as_slice).unwrap_or_default();
+
+  let updates = dirs
+    .iter()
+    .filter(|dir| {
+      folders.iter().any(|folder| {
+        let locations = folder["Locations"].as_array().into_iter().flatten();
+        contains(locations, dir)
+      })
+    })
+    .map(|dir| json!({"Path": dir.to_string_lossy(), "UpdateType": "Modified"}))
+    .collect::<Vec<_>>();
+  if updates.is_empty() {
+    return Ok(())
+  }
+
+  debug!(
+    url = server.url,
+    count = updates.len(),
+    "refreshing Jellyfin libraries"
+  );
+  let body = json!({ "Updates": updates }).to_string();
+  let url = format!("{}/Library/Media/Updated", server.url);
+  let _response = request("POST", &url, &headers, Some(&body)).await?;
+  Ok(())
+}
+
+
+/// Have the Plex `server` scan the directories in `dirs` belonging to
+/// any of its libraries.
+async fn refresh_plex(server: &MediaServer, dirs: &BTreeSet<PathBuf>) -> Result<()> {
+  let headers = [
+    format!("X-Plex-Token: {}", server.token),
+    "Accept: application/json".to_string(),
+  ];
+  let url = format!("{}/library/sections", server.url);
+  let response = request("GET", &url, &headers, None).await?;
+  let sections = parse(&response, server, "libraries")?;
+  let sections = sections["MediaContainer"]["Directory"]
+    .as_array()
+    .map(Vec::as_slice)
+    .unwrap_or_default();
+
+  for dir in dirs {
+    let section = sections.iter().find(|section| {
+      let locations = section["Location"].as_array().into_iter().flatten();
+      contains(locations.map(|location| &location["path"]), dir)
+    });
+    let Some(key) = section.and_then(|section| section["key"].as_str()) else {
+      continue
+    };
+
+    debug!(url = server.url, section = key, dir = %dir.display(), "refreshing Plex library");
+    let url = format!(
+      "{}/library/sections/{}/refresh?path={}",
+      server.url,
+      encode(key),
+      encode(&dir.to_string_lossy())
+    );
+    let _response = request("GET", &url, &headers, None).await?;
+  }
+  Ok(())
+}
+
+
+/// The libraries of media servers to refresh for the directories
+/// touched by applied renames.
+#[derive(Debug)]
+pub(crate) struct Libraries {
+  /// The servers whose libraries to refresh.
+  servers: Vec<MediaServer>,
+  /// The directories touched by renames applied so far.
+  dirs: Mutex<BTreeSet<PathBuf>>,
+}
+
+impl Libraries {
+  /// Create libraries for refreshing those of `servers`.
+  pub(crate) fn new(servers: Vec<MediaServer>) -> Self {
+    Self {
+      servers,
+      dirs: Mutex::new(BTreeSet::new()),
+    }
+  }
+
+  /// Remember the directories touched by the rename of `src` to `dst`.
+  pub(crate) fn renamed(&self, src: &Path, dst: &Path) {
+    let mut dirs = self.dirs.lock().unwrap_or_else(PoisonError::into_inner);
+    for path in [src, dst] {
+      if let Some(dir) = path.parent() {
+        let _inserted = dirs.insert(dir.to_path_buf());
+      }
+    }
+  }
+
+  /// Refresh the libraries of all servers for the directories touched
+  /// by renames, if any.
+  ///
+  /// Only directories belonging to a library are reported to the
+  /// respective server.
+  pub(crate) async fn refresh(&self) -> Result<()> {
+    let dirs = take(&mut *self.dirs.lock().unwrap_or_else(PoisonError::into_inner));
+    if dirs.is_empty() {
+      return Ok(())
+    }
+
+    for server in &self.servers {
+      let result = match server.kind {
+        MediaServerKind::Jellyfin => refresh_jellyfin(server, &dirs).await,
+        MediaServerKind::Plex => refresh_plex(server, &dirs).await,
+      };
+      let () = result.with_context(|| {
+        format!(
+          "failed to refresh libraries of {} server `{}`",
+          server.kind, server.url
+        )
+      })?;
+    }
+    Ok(())
+  }
+}
@@ -0,0 +1,2620 @@
+// Copyright (C) 2024 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+#![allow(clippy::let_and_return, clippy::let_unit_value)]
+
+mod analysis;
+#[cfg(feature = "archive")]
+mod archive;
+mod args;
+mod audit;
+mod compare;
+mod diff;
+mod editor;
+mod git;
+mod hook;
+mod http;
+mod library;
+mod manifest;
+mod notifier;
+mod order;
+mod progress;
+mod reference;
+#[cfg(all(target_os = "linux", feature = "landlock"))]
+mod restrict;
+mod review;
+mod saved;
+mod sequence;
+mod service;
+mod shell;
+#[cfg(target_os = "linux")]
+mod snapshot;
+mod state;
+mod suggest;
+mod term;
+mod theme;
+mod tui;
+#[cfg(feature = "self-update")]
+mod update;
+mod webhook;
+mod wizard;
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::env::args_os;
+use std::env::current_dir;
+#[cfg(all(target_os = "linux", feature = "landlock"))]
+use std::env::temp_dir;
+use std::ffi::OsStr;
+use std::ffi::OsString;
+use std::future::ready;
+use std::io::stderr;
+use std::io::stdout;
+use std::io::IsTerminal as _;
+use std::iter::repeat_n;
+use std::num::NonZeroUsize;
+#[cfg(all(target_os = "linux", feature = "landlock"))]
+use std::path::absolute;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::exit;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::MutexGuard;
+use std::sync::PoisonError;
+use std::time::Duration;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+use anyhow::bail;
+use anyhow::ensure;
+use anyhow::Context as _;
+use anyhow::Error;
+use anyhow::Result;
+
+use batch_renamer::backup;
+use batch_renamer::change::classify;
+use batch_renamer::change::Change;
+use batch_renamer::command_line;
+use batch_renamer::concurrency::buffered;
+use batch_renamer::concurrency::Concurrency;
+use batch_renamer::concurrency::Pipeline;
+use batch_renamer::config::Config;
+use batch_renamer::conflict::resolve_conflicts;
+use batch_renamer::conflict::Conflict;
+use batch_renamer::conflict::ConflictPolicy;
+use batch_renamer::conflict::Details;
+use batch_renamer::exchange;
+use batch_renamer::grapheme::graphemes;
+use batch_renamer::journal;
+use batch_renamer::journal::Drift;
+use batch_renamer::journal::Journal;
+use batch_renamer::journal::Session;
+use batch_renamer::journal::Verification;
+use batch_renamer::lint::Lints;
+use batch_renamer::lint::Severity;
+use batch_renamer::os_string_from_bytes;
+use batch_renamer::output::Format;
+use batch_renamer::output::Report;
+use batch_renamer::output::Status;
+use batch_renamer::output::Summary;
+use batch_renamer::plan::case_collisions;
+use batch_renamer::plan::is_within;
+use batch_renamer::plan::normalize;
+use batch_renamer::plan::split_blocked;
+use batch_renamer::plan::split_swaps;
+use batch_renamer::policy;
+use batch_renamer::policy::Policy;
+use batch_renamer::propose_batch;
+use batch_renamer::propose_chain;
+use batch_renamer::rename_replacing;
+use batch_renamer::rename_transaction_replacing;
+use batch_renamer::rename_with;
+use batch_renamer::resolve;
+use batch_renamer::retry::RetryPolicy;
+use batch_renamer::sanitize::Sanitizer;
+use batch_renamer::template::Template;
+use batch_renamer::traverse::traverse;
+use batch_renamer::traverse::Filter;
+use batch_renamer::traverse::TraverseOpts;
+use batch_renamer::Log as _;
+use batch_renamer::Protocol;
+use batch_renamer::RenameOpts;
+use batch_renamer::SymlinkPolicy;
+
+use chrono::DateTime;
+use chrono::Local;
+
+use clap::error::ErrorKind;
+
+use crossterm::terminal::size;
+
+use futures::stream;
+use futures::stream::LocalBoxStream;
+use futures::stream::StreamExt as _;
+use futures::TryStreamExt as _;
+
+#[cfg(all(target_os = "linux", feature = "landlock"))]
+use tokio::fs::create_dir_all;
+use tokio::fs::metadata;
+use tokio::fs::read;
+use tokio::fs::remove_file;
+use tokio::io::stdin;
+use tokio::io::AsyncReadExt as _;
+use tokio::runtime::Builder;
+use tokio::select;
+use tokio::signal::ctrl_c;
+use tokio::task::spawn_blocking;
+
+use tracing_subscriber::filter::LevelFilter;
+
+use crate::args::Args;
+use crate::audit::AuditLog;
+use crate::diff::highlight_chain;
+use crate::hook::Hooks;
+use crate::library::Libraries;
+use crate::notifier::Notifiers;
+use crate::progress::Progress;
+use crate::reference::References;
+use crate::review::proposed_path;
+use crate::sequence::Sequences;
+use crate::shell::quote_shell;
+use crate::state::State;
+use crate::term::edit_line;
+use crate::term::read_key;
+use crate::term::Answers;
+use crate::term::Input;
+use crate::term::Key;
+use crate::term::Line;
+use crate::theme::Theme;
+use crate::webhook::Webhooks;
+
+
+/// Lock a mutex, ignoring poisoning.
+///
+/// A poisoned lock only means that a previous holder panicked; the
+/// state we protect is still usable.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+  mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+
+/// Record the rename of `src` to `dst` along with the user provided
+/// `note` in `journal`, if provided.
+async fn record_annotated(
+  journal: Option<&Mutex<Journal>>,
+  src: &Path,
+  dst: &Path,
+  note: Option<&str>,
+) -> Result<()> {
+  if let Some(journal) = journal {
+    let digest = Journal::digest(journal, dst).await?;
+    let () = lock(journal).record_annotated(src, dst, digest, note)?;
+  }
+  Ok(())
+}
+
+
+/// Record that the proposed rename of `src` to `dst` got declined,
+/// along with the user provided `note`, in `journal`, if provided.
+fn record_declined(
+  journal: Option<&Mutex<Journal>>,
+  src: &Path,
+  dst: &Path,
+  note: &str,
+) -> Result<()> {
+  if let Some(journal) = journal {
+    let () = lock(journal).record_declined(src, dst, note)?;
+  }
+  Ok(())
+}
+
+
+/// Back up the file at `path` before it gets renamed, if requested in
+/// `opts`.
+async fn back_up(path: &Path, opts: &RenameOpts) -> Result<()> {
+  if let Some(suffix) = &opts.backup {
+    let _backup = backup(path, suffix).await?;
+  }
+  Ok(())
+}
+
+
+/// Exchange the names of `a` and `b`, running `hooks` for both files,
+/// and record the exchange in `journal`, if provided.
+async fn swap(
+  a: &Path,
+  b: &Path,
+  opts: &RenameOpts,
+  hooks: &Hooks,
+  journal: Option<&Mutex<Journal>>,
+) -> Result<()> {
+  let () = hooks.pre(a, b).await?;
+  let () = hooks.pre(b, a).await?;
+  let () = back_up(a, opts).await?;
+  let () = back_up(b, opts).await?;
+  if let Some(journal) = journal {
+    let () = lock(journal).intend_exchange(a, b)?;
+  }
+  if let Err(err) = exchange(a, b).await {
+    let _result = journal.abandon(a, b);
+    return Err(err.into())
+  }
+  if let Some(journal) = journal {
+    let digest = Journal::digest(journal, b).await?;
+    let src_digest = Journal::digest(journal, a).await?;
+    let () = lock(journal).record_exchange(a, b, digest, src_digest)?;
+  }
+  let () = hooks.post(a, b).await?;
+  let () = hooks.post(b, a).await?;
+  Ok(())
+}
+
+
+/// Rename `src` using `command`, running `hooks` around it, and record
+/// the rename in `journal`, if provided.
+///
+/// `dst` is the new path planned for `src`, as passed to the pre-rename
+/// hook.
+async fn apply(
+  src: &Path,
+  dst: &Path,
+  command: &[OsString],
+  opts: &RenameOpts,
+  hooks: &Hooks,
+  journal: Option<&Mutex<Journal>>,
+) -> Result<PathBuf> {
+  apply_annotated(src, dst, command, opts, None, hooks, journal).await
+}
+
+
+/// Rename `src` using `command`, running `hooks` around it, and record
+/// the rename along with the user provided `note` in `journal`, if
+/// provided.
+async fn apply_annotated(
+  src: &Path,
+  dst: &Path,
+  command: &[OsString],
+  opts: &RenameOpts,
+  note: Option<&str>,
+  hooks: &Hooks,
+  journal: Option<&Mutex<Journal>>,
+) -> Result<PathBuf> {
+  let mut opts = opts.clone();
+  opts.dry_run = false;
+  let abs_src = resolve(src, opts.symlinks).await?;
+  let () = hooks.pre(&abs_src, dst).await?;
+  let () = journal.intend(&abs_src, dst)?;
+  let path = match rename_with(src, command, &opts).await {
+    Ok(path) => path,
+    Err(err) => {
+      let _result = journal.abandon(&abs_src, dst);
+      return Err(err.into())
+    },
+  };
+  if path != abs_src {
+    let () = record_annotated(journal, &abs_src, &path, note).await?;
+    let () = hooks.post(&abs_src, &path).await?;
+  } else {
+    let () = journal.abandon(&abs_src, dst)?;
+  }
+  Ok(path)
+}
+
+
+/// The directory that renamed files have to stay within.
+#[derive(Clone, Debug)]
+enum Root {
+  /// Files have to stay within the directory they reside in.
+  Parent,
+  /// Files have to stay within the given directory.
+  Dir(PathBuf),
+  /// Files may be moved anywhere.
+  Anywhere,
+}
+
+impl Root {
+  /// Ensure that moving the file `src` to `dst` keeps it within the
+  /// root.
+  fn check(&self, src: &Path, dst: &Path) -> Result<()> {
+    let root = match self {
+      Self::Parent => src
+        .parent()
+        .with_context(|| format!("`{}` does not contain a parent", src.display()))?,
+      Self::Dir(dir) => dir,
+      Self::Anywhere => return Ok(()),
+    };
+
+    ensure!(
+      is_within(dst, root),
+      "`{}` would be moved to `{}`, outside of `{}`",
+      src.display(),
+      dst.display(),
+      root.display()
+    );
+    Ok(())
+  }
+
+  /// Determine the directories in which `files` or the sources of
+  /// `fixed_plan` may get renamed.
+  #[cfg(target_os = "linux")]
+  async fn dirs(
+    &self,
+    files: &[PathBuf],
+    fixed_plan: Option<&[(PathBuf, PathBuf, PathBuf)]>,
+    symlinks: SymlinkPolicy,
+  ) -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    match self {
+      Self::Dir(dir) => dirs.push(dir.clone()),
+      Self::Parent | Self::Anywhere => {
+        for file in files {
+          if let Ok(path) = resolve(file, symlinks).await {
+            let () = dirs.extend(path.parent().map(Path::to_path_buf));
+          }
+        }
+        for (_, abs_src, _) in fixed_plan.into_iter().flatten() {
+          let () = dirs.extend(abs_src.parent().map(Path::to_path_buf));
+        }
+      },
+    }
+    dirs
+  }
+}
+
+
+/// Print a message meant for the user.
+///
+/// Messages go to stdout, unless it is used for machine readable
+/// output.
+fn tell(output: Format, message: &str) {
+  if output == Format::Human {
+    println!("{message}")
+  } else {
+    eprintln!("{message}")
+  }
+}
+
+
+/// Determine the width of the terminal that messages meant for the user
+/// are printed to, as per `output`, if they are printed to one.
+fn columns(output: Format) -> Option<usize> {
+  let terminal = if output == Format::Human {
+    stdout().is_terminal()
+  } else {
+    stderr().is_terminal()
+  };
+  if !terminal {
+    return None
+  }
+  size()
+    .ok()
+    .map(|(cols, _lines)| usize::from(cols))
+    .filter(|cols| *cols > 0)
+}
+
+
+/// The user's response to a proposed rename.
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum Response {
+  /// The rename got accepted.
+  Accept,
+  /// The rename got accepted with the new path edited by the user, as
+  /// relative to the directory containing the file (if not absolute).
+  Edit(PathBuf),
+  /// The rename got declined.
+  Decline,
+  /// The rename and all subsequent ones got accepted.
+  AcceptAll,
+  /// The rename got accepted, with a conflict with an existing file to
+  /// be resolved as per the given policy.
+  Resolve(ConflictPolicy),
+  /// The file is to be removed in favor of the identical existing one.
+  Discard,
+  /// The user asked to stop processing further files.
+  Quit,
+  /// The user asked to stop processing further files and to revert
+  /// the renames applied so far.
+  Abort,
+  /// The user interrupted the program.
+  Interrupt,
+}
+
+
+/// Describe the details of a file involved in a conflict.
+fn describe(details: &Details) -> String {
+  let modified = details
+    .modified
+    .map(|time| {
+      DateTime::<Local>::from(time)
+        .format("%Y-%m-%d %H:%M:%S")
+        .to_string()
+    })
+    .unwrap_or_else(|| "unknown".to_string());
+  format!("{:>12} bytes, modified {modified}", details.size)
+}
+
+
+/// Ask the user whether to rename `src` to `dst`.
+///
+/// If `dst` exists already, `conflict` describes both files and the
+/// user may choose how to resolve the conflict. If provided, `command`
+/// describes the rename command that would be run for applying the
+/// rename, which the user may ask to see.
+#[allow(clippy::too_many_arguments)]
+async fn prompt(
+  output: Format,
+  src: &Path,
+  via: &[&Path],
+  dst: &Path,
+  conflict: Option<&Conflict>,
+  command: Option<&str>,
+  input: &Input,
+  theme: Theme,
+) -> Result<Response> {
+  // Highlight what changed, as that can be hard to spot in long names.
+  let names = [src]
+    .iter()
+    .chain(via)
+    .chain([&dst])
+    .map(|path| path.display().to_string())
+    .collect::<Vec<_>>();
+  // Keep each name on a single line, so that names remain aligned.
+  let widths = vec![columns(output); names.len()];
+  let mut styled = highlight_chain(&names, &widths, theme);
+  // SANITY: There is always a styled name for each of `src` and `dst`.
+  let new = styled.pop().unwrap();
+  let old = styled.remove(0);
+  let via = styled
+    .iter()
+    .map(|name| format!("via\n{name}\n"))
+    .collect::<String>();
+  let keys = if command.is_some() {
+    "Y/n/a/e/c/q/Q"
+  } else {
+    "Y/n/a/e/q/Q"
+  };
+  loop {
+    let message = match conflict {
+      None => format!(
+        "Would rename:\n{old}\n{via}to\n{new}\nAccept? ({keys}){}",
+        theme.reset()
+      ),
+      Some(conflict) => format!(
+        "Would rename:\n{old}\n{via}to existing\n{new}\n  \
+         incoming: {}\n  existing: {}\nContents {}\n\
+         Accept? ({keys}), or (o)verwrite, (s)kip, s(u)ffix{}{}",
+        describe(&conflict.src),
+        describe(&conflict.dst),
+        if conflict.identical {
+          "are identical"
+        } else {
+          "differ"
+        },
+        if conflict.identical {
+          ", (d)iscard incoming"
+        } else {
+          ""
+        },
+        theme.reset(),
+      ),
+    };
+    let () = tell(output, &message);
+
+    let key = {
+      let input = input.clone();
+      spawn_blocking(move || read_key(&input)).await??
+    };
+    let response = match key {
+      Key::Enter | Key::Char('y' | 'Y') => Response::Accept,
+      Key::Char('n' | 'N') => Response::Decline,
+      Key::Char('a' | 'A') => Response::AcceptAll,
+      Key::Char('q') => Response::Quit,
+      Key::Char('Q') => Response::Abort,
+      Key::Char('e') => {
+        let Some(initial) = dst.to_str().map(str::to_string) else {
+          let () = tell(output, "Paths that are not valid UTF-8 can't be edited");
+          continue
+        };
+        let input = input.clone();
+        match spawn_blocking(move || edit_line(&initial, &input)).await?? {
+          Line::Text(text) if !text.is_empty() => Response::Edit(PathBuf::from(text)),
+          Line::Text(..) | Line::Cancel => continue,
+          Line::Interrupt => Response::Interrupt,
+        }
+      },
+      Key::Char('c') if command.is_some() => {
+        // SANITY: We just checked that there is a command.
+        let () = tell(output, command.unwrap());
+        continue
+      },
+      Key::Char('o') if conflict.is_some() => Response::Resolve(ConflictPolicy::Overwrite),
+      Key::Char('s') if conflict.is_some() => Response::Resolve(ConflictPolicy::Skip),
+      Key::Char('u') if conflict.is_some() => Response::Resolve(ConflictPolicy::Suffix),
+      Key::Char('d') if conflict.is_some_and(|conflict| conflict.identical) => Response::Discard,
+      Key::Interrupt => Response::Interrupt,
+      Key::Char(c) => {
+        let () = tell(output, &format!("Response '{c}' not understood"));
+        continue
+      },
+    };
+    break Ok(response)
+  }
+}
+
+
+/// Report the outcome of processing the file `src`, with `dst` being
+/// its (proposed) new path, in the given output format, accounting for
+/// it in `summary`.
+fn report(
+  output: Format,
+  summary: &Mutex<Summary>,
+  src: &Path,
+  dst: &Path,
+  status: Status,
+  error: Option<&Error>,
+) -> Result<()> {
+  let change = status.is_rename().then(|| classify(src, dst)).flatten();
+  let () = lock(summary).record(status, change);
+  let report = Report {
+    src: src.to_path_buf(),
+    dst: dst.to_path_buf(),
+    status,
+    change,
+    error: error.map(|err| format!("{err:#}")),
+  };
+  report.write(output, stdout().lock()).map_err(Error::from)
+}
+
+
+/// Report that renaming `src` to `dst` got skipped, because `dst`
+/// exists already.
+fn report_skipped(output: Format, summary: &Mutex<Summary>, src: &Path, dst: &Path) -> Result<()> {
+  if output == Format::Human {
+    let () = tell(
+      output,
+      &format!(
+        "Skipped `{}`: `{}` exists already",
+        src.display(),
+        dst.display()
+      ),
+    );
+  }
+  report(output, summary, src, dst, Status::Skipped, None)
+}
+
+
+/// Report that renaming `src` to `dst` got skipped, because `blocker`
+/// stays in place, as its rename got declined.
+fn report_blocked(
+  output: Format,
+  summary: &Mutex<Summary>,
+  src: &Path,
+  dst: &Path,
+  blocker: &Path,
+) -> Result<()> {
+  if output == Format::Human {
+    let () = tell(
+      output,
+      &format!(
+        "Skipped `{}`: depends on declined rename of `{}`",
+        src.display(),
+        blocker.display()
+      ),
+    );
+  }
+  report(output, summary, src, dst, Status::Blocked, None)
+}
+
+
+/// Remove the file `src`, because a file with identical contents exists
+/// at its new path `dst` already.
+async fn discard(output: Format, summary: &Mutex<Summary>, src: &Path, dst: &Path) -> Result<()> {
+  let () = remove_file(src)
+    .await
+    .with_context(|| format!("failed to remove `{}`", src.display()))?;
+  if output == Format::Human {
+    let () = tell(
+      output,
+      &format!(
+        "Removed `{}`: identical to `{}`",
+        src.display(),
+        dst.display()
+      ),
+    );
+  }
+  report(output, summary, src, dst, Status::Discarded, None)
+}
+
+
+/// Print the renames in `plan`, skipping files whose path would not
+/// change, followed by a summary of the files accounted for in
+/// `summary`, which is machine readable as per `unattended`.
+///
+/// In human readable output, new paths are shown relative to the
+/// directory the file was provided in, as when editing them.
+/// Changes are highlighted using `theme` if stdout is a terminal, in
+/// which case paths are also truncated to fit on a single line.
+fn show_plan(
+  plan: &[(PathBuf, PathBuf, PathBuf)],
+  intermediates: &HashMap<PathBuf, Vec<PathBuf>>,
+  output: Format,
+  theme: Theme,
+  summary: &Mutex<Summary>,
+  unattended: bool,
+) -> Result<()> {
+  let (unchanged, plan) = plan
+    .iter()
+    .partition::<Vec<_>, _>(|(_, abs_src, dst)| abs_src == dst);
+  for _ in unchanged {
+    let () = lock(summary).record(Status::Unchanged, None);
+  }
+
+  if output != Format::Human {
+    for (_, abs_src, dst) in plan {
+      let () = report(output, summary, abs_src, dst, Status::Planned, None)?;
+    }
+    return show_summary(output, &lock(summary), true, unattended)
+  }
+
+  let width = plan
+    .iter()
+    .map(|(src, ..)| graphemes(&src.display().to_string()).count())
+    .max()
+    .unwrap_or(0);
+  // Only highlight changes when printing to a terminal, so that the
+  // output remains usable elsewhere.
+  let color = stdout().is_terminal();
+  let columns = columns(output);
+  // Old paths take up at most half of the terminal's width.
+  let width = columns.map_or(width, |cols| width.min(cols.saturating_sub(3) / 2));
+  for (src, abs_src, dst) in &plan {
+    let via = intermediates
+      .get(abs_src)
+      .map(Vec::as_slice)
+      .unwrap_or_default();
+    let names = [src.display().to_string()]
+      .into_iter()
+      .chain(
+        via
+          .iter()
+          .chain([dst])
+          .map(|path| proposed_path(src, abs_src, path).display().to_string()),
+      )
+      .collect::<Vec<_>>();
+    let padding = " ".repeat(width.saturating_sub(graphemes(&names[0]).count()));
+    let names = if color {
+      // The remaining columns are shared by the new paths.
+      let count = names.len() - 1;
+      let rest = columns.map(|cols| cols.saturating_sub(width + 3 * count) / count);
+      let widths = [columns.map(|_| width)]
+        .into_iter()
+        .chain(repeat_n(rest, count))
+        .collect::<Vec<_>>();
+      highlight_chain(&names, &widths, theme)
+    } else {
+      names
+    };
+    let () = tell(
+      output,
+      &format!("{}{padding} → {}", names[0], names[1..].join(" → ")),
+    );
+  }
+  for (_, abs_src, dst) in &plan {
+    let () = lock(summary).record(Status::Planned, classify(abs_src, dst));
+  }
+  show_summary(output, &lock(summary), true, unattended)
+}
+
+
+/// Print `summary`, phrased for a dry-run if `dry_run` is `true`.
+///
+/// For unattended runs, as per `unattended`, the summary is written to
+/// stdout in a machine readable form instead.
+fn show_summary(output: Format, summary: &Summary, dry_run: bool, unattended: bool) -> Result<()> {
+  if unattended {
+    return summary.write(dry_run, stdout().lock()).map_err(Error::from)
+  }
+
+  let message = if dry_run {
+    let changes = if summary.renamed > 0 {
+      format!(" ({})", summary.changes)
+    } else {
+      String::new()
+    };
+    format!(
+      "{} file(s) would be renamed{changes}, {} unchanged, {} failed",
+      summary.renamed, summary.unchanged, summary.failed
+    )
+  } else {
+    format!("Summary: {summary}")
+  };
+  let () = tell(output, &message);
+  Ok(())
+}
+
+
+/// Check the new paths of the files in `plan` for warnings and against
+/// `policy`, reporting all findings and failing if any is denied.
+///
+/// Files whose path would not change are only checked against the
+/// policy.
+async fn check_plan(
+  plan: &[(PathBuf, PathBuf, PathBuf)],
+  lints: &Lints,
+  policy: Option<&Policy>,
+  output: Format,
+) -> Result<()> {
+  let mut denied = 0;
+  let mut total = 0;
+  for (_, abs_src, dst) in plan {
+    for diagnostic in lints.check(abs_src, dst).await {
+      denied += usize::from(diagnostic.severity == Severity::Deny);
+      total += 1;
+      let () = tell(output, &diagnostic.to_string());
+    }
+    for violation in policy.map(|policy| policy.check(dst)).unwrap_or_default() {
+      denied += usize::from(violation.severity == Severity::Deny);
+      total += 1;
+      let () = tell(output, &violation.to_string());
+    }
+  }
+
+  ensure!(
+    denied == 0,
+    "found {total} problem(s), {denied} of which are denied"
+  );
+  let () = tell(output, &format!("found {total} problem(s)"));
+  Ok(())
+}
+
+
+/// Fail if any two of `destinations` differ only in case.
+fn ensure_case_distinct<'p, I>(destinations: I) -> Result<()>
+where
+  I: IntoIterator<Item = &'p Path>,
+{
+  let collisions = case_collisions(destinations);
+  if collisions.is_empty() {
+    return Ok(())
+  }
+
+  let list = collisions
+    .iter()
+    .map(|(a, b)| format!("\n  `{}` and `{}`", a.display(), b.display()))
+    .collect::<String>();
+  bail!("new names would differ only in case; refusing to rename:{list}")
+}
+
+
+/// Determine the new paths of all `files` using `command`, invoking it
+/// once for many files.
+///
+/// Returned are, for each file, its path as provided along with either
+/// its resolved form and its new path, or the error that prevented
+/// determining them.
+async fn plan_batch(
+  files: Vec<PathBuf>,
+  command: &[OsString],
+  opts: &RenameOpts,
+  root: &Root,
+) -> Vec<(PathBuf, Result<(PathBuf, PathBuf)>)> {
+  let mut sources = Vec::with_capacity(files.len());
+  let mut names = Vec::with_capacity(files.len());
+  for file in files {
+    let result = async {
+      let abs_src = resolve(&file, opts.symlinks).await?;
+      let name = abs_src
+        .file_name()
+        .with_context(|| format!("path `{}` does not have file name", abs_src.display()))?
+        .to_os_string();
+      Result::<_, Error>::Ok((abs_src, name))
+    }
+    .await;
+    let abs_src = result.map(|(abs_src, name)| {
+      let () = names.push(name);
+      abs_src
+    });
+    let () = sources.push((file, abs_src));
+  }
+
+  let mut proposals = propose_batch(&names, command, opts).await.into_iter();
+  sources
+    .into_iter()
+    .map(|(file, abs_src)| {
+      let result = abs_src.and_then(|abs_src| {
+        // SANITY: There is a proposal for each file resolved.
+        let new = proposals
+          .next()
+          .unwrap()
+          .with_context(|| format!("failed to determine new path of `{}`", abs_src.display()))?;
+        let dir = abs_src
+          .parent()
+          .with_context(|| format!("`{}` does not contain a parent", abs_src.display()))?;
+        let dst = dir.join(new);
+        let () = root.check(&abs_src, &dst)?;
+        Ok((abs_src, dst))
+      });
+      (file, result)
+    })
+    .collect()
+}
+
+
+/// Report that processing `file` failed with `err` and record the
+/// failure in `failures`, to be summarized at the end.
+fn fail(
+  failures: &Mutex<Vec<(PathBuf, Error)>>,
+  progress: Option<&Mutex<Progress>>,
+  output: Format,
+  summary: &Mutex<Summary>,
+  file: PathBuf,
+  err: Error,
+) -> Result<()> {
+  let () = report(output, summary, &file, &file, Status::Failed, Some(&err))?;
+  if let Some(progress) = progress {
+    let mut progress = lock(progress);
+    let () = progress.planned();
+    let () = progress.failed();
+  }
+  let () = lock(failures).push((file, err));
+  Ok(())
+}
+
+
+/// Summarize the `failures` collected, failing if there are any.
+fn summarize(failures: &Mutex<Vec<(PathBuf, Error)>>) -> Result<()> {
+  let failures = lock(failures);
+  if failures.is_empty() {
+    return Ok(())
+  }
+
+  let list = failures
+    .iter()
+    .map(|(file, err)| format!("\n  `{}`: {err:#}", file.display()))
+    .collect::<String>();
+  bail!("failed to process {} file(s):{list}", failures.len())
+}
+
+
+/// Read a list of files from `path`, or from stdin if `path` is `-`.
+///
+/// Files are separated by newlines or, if `null` is `true`, by NUL
+/// characters. Empty entries are ignored.
+async fn read_files(path: &Path, null: bool) -> Result<Vec<PathBuf>> {
+  let content = if path == Path::new("-") {
+    let mut content = Vec::new();
+    let _count = stdin()
+      .read_to_end(&mut content)
+      .await
+      .context("failed to read file list from stdin")?;
+    content
+  } else {
+    read(path)
+      .await
+      .with_context(|| format!("failed to read file list `{}`", path.display()))?
+  };
+
+  let separator = if null { b'\0' } else { b'\n' };
+  content
+    .split(|b| *b == separator)
+    .filter(|file| !file.is_empty())
+    .map(|file| os_string_from_bytes(file).map(PathBuf::from))
+    .collect::<Result<_, _>>()
+    .map_err(Error::from)
+}
+
+
+/// Load the journaled session with the given ID or, if none is
+/// provided, the most recent one that has not been undone.
+async fn load_session(dir: &Path, id: Option<&str>) -> Result<Session> {
+  if let Some(id) = id {
+    Session::load(dir, id).await.map_err(Error::from)
+  } else {
+    Session::latest(dir)
+      .await?
+      .context("no session found that has not been undone")
+  }
+}
+
+
+/// Revert the renames of a previously journaled session.
+async fn undo(dir: &Path, id: Option<&str>) -> Result<()> {
+  let mut session = load_session(dir, id).await?;
+  let result = session.undo().await.map_err(Error::from);
+  match session.snapshots() {
+    [] => result,
+    snapshots => result.with_context(|| {
+      let snapshots = snapshots
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(", ");
+      format!("the state before the session is preserved in {snapshots}")
+    }),
+  }
+}
+
+
+/// Revert the renames applied so far in the session recorded in
+/// `journal`, stored in `dir`, after the user aborted it.
+async fn revert(dir: &Path, journal: &Mutex<Journal>, output: Format) -> Result<()> {
+  let (id, recorded) = {
+    let journal = lock(journal);
+    (journal.id().to_string(), journal.is_recorded())
+  };
+  if !recorded {
+    let () = tell(output, "No renames got applied, nothing to revert");
+    return Ok(())
+  }
+
+  let () = undo(dir, Some(&id)).await?;
+  let () = tell(
+    output,
+    &format!("Reverted the renames applied in session {id}"),
+  );
+  Ok(())
+}
+
+
+/// Check a previously journaled session against the current state of
+/// the file system, reporting drift.
+async fn audit(dir: &Path, id: Option<&str>) -> Result<()> {
+  let session = load_session(dir, id).await?;
+  let results = session.audit()?;
+  let mut drifted = 0;
+  for (path, drift) in &results {
+    let status = match drift {
+      None => "ok",
+      Some(Drift::Missing) => "missing",
+      Some(Drift::Occupied) => "occupied",
+    };
+    if drift.is_some() {
+      drifted += 1;
+    }
+    println!("{status:>8}: {}", path.display());
+  }
+
+  ensure!(
+    drifted == 0,
+    "{drifted} of {} path(s) of session {} drifted",
+    results.len(),
+    session.id()
+  );
+  Ok(())
+}
+
+
+/// Recover a previously journaled session that got interrupted, with
+/// the given ID or, if none is provided, the most recent one.
+async fn recover(dir: &Path, id: Option<&str>) -> Result<()> {
+  let mut session = if let Some(id) = id {
+    Session::load(dir, id).await?
+  } else {
+    Session::interrupted(dir)
+      .await?
+      .context("no interrupted session found")?
+  };
+  session.recover().await.map_err(Error::from)
+}
+
+
+/// Verify the integrity of the files renamed in a previously journaled
+/// session.
+async fn verify(dir: &Path, id: Option<&str>) -> Result<()> {
+  let session = load_session(dir, id).await?;
+  let results = session.verify().await?;
+  let mut failed = 0;
+  for (path, verification) in &results {
+    let status = match verification {
+      Verification::Intact => "intact",
+      Verification::Modified => "modified",
+      Verification::Missing => "missing",
+    };
+    if *verification != Verification::Intact {
+      failed += 1;
+    }
+    println!("{status:>8}: {}", path.display());
+  }
+
+  ensure!(
+    failed == 0,
+    "{failed} of {} file(s) of session {} failed verification",
+    results.len(),
+    session.id()
+  );
+  Ok(())
+}
+
+
+/// Directly apply the renames in `batch`, running `hooks` around,
+/// reporting on, and recording each.
+///
+/// Conflicts with existing files are resolved as per
+/// `opts.on_conflict`, unless `overrides` maps a rename's source to a
+/// policy chosen by the user. Renames are attempted independently of
+/// each other. Returned are the files that failed to be renamed, along
+/// with the respective error.
+#[allow(clippy::too_many_arguments)]
+async fn apply_batch(
+  batch: Vec<(PathBuf, PathBuf)>,
+  overrides: &HashMap<PathBuf, ConflictPolicy>,
+  opts: &RenameOpts,
+  hooks: &Hooks,
+  journal: Option<&Mutex<Journal>>,
+  progress: Option<&Mutex<Progress>>,
+  output: Format,
+  summary: &Mutex<Summary>,
+) -> Result<Vec<(PathBuf, Error)>> {
+  let mut failures = Vec::new();
+  // Swaps cannot be applied as two independent renames.
+  let (swaps, batch) = split_swaps(batch);
+  for (a, b) in swaps {
+    let result = swap(&a, &b, opts, hooks, journal).await;
+    for (src, dst) in [(&a, &b), (&b, &a)] {
+      let () = report(
+        output,
+        summary,
+        src,
+        dst,
+        if result.is_ok() {
+          Status::Renamed
+        } else {
+          Status::Failed
+        },
+        result.as_ref().err(),
+      )?;
+      if let Some(progress) = progress {
+        let mut progress = lock(progress);
+        let () = if result.is_ok() {
+          progress.applied()
+        } else {
+          progress.failed()
+        };
+      }
+    }
+
+    if let Err(err) = result {
+      let () = failures.push((a, err));
+    }
+  }
+
+  // A conflict that can't be resolved, a failing pre-rename hook, or a
+  // failing backup prevents the rename.
+  let mut hooked = Vec::with_capacity(batch.len());
+  for (src, dst, resolution) in resolve_conflicts(batch, opts.on_conflict, overrides).await {
+    let dst = match resolution {
+      Ok(Some(path)) => path,
+      Ok(None) => {
+        let () = report_skipped(output, summary, &src, &dst)?;
+        continue
+      },
+      Err(err) => {
+        let err = Error::from(err);
+        let () = report(output, summary, &src, &dst, Status::Failed, Some(&err))?;
+        if let Some(progress) = progress {
+          let () = lock(progress).failed();
+        }
+        let () = failures.push((src, err));
+        continue
+      },
+    };
+
+    let result = async {
+      let () = hooks.pre(&src, &dst).await?;
+      back_up(&src, opts).await
+    }
+    .await;
+    match result {
+      Ok(()) => hooked.push((src, dst)),
+      Err(err) => {
+        let () = report(output, summary, &src, &dst, Status::Failed, Some(&err))?;
+        if let Some(progress) = progress {
+          let () = lock(progress).failed();
+        }
+        let () = failures.push((src, err));
+      },
+    }
+  }
+
+  // Files have to be moved out of the way before others can take
+  // their place, with cycles broken by means of temporary names. Each
+  // individual rename is recorded, so that cycles can be undone.
+  let overwrite = hooked
+    .iter()
+    .filter(|(src, _dst)| {
+      overrides.get(src).copied().unwrap_or(opts.on_conflict) == ConflictPolicy::Overwrite
+    })
+    .map(|(_src, dst)| dst.clone())
+    .collect();
+  let results = rename_replacing(&hooked, &overwrite, &opts.retry, &journal).await;
+
+  for ((src, dst), result) in hooked.iter().zip(results) {
+    let result = result.map_err(Error::from);
+
+    let () = report(
+      output,
+      summary,
+      src,
+      dst,
+      if result.is_ok() {
+        Status::Renamed
+      } else {
+        Status::Failed
+      },
+      result.as_ref().err(),
+    )?;
+
+    match result {
+      Ok(()) => {
+        if let Some(progress) = progress {
+          let () = lock(progress).applied();
+        }
+        if let Err(err) = hooks.post(src, dst).await {
+          let () = failures.push((src.clone(), err));
+        }
+      },
+      Err(err) => {
+        if let Some(progress) = progress {
+          let () = lock(progress).failed();
+        }
+        let () = failures.push((src.clone(), err));
+      },
+    }
+  }
+  Ok(failures)
+}
+
+
+/// Install a subscriber printing diagnostics to stderr, in the detail
+/// requested via `verbose` and `quiet`.
+fn init_tracing(verbose: u8, quiet: bool) {
+  let level = match (quiet, verbose) {
+    (true, _) => LevelFilter::ERROR,
+    (false, 0) => LevelFilter::WARN,
+    (false, 1) => LevelFilter::DEBUG,
+    (false, _) => LevelFilter::TRACE,
+  };
+  let () = tracing_subscriber::fmt()
+    .with_max_level(level)
+    .with_writer(stderr)
+    .without_time()
+    .with_target(false)
+    .init();
+}
+
+
+/// Create the options for renaming files as per `args`.
+fn rename_opts(args: &Args) -> RenameOpts {
+  // Planning and applying may use different levels of concurrency,
+  // falling back to the shared setting.
+  let concurrency = |specific: Option<NonZeroUsize>| {
+    if args.low_resource {
+      Concurrency::Fixed(1)
+    } else if let Some(jobs) = specific.or(args.jobs) {
+      Concurrency::Fixed(jobs.get())
+    } else if args.adaptive {
+      Concurrency::Adaptive { min: 1, max: 256 }
+    } else {
+      Concurrency::default()
+    }
+  };
+
+  let mut opts = RenameOpts::default();
+  opts.retry = RetryPolicy::new(
+    args.retries.unwrap_or(0),
+    Duration::from_millis(args.retry_backoff),
+  );
+  opts.on_conflict = args.on_conflict;
+  opts.symlinks = if args.no_dereference {
+    SymlinkPolicy::NoFollow
+  } else {
+    SymlinkPolicy::Follow
+  };
+  opts.protocol = args.protocol;
+  opts.plan_concurrency = concurrency(args.plan_jobs);
+  opts.apply_concurrency = concurrency(args.apply_jobs);
+  opts.timeout = args.timeout.map(|secs| Duration::from_secs(secs.get()));
+  opts.backup = args.backup.clone();
+  opts.sandbox = args.sandbox;
+  opts
+}
+
+
+/// Set up the source of answers to prompts.
+fn input(args: &Args) -> Result<Input> {
+  let stdin_consumed = args
+    .files_from
+    .as_deref()
+    .map(|path| path == Path::new("-"))
+    .unwrap_or(false);
+  let input = match &args.answers_from {
+    Some(path) => {
+      ensure!(
+        !(stdin_consumed && path == Path::new("-")),
+        "files and answers cannot both be read from stdin"
+      );
+      Input::Answers(Arc::new(Answers::open(path)?))
+    },
+    None => Input::Stdin {
+      consumed: stdin_consumed,
+    },
+  };
+  Ok(input)
+}
+
+
+/// Gather the files to rename, as provided on the command line, read
+/// from a file, staged with git, or found recursively, in the order
+/// requested.
+async fn gather_files(args: &Args) -> Result<Vec<PathBuf>> {
+  let mut files = args.files.clone();
+  if let Some(files_from) = &args.files_from {
+    let () = files.extend(read_files(files_from, args.null).await?);
+  }
+  if args.staged {
+    let () = files.extend(git::staged_files().await?);
+  }
+
+  let files = if args.recursive {
+    let mut opts = TraverseOpts::default();
+    opts.filter = Filter::new(&args.include, &args.exclude)?;
+    opts.max_depth = args.max_depth;
+    opts.dirs = args.dirs;
+    #[cfg(unix)]
+    {
+      opts.owner = args.owner;
+      opts.group = args.group;
+    }
+    opts.modified_before = args
+      .older_than
+      .map(|age| SystemTime::now().checked_sub(age).unwrap_or(UNIX_EPOCH));
+    traverse(&files, &opts).await?
+  } else {
+    files
+  };
+  if !args.recursive
+    && !args.directory
+    && !args.command.is_empty()
+    && args.template.is_none()
+    && !args.sanitize
+  {
+    // A rename command would otherwise operate on a stand-in file
+    // while determining the new name of a directory, but on the
+    // directory itself when applying the rename.
+    for file in &files {
+      if metadata(file).await.is_ok_and(|meta| meta.is_dir()) {
+        bail!(
+          "`{}` is a directory; use --recursive for renaming the files inside of it or --directory for renaming it itself",
+          file.display()
+        )
+      }
+    }
+  }
+  let files = order::sort(files, args.sort, args.reverse).await;
+  Ok(files)
+}
+
+
+/// Load the naming policy at `path` or, if none is provided, the one
+/// in the current directory, if present.
+async fn load_policy(path: Option<&Path>) -> Result<Option<Policy>> {
+  let policy = match path {
+    Some(path) => Some(Policy::load(path).await?),
+    None => {
+      let path = Path::new(policy::DEFAULT_FILE);
+      if path.is_file() {
+        Some(Policy::load(path).await?)
+      } else {
+        None
+      }
+    },
+  };
+  Ok(policy)
+}
+
+
+/// Generate a service running the invocation described by `args`
+/// periodically, installing it if requested.
+async fn generate_service(name: &str, args: &Args) -> Result<()> {
+  let journal_dir = (!args.no_journal).then(journal::default_dir).transpose()?;
+  let writable = [args.log.as_deref(), args.state.as_deref()]
+    .into_iter()
+    .flatten()
+    .filter_map(Path::parent)
+    .map(Path::to_path_buf)
+    .chain(journal_dir)
+    .collect::<Vec<_>>();
+  service::generate(
+    name,
+    &args.files,
+    &writable,
+    args.schedule.as_ref(),
+    args.install_service,
+  )
+  .await
+}
+
+
+/// A planned rename: the file as provided, its resolved path, and its
+/// new path.
+type Planned = (PathBuf, PathBuf, PathBuf);
+
+
+/// The settings and state shared by the phases of renaming files.
+struct Renaming {
+  /// The rename command.
+  cmd: Vec<OsString>,
+  /// The options for renaming files.
+  opts: RenameOpts,
+  /// The hooks to run around renames.
+  hooks: Hooks,
+  /// The directory renames are confined to.
+  root: Root,
+  /// The journal renames get recorded in, if any.
+  journal: Option<Arc<Mutex<Journal>>>,
+  /// The state of the invocation, for resuming it once interrupted.
+  state: Option<Arc<State>>,
+  /// The progress, if reported.
+  progress: Option<Arc<Mutex<Progress>>>,
+  /// The number of files per outcome, summarized at the end.
+  summary: Arc<Mutex<Summary>>,
+  /// The files that failed to be processed, if processing continues
+  /// past failures.
+  failures: Arc<Mutex<Vec<(PathBuf, Error)>>>,
+  /// The source of answers to prompts.
+  input: Input,
+  /// The format to report renames in.
+  output: Format,
+  /// The theme used for prompting.
+  theme: Theme,
+  /// The files being renamed. Renames onto any of them have to be
+  /// ordered with respect to the renames of these files, which is only
+  /// possible when applying renames directly.
+  sources: HashSet<PathBuf>,
+  /// The intermediate paths of files renamed by multiple commands.
+  intermediates: Mutex<HashMap<PathBuf, Vec<PathBuf>>>,
+  /// The positions of files within the batch, as told to the rename
+  /// command, so that it sees the same when renaming files for real.
+  positions: Mutex<HashMap<PathBuf, usize>>,
+  /// The number of files in the batch.
+  total: usize,
+  /// The changes accepted without prompting.
+  accept_change: Vec<Change>,
+  /// Whether to accept all renames without prompting.
+  yes: bool,
+  /// Whether to apply renames directly instead of running the rename
+  /// command on the actual files.
+  direct: bool,
+  /// Whether directories are renamed along with files.
+  dirs: bool,
+  /// Whether to only report files whose name changes.
+  changed_only: bool,
+  /// Whether to discard files identical to the existing destination.
+  discard_identical: bool,
+  /// Whether quitting reverts the renames applied so far.
+  abort_reverts: bool,
+  /// Whether to continue past failures.
+  keep_going: bool,
+}
+
+
+/// The decisions made on planned renames by [`interactive`].
+#[derive(Default)]
+struct Decisions {
+  /// The renames deferred to be applied directly, in one batch.
+  batch: Vec<(PathBuf, PathBuf)>,
+  /// The conflict resolutions chosen for individual deferred renames.
+  overrides: HashMap<PathBuf, ConflictPolicy>,
+  /// The files seen.
+  seen: HashSet<PathBuf>,
+  /// The files staying in place because their rename got declined,
+  /// which block renames depending on them.
+  kept: HashSet<PathBuf>,
+  /// Whether the user quit.
+  quit: bool,
+  /// Whether the user aborted, asking for applied renames to be
+  /// reverted.
+  aborted: bool,
+  /// Whether answers ran out before all renames were decided on.
+  interrupted: bool,
+}
+
+
+/// Decide on the renames in `src_dst`, prompting the user unless all
+/// of them are accepted upfront.
+///
+/// Renames not applied directly get applied in the background while
+/// the user is prompted for subsequent ones; all others are deferred
+/// to a batch to apply once all renames are decided on.
+async fn interactive(
+  renaming: &Renaming,
+  mut src_dst: LocalBoxStream<'_, Result<Planned>>,
+) -> Result<Decisions> {
+  let Renaming {
+    cmd,
+    opts,
+    hooks,
+    root,
+    journal,
+    state,
+    progress,
+    summary,
+    failures,
+    input,
+    output,
+    theme,
+    sources,
+    intermediates,
+    positions,
+    total,
+    accept_change,
+    yes,
+    direct,
+    dirs,
+    changed_only,
+    discard_identical,
+    abort_reverts,
+    keep_going,
+  } = renaming;
+  let (output, theme, total) = (*output, *theme, *total);
+
+  let mut renames = Pipeline::<_, Error>::new(opts.apply_concurrency.max(), *keep_going);
+  let mut decisions = Decisions::default();
+  let mut accept_all = *yes;
+
+  while let Some(result) = src_dst.next().await {
+    let (src, abs_src, mut dst) = result?;
+    if let Some(progress) = progress {
+      let () = lock(progress).planned();
+    }
+    let _inserted = decisions.seen.insert(abs_src.clone());
+
+    if abs_src == dst {
+      let () = if *changed_only {
+        lock(summary).record(Status::Unchanged, None)
+      } else {
+        report(output, summary, &abs_src, &dst, Status::Unchanged, None)?
+      };
+      continue
+    }
+
+    let src_file = abs_src
+      .file_name()
+      .with_context(|| format!("path `{}` does not have file name", abs_src.display()))?;
+    let src_file = Path::new(src_file);
+    let src_dir = abs_src
+      .parent()
+      .with_context(|| format!("`{}` does not contain a parent", abs_src.display()))?;
+
+    // A destination that is being renamed itself gets moved out of
+    // the way first and does not constitute a conflict.
+    let mut conflict = if (!accept_all || *discard_identical) && !sources.contains(&dst) {
+      Conflict::inspect(&abs_src, &dst).await?
+    } else {
+      None
+    };
+    if *discard_identical && conflict.as_ref().is_some_and(|conflict| conflict.identical) {
+      let () = discard(output, summary, &abs_src, &dst).await?;
+      continue
+    }
+
+    let mut on_conflict = opts.on_conflict;
+    let mut edited = false;
+    let via = lock(intermediates).remove(&abs_src).unwrap_or_default();
+    let is_dir = *dirs && metadata(&abs_src).await.is_ok_and(|meta| meta.is_dir());
+    let change = classify(&abs_src, &dst);
+    let accept = if accept_all || change.is_some_and(|change| accept_change.contains(&change)) {
+      true
+    } else {
+      let response = loop {
+        // The rename command is only run on the live data when the
+        // rename is not applied directly (see below).
+        let command = if *direct
+          || edited
+          || is_dir
+          || sources.contains(&dst)
+          || opts.protocol != Protocol::Rename
+        {
+          None
+        } else {
+          let (dir, line) = command_line(&abs_src, cmd, opts)?;
+          let line = line.iter().map(quote_shell).collect::<Vec<_>>();
+          Some(format!(
+            "Would run in `{}`:\n  {}",
+            dir.display(),
+            line.join(OsStr::new(" ")).to_string_lossy()
+          ))
+        };
+        // Show the new path relative to the file's directory, if
+        // possible.
+        let dst_rel = dst.strip_prefix(src_dir).unwrap_or(&dst);
+        // Intermediate paths are only meaningful for the proposed path.
+        let via = if edited {
+          Vec::new()
+        } else {
+          via
+            .iter()
+            .map(|path| path.strip_prefix(src_dir).unwrap_or(path))
+            .collect()
+        };
+        // Don't keep the user answering prompts once a rename applied
+        // in the background failed.
+        let response = renames
+          .race(prompt(
+            output,
+            src_file,
+            &via,
+            dst_rel,
+            conflict.as_ref(),
+            command.as_deref(),
+            input,
+            theme,
+          ))
+          .await??;
+        match response {
+          // Plainly accepting a rename conflicting with an existing
+          // file would merely fail it, so insist on a resolution.
+          Response::Accept | Response::AcceptAll
+            if conflict.is_some() && on_conflict == ConflictPolicy::Abort =>
+          {
+            let () = tell(
+              output,
+              "The new path exists already; (o)verwrite, (s)kip, s(u)ffix, or (e)dit it",
+            );
+            continue
+          },
+          Response::Abort if journal.is_none() => {
+            let () = tell(output, "Renames can't be reverted without the journal");
+            continue
+          },
+          Response::Edit(path) => {
+            let path = normalize(&src_dir.join(path));
+            if let Err(err) = root.check(&abs_src, &path) {
+              let () = tell(output, &format!("{err:#}"));
+              continue
+            }
+            dst = path;
+            edited = true;
+            // Only ask again if the edited path is in conflict.
+            conflict = if sources.contains(&dst) {
+              None
+            } else {
+              Conflict::inspect(&abs_src, &dst).await?
+            };
+            if conflict.is_some() {
+              continue
+            }
+            break Response::Accept
+          },
+          response => break response,
+        }
+      };
+
+      match response {
+        Response::Accept => true,
+        Response::Decline => false,
+        Response::AcceptAll => {
+          accept_all = true;
+          true
+        },
+        Response::Resolve(policy) => {
+          on_conflict = policy;
+          true
+        },
+        Response::Discard => {
+          let () = discard(output, summary, &abs_src, &dst).await?;
+          continue
+        },
+        Response::Quit if !abort_reverts => {
+          decisions.quit = true;
+          break
+        },
+        Response::Quit | Response::Abort => {
+          decisions.aborted = true;
+          break
+        },
+        Response::Interrupt => {
+          decisions.interrupted = true;
+          break
+        },
+        Response::Edit(..) => unreachable!(),
+      }
+    };
+
+    if !accept {
+      if let Some(state) = state {
+        let () = state.declined(&abs_src, &dst)?;
+      }
+      let () = report(output, summary, &abs_src, &dst, Status::Declined, None)?;
+      let _inserted = decisions.kept.insert(abs_src);
+    } else {
+      if let Some(progress) = progress {
+        let () = lock(progress).accepted();
+      }
+
+      // An edited path can't be produced by the rename command, so
+      // rename the file directly. The same goes for a file taking the
+      // place of another one being renamed, so that the two (or any
+      // cycle they are part of) can be applied in a safe order, and for
+      // a directory, which may only be renamed once all renames of
+      // files inside of it are done.
+      if *direct || edited || is_dir || sources.contains(&dst) {
+        // A conflict resolution chosen by the user applies to this
+        // rename only.
+        if on_conflict != opts.on_conflict {
+          let _prev = decisions.overrides.insert(abs_src.clone(), on_conflict);
+        }
+        let () = decisions.batch.push((abs_src, dst));
+        continue
+      }
+
+      let cmd = cmd.clone();
+      let mut opts = opts.clone();
+      opts.on_conflict = on_conflict;
+      let opts = match lock(positions).remove(&abs_src) {
+        Some(index) => opts.with_position(index, total),
+        None => opts,
+      };
+      let hooks = hooks.clone();
+      let journal = journal.clone();
+      let progress = progress.clone();
+      let summary = summary.clone();
+      // With a single job, the rename has to finish before the next
+      // file gets processed.
+      let () = renames
+        .push(abs_src.clone(), async move {
+          let result = apply(&src, &dst, &cmd, &opts, &hooks, journal.as_deref()).await;
+          let () = match &result {
+            Ok(path) if *path == abs_src => report_skipped(output, &summary, &abs_src, &dst)?,
+            Ok(path) => report(output, &summary, &abs_src, path, Status::Renamed, None)?,
+            Err(err) => report(output, &summary, &abs_src, &dst, Status::Failed, Some(err))?,
+          };
+          if let Some(progress) = progress {
+            let mut progress = lock(&progress);
+            let () = if result.is_ok() {
+              progress.applied()
+            } else {
+              progress.failed()
+            };
+          }
+          Ok(result.map(|_path| ()))
+        })
+        .await?;
+    }
+  }
+
+  let mut failed = renames.finish().await?;
+  let () = lock(failures).append(&mut failed);
+  Ok(decisions)
+}
+
+
+/// Apply the renames deferred to `batch`, as a transaction if
+/// `transactional` is set.
+async fn apply_deferred(
+  renaming: &Renaming,
+  batch: Vec<(PathBuf, PathBuf)>,
+  overrides: &HashMap<PathBuf, ConflictPolicy>,
+  transactional: bool,
+) -> Result<()> {
+  let Renaming {
+    opts,
+    hooks,
+    journal,
+    progress,
+    summary,
+    failures,
+    output,
+    keep_going,
+    ..
+  } = renaming;
+  let output = *output;
+
+  if !transactional {
+    let result = apply_batch(
+      batch,
+      overrides,
+      opts,
+      hooks,
+      journal.as_deref(),
+      progress.as_deref(),
+      output,
+      summary,
+    )
+    .await;
+    if let Some(progress) = progress {
+      let () = lock(progress).finish();
+    }
+    let mut failed = result?;
+    if *keep_going {
+      let () = lock(failures).append(&mut failed);
+    } else if let Some((_file, err)) = failed.into_iter().next() {
+      return Err(err)
+    }
+    return Ok(())
+  }
+
+  // Any conflict that can't be resolved aborts the transaction.
+  let mut resolved = Vec::with_capacity(batch.len());
+  for (src, dst, resolution) in resolve_conflicts(batch, opts.on_conflict, overrides).await {
+    match resolution? {
+      Some(path) => resolved.push((src, path)),
+      None => report_skipped(output, summary, &src, &dst)?,
+    }
+  }
+  let batch = resolved;
+
+  // A failing pre-rename hook (or backup) aborts the transaction
+  // before any file got renamed.
+  for (src, dst) in &batch {
+    let () = hooks.pre(src, dst).await?;
+    let () = back_up(src, opts).await?;
+  }
+
+  let overwrite = batch
+    .iter()
+    .filter(|(src, _dst)| {
+      overrides.get(src).copied().unwrap_or(opts.on_conflict) == ConflictPolicy::Overwrite
+    })
+    .map(|(_src, dst)| dst.clone())
+    .collect();
+  let result = rename_transaction_replacing(&batch, &overwrite, &opts.retry, &journal.as_deref())
+    .await
+    .map_err(Error::from);
+  for (src, dst) in &batch {
+    let () = report(
+      output,
+      summary,
+      src,
+      dst,
+      if result.is_ok() {
+        Status::Renamed
+      } else {
+        Status::Failed
+      },
+      result.as_ref().err(),
+    )?;
+  }
+  if let (Err(..), Some(progress)) = (&result, progress) {
+    let mut progress = lock(progress);
+    for _ in &batch {
+      let () = progress.failed();
+    }
+    let () = progress.finish();
+  }
+  let () = result?;
+
+  for (src, dst) in &batch {
+    let () = hooks.post(src, dst).await?;
+  }
+  if let Some(progress) = progress {
+    let mut progress = lock(progress);
+    for _ in &batch {
+      let () = progress.applied();
+    }
+    let () = progress.finish();
+  }
+  Ok(())
+}
+
+
+/// Report on the renames applied and notify interested parties.
+async fn conclude(renaming: &Renaming, notify: Option<&OsString>, unattended: bool) -> Result<()> {
+  let Renaming {
+    hooks,
+    summary,
+    output,
+    ..
+  } = renaming;
+  let output = *output;
+
+  let () = show_summary(output, &lock(summary), false, unattended)?;
+  if let Some(notify) = notify {
+    let summary = *lock(summary);
+    let () = hook::notify(notify, &summary).await?;
+  }
+  let summary = *lock(summary);
+  hooks.notifiers.finish(&summary, output).await
+}
+
+
+/// The means of determining the new paths of files.
+struct Planner {
+  /// The template to derive new paths from, if any.
+  template: Option<Template>,
+  /// The sanitizer to derive new paths with, if any.
+  sanitizer: Option<Sanitizer>,
+  /// The commands to apply in sequence, if renames are composed of
+  /// several.
+  commands: Vec<Vec<OsString>>,
+  /// The options for determining new paths, without renaming files.
+  opts: RenameOpts,
+}
+
+impl Planner {
+  /// Check whether new paths are determined by the rename command
+  /// alone.
+  fn is_command(&self) -> bool {
+    self.template.is_none() && self.sanitizer.is_none() && self.commands.is_empty()
+  }
+}
+
+
+/// Plan the renames of `files`, skipping the ones `processed`
+/// previously, numbering them starting at `first`.
+fn plan<'a>(
+  renaming: &'a Renaming,
+  planner: &'a Planner,
+  files: Vec<PathBuf>,
+  first: u64,
+  processed: &'a HashSet<PathBuf>,
+) -> LocalBoxStream<'a, Result<Planned>> {
+  let Renaming {
+    cmd,
+    root,
+    progress,
+    summary,
+    failures,
+    output,
+    intermediates,
+    positions,
+    total,
+    keep_going,
+    ..
+  } = renaming;
+  let Planner {
+    template,
+    sanitizer,
+    commands,
+    opts,
+  } = planner;
+  let (output, total, keep_going) = (*output, *total, *keep_going);
+
+  let planned = stream::iter(files.into_iter().zip(first..).zip(1..)).map(
+    move |((file, counter), index)| async move {
+      // Processed files still consume their counter, so that the
+      // remaining ones are numbered as they would have been.
+      if processed.contains(&file) {
+        return Ok(None)
+      }
+      let result = async {
+        let abs_src = resolve(&file, opts.symlinks).await?;
+        let opts = opts.clone().with_position(index, total);
+        let dst = match (template, sanitizer) {
+          (Some(template), _) => template.plan(&abs_src, counter).await?,
+          (None, Some(sanitizer)) => sanitizer.plan(&abs_src).await?,
+          // Without a command, current paths are checked.
+          (None, None) if cmd.is_empty() => abs_src.clone(),
+          (None, None) if !commands.is_empty() => {
+            let dir = abs_src
+              .parent()
+              .with_context(|| format!("`{}` does not contain a parent", abs_src.display()))?;
+            let name = abs_src
+              .file_name()
+              .with_context(|| format!("path `{}` does not have file name", abs_src.display()))?;
+            let mut opts = opts.with_file(&abs_src);
+            opts.directory = metadata(&abs_src).await.is_ok_and(|meta| meta.is_dir());
+            let mut steps = propose_chain(name, commands, &opts)
+              .await
+              .with_context(|| format!("failed to determine new path of `{}`", abs_src.display()))?
+              .into_iter()
+              .map(|step| dir.join(step))
+              .collect::<Vec<_>>();
+            // SANITY: There are always at least two commands.
+            let dst = steps.pop().unwrap();
+            let _prev = lock(intermediates).insert(abs_src.clone(), steps);
+            dst
+          },
+          (None, None) => rename_with(&abs_src, cmd, &opts)
+            .await
+            .with_context(|| format!("failed to determine new path of `{}`", abs_src.display()))?,
+        };
+        let () = root.check(&abs_src, &dst)?;
+        let _prev = lock(positions).insert(abs_src.clone(), index);
+        Result::<_, Error>::Ok((abs_src, dst))
+      }
+      .await;
+      match result {
+        Ok((abs_src, dst)) => Ok(Some((file, abs_src, dst))),
+        Err(err) if keep_going => {
+          let () = fail(failures, progress.as_deref(), output, summary, file, err)?;
+          Ok(None)
+        },
+        Err(err) => Err(err),
+      }
+    },
+  );
+  buffered(planned, opts.plan_concurrency)
+    .try_filter_map(|plan| ready(Ok(plan)))
+    .boxed_local()
+}
+
+
+/// Rename files as described by `args`.
+async fn rename(args: Args, config: Option<Config>) -> Result<()> {
+  let input = input(&args)?;
+  let opts = rename_opts(&args);
+  let files = gather_files(&args).await?;
+
+  let Args {
+    command: cmd,
+    files: _,
+    files_from: _,
+    null: _,
+    answers_from: _,
+    staged: _,
+    recursive: _,
+    directory: _,
+    include: _,
+    exclude: _,
+    max_depth: _,
+    #[cfg(unix)]
+      owner: _,
+    #[cfg(unix)]
+      group: _,
+    older_than: _,
+    dirs,
+    sort: _,
+    reverse: _,
+    #[cfg(feature = "archive")]
+      archive: _,
+    manifest,
+    diff_names,
+    pair_by,
+    reconcile: _,
+    sync,
+    apply_plan,
+    require_signed,
+    undo: _,
+    verify: _,
+    audit: _,
+    recover: _,
+    suggest: _,
+    #[cfg(feature = "self-update")]
+      self_update: _,
+    completions: _,
+    service: _,
+    install_service: _,
+    schedule: _,
+    no_journal,
+    checksums,
+    log,
+    since: _,
+    state: state_path,
+    backup: _,
+    dry_run,
+    save_plan,
+    sign_plan,
+    pre_hook,
+    post_hook,
+    notify,
+    webhook,
+    webhook_per_rename,
+    no_library_refresh,
+    update_refs,
+    update_links,
+    no_sequence_check,
+    yes,
+    abort_reverts,
+    batch: unattended,
+    editor_protocol,
+    edit,
+    tui,
+    retries: _,
+    retry_backoff: _,
+    adaptive: _,
+    jobs: _,
+    plan_jobs: _,
+    apply_jobs: _,
+    low_resource,
+    timeout: _,
+    sandbox: _,
+    direct,
+    batch_preview,
+    then_args: _,
+    then,
+    protocol: _,
+    transactional,
+    keep_going,
+    check_case,
+    deny,
+    allow,
+    only_change,
+    accept_change,
+    policy: policy_path,
+    check,
+    on_conflict: _,
+    discard_identical,
+    output,
+    changed_only,
+    analyze,
+    verbose: _,
+    quiet: _,
+    theme,
+    preset: _,
+    template,
+    sanitize,
+    lowercase,
+    normalize: normalization,
+    wizard,
+    locale,
+    continue_numbering,
+    no_dereference: _,
+    root,
+    no_confine,
+    #[cfg(all(target_os = "linux", feature = "landlock"))]
+    landlock,
+    #[cfg(target_os = "linux")]
+    snapshot,
+  } = args;
+  let symlinks = opts.symlinks;
+  let template = match (template, locale) {
+    (Some(template), Some(locale)) => Some(template.with_locale(locale)),
+    (template, _) => template,
+  }
+  .map(|template| template.with_symlink_policy(symlinks));
+  let sanitizer = sanitize.then(|| {
+    Sanitizer::default()
+      .with_lowercase(lowercase)
+      .with_normalization(normalization)
+      .with_symlink_policy(symlinks)
+  });
+  // Template based, batch previewed, chained, manifest based,
+  // reconciling, saved, and transactional renames are always applied
+  // directly.
+  let direct = direct
+    || batch_preview
+    || !then.is_empty()
+    || transactional
+    || template.is_some()
+    || sanitizer.is_some()
+    || wizard
+    || manifest.is_some()
+    || diff_names.is_some()
+    || apply_plan.is_some();
+
+  let mut hooks = Hooks {
+    pre: pre_hook,
+    post: post_hook,
+    log: None,
+    state: None,
+    notifiers: Notifiers {
+      webhooks: (!webhook.is_empty()).then(|| Arc::new(Webhooks::new(webhook, webhook_per_rename))),
+      libraries: config
+        .filter(|config| !no_library_refresh && !config.media_servers.is_empty())
+        .map(|config| Arc::new(Libraries::new(config.media_servers))),
+      references: (!update_refs.is_empty() || update_links)
+        .then(|| Arc::new(References::new(update_refs.clone(), update_links))),
+      sequences: (!no_sequence_check).then(|| Arc::new(Sequences::default())),
+    },
+  };
+
+  let root = if no_confine {
+    Root::Anywhere
+  } else if let Some(root) = root {
+    Root::Dir(resolve(&root, SymlinkPolicy::Follow).await?)
+  } else {
+    Root::Parent
+  };
+
+  let state = match &state_path {
+    Some(path) => Some(Arc::new(State::open(path)?)),
+    None => None,
+  };
+  // The files processed by a previous invocation that got interrupted.
+  let processed = match &state {
+    Some(state) => state.processed(&files).await,
+    None => HashSet::new(),
+  };
+  if !processed.is_empty() {
+    let () = tell(
+      output,
+      &format!("Skipping {} file(s) processed previously", processed.len()),
+    );
+  }
+  hooks.state = state.clone();
+
+  // The renames determined upfront, by a manifest, a comparison of
+  // directories, or a saved plan.
+  let fixed_plan = match (&manifest, diff_names.as_deref(), &apply_plan) {
+    (Some(path), ..) => Some(manifest::sync(path, sync, output).await?),
+    (None, Some([dir_a, dir_b]), _) => {
+      Some(compare::diff_names(dir_a, dir_b, pair_by, output).await?)
+    },
+    (None, _, Some(path)) => {
+      let plan = saved::load(path, require_signed.as_deref()).await?;
+      for (_, abs_src, dst) in &plan {
+        let () = root.check(abs_src, dst)?;
+      }
+      Some(plan)
+    },
+    (None, _, None) => None,
+  };
+
+  let template = if wizard {
+    match wizard::build(&files, symlinks, &input, output).await? {
+      Some(template) => Some(template),
+      None => return Ok(()),
+    }
+  } else {
+    template
+  };
+
+  // Record the template, manifest, compared directories, or saved plan
+  // in lieu of a command, if one is used.
+  let recorded = match (&template, &manifest, diff_names.as_deref(), &apply_plan) {
+    (Some(template), ..) => vec![
+      OsString::from("--template"),
+      OsString::from(template.to_string()),
+    ],
+    (None, ..) if sanitize => [
+      OsString::from("--sanitize"),
+      OsString::from("--normalize"),
+      OsString::from(normalization.to_string()),
+    ]
+    .into_iter()
+    .chain(lowercase.then(|| OsString::from("--lowercase")))
+    .collect(),
+    (None, Some(path), ..) => vec![OsString::from("--manifest"), path.clone().into_os_string()],
+    (None, None, Some(dirs), _) => [OsString::from("--diff-names")]
+      .into_iter()
+      .chain(dirs.iter().map(|dir| dir.clone().into_os_string()))
+      .chain([OsString::from("--reconcile")])
+      .collect(),
+    (None, None, None, Some(path)) => vec![
+      OsString::from("--apply-plan"),
+      path.clone().into_os_string(),
+    ],
+    (None, None, None, None) => cmd
+      .iter()
+      .cloned()
+      .chain(then.iter().flat_map(|command| {
+        [OsString::from("--then")]
+          .into_iter()
+          .chain(command.iter().cloned())
+      }))
+      .collect(),
+  };
+
+  let journal_dir = (!no_journal).then(journal::default_dir).transpose()?;
+  let journal = journal_dir.as_ref().map(|dir| {
+    let journal = Journal::new(dir, &recorded).with_checksums(checksums);
+    Arc::new(Mutex::new(journal))
+  });
+
+  #[cfg(target_os = "linux")]
+  if let Some(journal) = journal.as_deref().filter(|_| snapshot) {
+    // Snapshots have to be taken before confining ourselves, as they
+    // are created outside of the directories files get renamed in.
+    let dirs = root.dirs(&files, fixed_plan.as_deref(), symlinks).await;
+    let id = lock(journal).id().to_string();
+    for snapshot in snapshot::take(&dirs, &id).await? {
+      let () = lock(journal).record_snapshot(&snapshot)?;
+      let () = tell(output, &format!("Took {snapshot}"));
+    }
+  }
+
+  if let Some(path) = &log {
+    // Renames only get applied once the command succeeded.
+    let status = (template.is_none()
+      && !sanitize
+      && manifest.is_none()
+      && diff_names.is_none()
+      && apply_plan.is_none())
+    .then_some(0);
+    let log = AuditLog::open(path, &recorded, status)?;
+    hooks.log = Some(Arc::new(log));
+  }
+
+  #[cfg(all(target_os = "linux", feature = "landlock"))]
+  if landlock {
+    let mut writable = vec![temp_dir(), PathBuf::from("/dev")];
+    let () = writable.extend(root.dirs(&files, fixed_plan.as_deref(), symlinks).await);
+    let () = writable.extend(
+      [
+        log.as_deref(),
+        state_path.as_deref(),
+        manifest.as_deref(),
+        save_plan.as_deref(),
+      ]
+      .into_iter()
+      .flatten()
+      .chain(update_refs.iter().map(PathBuf::as_path))
+      .filter_map(Path::parent)
+      .map(Path::to_path_buf),
+    );
+    if let Some(journal_dir) = &journal_dir {
+      // The journal directory may not exist yet, but has to for being
+      // writable.
+      let () = create_dir_all(journal_dir)
+        .await
+        .with_context(|| format!("failed to create directory `{}`", journal_dir.display()))?;
+      let () = writable.push(journal_dir.clone());
+    }
+    let writable = writable
+      .iter()
+      .map(|dir| {
+        absolute(dir).with_context(|| format!("failed to make `{}` absolute", dir.display()))
+      })
+      .collect::<Result<Vec<_>>>()?;
+    let () = restrict::restrict(&writable)?;
+  }
+
+  if editor_protocol {
+    return editor::serve(&cmd, &opts, &hooks, journal.as_deref()).await
+  }
+
+  // Progress is only reported when running non-interactively, as
+  // it would otherwise interfere with prompting.
+  let progress = if yes && !unattended && !low_resource && stderr().is_terminal() {
+    let count = fixed_plan
+      .as_ref()
+      .map(Vec::len)
+      .unwrap_or(files.len() - processed.len());
+    Some(Arc::new(Mutex::new(Progress::new(count))))
+  } else {
+    None
+  };
+
+  let first = match &template {
+    Some(template) if continue_numbering => template.last_counter(&files).await? + 1,
+    _ => 1,
+  };
+
+  let mut sources = HashSet::new();
+  if !direct {
+    for file in &files {
+      if let Ok(path) = resolve(file, symlinks).await {
+        let _inserted = sources.insert(path);
+      }
+    }
+  }
+
+  let mut lints = Lints::default();
+  for warning in allow {
+    lints = lints.set(warning, Severity::Allow);
+  }
+  for warning in deny {
+    lints = lints.set(warning, Severity::Deny);
+  }
+  let policy = load_policy(policy_path.as_deref()).await?;
+  let mut plan_opts = opts.clone();
+  plan_opts.dry_run = true;
+  let planner = Planner {
+    template,
+    sanitizer,
+    commands: if then.is_empty() {
+      Vec::new()
+    } else {
+      [cmd.clone()].into_iter().chain(then).collect()
+    },
+    opts: plan_opts,
+  };
+
+  let renaming = Renaming {
+    cmd,
+    opts,
+    hooks,
+    root,
+    journal,
+    state,
+    progress,
+    summary: Arc::new(Mutex::new(Summary::default())),
+    failures: Arc::new(Mutex::new(Vec::new())),
+    input,
+    output,
+    theme,
+    sources,
+    intermediates: Mutex::new(HashMap::new()),
+    positions: Mutex::new(HashMap::new()),
+    total: files.len(),
+    accept_change,
+    yes,
+    direct,
+    dirs,
+    changed_only,
+    discard_identical,
+    abort_reverts,
+    keep_going,
+  };
+  let Renaming {
+    cmd,
+    opts,
+    hooks,
+    root,
+    journal,
+    failures,
+    ..
+  } = &renaming;
+  let (failures_ref, progress_ref, summary_ref) = (
+    &**failures,
+    renaming.progress.as_deref(),
+    &*renaming.summary,
+  );
+
+  let mut src_dst = if let Some(plan) = fixed_plan {
+    stream::iter(plan.into_iter().map(Ok)).boxed_local()
+  } else if batch_preview {
+    let files = files
+      .into_iter()
+      .filter(|file| !processed.contains(file))
+      .collect();
+    let mut plan = Vec::new();
+    for (file, result) in plan_batch(files, cmd, opts, root).await {
+      match result {
+        Ok((abs_src, dst)) => plan.push((file, abs_src, dst)),
+        Err(err) if keep_going => fail(failures_ref, progress_ref, output, summary_ref, file, err)?,
+        Err(err) => return Err(err),
+      }
+    }
+    stream::iter(plan.into_iter().map(Ok)).boxed_local()
+  } else {
+    plan(&renaming, &planner, files, first, &processed)
+  };
+  if !only_change.is_empty() {
+    let only_change = &only_change;
+    src_dst = src_dst
+      .try_filter_map(move |(src, abs_src, dst)| {
+        let result = match classify(&abs_src, &dst) {
+          Some(change) if !only_change.contains(&change) => {
+            report(output, summary_ref, &abs_src, &dst, Status::Declined, None).map(|()| None)
+          },
+          _ => Ok(Some((src, abs_src, dst))),
+        };
+        ready(result)
+      })
+      .boxed_local();
+  }
+  if check_case {
+    // All new names have to be known before they can be checked.
+    let plan = src_dst.try_collect::<Vec<_>>().await?;
+    let () = ensure_case_distinct(plan.iter().map(|(_, _, dst)| dst.as_path()))?;
+    src_dst = stream::iter(plan.into_iter().map(Ok)).boxed_local();
+  }
+
+  if analyze {
+    let plan = src_dst.try_collect::<Vec<_>>().await?;
+    let () = analysis::report(&plan, output);
+    src_dst = stream::iter(plan.into_iter().map(Ok)).boxed_local();
+  }
+
+  if check {
+    let plan = src_dst.try_collect::<Vec<_>>().await?;
+    let () = check_plan(&plan, &lints, policy.as_ref(), output).await?;
+    return summarize(failures)
+  }
+
+  let (lints_ref, policy_ref) = (&lints, &policy);
+  src_dst = src_dst
+    .and_then(move |(src, abs_src, dst)| async move {
+      for diagnostic in lints_ref.check(&abs_src, &dst).await {
+        if diagnostic.severity == Severity::Deny {
+          bail!(
+            "refusing to rename: {} [{}]",
+            diagnostic.message,
+            diagnostic.warning
+          )
+        }
+        let () = tell(output, &diagnostic.to_string());
+      }
+      if let (Some(policy), true) = (policy_ref, abs_src != dst) {
+        for violation in policy.check(&dst) {
+          if violation.severity == Severity::Deny {
+            bail!(
+              "refusing to rename `{}`: `{}` violates the naming policy: {}",
+              abs_src.display(),
+              dst.display(),
+              violation.message
+            )
+          }
+          let () = tell(output, &violation.to_string());
+        }
+      }
+      Ok((src, abs_src, dst))
+    })
+    .boxed_local();
+  if lints.denies_any() || policy.as_ref().is_some_and(Policy::denies_any) {
+    // Denied warnings have to be detected before the first rename.
+    let plan = src_dst.try_collect::<Vec<_>>().await?;
+    src_dst = stream::iter(plan.into_iter().map(Ok)).boxed_local();
+  }
+
+  if dry_run {
+    let plan = src_dst.try_collect::<Vec<_>>().await?;
+    let () = show_plan(
+      &plan,
+      &lock(&renaming.intermediates),
+      output,
+      theme,
+      &renaming.summary,
+      unattended,
+    )?;
+    if let Some(path) = &save_plan {
+      let () = saved::save(path, &plan, sign_plan.as_deref()).await?;
+    }
+    return summarize(failures)
+  }
+
+  if edit {
+    let plan = src_dst.try_collect::<Vec<_>>().await?;
+    let cmd = planner.is_command().then_some(cmd.as_slice());
+    let () = review::review(plan, cmd, opts, hooks, root, journal.as_deref()).await?;
+    return summarize(failures)
+  }
+
+  if tui {
+    let plan = src_dst.try_collect::<Vec<_>>().await?;
+    let cmd = planner.is_command().then_some(cmd.as_slice());
+    let () = tui::review(plan, cmd, opts, hooks, root, journal.as_deref(), theme).await?;
+    return summarize(failures)
+  }
+
+  let Decisions {
+    batch,
+    overrides,
+    seen,
+    mut kept,
+    quit,
+    aborted,
+    interrupted,
+  } = interactive(&renaming, src_dst).await?;
+
+  // Renames deferred to the end never got applied and are dropped
+  // along with the rest, while those applied already get reverted.
+  // Effects of hooks, such as notifications sent, persist.
+  if aborted {
+    if let Some(progress) = &renaming.progress {
+      let () = lock(progress).finish();
+    }
+    // SANITY: The session can only be aborted with a journal present.
+    let () = revert(
+      journal_dir.as_deref().unwrap(),
+      journal.as_deref().unwrap(),
+      output,
+    )
+    .await?;
+    // There is nothing left to resume.
+    if let Some(state) = &renaming.state {
+      let () = state.remove()?;
+    }
+    return summarize(failures)
+  }
+
+  // Renames deferred until the files whose place they take are moved
+  // away can't be applied if those stay in place, because their
+  // renames got declined or never got decided on.
+  if quit || interrupted {
+    let () = kept.extend(renaming.sources.difference(&seen).cloned());
+  }
+  let (blocked, batch) = split_blocked(batch, &kept);
+  for ((src, dst), blocker) in &blocked {
+    let () = report_blocked(output, &renaming.summary, src, dst, blocker)?;
+  }
+  let () = apply_deferred(&renaming, batch, &overrides, transactional).await?;
+
+  if let Some(path) = &manifest {
+    let () = manifest::update(path, sync).await?;
+  }
+  let () = conclude(&renaming, notify.as_ref(), unattended).await?;
+  if interrupted {
+    // Mirror the conventional exit status of a process terminated by
+    // SIGINT.
+    exit(130)
+  }
+  let () = summarize(failures)?;
+  // An invocation that ran to completion does not need resuming.
+  if let (Some(state), false) = (&renaming.state, quit) {
+    let () = state.remove()?;
+  }
+  Ok(())
+}
+
+
+async fn run() -> Result<()> {
+  let dir = current_dir().context("failed to determine current directory")?;
+  let config = match (Config::discover(&dir).await?, Config::load_user().await?) {
+    (Some(config), Some(user)) => Some(config.with_fallback(user)),
+    (config, user) => config.or(user),
+  };
+  let mut args = match Args::try_parse_grouped(args_os(), config.as_ref()) {
+    Ok(args) => args,
+    Err(err) => match err.kind() {
+      ErrorKind::DisplayHelp | ErrorKind::DisplayVersion => {
+        print!("{}", err);
+        return Ok(())
+      },
+      _ => return Err(err.into()),
+    },
+  };
+
+  let () = init_tracing(args.verbose, args.quiet);
+
+  if let Some(shell) = args.completions {
+    let () = Args::write_completions(shell, config.as_ref(), &mut stdout().lock());
+    return Ok(())
+  }
+
+  // Unattended runs decide on renames without prompting and report
+  // them in a machine readable manner only.
+  if args.batch {
+    args.yes = !args.dry_run && !args.check;
+    args.output = Format::Json;
+    args.theme = Theme::Monochrome;
+  }
+
+  #[cfg(feature = "self-update")]
+  if args.self_update {
+    return update::self_update().await
+  }
+  if let Some(name) = &args.service {
+    return generate_service(name, &args).await
+  }
+  if let Some(id) = &args.undo {
+    return undo(&journal::default_dir()?, id.as_deref()).await
+  }
+  if let Some(id) = &args.verify {
+    return verify(&journal::default_dir()?, id.as_deref()).await
+  }
+  if let Some(id) = &args.audit {
+    return audit(&journal::default_dir()?, id.as_deref()).await
+  }
+  if let (Some(cursor), Some(path)) = (args.since, &args.log) {
+    return audit::print_since(path, cursor)
+  }
+  if let Some(id) = &args.recover {
+    return recover(&journal::default_dir()?, id.as_deref()).await
+  }
+  if let (Some([dir_a, dir_b]), false) = (args.diff_names.as_deref(), args.reconcile) {
+    let _plan = compare::diff_names(dir_a, dir_b, args.pair_by, args.output).await?;
+    return Ok(())
+  }
+  if args.suggest {
+    let input = input(&args)?;
+    return suggest::suggest(&journal::default_dir()?, args.yes, &input, args.output).await
+  }
+  #[cfg(feature = "archive")]
+  if let Some(path) = &args.archive {
+    let input = input(&args)?;
+    let input = (!args.yes).then_some((&input, args.theme));
+    let opts = rename_opts(&args);
+    return crate::archive::rename(
+      path,
+      &args.command,
+      args.edit,
+      args.check_case,
+      &opts,
+      input,
+      args.output,
+    )
+    .await
+  }
+
+  rename(args, config).await
+}
+
+
+fn main() -> Result<()> {
+  let runtime = Builder::new_current_thread()
+    .enable_all()
+    .build()
+    .context("failed to create Tokio runtime")?;
+  let result = runtime.block_on(async {
+    select! {
+      result = run() => result.map(|()| false),
+      result = ctrl_c() => result.map(|()| true).context("failed to wait for Ctrl-C"),
+    }
+  });
+
+  if !matches!(result, Ok(false)) {
+    // Shutting down the runtime cancels all tasks still in flight,
+    // which in turn kills the commands they are running. Blocking
+    // operations, such as waiting for an editor or for the response
+    // to a prompt left behind by an error, are not waited for.
+    let () = runtime.shutdown_background();
+  }
+  if result? {
+    exit(130)
+  }
+  Ok(())
+}
@@ -0,0 +1,105 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::path::Path;
+use std::path::PathBuf;
+
+use anyhow::bail;
+use anyhow::Context as _;
+use anyhow::Result;
+
+use batch_renamer::manifest::Direction;
+use batch_renamer::manifest::Drift;
+use batch_renamer::manifest::Manifest;
+use batch_renamer::output::Format;
+use batch_renamer::resolve;
+use batch_renamer::SymlinkPolicy;
+
+use crate::tell;
+
+
+/// Resolve the path of the manifest `path`, which may not exist yet,
+/// returning it along with the directory it describes.
+async fn locate(path: &Path) -> Result<(PathBuf, PathBuf)> {
+  let name = path
+    .file_name()
+    .with_context(|| format!("path `{}` does not have file name", path.display()))?;
+  let dir = match path.parent() {
+    Some(dir) if !dir.as_os_str().is_empty() => dir,
+    _ => Path::new("."),
+  };
+  let dir = resolve(dir, SymlinkPolicy::Follow).await?;
+  Ok((dir.join(name), dir))
+}
+
+
+/// Describe `drift` for the user.
+fn describe(drift: &Drift) -> String {
+  match drift {
+    Drift::Moved { path, recorded } => format!(
+      "moved:     `{}` is recorded as `{}`",
+      path.display(),
+      recorded.display()
+    ),
+    Drift::Modified(path) => format!("modified:  `{}`", path.display()),
+    Drift::Untracked(path) => format!("untracked: `{}`", path.display()),
+    Drift::Missing(path) => format!("missing:   `{}`", path.display()),
+  }
+}
+
+
+/// Compare the manifest at `path` with the directory containing it and
+/// report any drift.
+///
+/// With [`Direction::Check`] drift is reported as an error. Otherwise
+/// returned are the renames necessary for making files match the
+/// manifest, if `direction` asks for it, as the paths of the files as
+/// well as their resolved forms, along with their new paths.
+pub(crate) async fn sync(
+  path: &Path,
+  direction: Direction,
+  output: Format,
+) -> Result<Vec<(PathBuf, PathBuf, PathBuf)>> {
+  let (path, dir) = locate(path).await?;
+  let recorded = Manifest::load(&path).await?;
+  let actual = Manifest::scan(&dir, &path).await?;
+  let drift = recorded.drift(&actual);
+  for drift in &drift {
+    let () = tell(output, &describe(drift));
+  }
+
+  match direction {
+    Direction::Check if drift.is_empty() => Ok(Vec::new()),
+    Direction::Check => bail!(
+      "manifest `{}` is out of sync with `{}`",
+      path.display(),
+      dir.display()
+    ),
+    Direction::Manifest => Ok(Vec::new()),
+    Direction::Files | Direction::Both => {
+      let plan = drift
+        .into_iter()
+        .filter_map(|drift| match drift {
+          Drift::Moved { path, recorded } => {
+            let src = dir.join(path);
+            Some((src.clone(), src, dir.join(recorded)))
+          },
+          _ => None,
+        })
+        .collect();
+      Ok(plan)
+    },
+  }
+}
+
+
+/// Update the manifest at `path` from the directory containing it, if
+/// `direction` asks for it.
+pub(crate) async fn update(path: &Path, direction: Direction) -> Result<()> {
+  if matches!(direction, Direction::Manifest | Direction::Both) {
+    let (path, dir) = locate(path).await?;
+    let actual = Manifest::scan(&dir, &path).await?;
+    let () = actual.save(&path).await?;
+  }
+  Ok(())
+}
@@ -0,0 +1,79 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Parties interested in applied renames, such as webhooks and media
+//! server libraries.
+
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::Result;
+
+use batch_renamer::output::Format;
+use batch_renamer::output::Summary;
+
+use crate::library::Libraries;
+use crate::reference::References;
+use crate::sequence::Sequences;
+use crate::tell;
+use crate::webhook::Webhooks;
+
+
+/// The parties to notify about each applied rename and, once all of
+/// them got applied, about the batch as a whole.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Notifiers {
+  /// The webhooks to notify about each applied rename.
+  pub webhooks: Option<Arc<Webhooks>>,
+  /// The libraries of media servers to refresh for the directories
+  /// touched by applied renames.
+  pub libraries: Option<Arc<Libraries>>,
+  /// The files referencing other files to keep consistent with applied
+  /// renames.
+  pub references: Option<Arc<References>>,
+  /// The sequences of numbered files to check for gaps and duplicates
+  /// once renames got applied.
+  pub sequences: Option<Arc<Sequences>>,
+}
+
+impl Notifiers {
+  /// Notify all parties about the rename of `old` to `new`.
+  pub(crate) async fn renamed(&self, old: &Path, new: &Path) {
+    if let Some(libraries) = &self.libraries {
+      let () = libraries.renamed(old, new);
+    }
+    if let Some(references) = &self.references {
+      let () = references.renamed(old, new);
+    }
+    if let Some(sequences) = &self.sequences {
+      let () = sequences.renamed(old, new);
+    }
+    if let Some(webhooks) = &self.webhooks {
+      let () = webhooks.renamed(old, new).await;
+    }
+  }
+
+  /// Notify all parties about the completion of the batch described
+  /// by `summary`, refreshing libraries, updating references, and
+  /// reporting on sequences as necessary.
+  pub(crate) async fn finish(&self, summary: &Summary, output: Format) -> Result<()> {
+    if let Some(webhooks) = &self.webhooks {
+      let () = webhooks.finish(summary).await;
+    }
+    if let Some(libraries) = &self.libraries {
+      let () = libraries.refresh().await?;
+    }
+    if let Some(references) = &self.references {
+      for path in references.update().await? {
+        let () = tell(
+          output,
+          &format!("Updated references in `{}`", path.display()),
+        );
+      }
+    }
+    if let Some(sequences) = &self.sequences {
+      let () = sequences.report(output).await?;
+    }
+    Ok(())
+  }
+}
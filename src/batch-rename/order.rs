@@ -0,0 +1,92 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Ordering of the files to rename.
+
+use std::path::Path;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::SystemTime;
+
+use anyhow::bail;
+use anyhow::Error;
+use anyhow::Result;
+
+use tokio::fs::metadata;
+
+
+/// The order in which to process files.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub(crate) enum Order {
+  /// Order files by their paths.
+  Name,
+  /// Order files by their modification times, oldest first.
+  Mtime,
+  /// Order files by their sizes, smallest first.
+  Size,
+  /// Keep files in the order they were provided in.
+  #[default]
+  None,
+}
+
+impl FromStr for Order {
+  type Err = Error;
+
+  fn from_str(s: &str) -> Result<Self> {
+    match s {
+      "name" => Ok(Self::Name),
+      "mtime" => Ok(Self::Mtime),
+      "size" => Ok(Self::Size),
+      "none" => Ok(Self::None),
+      _ => {
+        bail!("unsupported sort order `{s}`; expected one of `name`, `mtime`, `size`, or `none`")
+      },
+    }
+  }
+}
+
+
+/// The key by which a file gets ordered, besides its path.
+///
+/// Files whose metadata could not be retrieved have no key and are
+/// ordered last, leaving it to the actual rename to report the issue.
+async fn key(path: &Path, order: Order) -> Option<(SystemTime, u64)> {
+  let metadata = metadata(path).await.ok()?;
+  let key = match order {
+    Order::Mtime => (metadata.modified().ok()?, 0),
+    Order::Size => (SystemTime::UNIX_EPOCH, metadata.len()),
+    Order::Name | Order::None => (SystemTime::UNIX_EPOCH, 0),
+  };
+  Some(key)
+}
+
+
+/// Bring `files` into the given `order`, reversing it if `reverse` is
+/// set.
+///
+/// Files with equal modification times or sizes are ordered by their
+/// paths, so that the outcome does not depend on the order the files
+/// were provided in.
+pub(crate) async fn sort(files: Vec<PathBuf>, order: Order, reverse: bool) -> Vec<PathBuf> {
+  let mut files = match order {
+    Order::None => files,
+    Order::Name => {
+      let mut files = files;
+      let () = files.sort();
+      files
+    },
+    Order::Mtime | Order::Size => {
+      let mut keyed = Vec::with_capacity(files.len());
+      for file in files {
+        let key = key(&file, order).await;
+        let () = keyed.push((key.is_none(), key, file));
+      }
+      let () = keyed.sort();
+      keyed.into_iter().map(|(.., file)| file).collect()
+    },
+  };
+  if reverse {
+    let () = files.reverse();
+  }
+  files
+}
@@ -0,0 +1,151 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Progress reporting for non-interactive runs.
+
+use std::io::stderr;
+use std::io::Write as _;
+use std::time::Duration;
+
+use batch_renamer::progress::Eta;
+use batch_renamer::progress::Progress as Counts;
+
+
+/// Format a duration in a human readable and compact form.
+fn format_duration(duration: Duration) -> String {
+  let secs = duration.as_secs();
+  let (hours, mins, secs) = (secs / 3600, secs / 60 % 60, secs % 60);
+
+  if hours > 0 {
+    format!("{hours}h{mins:02}m{secs:02}s")
+  } else if mins > 0 {
+    format!("{mins}m{secs:02}s")
+  } else {
+    format!("{secs}s")
+  }
+}
+
+
+/// The width of the progress bar, in characters.
+const BAR_WIDTH: usize = 30;
+
+
+/// Render a bar filled to the fraction `done` of `total`.
+fn format_bar(done: usize, total: usize) -> String {
+  let filled = (done * BAR_WIDTH).checked_div(total).unwrap_or(BAR_WIDTH);
+  let filled = filled.min(BAR_WIDTH);
+  format!("[{}{}]", "#".repeat(filled), "-".repeat(BAR_WIDTH - filled))
+}
+
+
+/// The progress of a batch.
+#[derive(Debug)]
+pub(crate) struct Progress {
+  /// The counts reported by the library.
+  counts: Counts,
+  /// The number of files for which renaming has been accepted.
+  accepted: usize,
+  /// The estimator for the planning phase.
+  plan_eta: Eta,
+  /// The estimator for the apply phase.
+  apply_eta: Eta,
+}
+
+impl Progress {
+  pub(crate) fn new(total: usize) -> Self {
+    Self {
+      counts: Counts::new(total),
+      accepted: 0,
+      plan_eta: Eta::new(),
+      apply_eta: Eta::new(),
+    }
+  }
+
+  /// Record that a file has been planned.
+  pub(crate) fn planned(&mut self) {
+    self.counts.previewed += 1;
+    let () = self.plan_eta.tick();
+    let () = self.print();
+  }
+
+  /// Record that renaming a file has been accepted.
+  pub(crate) fn accepted(&mut self) {
+    self.accepted += 1;
+  }
+
+  /// Record that a file has been renamed.
+  pub(crate) fn applied(&mut self) {
+    self.counts.applied += 1;
+    let () = self.apply_eta.tick();
+    let () = self.print();
+  }
+
+  /// Record that renaming a file failed.
+  pub(crate) fn failed(&mut self) {
+    self.counts.failed += 1;
+    let () = self.apply_eta.tick();
+    let () = self.print();
+  }
+
+  /// Estimate the time remaining until the batch completes.
+  fn eta(&self) -> Option<Duration> {
+    let Counts {
+      total,
+      previewed,
+      applied,
+      failed,
+      ..
+    } = self.counts;
+    let unplanned = total - previewed;
+    let plan = self.plan_eta.estimate(unplanned);
+    // We don't know how many of the files yet to be planned will need
+    // renaming; assume all of them do.
+    let apply = self
+      .apply_eta
+      .estimate(self.accepted - applied - failed + unplanned);
+
+    match (plan, apply) {
+      (Some(plan), Some(apply)) => Some(plan.max(apply)),
+      (plan, None) => plan,
+      (None, apply) => apply,
+    }
+  }
+
+  /// Print the current progress to stderr, overwriting the previous
+  /// progress line.
+  fn print(&self) {
+    let Counts {
+      total,
+      previewed,
+      applied,
+      failed,
+      ..
+    } = self.counts;
+    // Files are done once renamed, failed, or planned but not accepted
+    // for renaming, e.g., because their name does not change.
+    let done = previewed - self.accepted + applied + failed;
+    let failed = if failed > 0 {
+      format!(", {failed} failed")
+    } else {
+      String::new()
+    };
+    let eta = self
+      .eta()
+      .map(|eta| format!(", ETA {}", format_duration(eta)))
+      .unwrap_or_default();
+    let mut stderr = stderr().lock();
+    // Progress reporting is best-effort only; ignore errors.
+    let _result = write!(
+      stderr,
+      "\r\x1b[K{} {done}/{total}: planned {previewed}, renamed {applied}{failed}{eta}",
+      format_bar(done, total),
+    );
+    let _result = stderr.flush();
+  }
+
+  /// Finish progress reporting, moving the cursor past the progress
+  /// line.
+  pub(crate) fn finish(&self) {
+    let _result = writeln!(stderr());
+  }
+}
@@ -0,0 +1,519 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Updating of references to renamed files in playlists, desktop
+//! entries, documents linking to them, and similar files listing paths.
+
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::ffi::OsStr;
+use std::io::Write as _;
+use std::mem::take;
+use std::path::Component;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::sync::PoisonError;
+
+use anyhow::Context as _;
+use anyhow::Result;
+
+use batch_renamer::plan::normalize;
+
+use tempfile::Builder;
+
+use tokio::fs::canonicalize;
+use tokio::fs::metadata;
+use tokio::fs::read_dir;
+use tokio::fs::read_to_string;
+use tokio::task::spawn_blocking;
+
+
+/// The keys of desktop entries whose values are paths.
+const DESKTOP_PATH_KEYS: [&str; 4] = ["Icon", "Path", "TryExec", "URL"];
+/// The key of desktop entries whose value is a command line.
+const DESKTOP_EXEC_KEY: &str = "Exec";
+
+
+/// The format of a file referencing other files.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Format {
+  /// An XML shareable playlist, referencing files by URI in
+  /// `<location>` elements.
+  Xspf,
+  /// A desktop entry, referencing files in the values of some keys.
+  Desktop,
+  /// A PLS playlist, referencing files in the values of `FileN` keys.
+  Pls,
+  /// A Markdown document, referencing files in links, including
+  /// `[[wiki links]]` as used by note-taking applications.
+  Markdown,
+  /// An HTML document, referencing files in `href` and `src`
+  /// attributes.
+  Html,
+  /// A list of paths, one per line, as in M3U playlists. Lines
+  /// starting with `#` are comments.
+  List,
+}
+
+impl Format {
+  /// Determine the format of the file at `path` from its extension.
+  fn detect(path: &Path) -> Self {
+    let ext = path
+      .extension()
+      .map(OsStr::to_string_lossy)
+      .unwrap_or_default()
+      .to_ascii_lowercase();
+    match ext.as_str() {
+      "xspf" => Self::Xspf,
+      "desktop" => Self::Desktop,
+      "pls" => Self::Pls,
+      "md" | "markdown" => Self::Markdown,
+      "html" | "htm" => Self::Html,
+      _ => Self::List,
+    }
+  }
+
+  /// Check whether the format is that of a document, linking to
+  /// files.
+  fn is_document(&self) -> bool {
+    matches!(self, Self::Markdown | Self::Html)
+  }
+}
+
+
+/// Percent-decode `s`, as used in URIs.
+fn decode(s: &str) -> Option<String> {
+  let mut bytes = Vec::with_capacity(s.len());
+  let mut iter = s.bytes();
+  while let Some(b) = iter.next() {
+    if b == b'%' {
+      let hex = [iter.next()?, iter.next()?];
+      let hex = std::str::from_utf8(&hex).ok()?;
+      let () = bytes.push(u8::from_str_radix(hex, 16).ok()?);
+    } else {
+      let () = bytes.push(b);
+    }
+  }
+  String::from_utf8(bytes).ok()
+}
+
+
+/// Percent-encode the path `s` for usage in a URI.
+fn encode(s: &str) -> String {
+  s.bytes()
+    .map(|b| match b {
+      b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+        char::from(b).to_string()
+      },
+      b => format!("%{b:02X}"),
+    })
+    .collect()
+}
+
+
+/// Escape the characters in the path `s` that may not appear in the
+/// target of a Markdown link, as used by Markdown editors.
+fn escape_markdown(s: &str) -> String {
+  s.replace('%', "%25")
+    .replace(' ', "%20")
+    .replace('(', "%28")
+    .replace(')', "%29")
+}
+
+
+/// Replace the text between each occurrence of `open` and the next
+/// `close` in `content` by the result of `f`, if any.
+fn replace_between<F>(content: &str, open: &str, close: &str, mut f: F) -> String
+where
+  F: FnMut(&str) -> Option<String>,
+{
+  let mut result = String::with_capacity(content.len());
+  let mut rest = content;
+  while let Some(start) = rest.find(open) {
+    let start = start + open.len();
+    let () = result.push_str(&rest[..start]);
+    rest = &rest[start..];
+    let Some(end) = rest.find(close) else { break };
+    let text = &rest[..end];
+    match f(text) {
+      Some(new) => result.push_str(&new),
+      None => result.push_str(text),
+    }
+    rest = &rest[end..];
+  }
+  let () = result.push_str(rest);
+  result
+}
+
+
+/// Split `link` into the path it references and its fragment, if any,
+/// including the leading `#`.
+fn split_fragment(link: &str) -> (&str, &str) {
+  match link.find('#') {
+    Some(idx) => link.split_at(idx),
+    None => (link, ""),
+  }
+}
+
+
+/// Unescape the XML entities in `s`.
+fn unescape_xml(s: &str) -> String {
+  s.replace("&lt;", "<")
+    .replace("&gt;", ">")
+    .replace("&quot;", "\"")
+    .replace("&apos;", "'")
+    .replace("&amp;", "&")
+}
+
+
+/// Escape `s` for usage as XML text.
+fn escape_xml(s: &str) -> String {
+  s.replace('&', "&amp;")
+    .replace('<', "&lt;")
+    .replace('>', "&gt;")
+}
+
+
+/// Express the absolute path `path` relative to the absolute directory
+/// `base`.
+fn relative(path: &Path, base: &Path) -> PathBuf {
+  let mut path_components = path.components().peekable();
+  let mut base_components = base.components().peekable();
+  while let (Some(a), Some(b)) = (path_components.peek(), base_components.peek()) {
+    if a != b {
+      break
+    }
+    let _next = path_components.next();
+    let _next = base_components.next();
+  }
+
+  base_components
+    .map(|_| Component::ParentDir)
+    .chain(path_components)
+    .collect()
+}
+
+
+/// Applied renames, for looking up the new paths of referenced files.
+#[derive(Debug, Default)]
+struct Renames(BTreeMap<PathBuf, PathBuf>);
+
+impl Renames {
+  /// Look up the new path of the file at the absolute `path`, which
+  /// changes if the file itself or any of the directories containing it
+  /// got renamed.
+  fn lookup(&self, path: &Path) -> Option<PathBuf> {
+    path.ancestors().find_map(|ancestor| {
+      let dst = self.0.get(ancestor)?;
+      // SANITY: `ancestor` is an ancestor of `path`.
+      let rest = path.strip_prefix(ancestor).unwrap();
+      if rest.as_os_str().is_empty() {
+        Some(dst.clone())
+      } else {
+        Some(dst.join(rest))
+      }
+    })
+  }
+
+  /// Rewrite the reference `value` to a renamed file, made by a file in
+  /// `dir`, returning `None` if the referenced file did not get
+  /// renamed.
+  ///
+  /// The reference is a path, absolute or relative to `dir`, or a
+  /// `file://` URI. With `uri` set, paths are percent-encoded. The
+  /// rewritten reference retains its form.
+  fn rewrite(&self, value: &str, dir: &Path, uri: bool) -> Option<String> {
+    let (scheme, raw) = match value.strip_prefix("file://") {
+      Some(raw) => (true, raw),
+      None => (false, value),
+    };
+    let decoded = if scheme || uri {
+      decode(raw)?
+    } else {
+      raw.to_string()
+    };
+    if decoded.is_empty() {
+      return None
+    }
+
+    let path = Path::new(&decoded);
+    let new = self.lookup(&normalize(&dir.join(path)))?;
+    let new = if path.is_absolute() {
+      new
+    } else {
+      relative(&new, dir)
+    };
+    let new = new.to_str()?;
+    let new = match (scheme, uri) {
+      (true, _) => format!("file://{}", encode(new)),
+      (false, true) => encode(new),
+      (false, false) => new.to_string(),
+    };
+    Some(new)
+  }
+
+  /// Rewrite the references to renamed files in `content` of a file of
+  /// the given format in `dir`.
+  fn rewrite_content(&self, content: &str, dir: &Path, format: Format) -> String {
+    match format {
+      Format::Xspf => return self.rewrite_xspf(content, dir),
+      Format::Markdown => return self.rewrite_markdown(content, dir),
+      Format::Html => return self.rewrite_html(content, dir),
+      Format::Desktop | Format::Pls | Format::List => (),
+    }
+
+    content
+      .split_inclusive('\n')
+      .map(|line| {
+        let text = line.trim_end_matches(['\r', '\n']);
+        let ending = &line[text.len()..];
+        let rewritten = match format {
+          Format::Desktop => self.rewrite_desktop_line(text, dir),
+          Format::Pls => self.rewrite_pls_line(text, dir),
+          Format::List if text.trim().is_empty() || text.starts_with('#') => None,
+          Format::List => self.rewrite(text, dir, false),
+          Format::Xspf | Format::Markdown | Format::Html => unreachable!(),
+        };
+        match rewritten {
+          Some(text) => format!("{text}{ending}"),
+          None => line.to_string(),
+        }
+      })
+      .collect()
+  }
+
+  /// Rewrite the URIs in the `<location>` elements of the XML
+  /// shareable playlist `content`.
+  fn rewrite_xspf(&self, content: &str, dir: &Path) -> String {
+    replace_between(content, "<location>", "</location>", |location| {
+      let new = self.rewrite(&unescape_xml(location.trim()), dir, true)?;
+      Some(escape_xml(&new))
+    })
+  }
+
+  /// Rewrite the links in the Markdown document `content`, both those
+  /// of the form `[text](target)` and `[[target]]` wiki links.
+  fn rewrite_markdown(&self, content: &str, dir: &Path) -> String {
+    let content = replace_between(content, "](", ")", |target| {
+      // Targets containing spaces may be enclosed in angle brackets.
+      // Otherwise they are percent-encoded and may be followed by a
+      // title.
+      if let Some(inner) = target.strip_prefix('<') {
+        let (link, tail) = inner.split_once('>')?;
+        let (path, fragment) = split_fragment(link);
+        let new = self.rewrite(path, dir, false)?;
+        Some(format!("<{new}{fragment}>{tail}"))
+      } else {
+        let (link, tail) = target.split_at(target.find(' ').unwrap_or(target.len()));
+        let (path, fragment) = split_fragment(link);
+        let new = self.rewrite(&decode(path)?, dir, false)?;
+        Some(format!("{}{fragment}{tail}", escape_markdown(&new)))
+      }
+    });
+
+    replace_between(&content, "[[", "]]", |target| {
+      // Wiki links may carry an alias and a heading, as in
+      // `[[note#heading|alias]]`, and commonly omit the `.md`
+      // extension of the note they link to.
+      let (link, alias) = target.split_at(target.find('|').unwrap_or(target.len()));
+      let (path, fragment) = split_fragment(link);
+      let new = self.rewrite(path, dir, false).or_else(|| {
+        let new = self.rewrite(&format!("{path}.md"), dir, false)?;
+        Some(new.strip_suffix(".md").unwrap_or(&new).to_string())
+      })?;
+      Some(format!("{new}{fragment}{alias}"))
+    })
+  }
+
+  /// Rewrite the URIs in the `href` and `src` attributes of the HTML
+  /// document `content`.
+  fn rewrite_html(&self, content: &str, dir: &Path) -> String {
+    ["href", "src"]
+      .into_iter()
+      .flat_map(|attr| [(attr, '"'), (attr, '\'')])
+      .fold(content.to_string(), |content, (attr, quote)| {
+        let open = format!("{attr}={quote}");
+        replace_between(&content, &open, &quote.to_string(), |link| {
+          let (path, fragment) = split_fragment(link);
+          let new = self.rewrite(&unescape_xml(path), dir, true)?;
+          Some(format!("{}{fragment}", escape_xml(&new)))
+        })
+      })
+  }
+
+  /// Rewrite the references in the line `line` of a desktop entry.
+  fn rewrite_desktop_line(&self, line: &str, dir: &Path) -> Option<String> {
+    let (key, value) = line.split_once('=')?;
+    // Keys may be localized, as in `Icon[de]`.
+    let name = key.split('[').next().unwrap_or(key).trim();
+    if DESKTOP_PATH_KEYS.contains(&name) {
+      let new = self.rewrite(value.trim(), dir, false)?;
+      Some(format!("{key}={new}"))
+    } else if name == DESKTOP_EXEC_KEY {
+      // Arguments of the command line are separated by spaces and may
+      // be quoted.
+      let mut changed = false;
+      let args = value
+        .split(' ')
+        .map(|arg| {
+          let (quote, inner) = match arg.strip_prefix('"').and_then(|arg| arg.strip_suffix('"')) {
+            Some(inner) => ("\"", inner),
+            None => ("", arg),
+          };
+          match self.rewrite(inner, dir, false) {
+            Some(new) => {
+              changed = true;
+              format!("{quote}{new}{quote}")
+            },
+            None => arg.to_string(),
+          }
+        })
+        .collect::<Vec<_>>();
+      changed.then(|| format!("{key}={}", args.join(" ")))
+    } else {
+      None
+    }
+  }
+
+  /// Rewrite the reference in the line `line` of a PLS playlist.
+  fn rewrite_pls_line(&self, line: &str, dir: &Path) -> Option<String> {
+    let (key, value) = line.split_once('=')?;
+    if !key.trim().starts_with("File") {
+      return None
+    }
+    let new = self.rewrite(value.trim(), dir, false)?;
+    Some(format!("{key}={new}"))
+  }
+}
+
+
+/// Replace the contents of the file at `path` with `content`, by means
+/// of a temporary file atomically replacing it.
+///
+/// This function blocks.
+fn replace(path: &Path, content: &str) -> Result<()> {
+  let dir = path
+    .parent()
+    .filter(|dir| !dir.as_os_str().is_empty())
+    .unwrap_or_else(|| Path::new("."));
+  let mut tmp = Builder::new()
+    .prefix(".batch-rename-")
+    .tempfile_in(dir)
+    .with_context(|| format!("failed to create temporary file in `{}`", dir.display()))?;
+  let context = || format!("failed to update `{}`", path.display());
+  let permissions = std::fs::metadata(path).with_context(context)?.permissions();
+  let () = tmp.write_all(content.as_bytes()).with_context(context)?;
+  let () = tmp
+    .as_file()
+    .set_permissions(permissions)
+    .with_context(context)?;
+  let () = tmp.as_file().sync_all().with_context(context)?;
+  let _file = tmp.persist(path).with_context(context)?;
+  Ok(())
+}
+
+
+/// Find the Markdown and HTML documents in `dirs`.
+async fn documents(dirs: &BTreeSet<&Path>) -> Result<Vec<PathBuf>> {
+  let mut documents = Vec::new();
+  for dir in dirs {
+    let context = || format!("failed to read directory `{}`", dir.display());
+    let mut entries = read_dir(dir).await.with_context(context)?;
+    while let Some(entry) = entries.next_entry().await.with_context(context)? {
+      let path = entry.path();
+      if Format::detect(&path).is_document() {
+        let () = documents.push(path);
+      }
+    }
+  }
+  Ok(documents)
+}
+
+
+/// Files referencing other files, such as playlists, whose references
+/// are kept consistent with applied renames.
+#[derive(Debug)]
+pub(crate) struct References {
+  /// The files referencing other files.
+  files: Vec<PathBuf>,
+  /// Whether to also update the links in the Markdown and HTML
+  /// documents in the directories of renamed files.
+  documents: bool,
+  /// The renames applied so far.
+  renames: Mutex<Renames>,
+}
+
+impl References {
+  /// Create references for updating those in `files` and, if
+  /// `documents` is set, in the documents next to renamed files.
+  pub(crate) fn new(files: Vec<PathBuf>, documents: bool) -> Self {
+    Self {
+      files,
+      documents,
+      renames: Mutex::new(Renames::default()),
+    }
+  }
+
+  /// Remember the rename of `src` to `dst`.
+  pub(crate) fn renamed(&self, src: &Path, dst: &Path) {
+    let mut renames = self.renames.lock().unwrap_or_else(PoisonError::into_inner);
+    let _prev = renames.0.insert(src.to_path_buf(), dst.to_path_buf());
+  }
+
+  /// Rewrite the references to renamed files in all files, returning
+  /// the paths of the files that changed.
+  ///
+  /// Relative references are kept relative, and references by URI are
+  /// kept as URIs.
+  pub(crate) async fn update(&self) -> Result<Vec<PathBuf>> {
+    let renames = take(&mut *self.renames.lock().unwrap_or_else(PoisonError::into_inner));
+    if renames.0.is_empty() {
+      return Ok(Vec::new())
+    }
+
+    let mut files = self.files.clone();
+    if self.documents {
+      // Documents in directories that got renamed themselves are found
+      // under their new paths.
+      let dirs = renames
+        .0
+        .iter()
+        .flat_map(|(src, dst)| [src.parent(), dst.parent()])
+        .flatten()
+        .filter(|dir| !renames.0.contains_key(*dir))
+        .collect::<BTreeSet<_>>();
+      let () = files.extend(documents(&dirs).await?);
+    }
+
+    let mut updated = Vec::new();
+    for file in &files {
+      // The file itself may have been renamed.
+      let abs_file = canonicalize(file)
+        .await
+        .ok()
+        .and_then(|path| renames.lookup(&path).or(Some(path)))
+        .with_context(|| format!("failed to canonicalize `{}`", file.display()))?;
+      let is_file = metadata(&abs_file).await.is_ok_and(|meta| meta.is_file());
+      if !is_file || updated.contains(&abs_file) {
+        continue
+      }
+      let content = read_to_string(&abs_file)
+        .await
+        .with_context(|| format!("failed to read `{}`", abs_file.display()))?;
+      // SANITY: A canonical path to a file always has a parent.
+      let dir = abs_file.parent().unwrap();
+      let rewritten = renames.rewrite_content(&content, dir, Format::detect(&abs_file));
+      if rewritten == content {
+        continue
+      }
+
+      let path = abs_file.clone();
+      let () = spawn_blocking(move || replace(&path, &rewritten))
+        .await
+        .context("update task failed")??;
+      let () = updated.push(abs_file);
+    }
+    Ok(updated)
+  }
+}
@@ -0,0 +1,42 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Restriction of the program to writing only where files get renamed,
+//! by means of Landlock.
+
+use std::path::PathBuf;
+
+use anyhow::ensure;
+use anyhow::Context as _;
+use anyhow::Result;
+
+use landlock::path_beneath_rules;
+use landlock::AccessFs;
+use landlock::Ruleset;
+use landlock::RulesetAttr as _;
+use landlock::RulesetCreatedAttr as _;
+use landlock::RulesetStatus;
+use landlock::ABI;
+
+
+/// Irrevocably restrict the current process and all processes it
+/// spawns to modifying the file system beneath the directories in
+/// `writable` only. Reading stays unrestricted.
+///
+/// Directories that do not exist are ignored.
+pub(crate) fn restrict(writable: &[PathBuf]) -> Result<()> {
+  // Renaming files across directories is only possible as of the
+  // second version of the ABI; the third covers truncation as well.
+  let access = AccessFs::from_write(ABI::V3);
+  let status = Ruleset::default()
+    .handle_access(access)
+    .and_then(|ruleset| ruleset.create())
+    .and_then(|ruleset| ruleset.add_rules(path_beneath_rules(writable, access)))
+    .and_then(|ruleset| ruleset.restrict_self())
+    .context("failed to restrict file system access using Landlock")?;
+  ensure!(
+    status.ruleset != RulesetStatus::NotEnforced,
+    "failed to restrict file system access: Landlock is not supported by the kernel"
+  );
+  Ok(())
+}
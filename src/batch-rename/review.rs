@@ -0,0 +1,425 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Review of proposed renames in a text editor.
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::env::current_dir;
+use std::env::var_os;
+use std::ffi::OsString;
+use std::fs::read;
+use std::fs::write;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::Mutex;
+
+use anyhow::bail;
+use anyhow::ensure;
+use anyhow::Context as _;
+use anyhow::Result;
+
+use batch_renamer::conflict::displace;
+use batch_renamer::conflict::resolve_conflict;
+use batch_renamer::conflict::ConflictPolicy;
+use batch_renamer::journal::Journal;
+use batch_renamer::os_str_bytes;
+use batch_renamer::os_string_from_bytes;
+use batch_renamer::plan::normalize;
+use batch_renamer::plan::order_chains;
+use batch_renamer::plan::split_blocked;
+use batch_renamer::plan::split_swaps;
+use batch_renamer::rename_to;
+use batch_renamer::retry::retry;
+use batch_renamer::Log as _;
+use batch_renamer::RenameOpts;
+
+use tempfile::Builder;
+
+use tokio::task::spawn_blocking;
+
+use crate::apply_annotated;
+use crate::back_up;
+use crate::hook::Hooks;
+use crate::record_annotated;
+use crate::record_declined;
+use crate::swap;
+use crate::Root;
+
+
+/// The editor to use if the user did not configure one.
+#[cfg(not(windows))]
+const DEFAULT_EDITOR: &str = "vi";
+#[cfg(windows)]
+const DEFAULT_EDITOR: &str = "notepad";
+
+
+/// Retrieve the editor to use as configured by the user.
+fn editor() -> OsString {
+  var_os("VISUAL")
+    .or_else(|| var_os("EDITOR"))
+    .filter(|editor| !editor.is_empty())
+    .unwrap_or_else(|| OsString::from(DEFAULT_EDITOR))
+}
+
+
+/// Create the command for opening `file` in `editor`.
+///
+/// The editor variable may contain arguments, so let the shell
+/// interpret it.
+#[cfg(not(windows))]
+fn editor_command(editor: &OsString, file: &Path) -> Command {
+  use std::fs::File;
+  use std::io::stdin;
+  use std::io::IsTerminal as _;
+
+  let mut command = Command::new("sh");
+  let _command = command
+    .arg("-c")
+    .arg("eval \"$0\" '\"$1\"'")
+    .arg(editor)
+    .arg(file);
+
+  // The editor needs a terminal to interact with the user, but stdin
+  // may have been used up for reading the list of files.
+  if !stdin().is_terminal() {
+    if let Ok(tty) = File::open("/dev/tty") {
+      let _command = command.stdin(tty);
+    }
+  }
+  command
+}
+
+/// Create the command for opening `file` in `editor`.
+///
+/// The editor variable may contain arguments, so let the command
+/// interpreter interpret it.
+#[cfg(windows)]
+fn editor_command(editor: &OsString, file: &Path) -> Command {
+  use std::os::windows::process::CommandExt as _;
+
+  let mut line = editor.clone();
+  let () = line.push(" \"");
+  let () = line.push(file);
+  let () = line.push("\"");
+
+  let mut command = Command::new("cmd");
+  let _command = command.arg("/C").raw_arg(line);
+  command
+}
+
+
+/// Open `file` in the user's editor and wait for it to exit.
+fn open_editor(file: &Path) -> Result<()> {
+  let editor = editor();
+  let status = editor_command(&editor, file)
+    .status()
+    .with_context(|| format!("failed to run editor `{}`", editor.to_string_lossy()))?;
+
+  ensure!(
+    status.success(),
+    "editor `{}` reported non-zero exit-status",
+    editor.to_string_lossy()
+  );
+  Ok(())
+}
+
+
+/// Remove the carriage return of a line that was terminated by CRLF,
+/// as editors on Windows may do.
+#[cfg(windows)]
+fn strip_carriage_return(line: &[u8]) -> &[u8] {
+  line.strip_suffix(b"\r").unwrap_or(line)
+}
+
+/// On other systems a carriage return is a valid part of a file name.
+#[cfg(not(windows))]
+fn strip_carriage_return(line: &[u8]) -> &[u8] {
+  line
+}
+
+
+/// A rename as reviewed by the user.
+#[derive(Debug)]
+pub(crate) struct Edit {
+  /// The path of the file, as provided by the user.
+  pub src: PathBuf,
+  /// The canonical path of the file.
+  pub abs_src: PathBuf,
+  /// The proposed new path of the file.
+  pub dst: PathBuf,
+  /// The new path of the file, as edited by the user. Equal to
+  /// `abs_src` if the rename got declined.
+  pub edited: PathBuf,
+  /// A note provided by the user.
+  pub note: Option<String>,
+}
+
+
+/// Express `dst`, the new path of the file `src`, relative to the
+/// directory `src` was provided in.
+///
+/// `abs_src` is the canonical form of `src`.
+pub(crate) fn proposed_path(src: &Path, abs_src: &Path, dst: &Path) -> PathBuf {
+  let src_dir = src.parent().unwrap_or_else(|| Path::new(""));
+  match abs_src.parent().and_then(|dir| dst.strip_prefix(dir).ok()) {
+    Some(rel) => src_dir.join(rel),
+    None => dst.to_path_buf(),
+  }
+}
+
+
+/// Map the path `edited`, as edited by the user for the file `src`, to
+/// an absolute path.
+///
+/// `abs_src` is the canonical form of `src`.
+pub(crate) fn edited_path(src: &Path, abs_src: &Path, edited: &Path) -> Result<PathBuf> {
+  let src_dir = src.parent().unwrap_or_else(|| Path::new(""));
+  let abs_dir = abs_src
+    .parent()
+    .with_context(|| format!("`{}` does not contain a parent", abs_src.display()))?;
+  let path = match edited.strip_prefix(src_dir) {
+    Ok(rel) => abs_dir.join(rel),
+    Err(_) => current_dir()
+      .context("failed to retrieve current working directory")?
+      .join(edited),
+  };
+  Ok(normalize(&path))
+}
+
+
+/// Let the user review and edit the proposed renames in `plan` using a
+/// text editor.
+///
+/// Each entry of `plan` comprises the path of a file, as provided by
+/// the user, its canonical form, and the new path proposed for it.
+/// Edited paths have to stay within `root`. Each line may be followed
+/// by a tab and a note, which ends up in the journal. Returned are all
+/// renames that change the path of a file or carry a note, in plan
+/// order.
+pub(crate) async fn edit(plan: Vec<(PathBuf, PathBuf, PathBuf)>, root: &Root) -> Result<Vec<Edit>> {
+  let mut content = Vec::new();
+  for (src, abs_src, dst) in &plan {
+    let proposed = proposed_path(src, abs_src, dst);
+    let proposed = os_str_bytes(proposed.as_os_str());
+    if proposed.contains(&b'\n') || proposed.contains(&b'\t') {
+      bail!(
+        "path `{}` contains a newline or tab and cannot be edited",
+        proposed.escape_ascii()
+      )
+    }
+
+    let () = content.extend_from_slice(&proposed);
+    let () = content.push(b'\n');
+  }
+
+  let file = Builder::new()
+    .prefix("batch-rename-")
+    .suffix(".txt")
+    .tempfile()
+    .context("failed to create temporary file")?;
+  let path = file.path().to_path_buf();
+  let () =
+    write(&path, &content).with_context(|| format!("failed to write `{}`", path.display()))?;
+  let () = spawn_blocking(move || open_editor(&path)).await??;
+
+  let content =
+    read(file.path()).with_context(|| format!("failed to read `{}`", file.path().display()))?;
+  let content = content.strip_suffix(b"\n").unwrap_or(&content);
+  let lines = if content.is_empty() {
+    Vec::new()
+  } else {
+    content
+      .split(|b| *b == b'\n')
+      .map(strip_carriage_return)
+      .collect::<Vec<_>>()
+  };
+
+  ensure!(
+    lines.len() == plan.len(),
+    "number of lines changed from {} to {}; refusing to rename",
+    plan.len(),
+    lines.len()
+  );
+
+  let mut renames = Vec::with_capacity(plan.len());
+  let mut destinations = HashSet::with_capacity(plan.len());
+  for ((src, abs_src, dst), line) in plan.into_iter().zip(lines) {
+    let (line, note) = match line.iter().position(|b| *b == b'\t') {
+      Some(idx) => (&line[..idx], Some(&line[idx + 1..])),
+      None => (line, None),
+    };
+    let note = note
+      .map(|note| String::from_utf8(note.trim_ascii().to_vec()))
+      .transpose()
+      .with_context(|| format!("note for `{}` is not valid UTF-8", line.escape_ascii()))?
+      .filter(|note| !note.is_empty());
+    let edited = PathBuf::from(os_string_from_bytes(line)?);
+    ensure!(
+      edited.file_name().is_some(),
+      "line `{}` does not contain a file name",
+      line.escape_ascii()
+    );
+
+    let edited_dst = edited_path(&src, &abs_src, &edited)?;
+    let () = root.check(&abs_src, &edited_dst)?;
+    ensure!(
+      destinations.insert(edited_dst.clone()),
+      "multiple files would be renamed to `{}`",
+      edited.display()
+    );
+
+    if edited_dst != abs_src || note.is_some() {
+      let () = renames.push(Edit {
+        src,
+        abs_src,
+        dst,
+        edited: edited_dst,
+        note,
+      });
+    }
+  }
+  Ok(renames)
+}
+
+
+/// Let the user review and edit the proposed renames in `plan` using a
+/// text editor and then apply them.
+///
+/// Unedited renames are applied using `command`, if provided, and
+/// directly otherwise.
+pub(crate) async fn review(
+  plan: Vec<(PathBuf, PathBuf, PathBuf)>,
+  command: Option<&[OsString]>,
+  opts: &RenameOpts,
+  hooks: &Hooks,
+  root: &Root,
+  journal: Option<&Mutex<Journal>>,
+) -> Result<()> {
+  let renames = edit(plan, root).await?;
+  apply_edits(renames, command, opts, hooks, journal).await
+}
+
+
+/// Apply the renames in `renames`, as edited by the user.
+///
+/// Unedited renames are applied using `command`, if provided, and
+/// directly otherwise. Declined renames are merely recorded in the
+/// journal, for the sake of their notes.
+pub(crate) async fn apply_edits(
+  renames: Vec<Edit>,
+  command: Option<&[OsString]>,
+  opts: &RenameOpts,
+  hooks: &Hooks,
+  journal: Option<&Mutex<Journal>>,
+) -> Result<()> {
+  let (declined, renames) = renames
+    .into_iter()
+    .partition::<Vec<_>, _>(|edit| edit.edited == edit.abs_src);
+  for edit in &declined {
+    if let Some(note) = &edit.note {
+      let () = record_declined(journal, &edit.abs_src, &edit.dst, note)?;
+    }
+  }
+
+  // Renames may have been accepted in any order. Those taking the
+  // place of a file whose rename got declined can't be applied at all,
+  // while all others are applied once the files whose place they take
+  // got moved away.
+  let kept = declined
+    .into_iter()
+    .map(|edit| edit.abs_src)
+    .collect::<HashSet<_>>();
+  let pairs = renames
+    .iter()
+    .map(|edit| (edit.abs_src.clone(), edit.edited.clone()))
+    .collect::<Vec<_>>();
+  let (blocked, pairs) = split_blocked(pairs, &kept);
+  let blocked = blocked
+    .into_iter()
+    .map(|((src, _dst), blocker)| (src, blocker))
+    .collect::<HashMap<_, _>>();
+  let order = order_chains(pairs)
+    .into_iter()
+    .enumerate()
+    .map(|(idx, (src, _dst))| (src, idx))
+    .collect::<HashMap<_, _>>();
+  let mut renames = renames
+    .into_iter()
+    .filter(|edit| match blocked.get(&edit.abs_src) {
+      Some(blocker) => {
+        eprintln!(
+          "Skipped `{}`: depends on declined rename of `{}`",
+          edit.abs_src.display(),
+          blocker.display()
+        );
+        false
+      },
+      None => true,
+    })
+    .collect::<Vec<_>>();
+  // SANITY: All renames that are not blocked got ordered.
+  let () = renames.sort_by_key(|edit| order[&edit.abs_src]);
+
+  // Swapping the names of two files can't be done one rename at a
+  // time. Because no other rename can involve either of the two files,
+  // swaps are applied upfront.
+  let direct = renames
+    .iter()
+    .filter(|edit| command.is_none() || edit.edited != edit.dst)
+    .map(|edit| (edit.abs_src.clone(), edit.edited.clone()))
+    .collect();
+  let (swaps, _rest) = split_swaps(direct);
+  let mut swapped = HashSet::with_capacity(swaps.len() * 2);
+  for (a, b) in swaps {
+    let () = swap(&a, &b, opts, hooks, journal).await?;
+    let () = swapped.extend([a, b]);
+  }
+
+  // Renames are applied sequentially, because we may be dealing with
+  // arbitrary user edits that depend on each other.
+  for Edit {
+    src,
+    abs_src,
+    dst,
+    edited,
+    note,
+  } in renames
+  {
+    if swapped.contains(&abs_src) {
+      continue
+    }
+
+    match command {
+      Some(command) if edited == dst => {
+        let _path =
+          apply_annotated(&src, &dst, command, opts, note.as_deref(), hooks, journal).await?;
+      },
+      _ => {
+        let Some(edited) = resolve_conflict(&abs_src, &edited, opts.on_conflict).await? else {
+          continue
+        };
+        let () = hooks.pre(&abs_src, &edited).await?;
+        let () = back_up(&abs_src, opts).await?;
+        let displaced = if opts.on_conflict == ConflictPolicy::Overwrite {
+          displace(&abs_src, &edited).await?
+        } else {
+          None
+        };
+        let () = journal.intend(&abs_src, &edited)?;
+        let result = retry(&opts.retry, || rename_to(&abs_src, &edited)).await;
+        if let Some(displaced) = displaced {
+          let () = displaced.settle(result.is_ok()).await;
+        }
+        if let Err(err) = result {
+          let _result = journal.abandon(&abs_src, &edited);
+          return Err(err.into())
+        }
+        let () = record_annotated(journal, &abs_src, &edited, note.as_deref()).await?;
+        let () = hooks.post(&abs_src, &edited).await?;
+      },
+    }
+  }
+  Ok(())
+}
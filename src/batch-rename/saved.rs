@@ -0,0 +1,150 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Plans saved for applying them later, optionally signed.
+//!
+//! A saved plan contains the absolute path of each file to rename and
+//! its new path, each terminated by a NUL character. Signatures are
+//! made and checked with minisign and stored next to the plan, with
+//! `.minisig` appended to its name.
+
+use std::ffi::OsStr;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Stdio;
+
+use anyhow::ensure;
+use anyhow::Context as _;
+use anyhow::Result;
+
+use batch_renamer::evaluate;
+use batch_renamer::os_str_bytes;
+use batch_renamer::os_string_from_bytes;
+
+use tokio::fs::read;
+use tokio::fs::write;
+use tokio::process::Command;
+
+
+/// The program used for signing plans and verifying their signatures.
+const MINISIGN: &str = "minisign";
+
+
+/// Save the renames in `plan` that change the path of a file to `path`,
+/// signing the result with the minisign secret key at `key`, if
+/// provided.
+pub(crate) async fn save(
+  path: &Path,
+  plan: &[(PathBuf, PathBuf, PathBuf)],
+  key: Option<&Path>,
+) -> Result<()> {
+  let mut content = Vec::new();
+  for (_, abs_src, dst) in plan {
+    if abs_src != dst {
+      for path in [abs_src, dst] {
+        let () = content.extend_from_slice(&os_str_bytes(path.as_os_str()));
+        let () = content.push(b'\0');
+      }
+    }
+  }
+  let () = write(path, content)
+    .await
+    .with_context(|| format!("failed to write plan `{}`", path.display()))?;
+
+  if let Some(key) = key {
+    let args = [
+      OsStr::new("-S"),
+      OsStr::new("-s"),
+      key.as_os_str(),
+      OsStr::new("-m"),
+      path.as_os_str(),
+    ];
+    // minisign may prompt for the password protecting the key.
+    let status = Command::new(MINISIGN)
+      .args(args)
+      .stdout(Stdio::null())
+      .status()
+      .await
+      .with_context(|| format!("failed to run `{MINISIGN}`"))?;
+    ensure!(
+      status.success(),
+      "failed to sign plan `{}` with key `{}`",
+      path.display(),
+      key.display()
+    );
+  }
+  Ok(())
+}
+
+
+/// Load the plan saved at `path`, refusing to do so unless it carries a
+/// valid signature made with the secret key belonging to the minisign
+/// public key at `key`, if provided.
+///
+/// Returned are the paths of the files to rename, twice, as the paths
+/// provided and in their resolved forms, along with their new paths.
+pub(crate) async fn load(
+  path: &Path,
+  key: Option<&Path>,
+) -> Result<Vec<(PathBuf, PathBuf, PathBuf)>> {
+  let content = read(path)
+    .await
+    .with_context(|| format!("failed to read plan `{}`", path.display()))?;
+
+  if let Some(key) = key {
+    let args = [
+      OsStr::new("-V"),
+      OsStr::new("-q"),
+      OsStr::new("-p"),
+      key.as_os_str(),
+      OsStr::new("-m"),
+      path.as_os_str(),
+    ];
+    let output = Command::new(MINISIGN)
+      .args(args)
+      .stdin(Stdio::null())
+      .stdout(Stdio::null())
+      .stderr(Stdio::piped())
+      .output()
+      .await
+      .with_context(|| format!("failed to run `{MINISIGN}`"))?;
+    let () = evaluate(&output, MINISIGN, args).with_context(|| {
+      format!(
+        "plan `{}` does not carry a valid signature for key `{}`",
+        path.display(),
+        key.display()
+      )
+    })?;
+    // Make sure that what got verified is what gets applied.
+    let verified = read(path)
+      .await
+      .with_context(|| format!("failed to read plan `{}`", path.display()))?;
+    ensure!(
+      verified == content,
+      "plan `{}` changed while verifying its signature",
+      path.display()
+    );
+  }
+
+  let mut fields = content.split(|b| *b == b'\0').collect::<Vec<_>>();
+  // The last field is not terminated and has to be empty.
+  let rest = fields.pop().unwrap_or_default();
+  ensure!(
+    rest.is_empty() && fields.len() % 2 == 0,
+    "plan `{}` is malformed",
+    path.display()
+  );
+
+  let mut plan = Vec::with_capacity(fields.len() / 2);
+  for record in fields.chunks_exact(2) {
+    let src = PathBuf::from(os_string_from_bytes(record[0])?);
+    let dst = PathBuf::from(os_string_from_bytes(record[1])?);
+    ensure!(
+      src.is_absolute() && dst.is_absolute(),
+      "plan `{}` contains relative paths",
+      path.display()
+    );
+    let () = plan.push((src.clone(), src, dst));
+  }
+  Ok(plan)
+}
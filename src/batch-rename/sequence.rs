@@ -0,0 +1,149 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Reporting of gaps and duplicates in the numbering of files after
+//! renames changing numbers got applied.
+
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::ffi::OsString;
+use std::mem::take;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::sync::PoisonError;
+
+use anyhow::Context as _;
+use anyhow::Result;
+
+use batch_renamer::change::changed_number;
+use batch_renamer::change::classify;
+use batch_renamer::change::Change;
+use batch_renamer::output::Format;
+
+use tokio::fs::read_dir;
+
+use crate::tell;
+
+
+/// A sequence of numbered files, identified by the directory
+/// containing them, the part of their names preceding the number, and
+/// their extension.
+type Key = (PathBuf, String, Option<OsString>);
+
+
+/// Describe the numbers missing from `numbers` between the smallest
+/// and largest one, collapsing runs into ranges, as in `7, 9-11`.
+fn gaps(numbers: &BTreeSet<u64>) -> Option<String> {
+  let mut gaps = Vec::new();
+  let mut iter = numbers.iter();
+  let mut prev = *iter.next()?;
+  for &number in iter {
+    match number - prev {
+      1 => (),
+      2 => gaps.push(format!("{}", prev + 1)),
+      _ => gaps.push(format!("{}-{}", prev + 1, number - 1)),
+    }
+    prev = number;
+  }
+  (!gaps.is_empty()).then(|| gaps.join(", "))
+}
+
+
+/// Collect the numbers of the files in the sequence `key`, along with
+/// the names of the files carrying each.
+async fn collect(key: &Key) -> Result<BTreeMap<u64, Vec<String>>> {
+  let (dir, prefix, extension) = key;
+  let context = || format!("failed to read directory `{}`", dir.display());
+  let mut entries = read_dir(dir).await.with_context(context)?;
+  let mut numbers = BTreeMap::<_, Vec<_>>::new();
+  while let Some(entry) = entries.next_entry().await.with_context(context)? {
+    let path = entry.path();
+    if path.extension().map(OsString::from) != *extension {
+      continue
+    }
+    let name = entry.file_name().to_string_lossy().into_owned();
+    let Some(rest) = name.strip_prefix(prefix.as_str()) else {
+      continue
+    };
+    let digits = rest
+      .find(|c: char| !c.is_ascii_digit())
+      .unwrap_or(rest.len());
+    if let Ok(number) = rest[..digits].parse() {
+      let () = numbers.entry(number).or_default().push(name);
+    }
+  }
+  for names in numbers.values_mut() {
+    let () = names.sort();
+  }
+  Ok(numbers)
+}
+
+
+/// The sequences of numbered files touched by applied renames, to
+/// check for gaps and duplicates.
+#[derive(Debug, Default)]
+pub(crate) struct Sequences {
+  /// The sequences touched by renames changing numbers so far.
+  keys: Mutex<BTreeSet<Key>>,
+}
+
+impl Sequences {
+  /// Remember the sequence the rename of `src` to `dst` contributes to,
+  /// if it changes a number in the file's name.
+  pub(crate) fn renamed(&self, src: &Path, dst: &Path) {
+    if classify(src, dst) != Some(Change::Numbering) {
+      return
+    }
+    if let (Some((prefix, _number)), Some(dir)) = (changed_number(src, dst), dst.parent()) {
+      let key = (
+        dir.to_path_buf(),
+        prefix,
+        dst.extension().map(OsString::from),
+      );
+      let mut keys = self.keys.lock().unwrap_or_else(PoisonError::into_inner);
+      let _inserted = keys.insert(key);
+    }
+  }
+
+  /// Report the gaps and duplicates in the numbering of the files in
+  /// all sequences touched by renames, if any.
+  ///
+  /// Sequences comprise all files in the respective directory, not
+  /// just the renamed ones. Numbers missing before the smallest one
+  /// present are not considered gaps.
+  pub(crate) async fn report(&self, output: Format) -> Result<()> {
+    let keys = take(&mut *self.keys.lock().unwrap_or_else(PoisonError::into_inner));
+    for key in keys {
+      let numbers = collect(&key).await?;
+      let (dir, prefix, extension) = &key;
+      let mut pattern = format!("{prefix}N");
+      if let Some(extension) = extension {
+        let () = pattern.push('.');
+        let () = pattern.push_str(&extension.to_string_lossy());
+      }
+      let pattern = dir.join(pattern);
+
+      if let Some(gaps) = gaps(&numbers.keys().copied().collect()) {
+        let () = tell(
+          output,
+          &format!("Numbering of `{}` misses {gaps}", pattern.display()),
+        );
+      }
+      for (number, names) in numbers.iter().filter(|(_, names)| names.len() > 1) {
+        let () = tell(
+          output,
+          &format!(
+            "Numbering of `{}` has {} files numbered {number}:",
+            pattern.display(),
+            names.len()
+          ),
+        );
+        for name in names {
+          let () = tell(output, &format!("  {name}"));
+        }
+      }
+    }
+    Ok(())
+  }
+}
@@ -0,0 +1,248 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Generation of systemd units for renaming files unattended, whenever
+//! the contents of directories change or on a schedule.
+
+use std::env::args_os;
+use std::env::current_dir;
+use std::env::current_exe;
+use std::ffi::OsString;
+use std::path::Path;
+use std::path::PathBuf;
+
+use anyhow::ensure;
+use anyhow::Context as _;
+use anyhow::Result;
+
+use batch_renamer::schedule::Schedule;
+
+use tokio::fs::canonicalize;
+use tokio::fs::create_dir_all;
+use tokio::fs::write;
+
+
+/// Convert `path` into a string, as required for usage in a unit.
+fn to_str(path: &Path) -> Result<&str> {
+  path
+    .to_str()
+    .with_context(|| format!("path `{}` is not valid UTF-8", path.display()))
+}
+
+
+/// Escape `%` characters in `s`, which systemd interprets as
+/// introducing a specifier.
+fn escape_specifiers(s: &str) -> String {
+  s.replace('%', "%%")
+}
+
+
+/// Quote `s` as a single word of a unit setting.
+fn quote(s: &str) -> String {
+  let mut quoted = String::from("\"");
+  for c in escape_specifiers(s).chars() {
+    if matches!(c, '"' | '\\') {
+      let () = quoted.push('\\');
+    }
+    let () = quoted.push(c);
+  }
+  let () = quoted.push('"');
+  quoted
+}
+
+
+/// Check whether `name` is usable as the name of a unit.
+fn is_unit_name(name: &str) -> bool {
+  !name.is_empty()
+    && name
+      .chars()
+      .all(|c| c.is_ascii_alphanumeric() || matches!(c, ':' | '-' | '_' | '.' | '\\'))
+}
+
+
+/// Derive the arguments to run the service with from those of the
+/// current invocation, `args`, which exclude the program name.
+///
+/// The arguments selecting the generation of units are removed and
+/// --batch is put first.
+fn service_args(args: Vec<OsString>) -> Vec<OsString> {
+  let mut args = args.into_iter();
+  let mut service_args = vec![OsString::from("--batch")];
+  while let Some(arg) = args.next() {
+    if arg == "--" {
+      let () = service_args.push(arg);
+      let () = service_args.extend(args);
+      break
+    }
+    if arg == "--service" || arg == "--schedule" {
+      let _value = args.next();
+      continue
+    }
+    let skip = arg == "--install-service"
+      || arg == "--batch"
+      || arg
+        .to_str()
+        .is_some_and(|arg| arg.starts_with("--service=") || arg.starts_with("--schedule="));
+    if !skip {
+      let () = service_args.push(arg);
+    }
+  }
+  service_args
+}
+
+
+/// Render the `.service` unit running the current invocation
+/// unattended, from within `working_dir` and with write access limited
+/// to `writable`.
+fn service_unit(name: &str, working_dir: &Path, writable: &[PathBuf]) -> Result<String> {
+  let exe = current_exe().context("failed to determine path of program")?;
+  let exec = [exe.into_os_string()]
+    .into_iter()
+    .chain(service_args(args_os().skip(1).collect()))
+    .map(|arg| {
+      let arg = arg
+        .to_str()
+        .with_context(|| format!("argument `{}` is not valid UTF-8", arg.to_string_lossy()))?;
+      Ok(quote(&arg.replace('$', "$$")))
+    })
+    .collect::<Result<Vec<_>>>()?
+    .join(" ");
+
+  let mut unit = format!(
+    "[Unit]\n\
+     Description=Rename files with batch-rename ({name})\n\
+     \n\
+     [Service]\n\
+     Type=oneshot\n\
+     WorkingDirectory={}\n\
+     ExecStart={exec}\n\
+     NoNewPrivileges=yes\n\
+     PrivateTmp=yes\n\
+     PrivateDevices=yes\n\
+     ProtectSystem=strict\n\
+     ProtectHome=read-only\n",
+    escape_specifiers(to_str(working_dir)?),
+  );
+  for path in writable {
+    let () = unit.push_str(&format!("ReadWritePaths={}\n", quote(to_str(path)?)));
+  }
+  Ok(unit)
+}
+
+
+/// Render the `.path` unit triggering the service whenever the contents
+/// of any of `dirs` change.
+fn path_unit(name: &str, dirs: &[PathBuf]) -> Result<String> {
+  let mut unit = format!(
+    "[Unit]\n\
+     Description=Watch for files to rename with batch-rename ({name})\n\
+     \n\
+     [Path]\n"
+  );
+  for dir in dirs {
+    let () = unit.push_str(&format!(
+      "PathChanged={}\n",
+      escape_specifiers(to_str(dir)?)
+    ));
+  }
+  let () = unit.push_str(&format!(
+    "Unit={name}.service\n\
+     \n\
+     [Install]\n\
+     WantedBy=default.target\n"
+  ));
+  Ok(unit)
+}
+
+
+/// Render the `.timer` unit triggering the service as per `schedule`.
+fn timer_unit(name: &str, schedule: &Schedule) -> String {
+  format!(
+    "[Unit]\n\
+     Description=Run batch-rename on a schedule ({name})\n\
+     \n\
+     [Timer]\n\
+     OnCalendar={}\n\
+     Persistent=true\n\
+     Unit={name}.service\n\
+     \n\
+     [Install]\n\
+     WantedBy=timers.target\n",
+    escape_specifiers(schedule.calendar()),
+  )
+}
+
+
+/// Generate systemd user units named `name` for running the current
+/// invocation unattended whenever the contents of any of `dirs`
+/// change or, if provided, as per `schedule`, granting write access to
+/// `dirs` as well as to the directories `writable` (relative to the
+/// current directory), which get created.
+///
+/// The units are printed, unless `install` is set, in which case they
+/// are written to the user's systemd configuration directory.
+pub(crate) async fn generate(
+  name: &str,
+  dirs: &[PathBuf],
+  writable: &[PathBuf],
+  schedule: Option<&Schedule>,
+  install: bool,
+) -> Result<()> {
+  ensure!(
+    is_unit_name(name),
+    "`{name}` is not a valid unit name; use letters, digits, and `:-_.\\` only"
+  );
+
+  let mut watched = Vec::with_capacity(dirs.len());
+  for dir in dirs {
+    let path = canonicalize(dir)
+      .await
+      .with_context(|| format!("failed to canonicalize `{}`", dir.display()))?;
+    ensure!(
+      path.is_dir(),
+      "`{}` is not a directory; only directories can be watched",
+      dir.display()
+    );
+    let () = watched.push(path);
+  }
+
+  let working_dir = current_dir().context("failed to determine current directory")?;
+  let mut paths = watched.clone();
+  for path in writable {
+    let path = working_dir.join(path);
+    // Paths made writable have to exist for the service to start.
+    let () = create_dir_all(&path)
+      .await
+      .with_context(|| format!("failed to create directory `{}`", path.display()))?;
+    let () = paths.push(path);
+  }
+  let service = service_unit(name, &working_dir, &paths)?;
+  let (kind, trigger) = match schedule {
+    Some(schedule) => ("timer", timer_unit(name, schedule)),
+    None => ("path", path_unit(name, &watched)?),
+  };
+
+  if !install {
+    print!("# {name}.service\n{service}\n# {name}.{kind}\n{trigger}");
+    return Ok(())
+  }
+
+  let dir = dirs::config_dir()
+    .context("failed to determine user configuration directory")?
+    .join("systemd")
+    .join("user");
+  let () = create_dir_all(&dir)
+    .await
+    .with_context(|| format!("failed to create directory `{}`", dir.display()))?;
+  for (unit, contents) in [("service", &service), (kind, &trigger)] {
+    let file = dir.join(format!("{name}.{unit}"));
+    let () = write(&file, contents)
+      .await
+      .with_context(|| format!("failed to write `{}`", file.display()))?;
+    println!("Installed `{}`", file.display());
+  }
+  println!(
+    "Activate with `systemctl --user daemon-reload && systemctl --user enable --now {name}.{kind}`"
+  );
+  Ok(())
+}
@@ -0,0 +1,143 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Quoting of strings for consumption by a shell.
+//!
+//! This module is shared by the `batch-rename` and `print-rename`
+//! binaries.
+
+use std::ffi::OsStr;
+use std::ffi::OsString;
+#[cfg(unix)]
+use std::os::unix::ffi::OsStrExt as _;
+#[cfg(unix)]
+use std::os::unix::ffi::OsStringExt as _;
+#[cfg(windows)]
+use std::os::windows::ffi::OsStrExt as _;
+#[cfg(windows)]
+use std::os::windows::ffi::OsStringExt as _;
+
+
+/// The unit an `OsStr` is made up of on this platform.
+#[cfg(unix)]
+type Unit = u8;
+#[cfg(windows)]
+type Unit = u16;
+
+
+/// Retrieve the units making up `s`.
+#[cfg(unix)]
+fn units(s: &OsStr) -> Vec<Unit> {
+  s.as_bytes().to_vec()
+}
+
+/// Retrieve the units making up `s`.
+#[cfg(windows)]
+fn units(s: &OsStr) -> Vec<Unit> {
+  s.encode_wide().collect()
+}
+
+
+/// Create an `OsString` from the units making it up.
+#[cfg(unix)]
+fn from_units(units: Vec<Unit>) -> OsString {
+  OsString::from_vec(units)
+}
+
+/// Create an `OsString` from the units making it up.
+#[cfg(windows)]
+fn from_units(units: Vec<Unit>) -> OsString {
+  OsString::from_wide(&units)
+}
+
+
+/// Quote a string for safe consumption by a POSIX compliant shell.
+///
+/// Strings not containing any characters with special meaning to the
+/// shell are returned as-is, all others are single-quoted.
+pub(crate) fn quote_shell<S>(s: S) -> OsString
+where
+  S: AsRef<OsStr>,
+{
+  let units = units(s.as_ref());
+  let quote = Unit::from(b'\'');
+  let safe = |u: &Unit| {
+    char::from_u32(u32::from(*u))
+      .map(|c| c.is_ascii_alphanumeric() || "%+,-./:=@_".contains(c))
+      .unwrap_or(false)
+  };
+
+  if !units.is_empty() && units.iter().all(safe) {
+    return s.as_ref().to_os_string()
+  }
+
+  let mut quoted = Vec::with_capacity(units.len() + 2);
+  let () = quoted.push(quote);
+  for u in units {
+    if u == quote {
+      let () = quoted.extend(b"'\\''".map(Unit::from));
+    } else {
+      let () = quoted.push(u);
+    }
+  }
+  let () = quoted.push(quote);
+  from_units(quoted)
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+
+  /// Check that strings without special characters are left alone.
+  #[test]
+  fn unquoted() {
+    for s in ["file.txt", "dir/a-b_c", "x=1,2:3@4%5+6"] {
+      assert_eq!(quote_shell(s), s);
+    }
+  }
+
+  /// Check that strings with special characters get single-quoted.
+  #[test]
+  fn quoted() {
+    assert_eq!(quote_shell(""), "''");
+    assert_eq!(quote_shell("a b"), "'a b'");
+    assert_eq!(quote_shell("$HOME"), "'$HOME'");
+    assert_eq!(quote_shell("a\"b"), "'a\"b'");
+    assert_eq!(quote_shell("line\nbreak"), "'line\nbreak'");
+    assert_eq!(quote_shell("*?[~]"), "'*?[~]'");
+    assert_eq!(quote_shell("caf\u{e9}"), "'caf\u{e9}'");
+  }
+
+  /// Check that single quotes inside quoted strings get escaped.
+  #[test]
+  fn single_quotes() {
+    assert_eq!(quote_shell("'"), "''\\'''");
+    assert_eq!(quote_shell("it's"), "'it'\\''s'");
+    assert_eq!(quote_shell("''"), "''\\'''\\'''");
+  }
+
+  /// Check that bytes not forming valid UTF-8 are preserved.
+  #[cfg(unix)]
+  #[test]
+  fn non_utf8() {
+    let s = OsStr::from_bytes(b"a\xffb");
+    assert_eq!(quote_shell(s).as_bytes(), b"'a\xffb'");
+  }
+
+  /// Check that a shell evaluates quoted strings to the original ones.
+  #[cfg(unix)]
+  #[test]
+  fn shell_roundtrip() {
+    use std::process::Command;
+
+    for s in ["", "a b", "$HOME `id`", "it's", "line\nbreak\n", "\\'\\"] {
+      let mut script = OsString::from("printf %s ");
+      let () = script.push(quote_shell(s));
+      let output = Command::new("sh").arg("-c").arg(script).output().unwrap();
+      assert!(output.status.success());
+      assert_eq!(output.stdout, s.as_bytes());
+    }
+  }
+}
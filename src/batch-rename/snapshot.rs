@@ -0,0 +1,162 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Snapshots of the file systems files get renamed on, taken before
+//! applying renames.
+
+use std::collections::BTreeSet;
+use std::ffi::OsStr;
+use std::os::unix::fs::MetadataExt as _;
+use std::path::absolute;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Stdio;
+
+use anyhow::bail;
+use anyhow::Context as _;
+use anyhow::Result;
+
+use batch_renamer::evaluate;
+use batch_renamer::journal::FileSystem;
+use batch_renamer::journal::Snapshot;
+
+use tokio::fs::create_dir_all;
+use tokio::fs::metadata;
+use tokio::process::Command;
+
+
+/// The inode number of the root directory of each Btrfs subvolume.
+const BTRFS_SUBVOLUME_INO: u64 = 256;
+/// The directory, relative to the root of a Btrfs subvolume, in which
+/// snapshots of it are stored.
+const BTRFS_SNAPSHOT_DIR: &str = ".snapshots";
+
+
+/// Run `command` with the given arguments, returning its output.
+async fn run<A>(command: &str, args: &[A]) -> Result<String>
+where
+  A: AsRef<OsStr>,
+{
+  let output = Command::new(command)
+    .args(args)
+    .stdin(Stdio::null())
+    .stderr(Stdio::piped())
+    .output()
+    .await
+    .with_context(|| format!("failed to run `{command}`"))?;
+  let () = evaluate(&output, command, args)?;
+  Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+
+/// Determine the type of the file system containing `dir` as well as
+/// the source it got mounted from.
+async fn mount(dir: &Path) -> Result<(String, String)> {
+  let args = [
+    OsStr::new("--noheadings"),
+    OsStr::new("--raw"),
+    OsStr::new("--output"),
+    OsStr::new("FSTYPE,SOURCE"),
+    OsStr::new("--target"),
+    dir.as_os_str(),
+  ];
+  let output = run("findmnt", &args).await?;
+  let mut fields = output.split_whitespace();
+  match (fields.next(), fields.next()) {
+    (Some(fstype), Some(source)) => Ok((fstype.to_string(), source.to_string())),
+    _ => bail!("failed to determine file system of `{}`", dir.display()),
+  }
+}
+
+
+/// Find the root of the Btrfs subvolume containing `dir`.
+async fn subvolume(dir: &Path) -> Result<PathBuf> {
+  let dev = metadata(dir)
+    .await
+    .with_context(|| format!("failed to retrieve metadata of `{}`", dir.display()))?
+    .dev();
+  for dir in dir.ancestors() {
+    let meta = metadata(dir)
+      .await
+      .with_context(|| format!("failed to retrieve metadata of `{}`", dir.display()))?;
+    // Subvolumes have device numbers of their own.
+    if meta.dev() != dev {
+      break
+    }
+    if meta.ino() == BTRFS_SUBVOLUME_INO {
+      return Ok(dir.to_path_buf())
+    }
+  }
+  bail!(
+    "failed to find Btrfs subvolume containing `{}`",
+    dir.display()
+  )
+}
+
+
+/// Take read-only snapshots of the file systems containing `dirs`,
+/// named after the session `name`.
+///
+/// Each Btrfs subvolume and ZFS dataset gets snapshotted once, no
+/// matter how many of `dirs` it contains. Other file systems are not
+/// supported.
+pub(crate) async fn take(dirs: &[PathBuf], name: &str) -> Result<Vec<Snapshot>> {
+  let name = format!("batch-renamer-{name}");
+  let mut subvolumes = BTreeSet::new();
+  let mut datasets = BTreeSet::new();
+  for dir in dirs.iter().collect::<BTreeSet<_>>() {
+    let dir =
+      absolute(dir).with_context(|| format!("failed to make `{}` absolute", dir.display()))?;
+    let (fstype, source) = mount(&dir).await?;
+    match fstype.as_str() {
+      "btrfs" => {
+        let _inserted = subvolumes.insert(subvolume(&dir).await?);
+      },
+      "zfs" => {
+        let _inserted = datasets.insert(source);
+      },
+      _ => bail!(
+        "`{}` resides on a file system of type `{fstype}`; only Btrfs and ZFS support snapshots",
+        dir.display()
+      ),
+    }
+  }
+
+  let mut snapshots = Vec::with_capacity(subvolumes.len() + datasets.len());
+  for subvolume in subvolumes {
+    let dir = subvolume.join(BTRFS_SNAPSHOT_DIR);
+    let () = create_dir_all(&dir)
+      .await
+      .with_context(|| format!("failed to create directory `{}`", dir.display()))?;
+    let path = dir.join(&name);
+    let args = [
+      OsStr::new("subvolume"),
+      OsStr::new("snapshot"),
+      OsStr::new("-r"),
+      subvolume.as_os_str(),
+      path.as_os_str(),
+    ];
+    let _output = run("btrfs", &args).await.with_context(|| {
+      format!(
+        "failed to snapshot Btrfs subvolume `{}`",
+        subvolume.display()
+      )
+    })?;
+    let () = snapshots.push(Snapshot {
+      fs: FileSystem::Btrfs,
+      id: path.to_string_lossy().into_owned(),
+    });
+  }
+
+  for dataset in datasets {
+    let id = format!("{dataset}@{name}");
+    let _output = run("zfs", &["snapshot", &id])
+      .await
+      .with_context(|| format!("failed to snapshot ZFS dataset `{dataset}`"))?;
+    let () = snapshots.push(Snapshot {
+      fs: FileSystem::Zfs,
+      id,
+    });
+  }
+  Ok(snapshots)
+}
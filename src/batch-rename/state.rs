@@ -0,0 +1,166 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Persistent state for resuming interrupted invocations.
+
+use std::collections::HashSet;
+use std::fs::remove_file;
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::io::ErrorKind;
+use std::io::Read as _;
+use std::io::Write as _;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::sync::PoisonError;
+
+use anyhow::bail;
+use anyhow::Context as _;
+use anyhow::Result;
+
+use batch_renamer::os_str_bytes;
+use batch_renamer::os_string_from_bytes;
+use batch_renamer::resolve;
+use batch_renamer::SymlinkPolicy;
+
+
+/// The kind of a record for a renamed file.
+const RENAMED: &[u8] = b"renamed";
+/// The kind of a record for a file whose rename got declined.
+const DECLINED: &[u8] = b"declined";
+
+
+/// The state of a (potentially interrupted) invocation, persisted in a
+/// file.
+///
+/// The file contains a record for each processed file, comprising the
+/// kind of record (`renamed` or `declined`), the file's path, and its
+/// (proposed) new path, each terminated by a NUL character.
+#[derive(Debug)]
+pub(crate) struct State {
+  /// The path to the state file.
+  path: PathBuf,
+  /// The state file, opened for appending.
+  file: Mutex<File>,
+  /// The paths of the files processed previously, along with the new
+  /// paths of the renamed ones.
+  done: HashSet<PathBuf>,
+}
+
+impl State {
+  /// Open the state file at `path`, creating it if necessary.
+  pub(crate) fn open(path: &Path) -> Result<Self> {
+    let mut file = OpenOptions::new()
+      .create(true)
+      .read(true)
+      .append(true)
+      .open(path)
+      .with_context(|| format!("failed to open state file `{}`", path.display()))?;
+    let mut content = Vec::new();
+    let _count = file
+      .read_to_end(&mut content)
+      .with_context(|| format!("failed to read state file `{}`", path.display()))?;
+
+    let mut done = HashSet::new();
+    let mut fields = content.split(|b| *b == b'\0').collect::<Vec<_>>();
+    // The last field is not terminated. It is empty, unless the last
+    // record got cut short, in which case the record is ignored.
+    let _partial = fields.pop();
+    for record in fields.chunks_exact(3) {
+      let (kind, src, dst) = (record[0], record[1], record[2]);
+      let src = PathBuf::from(os_string_from_bytes(src)?);
+      let dst = PathBuf::from(os_string_from_bytes(dst)?);
+      match kind {
+        RENAMED => {
+          let _inserted = done.insert(src);
+          let _inserted = done.insert(dst);
+        },
+        DECLINED => {
+          let _inserted = done.insert(src);
+        },
+        _ => bail!(
+          "state file `{}` contains invalid record kind `{}`",
+          path.display(),
+          String::from_utf8_lossy(kind)
+        ),
+      }
+    }
+
+    let slf = Self {
+      path: path.to_path_buf(),
+      file: Mutex::new(file),
+      done,
+    };
+    Ok(slf)
+  }
+
+  /// Append a record to the state file.
+  fn append(&self, kind: &[u8], src: &Path, dst: &Path) -> Result<()> {
+    let mut record = Vec::new();
+    for field in [
+      kind,
+      &os_str_bytes(src.as_os_str()),
+      &os_str_bytes(dst.as_os_str()),
+    ] {
+      let () = record.extend_from_slice(field);
+      let () = record.push(b'\0');
+    }
+
+    // The record is written in one go and synced, so that it is
+    // complete when the program gets interrupted right after.
+    let mut file = self.file.lock().unwrap_or_else(PoisonError::into_inner);
+    let () = file
+      .write_all(&record)
+      .and_then(|()| file.sync_data())
+      .with_context(|| format!("failed to write state file `{}`", self.path.display()))?;
+    Ok(())
+  }
+
+  /// Record the rename of `src` to `dst`.
+  pub(crate) fn renamed(&self, src: &Path, dst: &Path) -> Result<()> {
+    self.append(RENAMED, src, dst)
+  }
+
+  /// Record that the proposed rename of `src` to `dst` got declined.
+  pub(crate) fn declined(&self, src: &Path, dst: &Path) -> Result<()> {
+    self.append(DECLINED, src, dst)
+  }
+
+  /// Determine which of `files` got processed previously, i.e., either
+  /// are files that got renamed or declined or are the result of a
+  /// rename.
+  ///
+  /// Files that got renamed may no longer exist, so only the directory
+  /// containing a file is resolved.
+  pub(crate) async fn processed(&self, files: &[PathBuf]) -> HashSet<PathBuf> {
+    let mut processed = HashSet::new();
+    for file in files {
+      let name = match file.file_name() {
+        Some(name) => name,
+        None => continue,
+      };
+      let dir = match file.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+      };
+      if let Ok(dir) = resolve(dir, SymlinkPolicy::Follow).await {
+        if self.done.contains(&dir.join(name)) {
+          let _inserted = processed.insert(file.clone());
+        }
+      }
+    }
+    processed
+  }
+
+  /// Remove the state file, once the invocation completed.
+  pub(crate) fn remove(&self) -> Result<()> {
+    match remove_file(&self.path) {
+      Ok(()) => Ok(()),
+      Err(err) if err.kind() == ErrorKind::NotFound => Ok(()),
+      Err(err) => {
+        Err(err).with_context(|| format!("failed to remove state file `{}`", self.path.display()))
+      },
+    }
+  }
+}
@@ -0,0 +1,215 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Suggesting to repeat a previous session on the files that newly
+//! appeared in the current directory.
+
+use std::collections::BTreeSet;
+use std::env::current_dir;
+use std::env::current_exe;
+use std::ffi::OsStr;
+use std::fs::Metadata;
+#[cfg(unix)]
+use std::os::unix::fs::MetadataExt as _;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::exit;
+#[cfg(not(unix))]
+use std::time::UNIX_EPOCH;
+
+use anyhow::Context as _;
+use anyhow::Result;
+
+use batch_renamer::journal::Operation;
+use batch_renamer::journal::Session;
+use batch_renamer::output::Format;
+use batch_renamer::resolve;
+use batch_renamer::SymlinkPolicy;
+
+use tokio::fs::read_dir;
+use tokio::process::Command;
+use tokio::task::spawn_blocking;
+
+use crate::shell::quote_shell;
+use crate::tell;
+use crate::term::read_key;
+use crate::term::Input;
+use crate::term::Key;
+
+
+/// The arguments recorded in lieu of a command by sessions that rename
+/// files determined upfront instead of provided ones.
+const FIXED_PLAN_ARGS: [&str; 3] = ["--manifest", "--diff-names", "--apply-plan"];
+
+
+/// Retrieve the time at which the file described by `meta` last
+/// changed, including by getting renamed, in seconds since the Unix
+/// epoch.
+fn changed(meta: &Metadata) -> u64 {
+  #[cfg(unix)]
+  {
+    u64::try_from(meta.ctime()).unwrap_or_default()
+  }
+  #[cfg(not(unix))]
+  {
+    meta
+      .modified()
+      .ok()
+      .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+      .map(|duration| duration.as_secs())
+      .unwrap_or_default()
+  }
+}
+
+
+/// Check whether `session` renamed files in `dir`, not counting
+/// declined renames.
+fn touched(session: &Session, dir: &Path) -> bool {
+  session
+    .operations()
+    .iter()
+    .any(|operation| match operation {
+      Operation::Rename(entry) | Operation::Exchange(entry) => entry.src.parent() == Some(dir),
+      Operation::Declined(..) => false,
+    })
+}
+
+
+/// Collect the paths in `dir` that any of `sessions` left files at,
+/// i.e., the new paths of renamed files and the paths of files whose
+/// renames got declined.
+fn known(sessions: &[&Session], dir: &Path) -> BTreeSet<PathBuf> {
+  sessions
+    .iter()
+    .flat_map(|session| session.operations())
+    .map(|operation| match operation {
+      Operation::Rename(entry) | Operation::Exchange(entry) => &entry.dst,
+      Operation::Declined(entry) => &entry.src,
+    })
+    .filter(|path| path.parent() == Some(dir))
+    .cloned()
+    .collect()
+}
+
+
+/// Find the files in `dir` that changed after `since`, in seconds since
+/// the Unix epoch, with the exception of `known` ones and hidden ones.
+async fn new_files(dir: &Path, since: u64, known: &BTreeSet<PathBuf>) -> Result<Vec<PathBuf>> {
+  let context = || format!("failed to read directory `{}`", dir.display());
+  let mut entries = read_dir(dir).await.with_context(context)?;
+  let mut files = Vec::new();
+  while let Some(entry) = entries.next_entry().await.with_context(context)? {
+    let path = entry.path();
+    let hidden = entry.file_name().to_string_lossy().starts_with('.');
+    if hidden || known.contains(&path) {
+      continue
+    }
+    let meta = entry.metadata().await.with_context(context)?;
+    if meta.is_file() && changed(&meta) > since {
+      let () = files.push(path);
+    }
+  }
+  let () = files.sort();
+  Ok(files)
+}
+
+
+/// Offer to repeat the most recent session stored in `journal_dir`
+/// that renamed files in the current directory on the files newly
+/// present in it.
+///
+/// The session's command, template, or similar is repeated by running
+/// this program anew, which prompts for renames as usual. With `yes`
+/// set, the session is repeated without asking and renames are
+/// accepted without prompting.
+pub(crate) async fn suggest(
+  journal_dir: &Path,
+  yes: bool,
+  input: &Input,
+  output: Format,
+) -> Result<()> {
+  let dir = current_dir().context("failed to determine current directory")?;
+  let dir = resolve(&dir, SymlinkPolicy::Follow).await?;
+  let sessions = Session::load_all(journal_dir).await?;
+  let sessions = sessions
+    .iter()
+    .filter(|session| !session.is_undone() && touched(session, &dir))
+    .filter(|session| {
+      session
+        .command()
+        .first()
+        .is_none_or(|arg| !FIXED_PLAN_ARGS.iter().any(|fixed| arg == OsStr::new(fixed)))
+    })
+    .collect::<Vec<_>>();
+  let Some(session) = sessions.last() else {
+    let () = tell(
+      output,
+      &format!("No session found that renamed files in `{}`", dir.display()),
+    );
+    return Ok(())
+  };
+
+  let files = new_files(&dir, session.time(), &known(&sessions, &dir)).await?;
+  if files.is_empty() {
+    let () = tell(
+      output,
+      &format!(
+        "No files newly present in `{}` since session {}",
+        dir.display(),
+        session.id()
+      ),
+    );
+    return Ok(())
+  }
+
+  let command = session
+    .command()
+    .iter()
+    .map(quote_shell)
+    .collect::<Vec<_>>()
+    .join(OsStr::new(" "));
+  let () = tell(
+    output,
+    &format!(
+      "Session {} renamed files in `{}` via:\n  {}",
+      session.id(),
+      dir.display(),
+      command.to_string_lossy()
+    ),
+  );
+  let () = tell(output, &format!("{} file(s) newly present:", files.len()));
+  for file in &files {
+    // SANITY: All files are inside of `dir`.
+    let () = tell(
+      output,
+      &format!("  {}", file.strip_prefix(&dir).unwrap().display()),
+    );
+  }
+
+  if !yes {
+    let () = tell(output, "Repeat the session on them? (Y/n)");
+    let key = {
+      let input = input.clone();
+      spawn_blocking(move || read_key(&input)).await??
+    };
+    match key {
+      Key::Enter | Key::Char('y' | 'Y') => (),
+      Key::Char(..) | Key::Interrupt => return Ok(()),
+    }
+  }
+
+  let exe = current_exe().context("failed to determine path of running program")?;
+  let status = Command::new(&exe)
+    .args(session.command())
+    .args(yes.then_some("--yes"))
+    .arg("--")
+    .args(&files)
+    .status()
+    .await
+    .with_context(|| format!("failed to run `{}`", exe.display()))?;
+  if !status.success() {
+    // The failure got reported already.
+    exit(status.code().unwrap_or(1))
+  }
+  Ok(())
+}
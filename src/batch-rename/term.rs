@@ -0,0 +1,333 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Terminal interaction.
+
+use std::fs::File;
+use std::io::stderr;
+use std::io::stdin;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::IsTerminal as _;
+use std::io::Write as _;
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::PoisonError;
+
+use anyhow::Context as _;
+use anyhow::Result;
+
+use crossterm::event::read;
+use crossterm::event::Event;
+use crossterm::event::KeyCode;
+use crossterm::event::KeyEvent;
+use crossterm::event::KeyEventKind;
+use crossterm::event::KeyModifiers;
+use crossterm::terminal::disable_raw_mode;
+use crossterm::terminal::enable_raw_mode;
+
+
+/// A key as read from the user.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum Key {
+  /// A character key.
+  Char(char),
+  /// The enter key (or an empty line, when not reading from a
+  /// terminal).
+  Enter,
+  /// The user requested interruption, e.g., by pressing Ctrl-C, or
+  /// input reached end-of-file.
+  Interrupt,
+}
+
+
+/// Read a line from `reader`, without its terminator, or `None` at
+/// end-of-file.
+fn read_line<R>(reader: &mut R) -> Result<Option<String>>
+where
+  R: BufRead + ?Sized,
+{
+  let mut line = String::new();
+  let count = reader.read_line(&mut line).context("failed to read line")?;
+  if count == 0 {
+    return Ok(None)
+  }
+  let () = line.truncate(line.trim_end_matches(['\r', '\n']).len());
+  Ok(Some(line))
+}
+
+
+/// The state of a script of answers.
+struct Script {
+  /// The source of the answers.
+  reader: Box<dyn BufRead + Send>,
+  /// The replacement text accompanying the last key read, if any.
+  text: Option<String>,
+}
+
+
+/// Scripted answers to prompts, one per line.
+///
+/// A line's first character is the key pressed, with an empty line
+/// representing the enter key. A replacement for the text edited in
+/// response to a key may follow it on the same line, as in
+/// `e new-name.txt`, or be provided on the next line.
+pub(crate) struct Answers {
+  script: Mutex<Script>,
+}
+
+impl Answers {
+  /// Open the answers contained in the file at `path`, or provided via
+  /// stdin if `path` is `-`.
+  pub(crate) fn open(path: &Path) -> Result<Self> {
+    let reader = if path == Path::new("-") {
+      Box::new(BufReader::new(stdin())) as Box<dyn BufRead + Send>
+    } else {
+      let file = File::open(path)
+        .with_context(|| format!("failed to open answers file `{}`", path.display()))?;
+      Box::new(BufReader::new(file))
+    };
+
+    let slf = Self {
+      script: Mutex::new(Script { reader, text: None }),
+    };
+    Ok(slf)
+  }
+
+  /// Read the next key.
+  fn read_key(&self) -> Result<Key> {
+    let mut script = self.script.lock().unwrap_or_else(PoisonError::into_inner);
+    let line = match read_line(&mut script.reader).context("failed to read answer")? {
+      Some(line) => line,
+      None => return Ok(Key::Interrupt),
+    };
+
+    let mut chars = line.chars();
+    let key = chars.next().map(Key::Char).unwrap_or(Key::Enter);
+    let text = chars.as_str().trim_start();
+    script.text = (!text.is_empty()).then(|| text.to_string());
+    Ok(key)
+  }
+
+  /// Read the replacement for the text being edited.
+  fn edit_line(&self) -> Result<Line> {
+    let mut script = self.script.lock().unwrap_or_else(PoisonError::into_inner);
+    let line = match script.text.take() {
+      Some(text) => Some(text),
+      None => read_line(&mut script.reader).context("failed to read answer")?,
+    };
+    Ok(to_line(line))
+  }
+}
+
+
+/// The source of the user's responses to prompts.
+#[derive(Clone)]
+pub(crate) enum Input {
+  /// Responses are read from stdin, or from the controlling terminal if
+  /// stdin has been consumed for other purposes (`consumed` is
+  /// `true`).
+  Stdin { consumed: bool },
+  /// Responses are scripted.
+  Answers(Arc<Answers>),
+}
+
+
+/// A guard object keeping the terminal in raw mode for as long as it is
+/// alive.
+pub(crate) struct RawMode;
+
+impl RawMode {
+  pub(crate) fn enable() -> Result<Self> {
+    let () = enable_raw_mode().context("failed to enable terminal raw mode")?;
+    Ok(Self)
+  }
+}
+
+impl Drop for RawMode {
+  fn drop(&mut self) {
+    // There is nothing we can do about errors at this point.
+    let _result = disable_raw_mode();
+  }
+}
+
+
+/// Read a single key press from the terminal.
+fn read_terminal_key() -> Result<Key> {
+  let _guard = RawMode::enable()?;
+
+  loop {
+    let event = read().context("failed to read terminal event")?;
+    if let Event::Key(KeyEvent {
+      code,
+      modifiers,
+      kind: KeyEventKind::Press,
+      ..
+    }) = event
+    {
+      let key = match code {
+        KeyCode::Char('c' | 'd') if modifiers.contains(KeyModifiers::CONTROL) => Key::Interrupt,
+        KeyCode::Char(c) => Key::Char(c),
+        KeyCode::Enter => Key::Enter,
+        _ => continue,
+      };
+      break Ok(key)
+    }
+  }
+}
+
+
+/// Read a "key" from a line of non-terminal input, as provided by a
+/// script, for example.
+fn read_line_key() -> Result<Key> {
+  let key = match read_line(&mut stdin().lock()).context("failed to read from stdin")? {
+    Some(line) => line.chars().next().map(Key::Char).unwrap_or(Key::Enter),
+    None => Key::Interrupt,
+  };
+  Ok(key)
+}
+
+
+/// Read a single key from the user, as provided by `input`.
+///
+/// If stdin is a terminal, a single key press is read without the need
+/// to confirm it. The same happens if stdin has been consumed for other
+/// purposes, in which case the controlling terminal is used. Otherwise
+/// a line of input is read and its first character interpreted as the
+/// key.
+///
+/// This function blocks.
+pub(crate) fn read_key(input: &Input) -> Result<Key> {
+  match input {
+    Input::Answers(answers) => answers.read_key(),
+    Input::Stdin { consumed } if *consumed || stdin().is_terminal() => read_terminal_key(),
+    Input::Stdin { .. } => read_line_key(),
+  }
+}
+
+
+/// The outcome of editing a line of text.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) enum Line {
+  /// The user confirmed the given text.
+  Text(String),
+  /// The user abandoned editing.
+  Cancel,
+  /// The user requested interruption, e.g., by pressing Ctrl-C, or
+  /// input reached end-of-file.
+  Interrupt,
+}
+
+
+/// Draw the line being edited on stderr, placing the cursor at the
+/// character with index `cursor`.
+fn draw_line(text: &[char], cursor: usize) -> Result<()> {
+  let mut stderr = stderr().lock();
+  let line = text.iter().collect::<String>();
+  let () = write!(stderr, "\r\x1b[K> {line}\r\x1b[{}C", cursor + 2)?;
+  let () = stderr.flush()?;
+  Ok(())
+}
+
+
+/// Let the user edit `initial` on the current line of the terminal,
+/// which has to be in raw mode.
+pub(crate) fn edit_raw_line(initial: &str) -> Result<Line> {
+  let mut text = initial.chars().collect::<Vec<_>>();
+  let mut cursor = text.len();
+
+  let line = loop {
+    let () = draw_line(&text, cursor)?;
+    let event = read().context("failed to read terminal event")?;
+    let Event::Key(KeyEvent {
+      code,
+      modifiers,
+      kind: KeyEventKind::Press,
+      ..
+    }) = event
+    else {
+      continue
+    };
+
+    match code {
+      KeyCode::Char('c' | 'd') if modifiers.contains(KeyModifiers::CONTROL) => {
+        break Line::Interrupt
+      },
+      KeyCode::Char('a') if modifiers.contains(KeyModifiers::CONTROL) => cursor = 0,
+      KeyCode::Char('e') if modifiers.contains(KeyModifiers::CONTROL) => cursor = text.len(),
+      KeyCode::Char('u') if modifiers.contains(KeyModifiers::CONTROL) => {
+        let _removed = text.drain(..cursor);
+        cursor = 0;
+      },
+      KeyCode::Char(_) if modifiers.contains(KeyModifiers::CONTROL) => (),
+      KeyCode::Char(c) => {
+        let () = text.insert(cursor, c);
+        cursor += 1;
+      },
+      KeyCode::Backspace if cursor > 0 => {
+        cursor -= 1;
+        let _removed = text.remove(cursor);
+      },
+      KeyCode::Delete if cursor < text.len() => {
+        let _removed = text.remove(cursor);
+      },
+      KeyCode::Left => cursor = cursor.saturating_sub(1),
+      KeyCode::Right => cursor = (cursor + 1).min(text.len()),
+      KeyCode::Home => cursor = 0,
+      KeyCode::End => cursor = text.len(),
+      KeyCode::Enter => break Line::Text(text.into_iter().collect()),
+      KeyCode::Esc => break Line::Cancel,
+      _ => (),
+    }
+  };
+  Ok(line)
+}
+
+
+/// Let the user edit `initial` on the terminal.
+fn edit_terminal_line(initial: &str) -> Result<Line> {
+  let _guard = RawMode::enable()?;
+  let line = edit_raw_line(initial)?;
+  // The terminal is in raw mode, so we have to return the carriage
+  // ourselves.
+  let () = write!(stderr(), "\r\n")?;
+  Ok(line)
+}
+
+
+/// Read a replacement for the text being edited from a line of
+/// non-terminal input.
+fn edit_non_terminal_line() -> Result<Line> {
+  let line = read_line(&mut stdin().lock()).context("failed to read from stdin")?;
+  Ok(to_line(line))
+}
+
+
+/// Convert a line of non-terminal input, or `None` at end-of-file, into
+/// the outcome of editing.
+///
+/// An empty line abandons editing.
+fn to_line(line: Option<String>) -> Line {
+  match line {
+    Some(line) if line.is_empty() => Line::Cancel,
+    Some(line) => Line::Text(line),
+    None => Line::Interrupt,
+  }
+}
+
+
+/// Let the user edit the text `initial`, as provided by `input`.
+///
+/// On a terminal, the text is presented for editing in place, with
+/// support for basic cursor movement. Otherwise a line of input
+/// replaces the text. See [`read_key`] for when the terminal is used.
+///
+/// This function blocks.
+pub(crate) fn edit_line(initial: &str, input: &Input) -> Result<Line> {
+  match input {
+    Input::Answers(answers) => answers.edit_line(),
+    Input::Stdin { consumed } if *consumed || stdin().is_terminal() => edit_terminal_line(initial),
+    Input::Stdin { .. } => edit_non_terminal_line(),
+  }
+}
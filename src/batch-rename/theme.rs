@@ -0,0 +1,92 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Color themes for styling output on terminals.
+
+use std::str::FromStr;
+
+use anyhow::bail;
+use anyhow::Error;
+use anyhow::Result;
+
+
+/// The sequence resetting all styles.
+const RESET: &str = "\x1b[0m";
+
+
+/// The styles to use when printing to a terminal.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub(crate) enum Theme {
+  /// Colored output, as used traditionally.
+  #[default]
+  Default,
+  /// Bright colors on contrasting backgrounds, for better legibility.
+  HighContrast,
+  /// Text attributes only, for terminals without color support.
+  Monochrome,
+}
+
+impl Theme {
+  /// The style used for unchanged portions of a name.
+  pub(crate) fn unchanged(self) -> &'static str {
+    match self {
+      Self::Default => "\x1b[1;34m",
+      Self::HighContrast => "\x1b[1;97m",
+      Self::Monochrome => "\x1b[1m",
+    }
+  }
+
+  /// The style used for removed portions of the old name.
+  pub(crate) fn removed(self) -> &'static str {
+    match self {
+      Self::Default => "\x1b[1;31m",
+      Self::HighContrast => "\x1b[1;97;41m",
+      Self::Monochrome => "\x1b[9m",
+    }
+  }
+
+  /// The style used for inserted portions of the new name.
+  pub(crate) fn inserted(self) -> &'static str {
+    match self {
+      Self::Default => "\x1b[1;32m",
+      Self::HighContrast => "\x1b[1;30;102m",
+      Self::Monochrome => "\x1b[4m",
+    }
+  }
+
+  /// The style used for headings.
+  pub(crate) fn heading(self) -> &'static str {
+    match self {
+      Self::Default | Self::Monochrome => "\x1b[1m",
+      Self::HighContrast => "\x1b[1;97m",
+    }
+  }
+
+  /// The style used for the line under the cursor.
+  pub(crate) fn cursor(self) -> &'static str {
+    match self {
+      Self::Default | Self::Monochrome => "\x1b[7m",
+      Self::HighContrast => "\x1b[1;7m",
+    }
+  }
+
+  /// The sequence resetting all styles.
+  pub(crate) fn reset(self) -> &'static str {
+    RESET
+  }
+}
+
+impl FromStr for Theme {
+  type Err = Error;
+
+  fn from_str(s: &str) -> Result<Self> {
+    match s {
+      "default" => Ok(Self::Default),
+      "high-contrast" => Ok(Self::HighContrast),
+      "monochrome" => Ok(Self::Monochrome),
+      _ => bail!(
+        "unsupported theme `{s}`; expected one of `default`, `high-contrast`, or `monochrome`"
+      ),
+    }
+  }
+}
@@ -0,0 +1,500 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Review of proposed renames in a full screen terminal interface.
+
+use std::collections::HashSet;
+use std::ffi::OsString;
+use std::io::stderr;
+use std::io::IsTerminal as _;
+use std::io::Write as _;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use anyhow::bail;
+use anyhow::ensure;
+use anyhow::Context as _;
+use anyhow::Result;
+
+use batch_renamer::grapheme::graphemes;
+use batch_renamer::journal::Journal;
+use batch_renamer::RenameOpts;
+
+use crossterm::event::read;
+use crossterm::event::Event;
+use crossterm::event::KeyCode;
+use crossterm::event::KeyEvent;
+use crossterm::event::KeyEventKind;
+use crossterm::event::KeyModifiers;
+use crossterm::terminal::size;
+
+use globset::Glob;
+use globset::GlobMatcher;
+
+use regex::Regex;
+
+use tokio::task::spawn_blocking;
+
+use crate::hook::Hooks;
+use crate::review::apply_edits;
+use crate::review::edited_path;
+use crate::review::proposed_path;
+use crate::review::Edit;
+use crate::term::edit_raw_line;
+use crate::term::Line;
+use crate::term::RawMode;
+use crate::theme::Theme;
+use crate::Root;
+
+
+/// The keys available, as shown in the header.
+const HELP: &str =
+  "space: toggle, a: toggle all, e: edit, n: note, :accept/:reject/:toggle PATTERN, enter: apply, q: quit";
+
+
+/// A guard object showing the alternate screen, with the cursor
+/// hidden, for as long as it is alive.
+struct AlternateScreen;
+
+impl AlternateScreen {
+  fn enter() -> Result<Self> {
+    let () = write!(stderr(), "\x1b[?1049h\x1b[?25l").context("failed to write to terminal")?;
+    Ok(Self)
+  }
+}
+
+impl Drop for AlternateScreen {
+  fn drop(&mut self) {
+    // There is nothing we can do about errors at this point.
+    let _result = write!(stderr(), "\x1b[?25h\x1b[?1049l");
+    let _result = stderr().flush();
+  }
+}
+
+
+/// Shorten `s` to at most `width` characters, marking the omission.
+///
+/// Characters are grapheme clusters, so that combined characters and
+/// emoji sequences are never split.
+fn truncate(s: &str, width: usize) -> String {
+  if graphemes(s).count() <= width {
+    s.to_string()
+  } else {
+    let mut s = graphemes(s)
+      .take(width.saturating_sub(1))
+      .collect::<String>();
+    let () = s.push('…');
+    s
+  }
+}
+
+
+/// A pattern selecting renames by the path of the file.
+#[derive(Debug)]
+enum Pattern {
+  /// A glob pattern, matched against the file name or, if it contains
+  /// a path separator, against the path.
+  Glob { matcher: GlobMatcher, path: bool },
+  /// A regular expression, as in `re/IMG_\d+/`, searched for in the
+  /// path.
+  Regex(Regex),
+}
+
+impl Pattern {
+  fn parse(s: &str) -> Result<Self> {
+    if let Some(regex) = s.strip_prefix("re/").and_then(|s| s.strip_suffix('/')) {
+      let regex =
+        Regex::new(regex).with_context(|| format!("invalid regular expression `{regex}`"))?;
+      return Ok(Self::Regex(regex))
+    }
+
+    let matcher = Glob::new(s)
+      .with_context(|| format!("invalid glob pattern `{s}`"))?
+      .compile_matcher();
+    Ok(Self::Glob {
+      matcher,
+      path: s.contains('/'),
+    })
+  }
+
+  fn matches(&self, path: &Path) -> bool {
+    match self {
+      Self::Glob {
+        matcher,
+        path: false,
+      } => path.file_name().is_some_and(|name| matcher.is_match(name)),
+      Self::Glob {
+        matcher,
+        path: true,
+      } => matcher.is_match(path),
+      Self::Regex(regex) => regex.is_match(&path.to_string_lossy()),
+    }
+  }
+}
+
+
+/// A proposed rename, as shown on screen.
+#[derive(Debug)]
+struct Row {
+  /// The path of the file, as provided by the user.
+  src: PathBuf,
+  /// The canonical form of `src`.
+  abs_src: PathBuf,
+  /// The proposed new path of the file.
+  dst: PathBuf,
+  /// The new path of the file, as shown and edited by the user.
+  shown: String,
+  /// `shown` mapped to an absolute path.
+  edited: PathBuf,
+  /// Whether the rename is to be applied.
+  selected: bool,
+  /// A note provided by the user.
+  note: Option<String>,
+}
+
+
+/// The state of the review screen.
+#[derive(Debug)]
+struct Screen {
+  rows: Vec<Row>,
+  /// The index of the row the cursor is on.
+  cursor: usize,
+  /// The index of the first visible row.
+  offset: usize,
+  /// A message to show to the user.
+  status: String,
+  /// The theme to style the screen with.
+  theme: Theme,
+}
+
+impl Screen {
+  /// Draw the screen on the terminal.
+  fn draw(&mut self) -> Result<()> {
+    let (cols, lines) = size().context("failed to query terminal size")?;
+    let (cols, lines) = (usize::from(cols), usize::from(lines));
+    // One line each is reserved for the header and the status line.
+    let height = lines.saturating_sub(2).max(1);
+    if self.cursor < self.offset {
+      self.offset = self.cursor;
+    } else if self.cursor >= self.offset + height {
+      self.offset = self.cursor + 1 - height;
+    }
+
+    let selected = self.rows.iter().filter(|row| row.selected).count();
+    let src_width = self
+      .rows
+      .iter()
+      .map(|row| graphemes(&row.src.display().to_string()).count())
+      .max()
+      .unwrap_or(0)
+      .min(cols.saturating_sub(8) / 2);
+
+    let mut stderr = stderr().lock();
+    let header = format!("{selected}/{} selected; {HELP}", self.rows.len());
+    let () = write!(
+      stderr,
+      "\x1b[H\x1b[2J{}{}{}",
+      self.theme.heading(),
+      truncate(&header, cols),
+      self.theme.reset(),
+    )?;
+    for (idx, row) in self.rows.iter().enumerate().skip(self.offset).take(height) {
+      let mark = if row.selected { "[x]" } else { "[ ]" };
+      let src = truncate(&row.src.display().to_string(), src_width);
+      // Padding is applied manually, as formatting counts `char`s.
+      let padding = " ".repeat(src_width.saturating_sub(graphemes(&src).count()));
+      let mut line = format!("{mark} {src}{padding} → {}", row.shown);
+      if let Some(note) = &row.note {
+        let () = line.push_str(&format!("  # {note}"));
+      }
+      let line = truncate(&line, cols);
+      let (on, off) = if idx == self.cursor {
+        (self.theme.cursor(), self.theme.reset())
+      } else {
+        ("", "")
+      };
+      let () = write!(stderr, "\r\n{on}{line}{off}")?;
+    }
+    let () = write!(stderr, "\x1b[{lines};1H{}", truncate(&self.status, cols))?;
+    let () = stderr.flush()?;
+    Ok(())
+  }
+
+  /// Let the user edit the new path of the row under the cursor.
+  ///
+  /// Returns `false` if the user asked for interruption.
+  fn edit(&mut self, root: &Root) -> Result<bool> {
+    let (_cols, lines) = size().context("failed to query terminal size")?;
+    let row = &mut self.rows[self.cursor];
+    if row.edited.to_str().is_none() {
+      self.status = "Paths that are not valid UTF-8 can't be edited".to_string();
+      return Ok(true)
+    }
+
+    let () = write!(stderr(), "\x1b[{lines};1H\x1b[?25h")?;
+    let line = edit_raw_line(&row.shown);
+    let () = write!(stderr(), "\x1b[?25l")?;
+    let text = match line? {
+      Line::Text(text) if !text.is_empty() => text,
+      Line::Text(..) | Line::Cancel => return Ok(true),
+      Line::Interrupt => return Ok(false),
+    };
+
+    let edited = PathBuf::from(&text);
+    let result = edited_path(&row.src, &row.abs_src, &edited)
+      .and_then(|path| root.check(&row.abs_src, &path).map(|()| path));
+    match result {
+      Ok(path) if edited.file_name().is_some() => {
+        row.shown = text;
+        row.edited = path;
+        row.selected = true;
+      },
+      Ok(..) => self.status = format!("`{text}` does not contain a file name"),
+      Err(err) => self.status = format!("{err:#}"),
+    }
+    Ok(true)
+  }
+
+  /// Let the user edit the note of the row under the cursor.
+  ///
+  /// Returns `false` if the user asked for interruption.
+  fn annotate(&mut self) -> Result<bool> {
+    let (_cols, lines) = size().context("failed to query terminal size")?;
+    let row = &mut self.rows[self.cursor];
+
+    let () = write!(stderr(), "\x1b[{lines};1H\x1b[?25h")?;
+    let line = edit_raw_line(row.note.as_deref().unwrap_or(""));
+    let () = write!(stderr(), "\x1b[?25l")?;
+    match line? {
+      Line::Text(text) => {
+        let text = text.trim();
+        row.note = (!text.is_empty()).then(|| text.to_string());
+      },
+      Line::Cancel => (),
+      Line::Interrupt => return Ok(false),
+    }
+    Ok(true)
+  }
+
+  /// Let the user enter a command deciding about all renames of files
+  /// matching a pattern at once, as in `:reject *.bak`.
+  ///
+  /// Returns `false` if the user asked for interruption.
+  fn command(&mut self) -> Result<bool> {
+    let (_cols, lines) = size().context("failed to query terminal size")?;
+
+    let () = write!(stderr(), "\x1b[{lines};1H\x1b[?25h")?;
+    let line = edit_raw_line(":");
+    let () = write!(stderr(), "\x1b[?25l")?;
+    let text = match line? {
+      Line::Text(text) => text,
+      Line::Cancel => return Ok(true),
+      Line::Interrupt => return Ok(false),
+    };
+
+    let text = text.trim();
+    let text = text.strip_prefix(':').unwrap_or(text).trim_start();
+    if text.is_empty() {
+      return Ok(true)
+    }
+    match self.decide(text) {
+      Ok(status) => self.status = status,
+      Err(err) => self.status = format!("{err:#}"),
+    }
+    Ok(true)
+  }
+
+  /// Apply the command `text`, returning a message describing the
+  /// outcome.
+  fn decide(&mut self, text: &str) -> Result<String> {
+    let (command, pattern) = text
+      .split_once(char::is_whitespace)
+      .map(|(command, pattern)| (command, pattern.trim()))
+      .unwrap_or((text, ""));
+    let (decide, verb): (fn(bool) -> bool, _) = match command {
+      "accept" => (|_selected| true, "Accepted"),
+      "reject" => (|_selected| false, "Rejected"),
+      "toggle" => (|selected| !selected, "Toggled"),
+      _ => bail!("unknown command `{command}`; expected `accept`, `reject`, or `toggle`"),
+    };
+    ensure!(!pattern.is_empty(), "`{command}` requires a pattern");
+
+    let pattern = Pattern::parse(pattern)?;
+    let mut count = 0;
+    for row in &mut self.rows {
+      if pattern.matches(&row.src) {
+        row.selected = decide(row.selected);
+        count += 1;
+      }
+    }
+    Ok(format!("{verb} {count} rename(s)"))
+  }
+
+  /// Collect the selected renames, if they are free of collisions.
+  fn finish(&mut self) -> Option<Vec<Edit>> {
+    // Files not selected for renaming stay where they are.
+    let mut destinations = HashSet::with_capacity(self.rows.len());
+    for row in &self.rows {
+      let dst = if row.selected {
+        &row.edited
+      } else {
+        &row.abs_src
+      };
+      if !destinations.insert(dst) {
+        self.status = format!("multiple files would be renamed to `{}`", dst.display());
+        return None
+      }
+    }
+
+    // Deselected renames are kept only for the sake of their notes.
+    let edits = self
+      .rows
+      .drain(..)
+      .filter(|row| (row.selected && row.edited != row.abs_src) || row.note.is_some())
+      .map(|row| Edit {
+        edited: if row.selected {
+          row.edited
+        } else {
+          row.abs_src.clone()
+        },
+        src: row.src,
+        abs_src: row.abs_src,
+        dst: row.dst,
+        note: row.note,
+      })
+      .collect();
+    Some(edits)
+  }
+}
+
+
+/// Let the user select and edit the renames in `plan` on a full screen
+/// terminal interface.
+///
+/// Returned are the selected renames that change the path of a file
+/// and all annotated ones, with deselected renames leaving the path
+/// unchanged, or `None` if the user quit.
+///
+/// This function blocks.
+fn select(
+  plan: Vec<(PathBuf, PathBuf, PathBuf)>,
+  root: &Root,
+  theme: Theme,
+) -> Result<Option<Vec<Edit>>> {
+  let rows = plan
+    .into_iter()
+    .map(|(src, abs_src, dst)| Row {
+      shown: proposed_path(&src, &abs_src, &dst).display().to_string(),
+      edited: dst.clone(),
+      src,
+      abs_src,
+      dst,
+      selected: true,
+      note: None,
+    })
+    .collect();
+  let mut screen = Screen {
+    rows,
+    cursor: 0,
+    offset: 0,
+    status: String::new(),
+    theme,
+  };
+
+  let _raw = RawMode::enable()?;
+  let _screen = AlternateScreen::enter()?;
+
+  loop {
+    let () = screen.draw()?;
+    let event = read().context("failed to read terminal event")?;
+    let Event::Key(KeyEvent {
+      code,
+      modifiers,
+      kind: KeyEventKind::Press,
+      ..
+    }) = event
+    else {
+      // Anything else, including a resize, just causes a redraw.
+      continue
+    };
+
+    let () = screen.status.clear();
+    let (_cols, lines) = size().context("failed to query terminal size")?;
+    let page = usize::from(lines).saturating_sub(2).max(1);
+    let last = screen.rows.len().saturating_sub(1);
+    match code {
+      KeyCode::Char('c') if modifiers.contains(KeyModifiers::CONTROL) => break Ok(None),
+      KeyCode::Char('q') | KeyCode::Esc => break Ok(None),
+      KeyCode::Up | KeyCode::Char('k') => screen.cursor = screen.cursor.saturating_sub(1),
+      KeyCode::Down | KeyCode::Char('j') => screen.cursor = (screen.cursor + 1).min(last),
+      KeyCode::PageUp => screen.cursor = screen.cursor.saturating_sub(page),
+      KeyCode::PageDown => screen.cursor = (screen.cursor + page).min(last),
+      KeyCode::Home | KeyCode::Char('g') => screen.cursor = 0,
+      KeyCode::End | KeyCode::Char('G') => screen.cursor = last,
+      KeyCode::Char(' ') => {
+        if let Some(row) = screen.rows.get_mut(screen.cursor) {
+          row.selected = !row.selected;
+          screen.cursor = (screen.cursor + 1).min(last);
+        }
+      },
+      KeyCode::Char('a') => {
+        let select = !screen.rows.iter().all(|row| row.selected);
+        let () = screen.rows.iter_mut().for_each(|row| row.selected = select);
+      },
+      KeyCode::Char('e') if !screen.rows.is_empty() => {
+        let proceed = screen.edit(root)?;
+        if !proceed {
+          break Ok(None)
+        }
+      },
+      KeyCode::Char('n') if !screen.rows.is_empty() => {
+        let proceed = screen.annotate()?;
+        if !proceed {
+          break Ok(None)
+        }
+      },
+      KeyCode::Char(':') => {
+        let proceed = screen.command()?;
+        if !proceed {
+          break Ok(None)
+        }
+      },
+      KeyCode::Enter => {
+        if let Some(edits) = screen.finish() {
+          break Ok(Some(edits))
+        }
+      },
+      _ => (),
+    }
+  }
+}
+
+
+/// Let the user review, select, and edit the proposed renames in `plan`
+/// on a full screen terminal interface, styled using `theme`, and then
+/// apply them.
+///
+/// Unedited renames are applied using `command`, if provided, and
+/// directly otherwise.
+pub(crate) async fn review(
+  plan: Vec<(PathBuf, PathBuf, PathBuf)>,
+  command: Option<&[OsString]>,
+  opts: &RenameOpts,
+  hooks: &Hooks,
+  root: &Root,
+  journal: Option<&Mutex<Journal>>,
+  theme: Theme,
+) -> Result<()> {
+  ensure!(
+    stderr().is_terminal(),
+    "the terminal interface requires stderr to be a terminal"
+  );
+
+  let root = root.clone();
+  let edits = spawn_blocking(move || select(plan, &root, theme)).await??;
+  match edits {
+    Some(edits) => apply_edits(edits, command, opts, hooks, journal).await,
+    None => Ok(()),
+  }
+}
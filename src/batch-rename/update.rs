@@ -0,0 +1,208 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Updating of the program to the most recent release.
+//!
+//! Only `batch-rename` itself is updated; releases do not provide
+//! binaries of `print-rename`, which has to be updated by other means.
+
+use std::env::current_exe;
+use std::ffi::OsStr;
+use std::ffi::OsString;
+#[cfg(unix)]
+use std::fs::Permissions;
+#[cfg(unix)]
+use std::os::unix::fs::PermissionsExt as _;
+use std::path::Path;
+use std::process::Stdio;
+
+use anyhow::ensure;
+use anyhow::Context as _;
+use anyhow::Result;
+
+use batch_renamer::evaluate;
+
+use serde_json::Value;
+
+use tokio::fs::remove_file;
+use tokio::fs::rename;
+#[cfg(unix)]
+use tokio::fs::set_permissions;
+use tokio::fs::write;
+use tokio::process::Command;
+
+
+/// The URL of the feed describing the most recent release.
+const RELEASE_FEED: &str = "https://api.github.com/repos/d-e-s-o/batch-renamer/releases/latest";
+/// The name of the release asset containing the program built for the
+/// target we are running on.
+const ASSET: &str = concat!("batch-rename-", env!("TARGET"));
+/// The minisign public key belonging to the secret key release assets
+/// are signed with, as provided when building release binaries.
+const PUBLIC_KEY: Option<&str> = option_env!("BATCH_RENAMER_PUBLIC_KEY");
+/// The program used for verifying signatures of release assets.
+const MINISIGN: &str = "minisign";
+
+
+/// Run `command` with the given arguments, returning its output.
+async fn run<A>(command: &str, args: &[A]) -> Result<Vec<u8>>
+where
+  A: AsRef<OsStr>,
+{
+  let output = Command::new(command)
+    .args(args)
+    .stdin(Stdio::null())
+    .stderr(Stdio::piped())
+    .output()
+    .await
+    .with_context(|| format!("failed to run `{command}`"))?;
+  let () = evaluate(&output, command, args)?;
+  Ok(output.stdout)
+}
+
+
+/// Download the resource at `url` over HTTPS.
+async fn download(url: &str) -> Result<Vec<u8>> {
+  run("curl", &[
+    "--fail",
+    "--silent",
+    "--show-error",
+    "--location",
+    "--proto",
+    "=https",
+    url,
+  ])
+  .await
+}
+
+
+/// Parse a version of the form `1.2.3`, optionally prefixed with `v`.
+fn parse_version(version: &str) -> Option<Vec<u64>> {
+  version
+    .strip_prefix('v')
+    .unwrap_or(version)
+    .split('.')
+    .map(|part| part.parse().ok())
+    .collect()
+}
+
+
+/// Retrieve the download URL of the asset called `name` of `release`.
+fn asset_url<'rel>(release: &'rel Value, name: &str) -> Result<&'rel str> {
+  release["assets"]
+    .as_array()
+    .into_iter()
+    .flatten()
+    .find(|asset| asset["name"] == name)
+    .and_then(|asset| asset["browser_download_url"].as_str())
+    .with_context(|| format!("release does not provide `{name}`"))
+}
+
+
+/// Check `path` against the SHA-256 `checksum` file as published along
+/// with releases, in the format produced by `sha256sum`.
+async fn verify(path: &Path, checksum: &[u8]) -> Result<()> {
+  let expected = String::from_utf8_lossy(checksum);
+  let expected = expected
+    .split_whitespace()
+    .next()
+    .context("checksum file is empty")?;
+  let actual = run("sha256sum", &[path]).await?;
+  let actual = String::from_utf8_lossy(&actual);
+  let actual = actual.split_whitespace().next().unwrap_or_default();
+  ensure!(
+    actual.eq_ignore_ascii_case(expected),
+    "checksum mismatch for downloaded `{ASSET}`: expected {expected}, got {actual}"
+  );
+  Ok(())
+}
+
+
+/// Check that `signature`, as published along with releases, is a
+/// valid minisign signature of `path` made with the secret key
+/// belonging to `key`.
+async fn verify_signature(path: &Path, signature: &[u8], key: &str) -> Result<()> {
+  let mut sig_path = OsString::from(path);
+  let () = sig_path.push(".minisig");
+  let () = write(&sig_path, signature)
+    .await
+    .with_context(|| format!("failed to write `{}`", sig_path.to_string_lossy()))?;
+
+  let args = [
+    OsStr::new("-V"),
+    OsStr::new("-q"),
+    OsStr::new("-P"),
+    OsStr::new(key),
+    OsStr::new("-m"),
+    path.as_os_str(),
+    OsStr::new("-x"),
+    &sig_path,
+  ];
+  let result = run(MINISIGN, &args)
+    .await
+    .with_context(|| format!("downloaded `{ASSET}` does not carry a valid signature"));
+  let _result = remove_file(&sig_path).await;
+  let _stdout = result?;
+  Ok(())
+}
+
+
+/// Replace the running program with the most recent release, if it is
+/// newer.
+///
+/// The release is downloaded next to the program and verified against
+/// its published checksum and signature, before taking the program's
+/// place in a single rename. Only `batch-rename` is updated, never
+/// `print-rename`.
+pub(crate) async fn self_update() -> Result<()> {
+  let current = env!("CARGO_PKG_VERSION");
+  let feed = download(RELEASE_FEED).await?;
+  let release = serde_json::from_slice::<Value>(&feed).context("failed to parse release feed")?;
+  let tag = release["tag_name"]
+    .as_str()
+    .context("release feed does not contain a tag")?;
+  let latest = parse_version(tag).with_context(|| format!("invalid release version `{tag}`"))?;
+  // SANITY: Our own version is always valid.
+  if latest <= parse_version(current).unwrap() {
+    println!("batch-rename {current} is up to date");
+    return Ok(())
+  }
+
+  // Without the key there is no telling whether a release is
+  // authentic, e.g., for builds not made by the release workflow.
+  let key = PUBLIC_KEY.context(
+    "this build does not know the key releases are signed with; please update by other means",
+  )?;
+  let exe = current_exe().context("failed to determine path of running program")?;
+  let binary = asset_url(&release, ASSET)?;
+  let checksum = asset_url(&release, &format!("{ASSET}.sha256"))?;
+  let signature = asset_url(&release, &format!("{ASSET}.minisig"))?;
+  let contents = download(binary).await?;
+  let checksum = download(checksum).await?;
+  let signature = download(signature).await?;
+
+  let tmp = exe.with_file_name(format!(".{ASSET}.update"));
+  let () = write(&tmp, contents)
+    .await
+    .with_context(|| format!("failed to write `{}`", tmp.display()))?;
+  let result = async {
+    let () = verify(&tmp, &checksum).await?;
+    let () = verify_signature(&tmp, &signature, key).await?;
+    #[cfg(unix)]
+    let () = set_permissions(&tmp, Permissions::from_mode(0o755))
+      .await
+      .with_context(|| format!("failed to make `{}` executable", tmp.display()))?;
+    rename(&tmp, &exe)
+      .await
+      .with_context(|| format!("failed to replace `{}`", exe.display()))
+  }
+  .await;
+
+  if let Err(err) = result {
+    let _result = remove_file(&tmp).await;
+    return Err(err)
+  }
+
+  println!("updated batch-rename {current} to {tag}; print-rename, if installed, is unaffected");
+  Ok(())
+}
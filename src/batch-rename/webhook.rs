@@ -0,0 +1,123 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Notification of web services about applied renames.
+//!
+//! Delivery is best-effort: renames got applied regardless of whether
+//! anybody could be told about them, so failures to notify a webhook
+//! are merely reported as warnings.
+
+use std::path::Path;
+use std::sync::Mutex;
+use std::sync::PoisonError;
+
+use anyhow::Context as _;
+use anyhow::Result;
+
+use batch_renamer::change::classify;
+use batch_renamer::output::Batch;
+use batch_renamer::output::Report;
+use batch_renamer::output::Status;
+use batch_renamer::output::Summary;
+
+use serde::Serialize;
+
+use tracing::warn;
+
+use crate::http::request;
+
+
+/// Send `record` to `url` in the body of an HTTP POST request, as JSON
+/// object.
+async fn post<T>(url: &str, record: &T) -> Result<()>
+where
+  T: Serialize,
+{
+  let body = serde_json::to_string(record).context("failed to serialize webhook record")?;
+  let headers = ["Content-Type: application/json".to_string()];
+  let _response = request("POST", url, &headers, Some(&body))
+    .await
+    .with_context(|| format!("webhook `{url}` failed"))?;
+  Ok(())
+}
+
+
+/// Send `record` to each of `urls`, as per [`post`], warning about
+/// failures.
+async fn post_all<T>(urls: &[String], record: &T)
+where
+  T: Serialize,
+{
+  for url in urls {
+    if let Err(err) = post(url, record).await {
+      warn!("{err:#}");
+    }
+  }
+}
+
+
+/// Webhooks notified about applied renames.
+#[derive(Debug)]
+pub(crate) struct Webhooks {
+  /// The URLs to send requests to.
+  urls: Vec<String>,
+  /// Whether to send a request per applied rename, as opposed to one
+  /// per batch.
+  per_rename: bool,
+  /// The renames applied so far, to be sent along with the batch.
+  renames: Mutex<Vec<Report>>,
+}
+
+impl Webhooks {
+  /// Create webhooks sending requests to `urls`, per applied rename if
+  /// `per_rename` is set or per batch otherwise.
+  pub(crate) fn new(urls: Vec<String>, per_rename: bool) -> Self {
+    Self {
+      urls,
+      per_rename,
+      renames: Mutex::new(Vec::new()),
+    }
+  }
+
+  /// Notify the webhooks about the rename of `src` to `dst` or, when
+  /// sending requests per batch, remember it for doing so later.
+  pub(crate) async fn renamed(&self, src: &Path, dst: &Path) {
+    let report = Report {
+      src: src.to_path_buf(),
+      dst: dst.to_path_buf(),
+      status: Status::Renamed,
+      change: classify(src, dst),
+      error: None,
+    };
+
+    if self.per_rename {
+      let () = post_all(&self.urls, &report).await;
+    } else {
+      let () = self
+        .renames
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .push(report);
+    }
+  }
+
+  /// Notify the webhooks about the completed batch described by
+  /// `summary`, if any file got renamed and requests are sent per
+  /// batch.
+  pub(crate) async fn finish(&self, summary: &Summary) {
+    let renames = self
+      .renames
+      .lock()
+      .unwrap_or_else(PoisonError::into_inner)
+      .split_off(0);
+    if self.per_rename || renames.is_empty() {
+      return
+    }
+
+    let batch = Batch {
+      summary: *summary,
+      renames,
+    };
+    let () = post_all(&self.urls, &batch).await;
+  }
+}
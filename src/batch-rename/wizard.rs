@@ -0,0 +1,308 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Interactive construction of a template.
+
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+
+use anyhow::ensure;
+use anyhow::Result;
+
+use batch_renamer::output::Format;
+use batch_renamer::resolve;
+use batch_renamer::template::Template;
+use batch_renamer::SymlinkPolicy;
+
+use chrono::NaiveDate;
+
+use tokio::task::spawn_blocking;
+
+use crate::tell;
+use crate::term::edit_line;
+use crate::term::read_key;
+use crate::term::Input;
+use crate::term::Key;
+use crate::term::Line;
+
+
+/// The number of files inspected for detecting their structure.
+const SAMPLE_SIZE: usize = 5;
+
+/// The metadata tokens checked for availability.
+#[cfg(feature = "metadata")]
+const METADATA: [&str; 5] = [
+  "exif.date",
+  "exif.model",
+  "id3.title",
+  "id3.artist",
+  "id3.track",
+];
+
+
+/// A date found in a file stem, expressed by the (one-based) indices of
+/// the numbers making up year, month, and day.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+struct Date {
+  year: usize,
+  month: usize,
+  day: usize,
+}
+
+impl Date {
+  /// The template tokens reproducing the date as `YYYY-MM-DD`.
+  fn tokens(&self) -> String {
+    format!(
+      "{{f{}}}-{{f{}:02}}-{{f{}:02}}",
+      self.year, self.month, self.day
+    )
+  }
+}
+
+
+/// Find a date made up of three consecutive numbers in `stem`, as in
+/// `2023-07-14` or `2023_07_14`.
+///
+/// Dates written without delimiters can't be addressed by number
+/// tokens and are not reported.
+fn find_date(stem: &str) -> Option<Date> {
+  let numbers = stem
+    .split(|c: char| !c.is_ascii_digit())
+    .filter(|digits| !digits.is_empty())
+    .collect::<Vec<_>>();
+
+  numbers.windows(3).enumerate().find_map(|(idx, window)| {
+    let [year, month, day] = window else {
+      return None
+    };
+    let valid = year.len() == 4
+      && month.len() <= 2
+      && day.len() <= 2
+      && NaiveDate::parse_from_str(&format!("{year}-{month}-{day}"), "%Y-%m-%d").is_ok();
+    valid.then_some(Date {
+      year: idx + 1,
+      month: idx + 2,
+      day: idx + 3,
+    })
+  })
+}
+
+
+/// The structure detected in a sample of files.
+#[derive(Debug, Default)]
+struct Structure {
+  /// The minimum number of numbers in the stems of the files.
+  numbers: usize,
+  /// The date common to all files, if any.
+  date: Option<Date>,
+  /// The delimiters used in file stems.
+  delimiters: BTreeSet<char>,
+  /// The extensions of the files.
+  extensions: BTreeSet<String>,
+  /// The metadata tokens available, along with the number of files
+  /// they are available for.
+  metadata: Vec<(&'static str, usize)>,
+}
+
+impl Structure {
+  /// Inspect the files in `sample`.
+  async fn detect(sample: &[PathBuf]) -> Self {
+    let mut structure = Self {
+      numbers: usize::MAX,
+      ..Default::default()
+    };
+    let mut dates = Vec::with_capacity(sample.len());
+
+    for path in sample {
+      let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+      let numbers = stem
+        .split(|c: char| !c.is_ascii_digit())
+        .filter(|digits| !digits.is_empty())
+        .count();
+      structure.numbers = structure.numbers.min(numbers);
+      let () = dates.push(find_date(&stem));
+      let () = structure.delimiters.extend(
+        stem
+          .chars()
+          .filter(|c| !c.is_alphanumeric() && !c.is_whitespace())
+          .chain(stem.contains(' ').then_some(' ')),
+      );
+      if let Some(ext) = path.extension() {
+        let _inserted = structure
+          .extensions
+          .insert(format!(".{}", ext.to_string_lossy()));
+      }
+    }
+
+    if structure.numbers == usize::MAX {
+      structure.numbers = 0;
+    }
+    structure.date = match dates.split_first() {
+      Some((first @ Some(_), rest)) if rest.iter().all(|date| date == first) => *first,
+      _ => None,
+    };
+
+    #[cfg(feature = "metadata")]
+    for name in METADATA {
+      // SANITY: The token names are all valid.
+      let template = Template::parse(&format!("{{{name}}}")).unwrap();
+      let mut count = 0;
+      for path in sample {
+        if template.render(path, 1).await.is_ok() {
+          count += 1;
+        }
+      }
+      if count > 0 {
+        let () = structure.metadata.push((name, count));
+      }
+    }
+
+    structure
+  }
+
+  /// Describe the structure for the user.
+  fn describe(&self, count: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    if self.numbers > 0 {
+      let tokens = (1..=self.numbers)
+        .map(|n| format!("{{f{n}}}"))
+        .collect::<Vec<_>>();
+      let () = lines.push(format!(
+        "numbers:    {} per name, available as {}",
+        self.numbers,
+        tokens.join(", ")
+      ));
+    }
+    if let Some(date) = &self.date {
+      let () = lines.push(format!("date:       available as {}", date.tokens()));
+    }
+    if !self.delimiters.is_empty() {
+      let delimiters = self
+        .delimiters
+        .iter()
+        .map(|c| format!("`{c}`"))
+        .collect::<Vec<_>>();
+      let () = lines.push(format!("delimiters: {}", delimiters.join(", ")));
+    }
+    if !self.extensions.is_empty() {
+      let extensions = self
+        .extensions
+        .iter()
+        .map(|ext| format!("`{ext}`"))
+        .collect::<Vec<_>>();
+      let () = lines.push(format!("extensions: {}", extensions.join(", ")));
+    }
+    for (name, available) in &self.metadata {
+      let () = lines.push(format!(
+        "metadata:   {{{name}}} available for {available} of {count} files"
+      ));
+    }
+    lines
+  }
+
+  /// Suggest a template based on the structure.
+  fn suggest(&self, count: usize) -> String {
+    let date = if self
+      .metadata
+      .iter()
+      .any(|(name, available)| *name == "exif.date" && *available == count)
+    {
+      "{exif.date}".to_string()
+    } else if let Some(date) = &self.date {
+      date.tokens()
+    } else {
+      "{mtime}".to_string()
+    };
+    format!("{date}_{{counter:03}}{{ext}}")
+  }
+}
+
+
+/// Show what `template` does to the files in `sample`.
+async fn preview(output: Format, template: &Template, sample: &[PathBuf]) {
+  for (path, counter) in sample.iter().zip(1..) {
+    let name = path
+      .file_name()
+      .map(|name| name.to_string_lossy().into_owned())
+      .unwrap_or_default();
+    let message = match template.render(path, counter).await {
+      Ok(new) => format!("  {name} → {}", new.to_string_lossy()),
+      Err(err) => format!("  {name}: {err:#}"),
+    };
+    let () = tell(output, &message);
+  }
+}
+
+
+/// Interactively build a template for renaming `files`.
+///
+/// A sample of the files is inspected and its structure shown to the
+/// user, along with a suggested template. The user can then edit the
+/// template and see its effect on the sample until satisfied. `None`
+/// is returned if the user quit.
+pub(crate) async fn build(
+  files: &[PathBuf],
+  symlinks: SymlinkPolicy,
+  input: &Input,
+  output: Format,
+) -> Result<Option<Template>> {
+  ensure!(!files.is_empty(), "no files to build a template for");
+
+  let mut sample = Vec::with_capacity(SAMPLE_SIZE);
+  for file in files.iter().take(SAMPLE_SIZE) {
+    let () = sample.push(resolve(file, symlinks).await?);
+  }
+
+  let structure = Structure::detect(&sample).await;
+  let () = tell(
+    output,
+    &format!("Inspected {} of {} files:", sample.len(), files.len()),
+  );
+  for line in structure.describe(sample.len()) {
+    let () = tell(output, &format!("  {line}"));
+  }
+
+  let mut text = structure.suggest(sample.len());
+  loop {
+    let () = tell(
+      output,
+      &format!("Template `{text}`; edit it or press enter to keep it (see --help for tokens):"),
+    );
+    let initial = text.clone();
+    let line = {
+      let input = input.clone();
+      spawn_blocking(move || edit_line(&initial, &input)).await??
+    };
+    match line {
+      Line::Text(edited) if !edited.is_empty() => text = edited,
+      Line::Text(..) | Line::Cancel => (),
+      Line::Interrupt => return Ok(None),
+    }
+
+    let template = match Template::parse(&text) {
+      Ok(template) => template.with_symlink_policy(symlinks),
+      Err(err) => {
+        let () = tell(output, &err.to_string());
+        continue
+      },
+    };
+
+    let () = preview(output, &template, &sample).await;
+    let () = tell(output, "Use this template? (Y/n/q)");
+    let key = {
+      let input = input.clone();
+      spawn_blocking(move || read_key(&input)).await??
+    };
+    match key {
+      Key::Enter | Key::Char('y' | 'Y') => {
+        let () = tell(output, &format!("Using --template '{template}'"));
+        break Ok(Some(template))
+      },
+      Key::Char('q') | Key::Interrupt => break Ok(None),
+      Key::Char(..) => (),
+    }
+  }
+}
@@ -0,0 +1,321 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Classification of renames by the kind of change they make to file
+//! names.
+
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Result as FmtResult;
+use std::path::Path;
+use std::str::FromStr;
+
+use serde::Serialize;
+
+use crate::error::Error;
+use crate::error::Result;
+
+
+/// The kind of change a rename makes to the name of a file.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Change {
+  /// Only the case of letters changes.
+  CaseOnly,
+  /// Only the extension changes.
+  Extension,
+  /// The words making up the name are put into a different order.
+  Reorder,
+  /// A date gets added to the name.
+  Date,
+  /// Numbers get added to the name or changed, as when numbering
+  /// files.
+  Numbering,
+  /// Any other change.
+  FreeForm,
+}
+
+impl Change {
+  /// All kinds of changes, in the order they are checked for.
+  pub const ALL: [Self; 6] = [
+    Self::CaseOnly,
+    Self::Extension,
+    Self::Reorder,
+    Self::Date,
+    Self::Numbering,
+    Self::FreeForm,
+  ];
+}
+
+impl FromStr for Change {
+  type Err = Error;
+
+  fn from_str(s: &str) -> Result<Self> {
+    match s {
+      "case-only" => Ok(Self::CaseOnly),
+      "extension" => Ok(Self::Extension),
+      "reorder" => Ok(Self::Reorder),
+      "date" => Ok(Self::Date),
+      "numbering" => Ok(Self::Numbering),
+      "free-form" => Ok(Self::FreeForm),
+      _ => Err(Error::Invalid(format!(
+        "unsupported change `{s}`; expected one of `case-only`, `extension`, `reorder`, `date`, `numbering`, or `free-form`"
+      ))),
+    }
+  }
+}
+
+impl Display for Change {
+  fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+    let s = match self {
+      Self::CaseOnly => "case-only",
+      Self::Extension => "extension",
+      Self::Reorder => "reorder",
+      Self::Date => "date",
+      Self::Numbering => "numbering",
+      Self::FreeForm => "free-form",
+    };
+    f.write_str(s)
+  }
+}
+
+
+/// The number of renames per kind of change.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Changes {
+  /// The number of renames changing only the case of letters.
+  pub case_only: usize,
+  /// The number of renames changing only the extension.
+  pub extension: usize,
+  /// The number of renames reordering words.
+  pub reorder: usize,
+  /// The number of renames adding a date.
+  pub date: usize,
+  /// The number of renames adding or changing numbers.
+  pub numbering: usize,
+  /// The number of renames making any other change.
+  pub free_form: usize,
+}
+
+impl Changes {
+  /// Account for a rename making the given change.
+  pub fn record(&mut self, change: Change) {
+    *self.count_mut(change) += 1;
+  }
+
+  /// Retrieve the number of renames making the given change.
+  pub fn count(&self, change: Change) -> usize {
+    match change {
+      Change::CaseOnly => self.case_only,
+      Change::Extension => self.extension,
+      Change::Reorder => self.reorder,
+      Change::Date => self.date,
+      Change::Numbering => self.numbering,
+      Change::FreeForm => self.free_form,
+    }
+  }
+
+  /// Retrieve a mutable reference to the number of renames making the
+  /// given change.
+  fn count_mut(&mut self, change: Change) -> &mut usize {
+    match change {
+      Change::CaseOnly => &mut self.case_only,
+      Change::Extension => &mut self.extension,
+      Change::Reorder => &mut self.reorder,
+      Change::Date => &mut self.date,
+      Change::Numbering => &mut self.numbering,
+      Change::FreeForm => &mut self.free_form,
+    }
+  }
+}
+
+impl Display for Changes {
+  fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+    // Only the kinds of changes made are listed, as in `2 case-only, 1
+    // date`.
+    let changes = Change::ALL
+      .into_iter()
+      .filter_map(|change| {
+        let count = self.count(change);
+        (count > 0).then(|| format!("{count} {change}"))
+      })
+      .collect::<Vec<_>>();
+    f.write_str(&changes.join(", "))
+  }
+}
+
+
+/// Split `name` into its stem and extension, if any.
+fn split_extension(name: &str) -> (&str, Option<&str>) {
+  match name.rsplit_once('.') {
+    Some((stem, ext)) if !stem.is_empty() => (stem, Some(ext)),
+    _ => (name, None),
+  }
+}
+
+
+/// Split `s` into its words, i.e., runs of alphanumeric characters.
+fn words(s: &str) -> Vec<&str> {
+  s.split(|c: char| !c.is_alphanumeric())
+    .filter(|word| !word.is_empty())
+    .collect()
+}
+
+
+/// Determine the length, in bytes, of the text that `old` and `new`
+/// have in common at their start.
+fn common_prefix(old: &str, new: &str) -> usize {
+  old
+    .char_indices()
+    .zip(new.chars())
+    .find(|((_, a), b)| a != b)
+    .map(|((idx, _), _)| idx)
+    .unwrap_or(old.len().min(new.len()))
+}
+
+
+/// Strip the text that `old` and `new` have in common at their start
+/// and end, returning what remains of each.
+fn strip_common<'s>(old: &'s str, new: &'s str) -> (&'s str, &'s str) {
+  let prefix = common_prefix(old, new);
+  let (old, new) = (&old[prefix..], &new[prefix..]);
+  let suffix = old
+    .chars()
+    .rev()
+    .zip(new.chars().rev())
+    .take_while(|(a, b)| a == b)
+    .map(|(a, _)| a.len_utf8())
+    .sum::<usize>();
+  (&old[..old.len() - suffix], &new[..new.len() - suffix])
+}
+
+
+/// Check whether `year`, `month`, and `day` make up a plausible date.
+fn is_plausible_date(year: &str, month: &str, day: &str) -> bool {
+  let parse = |s: &str| s.parse::<u32>().ok();
+  match (parse(year), parse(month), parse(day)) {
+    (Some(year), Some(month), Some(day)) => {
+      (1900..=2099).contains(&year) && (1..=12).contains(&month) && (1..=31).contains(&day)
+    },
+    _ => false,
+  }
+}
+
+
+/// Check whether `s` contains a date, as in `2023-07-14`,
+/// `14.07.2023`, or `20230714`.
+fn contains_date(s: &str) -> bool {
+  let numbers = s
+    .split(|c: char| !c.is_ascii_digit())
+    .filter(|number| !number.is_empty())
+    .collect::<Vec<_>>();
+  let compact = numbers.iter().any(|number| {
+    number.len() == 8 && is_plausible_date(&number[..4], &number[4..6], &number[6..])
+  });
+  compact
+    || numbers.windows(3).any(|window| match window {
+      [year, month, day] if year.len() == 4 && month.len() <= 2 && day.len() <= 2 => {
+        is_plausible_date(year, month, day)
+      },
+      [a, b, year] if a.len() <= 2 && b.len() <= 2 && year.len() == 4 => {
+        is_plausible_date(year, a, b) || is_plausible_date(year, b, a)
+      },
+      _ => false,
+    })
+}
+
+
+/// Find the number that the rename of `src` to `dst` added to or
+/// changed in the file's name, returning the part of the new name
+/// preceding it along with its value.
+///
+/// This is meant for renames classified as [`Change::Numbering`], as
+/// in `ep1.mkv` becoming `ep01.mkv`, for which the number is `1`
+/// preceded by `ep`.
+pub fn changed_number(src: &Path, dst: &Path) -> Option<(String, u64)> {
+  let old = src.file_name()?.to_string_lossy();
+  let new = dst.file_name()?.to_string_lossy();
+  let start = common_prefix(&old, &new);
+  let (_removed, added) = strip_common(&old, &new);
+  let end = start + added.len();
+
+  // The number may extend beyond the changed part of the name, as it
+  // does when changing `9` to `19`.
+  let bytes = new.as_bytes();
+  let mut idx = 0;
+  while idx < bytes.len() {
+    if !bytes[idx].is_ascii_digit() {
+      idx += 1;
+      continue
+    }
+    let begin = idx;
+    while idx < bytes.len() && bytes[idx].is_ascii_digit() {
+      idx += 1;
+    }
+    if begin <= end && idx >= start {
+      let number = new[begin..idx].parse().ok()?;
+      return Some((new[..begin].to_string(), number))
+    }
+  }
+  None
+}
+
+
+/// Classify the rename of `src` to `dst` by the kind of change it makes
+/// to the file's name, using a heuristic.
+///
+/// `None` is returned if the paths are equal. Renames moving a file to
+/// a different directory without changing its name are considered
+/// [`Change::FreeForm`].
+pub fn classify(src: &Path, dst: &Path) -> Option<Change> {
+  if src == dst {
+    return None
+  }
+
+  let name = |path: &Path| {
+    path
+      .file_name()
+      .map(|name| name.to_string_lossy().into_owned())
+      .unwrap_or_default()
+  };
+  let (old, new) = (name(src), name(dst));
+  if old == new {
+    return Some(Change::FreeForm)
+  }
+  if old.to_lowercase() == new.to_lowercase() {
+    return Some(Change::CaseOnly)
+  }
+
+  let ((old_stem, old_ext), (new_stem, new_ext)) = (split_extension(&old), split_extension(&new));
+  if old_stem == new_stem {
+    return Some(Change::Extension)
+  }
+  if old_ext == new_ext {
+    let (mut old_words, mut new_words) = (words(old_stem), words(new_stem));
+    let () = old_words.sort_unstable();
+    let () = new_words.sort_unstable();
+    if !old_words.is_empty() && old_words == new_words {
+      return Some(Change::Reorder)
+    }
+  }
+
+  // Dates and numbering only ever add or change digits, along with
+  // the characters separating them.
+  let (removed, added) = strip_common(&old, &new);
+  let is_numeric = |s: &str| {
+    s.chars()
+      .all(|c| c.is_ascii_digit() || !c.is_alphanumeric())
+  };
+  if !is_numeric(removed) || !is_numeric(added) {
+    return Some(Change::FreeForm)
+  }
+  if removed.chars().all(|c| !c.is_alphanumeric()) && contains_date(&new) && !contains_date(&old) {
+    return Some(Change::Date)
+  }
+  if removed.contains(|c: char| c.is_ascii_digit()) || added.contains(|c: char| c.is_ascii_digit())
+  {
+    return Some(Change::Numbering)
+  }
+  Some(Change::FreeForm)
+}
@@ -0,0 +1,813 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Concurrency control for batch operations.
+
+use std::future::pending;
+use std::future::Future;
+use std::io;
+use std::io::ErrorKind;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::PoisonError;
+use std::task::Context;
+use std::task::Poll;
+use std::time::Duration;
+use std::time::Instant;
+
+use futures::stream::Fuse;
+use futures::stream::FuturesOrdered;
+use futures::Stream;
+use futures::StreamExt as _;
+
+use tokio::process::Child;
+use tokio::process::Command;
+use tokio::select;
+use tokio::task::JoinError;
+use tokio::task::JoinSet;
+use tokio::time::sleep;
+
+
+/// The weight of the most recent latency sample in the moving average.
+const ALPHA: f64 = 0.2;
+/// The factor by which a latency has to exceed the moving average to be
+/// considered a spike.
+const SPIKE_FACTOR: f64 = 3.0;
+/// The relative change in throughput between two windows that is
+/// considered significant.
+const THRESHOLD: f64 = 0.1;
+/// The interval in which a process waiting to be spawned checks
+/// whether it may proceed.
+const SPAWN_POLL: Duration = Duration::from_millis(10);
+/// The number of times spawning a process is attempted while no other
+/// process of ours is running, before the failure is reported.
+const SPAWN_ATTEMPTS: u32 = 8;
+
+
+/// The concurrency with which to process a batch.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Concurrency {
+  /// Process a fixed number of items concurrently.
+  Fixed(usize),
+  /// Adapt the number of items processed concurrently to observed
+  /// latencies, staying within the given bounds.
+  Adaptive {
+    /// The minimum number of items to process concurrently.
+    min: usize,
+    /// The maximum number of items to process concurrently.
+    max: usize,
+  },
+}
+
+impl Concurrency {
+  /// The maximum number of items processed concurrently.
+  pub fn max(&self) -> usize {
+    match self {
+      Self::Fixed(n) => (*n).max(1),
+      Self::Adaptive { max, .. } => (*max).max(1),
+    }
+  }
+}
+
+impl Default for Concurrency {
+  fn default() -> Self {
+    Self::Fixed(32)
+  }
+}
+
+
+#[derive(Debug)]
+struct State {
+  /// The current limit.
+  limit: usize,
+  /// The moving average of observed latencies, in seconds.
+  latency: Option<f64>,
+  /// The start of the current observation window.
+  window_start: Instant,
+  /// The number of completions in the current observation window.
+  window_count: usize,
+  /// The throughput observed in the previous window, in completions
+  /// per second.
+  throughput: Option<f64>,
+  /// Whether the limit is still in its initial phase of exponential
+  /// growth, which lasts until the first decrease.
+  slow_start: bool,
+}
+
+
+/// A concurrency limit, potentially adapting itself to observed
+/// latencies.
+///
+/// An adaptive limit uses an additive-increase/multiplicative-decrease
+/// scheme: at the end of each observation window (comprising as many
+/// completions as the current limit), the limit is increased by one if
+/// throughput improved compared to the previous window and decreased by
+/// one if it degraded. On a latency spike the limit is halved right
+/// away. Until the first decrease, the limit doubles instead of
+/// growing by one, so that suitable concurrency is found quickly.
+#[derive(Debug)]
+pub struct Limit {
+  /// The lower and upper bounds of the limit.
+  bounds: (usize, usize),
+  /// The mutable state of the limit.
+  state: Mutex<State>,
+}
+
+impl Limit {
+  /// Create a new limit for the given concurrency.
+  pub fn new(concurrency: Concurrency) -> Self {
+    let (min, max, initial) = match concurrency {
+      Concurrency::Fixed(n) => (n.max(1), n.max(1), n.max(1)),
+      Concurrency::Adaptive { min, max } => {
+        let min = min.max(1);
+        let max = max.max(min);
+        (min, max, min)
+      },
+    };
+
+    Self {
+      bounds: (min, max),
+      state: Mutex::new(State {
+        limit: initial,
+        latency: None,
+        window_start: Instant::now(),
+        window_count: 0,
+        throughput: None,
+        slow_start: true,
+      }),
+    }
+  }
+
+  fn state(&self) -> std::sync::MutexGuard<'_, State> {
+    self.state.lock().unwrap_or_else(PoisonError::into_inner)
+  }
+
+  /// Retrieve the current limit.
+  pub fn get(&self) -> usize {
+    self.state().limit
+  }
+
+  /// Lower the upper bound of the limit to at most `max`, e.g., because
+  /// an external resource limit was hit.
+  pub fn cap(&self, max: usize) {
+    let mut state = self.state();
+    state.limit = state.limit.min(max.max(1));
+  }
+
+  /// Record the completion of an item that took `latency` to process.
+  pub fn complete(&self, latency: Duration) {
+    let (min, max) = self.bounds;
+    if min == max {
+      return
+    }
+
+    let latency = latency.as_secs_f64();
+    let mut state = self.state();
+
+    if let Some(average) = state.latency {
+      if latency > average * SPIKE_FACTOR {
+        state.limit = (state.limit / 2).max(min);
+        state.slow_start = false;
+        state.latency = Some(latency);
+        state.window_start = Instant::now();
+        state.window_count = 0;
+        state.throughput = None;
+        return
+      }
+    }
+
+    state.latency = Some(match state.latency {
+      Some(average) => ALPHA * latency + (1.0 - ALPHA) * average,
+      None => latency,
+    });
+
+    state.window_count += 1;
+    if state.window_count >= state.limit {
+      let elapsed = state.window_start.elapsed().as_secs_f64();
+      let throughput = state.window_count as f64 / elapsed.max(f64::EPSILON);
+
+      match state.throughput {
+        Some(previous) if throughput < previous * (1.0 - THRESHOLD) => {
+          state.limit = state.limit.saturating_sub(1).max(min);
+          state.slow_start = false;
+        },
+        Some(previous) if throughput <= previous * (1.0 + THRESHOLD) => (),
+        _ if state.slow_start => state.limit = (state.limit * 2).min(max),
+        _ => state.limit = (state.limit + 1).min(max),
+      }
+
+      state.throughput = Some(throughput);
+      state.window_start = Instant::now();
+      state.window_count = 0;
+    }
+  }
+}
+
+
+/// A future reporting the time it took to complete.
+struct Timed<F> {
+  future: Pin<Box<F>>,
+  start: Option<Instant>,
+}
+
+impl<F> Future for Timed<F>
+where
+  F: Future,
+{
+  type Output = (F::Output, Duration);
+
+  fn poll(mut self: Pin<&mut Self>, ctx: &mut Context<'_>) -> Poll<Self::Output> {
+    let start = *self.start.get_or_insert_with(Instant::now);
+    self
+      .future
+      .as_mut()
+      .poll(ctx)
+      .map(|output| (output, start.elapsed()))
+  }
+}
+
+
+/// A stream buffering the futures produced by an underlying stream,
+/// running up to a potentially varying number of them concurrently.
+///
+/// Results are reported in the order of the underlying stream.
+pub struct Buffered<St>
+where
+  St: Stream,
+  St::Item: Future,
+{
+  stream: Fuse<St>,
+  queue: FuturesOrdered<Timed<St::Item>>,
+  limit: Arc<Limit>,
+}
+
+impl<St> Buffered<St>
+where
+  St: Stream,
+  St::Item: Future,
+{
+  /// Retrieve the limit governing the stream.
+  pub fn limit(&self) -> &Arc<Limit> {
+    &self.limit
+  }
+}
+
+impl<St> Stream for Buffered<St>
+where
+  St: Stream + Unpin,
+  St::Item: Future,
+{
+  type Item = <St::Item as Future>::Output;
+
+  fn poll_next(mut self: Pin<&mut Self>, ctx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+    let this = &mut *self;
+
+    while this.queue.len() < this.limit.get() {
+      match this.stream.poll_next_unpin(ctx) {
+        Poll::Ready(Some(future)) => {
+          let () = this.queue.push_back(Timed {
+            future: Box::pin(future),
+            start: None,
+          });
+        },
+        Poll::Ready(None) | Poll::Pending => break,
+      }
+    }
+
+    match this.queue.poll_next_unpin(ctx) {
+      Poll::Ready(Some((output, latency))) => {
+        let () = this.limit.complete(latency);
+        Poll::Ready(Some(output))
+      },
+      Poll::Ready(None) if this.stream.is_done() => Poll::Ready(None),
+      Poll::Ready(None) | Poll::Pending => Poll::Pending,
+    }
+  }
+}
+
+
+/// Buffer the futures produced by `stream`, running them with the given
+/// concurrency.
+pub fn buffered<St>(stream: St, concurrency: Concurrency) -> Buffered<St>
+where
+  St: Stream + Unpin,
+  St::Item: Future,
+{
+  buffered_with(stream, Arc::new(Limit::new(concurrency)))
+}
+
+
+/// Buffer the futures produced by `stream`, running them concurrently
+/// as governed by `limit`.
+///
+/// The limit may be shared with other parties, allowing them to
+/// influence the concurrency of the stream.
+pub fn buffered_with<St>(stream: St, limit: Arc<Limit>) -> Buffered<St>
+where
+  St: Stream + Unpin,
+  St::Item: Future,
+{
+  Buffered {
+    stream: stream.fuse(),
+    queue: FuturesOrdered::new(),
+    limit,
+  }
+}
+
+
+/// A group of fallible tasks running in the background, scoped to the
+/// lifetime of the group.
+///
+/// Dropping the group aborts all tasks still running, so that no task
+/// outlives the code that spawned it, e.g., when bailing out early
+/// because of an error. A task panicking or getting cancelled counts
+/// as failing, with the [`JoinError`] converted into `E`.
+#[derive(Debug)]
+pub struct TaskGroup<T, E> {
+  tasks: JoinSet<Result<T, E>>,
+}
+
+impl<T, E> TaskGroup<T, E>
+where
+  T: Send + 'static,
+  E: From<JoinError> + Send + 'static,
+{
+  /// Create a new, empty, task group.
+  pub fn new() -> Self {
+    Self {
+      tasks: JoinSet::new(),
+    }
+  }
+
+  /// Retrieve the number of tasks in the group that have not been
+  /// joined yet.
+  #[inline]
+  pub fn len(&self) -> usize {
+    self.tasks.len()
+  }
+
+  /// Check whether the group contains no tasks that have not been
+  /// joined yet.
+  #[inline]
+  pub fn is_empty(&self) -> bool {
+    self.tasks.is_empty()
+  }
+
+  /// Spawn `task` as part of the group.
+  pub fn spawn<F>(&mut self, task: F)
+  where
+    F: Future<Output = Result<T, E>> + Send + 'static,
+  {
+    let _handle = self.tasks.spawn(task);
+  }
+
+  /// Wait for the next task to finish and report its outcome, or
+  /// `None` if the group is empty.
+  pub async fn join_next(&mut self) -> Option<Result<T, E>> {
+    self
+      .tasks
+      .join_next()
+      .await
+      .map(|result| result.map_err(E::from).and_then(|result| result))
+  }
+
+  /// Wait for all tasks to finish, reporting the first failure.
+  ///
+  /// Once a task failed, all remaining ones are aborted.
+  pub async fn join_all(&mut self) -> Result<(), E> {
+    while let Some(result) = self.join_next().await {
+      if let Err(err) = result {
+        let () = self.tasks.abort_all();
+        return Err(err)
+      }
+    }
+    Ok(())
+  }
+
+  /// Wait for a task to fail, joining the ones finishing successfully
+  /// in the meantime.
+  ///
+  /// The returned future never resolves if no task fails, allowing it
+  /// to be raced against other work, so that failures of background
+  /// tasks surface right away instead of once that work is done.
+  pub async fn failure(&mut self) -> E {
+    loop {
+      match self.join_next().await {
+        Some(Ok(_output)) => (),
+        Some(Err(err)) => return err,
+        None => pending().await,
+      }
+    }
+  }
+}
+
+impl<T, E> Default for TaskGroup<T, E>
+where
+  T: Send + 'static,
+  E: From<JoinError> + Send + 'static,
+{
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+
+/// A pipeline applying items, such as renames, in the background while
+/// further ones are being decided on, e.g., by prompting the user.
+///
+/// Each item is applied by a task in a [`TaskGroup`], with at most
+/// `limit` of them in flight at a time. A task reports the failure of
+/// its item as the inner error and failures that affect the pipeline as
+/// a whole, such as the inability to report on the item, as the outer
+/// one. Item failures are collected if the pipeline keeps going and
+/// fail the pipeline otherwise.
+#[derive(Debug)]
+pub struct Pipeline<K, E> {
+  /// The tasks applying items, reporting the failure of their item, if
+  /// it is to be collected.
+  tasks: TaskGroup<Option<(K, E)>, E>,
+  /// The maximum number of items applied concurrently.
+  limit: usize,
+  /// Whether to collect failures of items instead of failing the
+  /// pipeline.
+  keep_going: bool,
+  /// The items that failed, along with the respective error.
+  failures: Vec<(K, E)>,
+}
+
+impl<K, E> Pipeline<K, E>
+where
+  K: Send + 'static,
+  E: From<JoinError> + Send + 'static,
+{
+  /// Create a new pipeline applying up to `limit` items concurrently,
+  /// collecting failures of items instead of failing if `keep_going`
+  /// is set.
+  pub fn new(limit: usize, keep_going: bool) -> Self {
+    Self {
+      tasks: TaskGroup::new(),
+      limit: limit.max(1),
+      keep_going,
+      failures: Vec::new(),
+    }
+  }
+
+  /// Record the outcome of a task.
+  fn collect(&mut self, result: Result<Option<(K, E)>, E>) -> Result<(), E> {
+    if let Some(failure) = result? {
+      let () = self.failures.push(failure);
+    }
+    Ok(())
+  }
+
+  /// Apply the item identified by `key` by means of `task`, in the
+  /// background.
+  ///
+  /// Should the limit of items in flight be reached, wait for some to
+  /// finish first; with a limit of one, the item is applied by the time
+  /// this function returns. A failure of any item waited for that is
+  /// not collected is reported.
+  pub async fn push<F>(&mut self, key: K, task: F) -> Result<(), E>
+  where
+    F: Future<Output = Result<Result<(), E>, E>> + Send + 'static,
+  {
+    let keep_going = self.keep_going;
+    let () = self.tasks.spawn(async move {
+      match task.await? {
+        Ok(()) => Ok(None),
+        Err(err) if keep_going => Ok(Some((key, err))),
+        Err(err) => Err(err),
+      }
+    });
+
+    while self.tasks.len() >= self.limit {
+      if let Some(result) = self.tasks.join_next().await {
+        let () = self.collect(result)?;
+      }
+    }
+    Ok(())
+  }
+
+  /// Wait for `work` to finish, while applying items in the background.
+  ///
+  /// Should an item fail the pipeline in the meantime, `work` is
+  /// dropped and the failure reported right away, instead of once
+  /// `work` is done.
+  pub async fn race<F>(&mut self, work: F) -> Result<F::Output, E>
+  where
+    F: Future,
+  {
+    let failure = async {
+      loop {
+        match self.tasks.join_next().await {
+          Some(result) => {
+            if let Err(err) = self.collect(result) {
+              break err
+            }
+          },
+          None => pending().await,
+        }
+      }
+    };
+
+    select! {
+      output = work => Ok(output),
+      err = failure => Err(err),
+    }
+  }
+
+  /// Wait for all items to be applied, reporting the ones that failed
+  /// along with the respective error.
+  ///
+  /// Once an item fails the pipeline, all remaining ones are aborted.
+  pub async fn finish(mut self) -> Result<Vec<(K, E)>, E> {
+    while let Some(result) = self.tasks.join_next().await {
+      let () = self.collect(result)?;
+    }
+    Ok(self.failures)
+  }
+}
+
+
+#[derive(Debug, Default)]
+struct Processes {
+  /// The number of processes currently running.
+  running: usize,
+  /// The maximum number of processes to run concurrently, if a
+  /// resource limit was hit.
+  ceiling: Option<usize>,
+}
+
+
+/// A token accounting for a process spawned through a [`Governor`]
+/// until it gets dropped, which should happen once the process exited.
+#[derive(Debug)]
+pub struct Running<'gov> {
+  governor: &'gov Governor,
+}
+
+impl Drop for Running<'_> {
+  fn drop(&mut self) {
+    let mut processes = self.governor.processes();
+    processes.running = processes.running.saturating_sub(1);
+  }
+}
+
+
+/// A governor for the spawning of processes, honoring hard limits on
+/// the number of processes, such as `ulimit -u` or the pid limit of a
+/// cgroup.
+///
+/// Once process creation fails because such a limit was hit, the
+/// number of processes spawned through the governor that may run
+/// concurrently is reduced to the number running at that moment, and
+/// the spawn is retried once one of them finished. Spawning only fails
+/// if the limit is hit while none of them is running.
+#[derive(Debug, Default)]
+pub struct Governor {
+  processes: Mutex<Processes>,
+}
+
+impl Governor {
+  fn processes(&self) -> std::sync::MutexGuard<'_, Processes> {
+    self
+      .processes
+      .lock()
+      .unwrap_or_else(PoisonError::into_inner)
+  }
+
+  /// Retrieve the maximum number of processes run concurrently, if it
+  /// got reduced because of a resource limit.
+  pub fn ceiling(&self) -> Option<usize> {
+    self.processes().ceiling
+  }
+
+  /// Spawn `command`, waiting for other processes to finish as
+  /// necessary.
+  pub async fn spawn(&self, command: &mut Command) -> io::Result<(Child, Running<'_>)> {
+    let mut attempts = 0;
+    let mut delay = SPAWN_POLL;
+
+    loop {
+      {
+        let mut processes = self.processes();
+        if processes
+          .ceiling
+          .is_none_or(|ceiling| processes.running < ceiling)
+        {
+          match command.spawn() {
+            Ok(child) => {
+              processes.running += 1;
+              return Ok((child, Running { governor: self }))
+            },
+            // `EAGAIN` is reported when the number of processes is
+            // exhausted.
+            Err(err) if err.kind() == ErrorKind::WouldBlock => {
+              if processes.running == 0 {
+                attempts += 1;
+                if attempts >= SPAWN_ATTEMPTS {
+                  return Err(err)
+                }
+              } else {
+                processes.ceiling = Some(processes.running);
+              }
+            },
+            Err(err) => return Err(err),
+          }
+        }
+      }
+
+      // Back off exponentially while none of our processes is
+      // running, as other processes are holding up resources then.
+      let () = sleep(if attempts > 0 { delay } else { SPAWN_POLL }).await;
+      if attempts > 0 {
+        delay = delay.saturating_mul(2);
+      }
+    }
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::sync::atomic::AtomicBool;
+  use std::sync::atomic::Ordering;
+
+  use tokio::task::yield_now;
+  use tokio::time::timeout;
+
+
+  /// Sets a flag once dropped, e.g., because the task owning it got
+  /// cancelled.
+  struct Guard(Arc<AtomicBool>);
+
+  impl Drop for Guard {
+    fn drop(&mut self) {
+      let () = self.0.store(true, Ordering::SeqCst);
+    }
+  }
+
+
+  /// Create an error for the item `idx`.
+  fn error(idx: usize) -> io::Error {
+    io::Error::other(format!("item {idx} failed"))
+  }
+
+  /// Wait for `flag` to be set, failing after a while.
+  async fn wait_for(flag: &AtomicBool) {
+    let () = timeout(Duration::from_secs(10), async {
+      while !flag.load(Ordering::SeqCst) {
+        let () = yield_now().await;
+      }
+    })
+    .await
+    .unwrap();
+  }
+
+
+  /// Check that a failing task gets reported while other work is in
+  /// progress.
+  #[tokio::test]
+  async fn task_group_failure() {
+    let mut group = TaskGroup::<(), io::Error>::new();
+    let () = group.spawn(async { Ok(()) });
+    let () = group.spawn(async { Err(error(1)) });
+    let () = group.spawn(pending());
+
+    let err = select! {
+      err = group.failure() => err,
+      () = pending() => unreachable!(),
+    };
+    assert_eq!(err.to_string(), "item 1 failed");
+    assert_eq!(group.len(), 1);
+  }
+
+  /// Check that joining a task group cancels remaining tasks once one
+  /// failed.
+  #[tokio::test]
+  async fn task_group_join_all_cancels() {
+    let cancelled = Arc::new(AtomicBool::new(false));
+    let mut group = TaskGroup::<(), io::Error>::new();
+    let guard = Guard(cancelled.clone());
+    let () = group.spawn(async move {
+      let _guard = guard;
+      pending().await
+    });
+    let () = group.spawn(async { Err(error(1)) });
+
+    let err = group.join_all().await.unwrap_err();
+    assert_eq!(err.to_string(), "item 1 failed");
+    let () = wait_for(&cancelled).await;
+  }
+
+  /// Check that dropping a task group cancels all of its tasks.
+  #[tokio::test]
+  async fn task_group_drop_cancels() {
+    let cancelled = Arc::new(AtomicBool::new(false));
+    let mut group = TaskGroup::<(), io::Error>::new();
+    let guard = Guard(cancelled.clone());
+    let () = group.spawn(async move {
+      let _guard = guard;
+      pending().await
+    });
+
+    let () = drop(group);
+    let () = wait_for(&cancelled).await;
+  }
+
+  /// Check that a task panicking counts as failing.
+  #[tokio::test]
+  async fn task_group_panic() {
+    let mut group = TaskGroup::<(), io::Error>::new();
+    let () = group.spawn(async { panic!("oops") });
+    assert!(group.join_all().await.is_err());
+  }
+
+  /// Check that a pipeline with a limit of one applies each item before
+  /// accepting the next.
+  #[tokio::test]
+  async fn pipeline_sequential() {
+    let mut pipeline = Pipeline::<usize, io::Error>::new(1, false);
+    for idx in 0..3 {
+      let applied = Arc::new(AtomicBool::new(false));
+      let flag = applied.clone();
+      let () = pipeline
+        .push(idx, async move {
+          let () = yield_now().await;
+          let () = flag.store(true, Ordering::SeqCst);
+          Ok(Ok(()))
+        })
+        .await
+        .unwrap();
+      assert!(applied.load(Ordering::SeqCst));
+    }
+    assert!(pipeline.finish().await.unwrap().is_empty());
+  }
+
+  /// Check that a pipeline keeping going collects failed items.
+  #[tokio::test]
+  async fn pipeline_keep_going() {
+    let mut pipeline = Pipeline::<usize, io::Error>::new(2, true);
+    for idx in 0..4 {
+      let () = pipeline
+        .push(idx, async move {
+          if idx % 2 == 1 {
+            Ok(Err(error(idx)))
+          } else {
+            Ok(Ok(()))
+          }
+        })
+        .await
+        .unwrap();
+    }
+
+    let output = pipeline.race(async { 42 }).await.unwrap();
+    assert_eq!(output, 42);
+
+    let mut failures = pipeline.finish().await.unwrap();
+    let () = failures.sort_by_key(|(idx, _err)| *idx);
+    let failures = failures
+      .into_iter()
+      .map(|(idx, err)| (idx, err.to_string()))
+      .collect::<Vec<_>>();
+    assert_eq!(failures, vec![
+      (1, "item 1 failed".to_string()),
+      (3, "item 3 failed".to_string()),
+    ]);
+  }
+
+  /// Check that an item failing the pipeline interrupts work raced
+  /// against it.
+  #[tokio::test]
+  async fn pipeline_failure_interrupts() {
+    let mut pipeline = Pipeline::<usize, io::Error>::new(4, false);
+    let () = pipeline.push(1, async { Ok(Err(error(1))) }).await.unwrap();
+
+    let err = pipeline.race(pending::<()>()).await.unwrap_err();
+    assert_eq!(err.to_string(), "item 1 failed");
+  }
+
+  /// Check that an outer failure fails the pipeline even if it keeps
+  /// going, cancelling the items still in flight.
+  #[tokio::test]
+  async fn pipeline_fatal_failure() {
+    let cancelled = Arc::new(AtomicBool::new(false));
+    let mut pipeline = Pipeline::<usize, io::Error>::new(4, true);
+    let guard = Guard(cancelled.clone());
+    let () = pipeline
+      .push(1, async move {
+        let _guard = guard;
+        pending().await
+      })
+      .await
+      .unwrap();
+    let () = pipeline.push(2, async { Err(error(2)) }).await.unwrap();
+
+    let err = pipeline.finish().await.unwrap_err();
+    assert_eq!(err.to_string(), "item 2 failed");
+    let () = wait_for(&cancelled).await;
+  }
+}
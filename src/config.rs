@@ -0,0 +1,440 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Directory-local defaults for renaming files.
+//!
+//! Defaults are read from a file called `.batch-renamer.toml` in the
+//! current directory or, if there is none, the closest of its ancestors
+//! containing one, akin to how `.editorconfig` files are discovered.
+//! The file is written in a subset of TOML. Supported keys are:
+//! - `command`: the rename command to use if none is provided, as a
+//!   string or as an array of the command and its arguments
+//! - `protocol`: the protocol adhered to by said command
+//! - `include`: glob patterns of files to include during recursive
+//!   traversal
+//! - `exclude`: glob patterns of files and directories to exclude
+//!   during recursive traversal
+//! - `theme`: the color theme to use for highlighting output
+//!
+//! In addition, a `[functions]` table may define functions callable
+//! from templates, each mapping a name to the template snippet the
+//! function expands to, as in `shortdate = "{mtime:%y%m%d}"`.
+//!
+//! Tables called `[presets.<name>]` define named presets, selected on
+//! the command line via `--preset <name>`. Supported keys are:
+//! - `command`: the rename command to use, as above
+//! - `protocol`: the protocol adhered to by said command
+//! - `on-conflict`: the policy for dealing with existing destinations
+//! - `flags`: an array of additional command line flags, such as
+//!   `["--recursive", "--jobs=4"]`
+//! - `schedule`: a cron-style expression, as in `"0 3 * * *"`, for
+//!   running the preset periodically by means of the units generated
+//!   via `--service`
+//!
+//! A preset called `archive` is built in, unless configured otherwise:
+//! it moves files last modified more than 30 days ago below the
+//! traversed directories into an `archive` hierarchy organized by year
+//! and month of modification, prefixing their names with the date of
+//! modification, as in `archive/2023/07/2023-07-14_notes.txt`. Being a
+//! preset, its traversal and age threshold can be adjusted on the
+//! command line, as in `--preset archive --older-than 90d`.
+//!
+//! Tables called `[jellyfin]` and `[plex]` configure media servers,
+//! whose libraries get refreshed for the directories touched once
+//! renames got applied. Supported keys are:
+//! - `url`: the base URL of the server, as in `"http://localhost:8096"`
+//! - `token`: the API key or access token to authenticate with
+//!
+//! A user-wide configuration file, `batch-renamer/config.toml` in the
+//! user's configuration directory, is consulted as well. Settings in
+//! directory-local files take precedence over those in it.
+
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Result as FmtResult;
+use std::path::Path;
+use std::path::PathBuf;
+
+use tokio::fs::read_to_string;
+use tokio::fs::try_exists;
+
+use crate::conflict::ConflictPolicy;
+use crate::error::Context as _;
+use crate::error::Error;
+use crate::error::Result;
+use crate::schedule::Schedule;
+use crate::toml::parse_header;
+use crate::toml::parse_pair;
+use crate::toml::strip_comment;
+use crate::toml::Value;
+use crate::Protocol;
+
+
+/// The name of the file containing directory-local defaults.
+pub const FILE: &str = ".batch-renamer.toml";
+
+/// The path of the user-wide configuration file, relative to the
+/// user's configuration directory.
+const USER_FILE: &str = "batch-renamer/config.toml";
+
+
+/// The table of a configuration a line belongs to.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+enum Table {
+  /// The top-level table, containing defaults.
+  #[default]
+  Root,
+  /// The `[functions]` table.
+  Functions,
+  /// A `[presets.<name>]` table, with the preset's name.
+  Preset(String),
+  /// The table of a media server.
+  MediaServer(MediaServerKind),
+}
+
+
+/// A named set of options, selected via `--preset`.
+#[derive(Clone, Debug, Default)]
+pub struct Preset {
+  /// The rename command, along with its arguments.
+  pub command: Vec<OsString>,
+  /// The protocol adhered to by the rename command.
+  pub protocol: Option<Protocol>,
+  /// The policy for dealing with destinations that exist already.
+  pub on_conflict: Option<ConflictPolicy>,
+  /// Additional command line flags.
+  pub flags: Vec<String>,
+  /// The schedule for running the preset periodically.
+  pub schedule: Option<Schedule>,
+}
+
+impl Preset {
+  /// Retrieve the presets built into the program, by name.
+  ///
+  /// Configured presets of the same name take precedence over them.
+  pub fn builtin() -> BTreeMap<String, Self> {
+    let archive = Self {
+      flags: [
+        "--recursive",
+        "--older-than=30d",
+        "--exclude=archive",
+        "--template=archive/{mtime:%Y}/{mtime:%m}/{mtime:%Y-%m-%d}_{name}",
+      ]
+      .map(String::from)
+      .to_vec(),
+      ..Default::default()
+    };
+    BTreeMap::from([("archive".to_string(), archive)])
+  }
+
+  fn set(&mut self, key: &str, value: Value) -> Result<()> {
+    match (key, value) {
+      ("command", Value::String(command)) => self.command = vec![OsString::from(command)],
+      ("command", Value::Array(command)) => {
+        self.command = command.into_iter().map(OsString::from).collect()
+      },
+      ("protocol", Value::String(protocol)) => self.protocol = Some(protocol.parse()?),
+      ("on-conflict", Value::String(policy)) => self.on_conflict = Some(policy.parse()?),
+      ("flags", Value::Array(flags)) => self.flags = flags,
+      ("schedule", Value::String(schedule)) => self.schedule = Some(schedule.parse()?),
+      ("command", _) => {
+        return Err(Error::Invalid(format!(
+          "invalid value for `{key}`; expected a string or array"
+        )))
+      },
+      ("protocol" | "on-conflict" | "schedule", _) => {
+        return Err(Error::Invalid(format!(
+          "invalid value for `{key}`; expected a string"
+        )))
+      },
+      ("flags", _) => {
+        return Err(Error::Invalid(format!(
+          "invalid value for `{key}`; expected an array"
+        )))
+      },
+      (key, _) => return Err(Error::Invalid(format!("unsupported key `{key}`"))),
+    }
+    Ok(())
+  }
+}
+
+
+/// The kind of a media server.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum MediaServerKind {
+  /// A Jellyfin server.
+  Jellyfin,
+  /// A Plex Media Server.
+  Plex,
+}
+
+impl Display for MediaServerKind {
+  fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+    match self {
+      Self::Jellyfin => f.write_str("jellyfin"),
+      Self::Plex => f.write_str("plex"),
+    }
+  }
+}
+
+
+/// A media server whose libraries to refresh for the directories
+/// touched by renames.
+#[derive(Clone, Debug)]
+pub struct MediaServer {
+  /// The kind of server.
+  pub kind: MediaServerKind,
+  /// The base URL of the server.
+  pub url: String,
+  /// The API key or access token to authenticate with.
+  pub token: String,
+}
+
+impl MediaServer {
+  fn set(&mut self, key: &str, value: Value) -> Result<()> {
+    match (key, value) {
+      ("url", Value::String(url)) => self.url = url.trim_end_matches('/').to_string(),
+      ("token", Value::String(token)) => self.token = token,
+      ("url" | "token", _) => {
+        return Err(Error::Invalid(format!(
+          "invalid value for `{key}`; expected a string"
+        )))
+      },
+      (key, _) => return Err(Error::Invalid(format!("unsupported key `{key}`"))),
+    }
+    Ok(())
+  }
+}
+
+
+/// Defaults applying to a directory tree.
+#[derive(Clone, Debug, Default)]
+pub struct Config {
+  /// The path of the file the configuration got loaded from.
+  pub path: PathBuf,
+  /// The default rename command, along with its arguments.
+  pub command: Vec<OsString>,
+  /// The protocol adhered to by the default rename command.
+  pub protocol: Option<Protocol>,
+  /// Glob patterns of files to include during recursive traversal.
+  pub include: Vec<String>,
+  /// Glob patterns of files and directories to exclude during recursive
+  /// traversal.
+  pub exclude: Vec<String>,
+  /// The name of the color theme to use for highlighting output.
+  pub theme: Option<String>,
+  /// Functions callable from templates, mapping their names to the
+  /// template snippets they expand to.
+  pub functions: BTreeMap<String, String>,
+  /// Named presets, selected via `--preset`.
+  pub presets: BTreeMap<String, Preset>,
+  /// The media servers whose libraries to refresh.
+  pub media_servers: Vec<MediaServer>,
+}
+
+impl Config {
+  fn set(&mut self, key: &str, value: Value) -> Result<()> {
+    match (key, value) {
+      ("command", Value::String(command)) => self.command = vec![OsString::from(command)],
+      ("command", Value::Array(command)) => {
+        self.command = command.into_iter().map(OsString::from).collect()
+      },
+      ("protocol", Value::String(protocol)) => self.protocol = Some(protocol.parse()?),
+      ("include", Value::Array(include)) => self.include = include,
+      ("exclude", Value::Array(exclude)) => self.exclude = exclude,
+      ("theme", Value::String(theme)) => self.theme = Some(theme),
+      ("command", _) => {
+        return Err(Error::Invalid(format!(
+          "invalid value for `{key}`; expected a string or array"
+        )))
+      },
+      ("protocol" | "theme", _) => {
+        return Err(Error::Invalid(format!(
+          "invalid value for `{key}`; expected a string"
+        )))
+      },
+      ("include" | "exclude", _) => {
+        return Err(Error::Invalid(format!(
+          "invalid value for `{key}`; expected an array"
+        )))
+      },
+      (key, _) => return Err(Error::Invalid(format!("unsupported key `{key}`"))),
+    }
+    Ok(())
+  }
+
+  fn set_function(&mut self, name: &str, value: Value) -> Result<()> {
+    if !name.starts_with(|c: char| c.is_ascii_alphabetic()) || name.contains('-') {
+      return Err(Error::Invalid(format!(
+        "invalid function name `{name}`; expected a letter followed by letters, digits, or `_`"
+      )))
+    }
+    match value {
+      Value::String(body) => {
+        let _prev = self.functions.insert(name.to_string(), body);
+      },
+      _ => {
+        return Err(Error::Invalid(format!(
+          "invalid value for function `{name}`; expected a string"
+        )))
+      },
+    }
+    Ok(())
+  }
+
+  /// Parse a single line of a configuration, with `table` being the
+  /// table being defined.
+  fn parse_line(&mut self, line: &str, table: &mut Table) -> Result<()> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+      return Ok(())
+    }
+
+    if let Some(header) = line.strip_prefix('[') {
+      let (header, rest) = header.rsplit_once(']').context("unterminated table name")?;
+      let _rest = strip_comment(rest)?;
+      let name = parse_header(header)?;
+      *table = if name == "functions" {
+        Table::Functions
+      } else if let Some(preset) = name.strip_prefix("presets.") {
+        if preset.is_empty()
+          || !preset
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+          return Err(Error::Invalid(format!("invalid preset name `{preset}`")))
+        }
+        let _prev = self.presets.insert(preset.to_string(), Preset::default());
+        Table::Preset(preset.to_string())
+      } else {
+        let kind = match name.as_str() {
+          "jellyfin" => MediaServerKind::Jellyfin,
+          "plex" => MediaServerKind::Plex,
+          _ => return Err(Error::Invalid(format!("unsupported table `{name}`"))),
+        };
+        if self.media_servers.iter().any(|server| server.kind == kind) {
+          return Err(Error::Invalid(format!(
+            "table `{name}` is defined more than once"
+          )))
+        }
+        let () = self.media_servers.push(MediaServer {
+          kind,
+          url: String::new(),
+          token: String::new(),
+        });
+        Table::MediaServer(kind)
+      };
+    } else {
+      let (key, value) = parse_pair(line)?;
+      let () = match table {
+        Table::Root => self.set(&key, value)?,
+        Table::Functions => self.set_function(&key, value)?,
+        // SANITY: The preset got inserted when its table started.
+        Table::Preset(name) => self.presets.get_mut(name).unwrap().set(&key, value)?,
+        // SANITY: The server got inserted when its table started.
+        Table::MediaServer(kind) => self
+          .media_servers
+          .iter_mut()
+          .find(|server| server.kind == *kind)
+          .unwrap()
+          .set(&key, value)?,
+      };
+    }
+    Ok(())
+  }
+
+  /// Parse the configuration in `content`, as stored at `path`.
+  pub fn parse(content: &str, path: &Path) -> Result<Self> {
+    let mut config = Self {
+      path: path.to_path_buf(),
+      ..Default::default()
+    };
+
+    let mut table = Table::Root;
+    for (idx, line) in content.lines().enumerate() {
+      let () = config
+        .parse_line(line, &mut table)
+        .with_context(|| format!("invalid configuration in line {}", idx + 1))?;
+    }
+    for server in &config.media_servers {
+      let kind = server.kind;
+      if server.url.is_empty() {
+        return Err(Error::Invalid(format!("table `{kind}` lacks a `url`")))
+      }
+      if server.token.is_empty() {
+        return Err(Error::Invalid(format!("table `{kind}` lacks a `token`")))
+      }
+    }
+    Ok(config)
+  }
+
+  /// Load the configuration from the file at `path`.
+  pub async fn load(path: &Path) -> Result<Self> {
+    let content = read_to_string(path)
+      .await
+      .with_context(|| format!("failed to read configuration `{}`", path.display()))?;
+    Self::parse(&content, path)
+      .with_context(|| format!("failed to parse configuration `{}`", path.display()))
+  }
+
+  /// Load the configuration applying to `dir`, stored in it or the
+  /// closest of its ancestors, if any.
+  pub async fn discover(dir: &Path) -> Result<Option<Self>> {
+    for dir in dir.ancestors() {
+      let path = dir.join(FILE);
+      if try_exists(&path)
+        .await
+        .with_context(|| format!("failed to check existence of `{}`", path.display()))?
+      {
+        return Self::load(&path).await.map(Some)
+      }
+    }
+    Ok(None)
+  }
+
+  /// Load the user-wide configuration, if there is one.
+  pub async fn load_user() -> Result<Option<Self>> {
+    let Some(dir) = dirs::config_dir() else {
+      return Ok(None)
+    };
+    let path = dir.join(USER_FILE);
+    if try_exists(&path)
+      .await
+      .with_context(|| format!("failed to check existence of `{}`", path.display()))?
+    {
+      Self::load(&path).await.map(Some)
+    } else {
+      Ok(None)
+    }
+  }
+
+  /// Fill in the settings not present in this configuration from
+  /// `fallback`, merging functions and presets.
+  pub fn with_fallback(mut self, fallback: Self) -> Self {
+    if self.command.is_empty() {
+      self.command = fallback.command;
+      self.protocol = fallback.protocol;
+    }
+    if self.include.is_empty() {
+      self.include = fallback.include;
+    }
+    if self.exclude.is_empty() {
+      self.exclude = fallback.exclude;
+    }
+    if self.theme.is_none() {
+      self.theme = fallback.theme;
+    }
+    for (name, body) in fallback.functions {
+      let _body = self.functions.entry(name).or_insert(body);
+    }
+    for (name, preset) in fallback.presets {
+      let _preset = self.presets.entry(name).or_insert(preset);
+    }
+    if self.media_servers.is_empty() {
+      self.media_servers = fallback.media_servers;
+    }
+    self
+  }
+}
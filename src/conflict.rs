@@ -0,0 +1,648 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Handling of renames whose destination exists already.
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::ffi::OsString;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Result as FmtResult;
+use std::fs::File;
+use std::fs::Metadata;
+use std::io::BufRead as _;
+use std::io::BufReader;
+use std::io::ErrorKind;
+use std::path::Path;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::SystemTime;
+
+#[cfg(not(unix))]
+use tokio::fs::canonicalize;
+use tokio::fs::remove_file;
+use tokio::fs::symlink_metadata;
+use tokio::task::spawn_blocking;
+
+use tracing::warn;
+
+use crate::error::Context as _;
+use crate::error::Error;
+use crate::error::Result;
+use crate::os::rename_noreplace;
+use crate::rename_to;
+use crate::temp_path;
+
+
+/// A policy for dealing with renames whose destination exists already.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum ConflictPolicy {
+  /// Fail the rename.
+  #[default]
+  Abort,
+  /// Leave the file alone.
+  Skip,
+  /// Replace the existing file.
+  Overwrite,
+  /// Append a numbered suffix, as in `photo (1).jpg`, to the file name
+  /// until it no longer conflicts.
+  Suffix,
+}
+
+impl FromStr for ConflictPolicy {
+  type Err = Error;
+
+  fn from_str(s: &str) -> Result<Self> {
+    match s {
+      "abort" => Ok(Self::Abort),
+      "skip" => Ok(Self::Skip),
+      "overwrite" => Ok(Self::Overwrite),
+      "suffix" => Ok(Self::Suffix),
+      _ => Err(Error::Invalid(format!(
+        "unsupported conflict policy `{s}`; expected one of `abort`, `skip`, `overwrite`, or `suffix`"
+      ))),
+    }
+  }
+}
+
+impl Display for ConflictPolicy {
+  fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+    let s = match self {
+      Self::Abort => "abort",
+      Self::Skip => "skip",
+      Self::Overwrite => "overwrite",
+      Self::Suffix => "suffix",
+    };
+    f.write_str(s)
+  }
+}
+
+
+/// Check whether a file exists at `path`, without following symbolic
+/// links.
+async fn exists(path: &Path) -> Result<bool> {
+  match symlink_metadata(path).await {
+    Ok(_metadata) => Ok(true),
+    Err(err) if err.kind() == ErrorKind::NotFound => Ok(false),
+    Err(err) => Err(err).with_context(|| format!("failed to check for `{}`", path.display())),
+  }
+}
+
+
+/// Check whether `a` and `b` refer to the same file, without following
+/// symbolic links.
+#[cfg(unix)]
+async fn same_file(a: &Path, b: &Path) -> bool {
+  use std::os::unix::fs::MetadataExt as _;
+
+  match (symlink_metadata(a).await, symlink_metadata(b).await) {
+    (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+    _ => false,
+  }
+}
+
+/// Check whether `a` and `b` refer to the same file.
+#[cfg(not(unix))]
+async fn same_file(a: &Path, b: &Path) -> bool {
+  match (canonicalize(a).await, canonicalize(b).await) {
+    (Ok(a), Ok(b)) => a == b,
+    _ => false,
+  }
+}
+
+
+/// Check whether renaming `src` to `dst` would conflict with an
+/// existing file.
+///
+/// A destination referring to `src` itself, as can be the case when
+/// merely changing the case of a name on a case-insensitive file
+/// system, is not considered a conflict.
+async fn conflicts(src: &Path, dst: &Path) -> Result<bool> {
+  if !exists(dst).await? {
+    return Ok(false)
+  }
+  Ok(!same_file(src, dst).await)
+}
+
+
+/// Details about a file involved in a conflict.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct Details {
+  /// The size of the file, in bytes.
+  pub size: u64,
+  /// The time the file was last modified, if available.
+  pub modified: Option<SystemTime>,
+}
+
+impl Details {
+  fn new(metadata: &Metadata) -> Self {
+    Self {
+      size: metadata.len(),
+      modified: metadata.modified().ok(),
+    }
+  }
+}
+
+
+/// Check whether the files at `a` and `b` have the same contents.
+///
+/// This function blocks.
+fn same_contents(a: &Path, b: &Path) -> Result<bool> {
+  let open = |path: &Path| {
+    File::open(path)
+      .map(BufReader::new)
+      .with_context(|| format!("failed to open `{}`", path.display()))
+  };
+  let (mut a_file, mut b_file) = (open(a)?, open(b)?);
+
+  loop {
+    let a_buf = a_file
+      .fill_buf()
+      .with_context(|| format!("failed to read `{}`", a.display()))?;
+    let b_buf = b_file
+      .fill_buf()
+      .with_context(|| format!("failed to read `{}`", b.display()))?;
+    let len = a_buf.len().min(b_buf.len());
+    if len == 0 {
+      break Ok(a_buf.is_empty() && b_buf.is_empty())
+    }
+    if a_buf[..len] != b_buf[..len] {
+      break Ok(false)
+    }
+    let () = a_file.consume(len);
+    let () = b_file.consume(len);
+  }
+}
+
+
+/// A conflict of renaming a file onto an existing one.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct Conflict {
+  /// Details about the file to rename.
+  pub src: Details,
+  /// Details about the existing file.
+  pub dst: Details,
+  /// Whether both files are regular files with identical contents.
+  pub identical: bool,
+}
+
+impl Conflict {
+  /// Inspect the conflict of renaming `src` to `dst`, if there is one.
+  pub async fn inspect(src: &Path, dst: &Path) -> Result<Option<Self>> {
+    if !conflicts(src, dst).await? {
+      return Ok(None)
+    }
+
+    let context = |path: &Path| format!("failed to retrieve metadata of `{}`", path.display());
+    let src_meta = symlink_metadata(src).await.with_context(|| context(src))?;
+    let dst_meta = symlink_metadata(dst).await.with_context(|| context(dst))?;
+
+    let comparable = src_meta.is_file() && dst_meta.is_file();
+    let identical = if comparable && src_meta.len() == dst_meta.len() {
+      let (a, b) = (src.to_path_buf(), dst.to_path_buf());
+      spawn_blocking(move || same_contents(&a, &b))
+        .await
+        .context("comparison task failed")??
+    } else {
+      false
+    };
+
+    let slf = Self {
+      src: Details::new(&src_meta),
+      dst: Details::new(&dst_meta),
+      identical,
+    };
+    Ok(Some(slf))
+  }
+}
+
+
+/// Derive the `n`th numbered variant of `path`, as in `photo (n).jpg`.
+fn numbered(path: &Path, n: usize) -> Result<PathBuf> {
+  let stem = path
+    .file_stem()
+    .with_context(|| format!("path `{}` does not have file name", path.display()))?;
+  let mut name = OsString::from(stem);
+  let () = name.push(format!(" ({n})"));
+  if let Some(ext) = path.extension() {
+    let () = name.push(".");
+    let () = name.push(ext);
+  }
+  Ok(path.with_file_name(name))
+}
+
+
+/// Find the first numbered variant of `dst` that neither exists nor is
+/// part of `taken`.
+async fn free_name(dst: &Path, taken: &HashSet<PathBuf>) -> Result<PathBuf> {
+  for n in 1.. {
+    let path = numbered(dst, n)?;
+    if !taken.contains(&path) && !exists(&path).await? {
+      return Ok(path)
+    }
+  }
+  unreachable!()
+}
+
+
+/// A file moved out of the way of a rename overwriting it.
+///
+/// The file is kept at a hidden, temporary name next to its original
+/// path until the rename taking its place either succeeded, upon which
+/// it gets removed, or did not, upon which it gets restored. A
+/// displaced file dropped without being settled is restored on a
+/// best-effort basis.
+#[derive(Debug)]
+pub struct Displaced {
+  /// The original path of the file.
+  path: PathBuf,
+  /// The temporary path of the file, until settled.
+  tmp: Option<PathBuf>,
+}
+
+impl Displaced {
+  /// Retrieve the original path of the file.
+  #[inline]
+  pub fn path(&self) -> &Path {
+    &self.path
+  }
+
+  /// Settle the fate of the file, removing it if `replaced` is set, as
+  /// the rename taking its place succeeded, and restoring it
+  /// otherwise.
+  ///
+  /// Failures are reported as warnings, as they don't affect the
+  /// outcome of the rename, and leave the file at its temporary path.
+  pub async fn settle(mut self, replaced: bool) {
+    // SANITY: The temporary path is only taken when settling, which
+    //         consumes the object.
+    let tmp = self.tmp.take().unwrap();
+    if replaced {
+      if let Err(err) = remove_file(&tmp).await {
+        warn!(
+          "failed to remove `{}`, replaced at `{}`: {err}",
+          tmp.display(),
+          self.path.display()
+        );
+      }
+    } else if let Err(err) = rename_to(&tmp, &self.path).await {
+      warn!(
+        "failed to restore `{}` from `{}`: {err:#}",
+        self.path.display(),
+        tmp.display()
+      );
+    }
+  }
+}
+
+impl Drop for Displaced {
+  fn drop(&mut self) {
+    if let Some(tmp) = self.tmp.take() {
+      if let Err(err) = rename_noreplace(&tmp, &self.path) {
+        warn!(
+          "failed to restore `{}` from `{}`: {err}",
+          self.path.display(),
+          tmp.display()
+        );
+      }
+    }
+  }
+}
+
+
+/// Move the file in the way of renaming `src` to `dst` out of the way,
+/// for the rename to overwrite it.
+///
+/// Nothing is moved if there is no conflict, in which case `None` is
+/// returned. Directories are never overwritten and reported as
+/// [`Error::Exists`].
+pub async fn displace(src: &Path, dst: &Path) -> Result<Option<Displaced>> {
+  if !conflicts(src, dst).await? {
+    return Ok(None)
+  }
+
+  let metadata = symlink_metadata(dst)
+    .await
+    .with_context(|| format!("failed to retrieve metadata for `{}`", dst.display()))?;
+  if metadata.is_dir() {
+    return Err(Error::Exists {
+      src: src.to_path_buf(),
+      dst: dst.to_path_buf(),
+    })
+  }
+
+  let tmp = temp_path(dst, "overwrite")?;
+  let () = rename_to(dst, &tmp).await?;
+  let displaced = Displaced {
+    path: dst.to_path_buf(),
+    tmp: Some(tmp),
+  };
+  Ok(Some(displaced))
+}
+
+
+/// Resolve a conflict of renaming `src` to `dst` according to `policy`.
+///
+/// Returned is the destination to rename `src` to, or `None` if the
+/// rename is to be skipped. With [`ConflictPolicy::Abort`] a conflict
+/// is reported as [`Error::Exists`]. With [`ConflictPolicy::Overwrite`]
+/// a conflicting file is left in place, for the rename to overwrite it
+/// by means of [`displace`] and only once it actually happens;
+/// directories are never overwritten.
+pub async fn resolve_conflict(
+  src: &Path,
+  dst: &Path,
+  policy: ConflictPolicy,
+) -> Result<Option<PathBuf>> {
+  resolve_conflict_impl(src, dst, policy, &HashSet::new()).await
+}
+
+async fn resolve_conflict_impl(
+  src: &Path,
+  dst: &Path,
+  policy: ConflictPolicy,
+  taken: &HashSet<PathBuf>,
+) -> Result<Option<PathBuf>> {
+  if !conflicts(src, dst).await? {
+    return Ok(Some(dst.to_path_buf()))
+  }
+
+  match policy {
+    ConflictPolicy::Abort => Err(Error::Exists {
+      src: src.to_path_buf(),
+      dst: dst.to_path_buf(),
+    }),
+    ConflictPolicy::Skip => Ok(None),
+    ConflictPolicy::Overwrite => {
+      let metadata = symlink_metadata(dst)
+        .await
+        .with_context(|| format!("failed to retrieve metadata for `{}`", dst.display()))?;
+      if metadata.is_dir() {
+        return Err(Error::Exists {
+          src: src.to_path_buf(),
+          dst: dst.to_path_buf(),
+        })
+      }
+      Ok(Some(dst.to_path_buf()))
+    },
+    ConflictPolicy::Suffix => free_name(dst, taken).await.map(Some),
+  }
+}
+
+
+/// Resolve conflicts of the renames in `renames` with existing files
+/// according to `policy`, or to the policy `overrides` maps a rename's
+/// source to, if any.
+///
+/// Destinations that are the source of another rename in `renames`
+/// are not considered conflicting, as that file is going to be moved
+/// away. Conflicts are resolved independently of each other. Returned
+/// is the outcome for each rename, in the order of `renames`, along
+/// with its source and original destination: the destination to
+/// rename to, adjusted as necessary, `None` if the rename is to be
+/// skipped, or the error preventing its conflict from being resolved.
+pub async fn resolve_conflicts(
+  renames: Vec<(PathBuf, PathBuf)>,
+  policy: ConflictPolicy,
+  overrides: &HashMap<PathBuf, ConflictPolicy>,
+) -> Vec<(PathBuf, PathBuf, Result<Option<PathBuf>>)> {
+  let sources = renames
+    .iter()
+    .map(|(src, _dst)| src.clone())
+    .collect::<HashSet<_>>();
+  let mut taken = renames
+    .iter()
+    .map(|(_src, dst)| dst.clone())
+    .collect::<HashSet<_>>();
+
+  let mut outcomes = Vec::with_capacity(renames.len());
+  for (src, dst) in renames {
+    let outcome = if sources.contains(&dst) {
+      Ok(Some(dst.clone()))
+    } else {
+      let policy = overrides.get(&src).copied().unwrap_or(policy);
+      let outcome = resolve_conflict_impl(&src, &dst, policy, &taken).await;
+      if let Ok(Some(path)) = &outcome {
+        let _inserted = taken.insert(path.clone());
+      }
+      outcome
+    };
+    let () = outcomes.push((src, dst, outcome));
+  }
+  outcomes
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::fs::create_dir;
+  use std::fs::read_dir;
+  use std::fs::read_to_string;
+  use std::fs::write;
+
+  use tempfile::tempdir;
+  use tempfile::TempDir;
+
+
+  /// Create a directory containing the files `files`, each with its
+  /// name as contents.
+  fn files<const N: usize>(files: [&str; N]) -> TempDir {
+    let dir = tempdir().unwrap();
+    for file in files {
+      let () = write(dir.path().join(file), file).unwrap();
+    }
+    dir
+  }
+
+  /// List the names of the entries of the directory `dir`, sorted.
+  fn entries(dir: &Path) -> Vec<String> {
+    let mut entries = read_dir(dir)
+      .unwrap()
+      .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+      .collect::<Vec<_>>();
+    let () = entries.sort();
+    entries
+  }
+
+
+  /// Check that numbered variants get derived as expected, including
+  /// for names without extension, dotfiles, and multiple extensions.
+  #[test]
+  fn numbered_variants() {
+    let cases = [
+      ("dir/photo.jpg", 1, "dir/photo (1).jpg"),
+      ("photo.jpg", 12, "photo (12).jpg"),
+      ("README", 2, "README (2)"),
+      (".profile", 1, ".profile (1)"),
+      (".config.toml", 1, ".config (1).toml"),
+      ("archive.tar.gz", 3, "archive.tar (3).gz"),
+    ];
+    for (path, n, expected) in cases {
+      assert_eq!(numbered(Path::new(path), n).unwrap(), Path::new(expected));
+    }
+    assert!(numbered(Path::new("/"), 1).is_err());
+  }
+
+  /// Check that the first free numbered variant gets picked, skipping
+  /// ones that exist on disk or are taken otherwise.
+  #[tokio::test]
+  async fn free_names() {
+    let dir = files(["photo.jpg", "photo (1).jpg", "photo (3).jpg"]);
+    let dst = dir.path().join("photo.jpg");
+
+    let free = free_name(&dst, &HashSet::new()).await.unwrap();
+    assert_eq!(free, dir.path().join("photo (2).jpg"));
+
+    let taken = HashSet::from([dir.path().join("photo (2).jpg")]);
+    let free = free_name(&dst, &taken).await.unwrap();
+    assert_eq!(free, dir.path().join("photo (4).jpg"));
+  }
+
+  /// Check that conflicts get resolved as per the policy in effect.
+  #[tokio::test]
+  async fn resolve_policies() {
+    let dir = files(["a.txt", "b.txt"]);
+    let src = dir.path().join("a.txt");
+    let dst = dir.path().join("b.txt");
+    let free = dir.path().join("c.txt");
+
+    for policy in [
+      ConflictPolicy::Abort,
+      ConflictPolicy::Skip,
+      ConflictPolicy::Overwrite,
+      ConflictPolicy::Suffix,
+    ] {
+      let resolved = resolve_conflict(&src, &free, policy).await.unwrap();
+      assert_eq!(resolved, Some(free.clone()), "{policy}");
+      let resolved = resolve_conflict(&src, &src, policy).await.unwrap();
+      assert_eq!(resolved, Some(src.clone()), "{policy}");
+    }
+
+    let err = resolve_conflict(&src, &dst, ConflictPolicy::Abort)
+      .await
+      .unwrap_err();
+    assert!(matches!(err, Error::Exists { .. }), "{err:?}");
+    let resolved = resolve_conflict(&src, &dst, ConflictPolicy::Skip)
+      .await
+      .unwrap();
+    assert_eq!(resolved, None);
+    let resolved = resolve_conflict(&src, &dst, ConflictPolicy::Overwrite)
+      .await
+      .unwrap();
+    assert_eq!(resolved, Some(dst.clone()));
+    let resolved = resolve_conflict(&src, &dst, ConflictPolicy::Suffix)
+      .await
+      .unwrap();
+    assert_eq!(resolved, Some(dir.path().join("b (1).txt")));
+  }
+
+  /// Check that overwriting a directory is refused.
+  #[tokio::test]
+  async fn overwrite_directory() {
+    let dir = files(["a.txt"]);
+    let src = dir.path().join("a.txt");
+    let dst = dir.path().join("dir");
+    let () = create_dir(&dst).unwrap();
+
+    let err = resolve_conflict(&src, &dst, ConflictPolicy::Overwrite)
+      .await
+      .unwrap_err();
+    assert!(matches!(err, Error::Exists { .. }), "{err:?}");
+
+    let err = displace(&src, &dst).await.unwrap_err();
+    assert!(matches!(err, Error::Exists { .. }), "{err:?}");
+    assert!(dst.is_dir());
+  }
+
+  /// Check that renames of a batch competing for the same numbered
+  /// variant each get a distinct one.
+  #[tokio::test]
+  async fn resolve_batch_suffixes() {
+    let dir = files(["a.jpg", "b.jpg", "c.jpg", "photo.jpg", "photo (1).jpg"]);
+    let path = |name: &str| dir.path().join(name);
+    let renames = vec![
+      (path("a.jpg"), path("photo.jpg")),
+      (path("b.jpg"), path("photo.jpg")),
+      (path("c.jpg"), path("photo (2).jpg")),
+    ];
+
+    let outcomes = resolve_conflicts(renames, ConflictPolicy::Suffix, &HashMap::new()).await;
+    let resolved = outcomes
+      .into_iter()
+      .map(|(_src, _dst, outcome)| outcome.unwrap().unwrap())
+      .collect::<Vec<_>>();
+    assert_eq!(resolved, vec![
+      path("photo (3).jpg"),
+      path("photo (4).jpg"),
+      path("photo (2).jpg"),
+    ]);
+  }
+
+  /// Check that destinations that are the source of another rename of
+  /// the batch don't count as conflicts, and that policies can be
+  /// overridden for individual renames.
+  #[tokio::test]
+  async fn resolve_batch_overrides() {
+    let dir = files(["a.txt", "b.txt", "c.txt", "d.txt"]);
+    let path = |name: &str| dir.path().join(name);
+    let renames = vec![
+      (path("a.txt"), path("b.txt")),
+      (path("b.txt"), path("x.txt")),
+      (path("c.txt"), path("d.txt")),
+      (path("d.txt"), path("x.txt")),
+    ];
+    let overrides = HashMap::from([(path("c.txt"), ConflictPolicy::Skip)]);
+
+    let outcomes = resolve_conflicts(renames, ConflictPolicy::Abort, &overrides).await;
+    let resolved = outcomes
+      .into_iter()
+      .map(|(_src, _dst, outcome)| outcome.unwrap())
+      .collect::<Vec<_>>();
+    assert_eq!(resolved, vec![
+      Some(path("b.txt")),
+      Some(path("x.txt")),
+      Some(path("d.txt")),
+      Some(path("x.txt")),
+    ]);
+
+    let renames = vec![(path("c.txt"), path("a.txt"))];
+    let outcomes = resolve_conflicts(renames, ConflictPolicy::Abort, &overrides).await;
+    assert!(matches!(outcomes[0].2, Ok(None)), "{outcomes:?}");
+  }
+
+  /// Check that a displaced file gets removed once replaced, and
+  /// restored otherwise.
+  #[tokio::test]
+  async fn settle_displaced() {
+    let dir = files(["a.txt", "b.txt"]);
+    let src = dir.path().join("a.txt");
+    let dst = dir.path().join("b.txt");
+
+    let displaced = displace(&src, &dst).await.unwrap().unwrap();
+    assert_eq!(displaced.path(), dst);
+    assert!(!dst.exists());
+    let () = displaced.settle(false).await;
+    assert_eq!(entries(dir.path()), vec!["a.txt", "b.txt"]);
+    assert_eq!(read_to_string(&dst).unwrap(), "b.txt");
+
+    let displaced = displace(&src, &dst).await.unwrap().unwrap();
+    drop(displaced);
+    assert_eq!(entries(dir.path()), vec!["a.txt", "b.txt"]);
+    assert_eq!(read_to_string(&dst).unwrap(), "b.txt");
+
+    let displaced = displace(&src, &dst).await.unwrap().unwrap();
+    let () = rename_to(&src, &dst).await.unwrap();
+    let () = displaced.settle(true).await;
+    assert_eq!(entries(dir.path()), vec!["b.txt"]);
+    assert_eq!(read_to_string(&dst).unwrap(), "a.txt");
+
+    let src = dir.path().join("b.txt");
+    let dst = dir.path().join("c.txt");
+    assert!(displace(&src, &dst).await.unwrap().is_none());
+  }
+}
@@ -0,0 +1,226 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Detection of dates and numbers written in locale specific formats,
+//! for bringing them into a normalized form.
+
+use chrono::NaiveDate;
+
+use crate::locale::Locale;
+
+
+/// The characters grouping the digits of numbers, in addition to `.`
+/// and `,`, which may separate the fractional part of a number as well.
+const GROUPING: [char; 3] = ['\'', '\u{a0}', '\u{202f}'];
+
+
+/// A number or word found in text.
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum Item<'s> {
+  /// A run of ASCII digits.
+  Number(&'s str),
+  /// A run of letters, in lower case.
+  Word(String),
+}
+
+
+/// Split `s` into numbers and words, each along with the text
+/// separating it from its predecessor.
+fn items<'s>(s: &'s str, locale: &Locale) -> Vec<(&'s str, Item<'s>)> {
+  let mut items = Vec::new();
+  let mut end = 0;
+  let mut chars = s.char_indices().peekable();
+  while let Some((start, c)) = chars.next() {
+    let digit = c.is_ascii_digit();
+    if !digit && !c.is_alphabetic() {
+      continue
+    }
+
+    let sep = &s[end..start];
+    end = start + c.len_utf8();
+    while let Some((idx, c)) = chars.next_if(|(_, c)| {
+      if digit {
+        c.is_ascii_digit()
+      } else {
+        c.is_alphabetic()
+      }
+    }) {
+      end = idx + c.len_utf8();
+    }
+
+    let text = &s[start..end];
+    let item = if digit {
+      Item::Number(text)
+    } else {
+      Item::Word(locale.to_lowercase(text))
+    };
+    let () = items.push((sep, item));
+  }
+  items
+}
+
+
+/// Check whether `sep` separates the components of a date spelled out
+/// with the name of its month, as in `14. März 2023` or `March 14,
+/// 2023`.
+fn is_delimiter(sep: &str) -> bool {
+  sep.chars().count() <= 2 && sep.chars().all(|c| " .,-_/".contains(c))
+}
+
+
+/// Parse a date made up of numbers only from the start of `items`, as
+/// in `2023-07-14`, `14.07.2023`, `07/14/2023`, or `20230714`.
+fn numeric(items: &[(&str, Item<'_>)], locale: &Locale) -> Option<NaiveDate> {
+  match items {
+    [(_, Item::Number(a)), (sep1, Item::Number(b)), (sep2, Item::Number(c)), ..]
+      if sep1 == sep2 && sep1.len() == 1 && "-_./ ".contains(*sep1) =>
+    {
+      if a.len() == 4 && b.len() <= 2 && c.len() <= 2 {
+        NaiveDate::from_ymd_opt(a.parse().ok()?, b.parse().ok()?, c.parse().ok()?)
+      } else if a.len() <= 2 && b.len() <= 2 && c.len() == 4 {
+        let (a, b) = (a.parse::<u32>().ok()?, b.parse::<u32>().ok()?);
+        // Numbers beyond twelve can't be months, settling the order.
+        // Otherwise dots indicate the day coming first, as they are
+        // not used with the month first. Only then does the locale
+        // decide.
+        let day_first = if a > 12 {
+          true
+        } else if b > 12 {
+          false
+        } else {
+          *sep1 == "." || locale.is_day_first()
+        };
+        let (day, month) = if day_first { (a, b) } else { (b, a) };
+        NaiveDate::from_ymd_opt(c.parse().ok()?, month, day)
+      } else {
+        None
+      }
+    },
+    [(_, Item::Number(digits)), ..] if digits.len() == 8 => {
+      NaiveDate::parse_from_str(digits, "%Y%m%d").ok()
+    },
+    _ => None,
+  }
+}
+
+
+/// Parse a date spelled out with the name of its month from the start
+/// of `items`, as in `14. März 2023`, `March 14, 2023`, or
+/// `2023-Mar-14`.
+fn named(items: &[(&str, Item<'_>)], months: &[(String, u32)]) -> Option<NaiveDate> {
+  let month = |word: &str| {
+    months
+      .iter()
+      .find(|(name, _)| name == word)
+      .map(|(_, month)| *month)
+  };
+
+  let [(_, first), (sep1, second), (sep2, third), ..] = items else {
+    return None
+  };
+  if !is_delimiter(sep1) || !is_delimiter(sep2) {
+    return None
+  }
+
+  let (year, month, day) = match (first, second, third) {
+    (Item::Number(year), Item::Word(word), Item::Number(day)) if year.len() == 4 => {
+      (year, month(word)?, day)
+    },
+    (Item::Number(day), Item::Word(word), Item::Number(year))
+    | (Item::Word(word), Item::Number(day), Item::Number(year)) => (year, month(word)?, day),
+    _ => return None,
+  };
+  if year.len() != 4 || day.len() > 2 {
+    return None
+  }
+  NaiveDate::from_ymd_opt(year.parse().ok()?, month, day.parse().ok()?)
+}
+
+
+/// Find the first date in `s`, written in one of the formats common
+/// to `locale` or to any of a few widely used languages.
+///
+/// Numeric dates with the year coming last are ambiguous, as in
+/// `03/04/2023`; the locale decides whether the day or the month
+/// comes first, unless dots separate the components, which indicates
+/// the day coming first.
+pub(crate) fn find_date(s: &str, locale: &Locale) -> Option<NaiveDate> {
+  let items = items(s, locale);
+  let months = locale.months();
+  (0..items.len()).find_map(|idx| {
+    let items = &items[idx..];
+    numeric(items, locale).or_else(|| named(items, &months))
+  })
+}
+
+
+/// Find the first number in `s` and normalize it, by removing the
+/// grouping of its digits and using `.` as decimal separator.
+///
+/// A number containing a single `.` or `,` followed by three digits is
+/// ambiguous, as in `1,234`; it is taken to be a decimal separator
+/// only if it is the decimal separator of `locale`.
+pub(crate) fn find_number(s: &str, locale: &Locale) -> Option<String> {
+  let start = s.find(|c: char| c.is_ascii_digit())?;
+  let mut rest = &s[start..];
+  let mut groups = Vec::new();
+  let mut sep = None;
+  loop {
+    let len = rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    let () = groups.push((sep, &rest[..len]));
+    rest = &rest[len..];
+
+    let mut chars = rest.chars();
+    match chars.next() {
+      Some(c)
+        if (c == '.' || c == ',' || GROUPING.contains(&c))
+          && chars.as_str().starts_with(|c: char| c.is_ascii_digit()) =>
+      {
+        sep = Some(c);
+        rest = chars.as_str();
+      },
+      _ => break,
+    }
+  }
+
+  let (fraction, integer) = match groups.split_last() {
+    Some(((Some(last), digits), others)) => {
+      let grouping = GROUPING.contains(last)
+        || others.iter().any(|(sep, _)| *sep == Some(*last))
+        || (others.len() == 1
+          && others[0].1.len() <= 3
+          && digits.len() == 3
+          && *last != locale.decimal_point());
+      if grouping {
+        (None, groups.as_slice())
+      } else {
+        (Some(*digits), others)
+      }
+    },
+    _ => (None, groups.as_slice()),
+  };
+
+  // SANITY: There always is at least one group, containing the first
+  //         digit.
+  let (first, others) = integer.split_first().unwrap();
+  let grouped = others.is_empty()
+    || (first.1.len() <= 3
+      && others
+        .iter()
+        .all(|(sep, digits)| *sep == others[0].0 && digits.len() == 3));
+  if !grouped {
+    // Separators not grouping digits in a consistent manner are not
+    // part of the number, as in `2023.07.14`.
+    return Some(first.1.to_string())
+  }
+
+  let mut number = integer
+    .iter()
+    .map(|(_, digits)| *digits)
+    .collect::<String>();
+  if let Some(fraction) = fraction {
+    let () = number.push('.');
+    let () = number.push_str(fraction);
+  }
+  Some(number)
+}
@@ -0,0 +1,288 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! The error type reported by the renaming core.
+
+use std::error::Error as StdError;
+use std::ffi::OsString;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Result as FmtResult;
+use std::io;
+use std::path::PathBuf;
+use std::result;
+use std::time::Duration;
+
+
+/// A result type using [`Error`] by default.
+pub type Result<T, E = Error> = result::Result<T, E>;
+
+
+/// An error reported by the renaming core.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+  /// A command could not be run.
+  Spawn {
+    /// The command, including its arguments.
+    command: String,
+    /// The underlying error.
+    source: io::Error,
+  },
+  /// A command exited unsuccessfully.
+  Command {
+    /// The command, including its arguments.
+    command: String,
+    /// The exit code of the command, or `None` if it got terminated by
+    /// a signal.
+    status: Option<i32>,
+    /// What the command printed to stderr.
+    stderr: String,
+  },
+  /// A command did not finish in time and got killed.
+  Timeout {
+    /// The command, including its arguments.
+    command: String,
+    /// The time the command was allowed to run for.
+    timeout: Duration,
+  },
+  /// A command printed something other than the new names of files.
+  Output {
+    /// The command, including its arguments.
+    command: String,
+    /// A description of what is wrong with the output.
+    message: String,
+  },
+  /// Renaming a file in a temporary directory left behind no file,
+  /// presumably because the command deleted it.
+  NoFile {
+    /// The name of the file.
+    name: OsString,
+  },
+  /// Renaming a file in a temporary directory produced several files.
+  MultipleFiles {
+    /// The name of the file.
+    name: OsString,
+    /// The files produced, relative to the directory containing the
+    /// file.
+    files: Vec<PathBuf>,
+  },
+  /// A path could not be canonicalized, typically because it does not
+  /// exist.
+  Canonicalize {
+    /// The path.
+    path: PathBuf,
+    /// The underlying error.
+    source: io::Error,
+  },
+  /// The destination of a rename exists already.
+  Exists {
+    /// The path of the file to rename.
+    src: PathBuf,
+    /// The path of the existing file.
+    dst: PathBuf,
+  },
+  /// A rename that is part of a transaction failed.
+  Transaction {
+    /// The path of the file whose rename failed.
+    src: PathBuf,
+    /// The destination of the failed rename.
+    dst: PathBuf,
+    /// The reason for the failure.
+    source: Box<Error>,
+    /// The error encountered while rolling back the renames performed
+    /// already, if any. Without one, the file system has been restored
+    /// to its original state.
+    rollback: Option<Box<Error>>,
+  },
+  /// An I/O operation failed.
+  Io {
+    /// A description of the operation.
+    context: String,
+    /// The underlying error.
+    source: io::Error,
+  },
+  /// The provided input, such as a path or command, is unsuitable.
+  Invalid(String),
+  /// An operation failed for a reason other than I/O, such as
+  /// malformed data.
+  Other {
+    /// A description of the operation.
+    context: String,
+    /// The underlying error.
+    source: Box<dyn StdError + Send + Sync>,
+  },
+}
+
+impl Display for Error {
+  /// Format the error.
+  ///
+  /// With the alternate flag (`{:#}`), the descriptions of all
+  /// underlying errors are included, separated by colons.
+  fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+    let () = match self {
+      Self::Spawn { command, .. } => write!(f, "failed to run `{command}`"),
+      Self::Command {
+        command,
+        status,
+        stderr,
+      } => {
+        let code = if let Some(code) = status {
+          format!(" ({code})")
+        } else {
+          " (terminated by signal)".to_string()
+        };
+        let stderr = stderr.trim_end();
+        let stderr = if !stderr.is_empty() {
+          format!(": {stderr}")
+        } else {
+          String::new()
+        };
+        write!(f, "`{command}` reported non-zero exit-status{code}{stderr}")
+      },
+      Self::Timeout { command, timeout } => write!(
+        f,
+        "`{command}` did not finish within {:.1}s and got killed",
+        timeout.as_secs_f64()
+      ),
+      Self::Output { command, message } => write!(f, "`{command}` {message}"),
+      Self::NoFile { name } => write!(
+        f,
+        "renaming `{}` produced no file; did the rename operation delete instead?",
+        name.to_string_lossy()
+      ),
+      Self::MultipleFiles { name, files } => {
+        let list = files
+          .iter()
+          .map(|file| format!("`{}`", file.display()))
+          .collect::<Vec<_>>()
+          .join(", ");
+        write!(
+          f,
+          "renaming `{}` produced {} files instead of one: {list}",
+          name.to_string_lossy(),
+          files.len()
+        )
+      },
+      Self::Canonicalize { path, .. } => {
+        write!(f, "failed to canonicalize `{}`", path.display())
+      },
+      Self::Exists { src, dst } => write!(
+        f,
+        "failed to rename `{}` to `{}`: destination exists",
+        src.display(),
+        dst.display()
+      ),
+      Self::Transaction {
+        src,
+        dst,
+        rollback: None,
+        ..
+      } => write!(
+        f,
+        "failed to rename `{}` to `{}`; all renames got rolled back",
+        src.display(),
+        dst.display()
+      ),
+      Self::Transaction {
+        src,
+        dst,
+        rollback: Some(rollback),
+        ..
+      } => {
+        let () = write!(
+          f,
+          "failed to rename `{}` to `{}` and to roll back renames: {rollback}",
+          src.display(),
+          dst.display()
+        )?;
+        if let Some(source) = rollback.source() {
+          let () = write!(f, ": {source}")?;
+        }
+        Ok(())
+      },
+      Self::Io { context, .. } | Self::Other { context, .. } => f.write_str(context),
+      Self::Invalid(message) => f.write_str(message),
+    }?;
+
+    if f.alternate() {
+      let mut source = self.source();
+      while let Some(err) = source {
+        let () = write!(f, ": {err}")?;
+        source = err.source();
+      }
+    }
+    Ok(())
+  }
+}
+
+impl StdError for Error {
+  fn source(&self) -> Option<&(dyn StdError + 'static)> {
+    match self {
+      Self::Spawn { source, .. } | Self::Canonicalize { source, .. } | Self::Io { source, .. } => {
+        Some(source)
+      },
+      Self::Transaction { source, .. } => Some(source.as_ref()),
+      Self::Other { source, .. } => Some(source.as_ref()),
+      Self::Command { .. }
+      | Self::Timeout { .. }
+      | Self::Output { .. }
+      | Self::NoFile { .. }
+      | Self::MultipleFiles { .. }
+      | Self::Exists { .. }
+      | Self::Invalid(..) => None,
+    }
+  }
+}
+
+
+/// An extension trait for attaching context to failures, turning them
+/// into [`Error`] objects.
+pub(crate) trait Context<T> {
+  /// Attach the context produced by `f`.
+  fn with_context<F, S>(self, f: F) -> Result<T>
+  where
+    F: FnOnce() -> S,
+    S: Into<String>;
+
+  /// Attach the given context.
+  fn context<S>(self, context: S) -> Result<T>
+  where
+    Self: Sized,
+    S: Into<String>,
+  {
+    self.with_context(|| context)
+  }
+}
+
+impl<T, E> Context<T> for Result<T, E>
+where
+  E: StdError + Send + Sync + 'static,
+{
+  fn with_context<F, S>(self, f: F) -> Result<T>
+  where
+    F: FnOnce() -> S,
+    S: Into<String>,
+  {
+    self.map_err(|err| {
+      let context = f().into();
+      match Box::<dyn StdError + Send + Sync>::from(err).downcast::<io::Error>() {
+        Ok(source) => Error::Io {
+          context,
+          source: *source,
+        },
+        Err(source) => Error::Other { context, source },
+      }
+    })
+  }
+}
+
+impl<T> Context<T> for Option<T> {
+  fn with_context<F, S>(self, f: F) -> Result<T>
+  where
+    F: FnOnce() -> S,
+    S: Into<String>,
+  {
+    self.ok_or_else(|| Error::Invalid(f().into()))
+  }
+}
@@ -0,0 +1,526 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Integer arithmetic over numeric template values.
+
+use std::ops::Range;
+use std::str::FromStr;
+
+use crate::error::Context as _;
+use crate::error::Error;
+use crate::error::Result;
+
+
+/// The maximum nesting depth of parentheses and negations in an
+/// expression.
+const MAX_DEPTH: usize = 64;
+
+
+/// A syntax error in (part of) a template.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct SyntaxError {
+  /// The byte range of the offending input.
+  pub span: Range<usize>,
+  /// A description of the problem.
+  pub message: String,
+  /// Descriptions of the input that would have been accepted instead.
+  pub expected: Vec<String>,
+}
+
+impl SyntaxError {
+  pub(crate) fn new<M>(span: Range<usize>, message: M) -> Self
+  where
+    M: Into<String>,
+  {
+    Self {
+      span,
+      message: message.into(),
+      expected: Vec::new(),
+    }
+  }
+
+  /// Set the descriptions of the input that would have been accepted.
+  pub(crate) fn expected<I, S>(mut self, expected: I) -> Self
+  where
+    I: IntoIterator<Item = S>,
+    S: Into<String>,
+  {
+    self.expected = expected.into_iter().map(S::into).collect();
+    self
+  }
+
+  /// Move the span of the error by `offset` bytes.
+  pub(crate) fn shift(mut self, offset: usize) -> Self {
+    self.span = self.span.start + offset..self.span.end + offset;
+    self
+  }
+}
+
+
+/// A value an expression may refer to.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum Operand {
+  /// The batch-wide counter.
+  Counter,
+  /// The size of the file, in bytes.
+  Size,
+  /// The number at the given (one-based) position in the file stem.
+  Number(usize),
+}
+
+impl Operand {
+  /// The descriptions of all operands.
+  const EXPECTED: [&str; 3] = ["`counter`", "`size`", "`f<N>`"];
+
+  fn parse(name: &str) -> Option<Self> {
+    let operand = match name {
+      "counter" => Self::Counter,
+      "size" => Self::Size,
+      name => match name.strip_prefix('f').map(usize::from_str) {
+        Some(Ok(n)) if n > 0 => Self::Number(n),
+        _ => return None,
+      },
+    };
+    Some(operand)
+  }
+}
+
+
+/// An arithmetic operator.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum Op {
+  Add,
+  Sub,
+  Mul,
+  Div,
+  Rem,
+}
+
+impl Op {
+  fn apply(&self, lhs: i64, rhs: i64) -> Result<i64> {
+    let result = match self {
+      Self::Add => lhs.checked_add(rhs),
+      Self::Sub => lhs.checked_sub(rhs),
+      Self::Mul => lhs.checked_mul(rhs),
+      Self::Div | Self::Rem => {
+        if rhs == 0 {
+          return Err(Error::Invalid("division by zero".to_string()))
+        }
+        if *self == Self::Div {
+          lhs.checked_div(rhs)
+        } else {
+          lhs.checked_rem(rhs)
+        }
+      },
+    };
+    result.context("arithmetic overflow")
+  }
+}
+
+
+/// A lexical element of an expression.
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum Lexeme<'s> {
+  Number(i64),
+  Name(&'s str),
+  Punct(char),
+}
+
+/// Split `s` into its lexical elements, along with their byte ranges.
+fn lex(s: &str) -> Result<Vec<(Range<usize>, Lexeme<'_>)>, SyntaxError> {
+  let is_word = |c: char| c.is_ascii_alphanumeric() || c == '_';
+  let mut lexemes = Vec::new();
+  let mut chars = s.char_indices().peekable();
+
+  while let Some((start, c)) = chars.next() {
+    let mut end = start + c.len_utf8();
+    let lexeme = match c {
+      c if c.is_whitespace() => continue,
+      '+' | '-' | '*' | '/' | '%' | '(' | ')' => Lexeme::Punct(c),
+      c if is_word(c) => {
+        while let Some((idx, c)) = chars.next_if(|(_, c)| is_word(*c)) {
+          end = idx + c.len_utf8();
+        }
+        let word = &s[start..end];
+        if c.is_ascii_digit() {
+          let number = word.parse().map_err(|_| {
+            SyntaxError::new(start..end, format!("invalid number `{word}`"))
+              .expected(["a 64 bit signed integer"])
+          })?;
+          Lexeme::Number(number)
+        } else {
+          Lexeme::Name(word)
+        }
+      },
+      c => {
+        return Err(
+          SyntaxError::new(start..end, format!("unexpected character `{c}`")).expected([
+            "a number",
+            "a value",
+            "an operator",
+            "`(`",
+            "`)`",
+          ]),
+        )
+      },
+    };
+    let () = lexemes.push((start..end, lexeme));
+  }
+  Ok(lexemes)
+}
+
+
+/// A recursive descent parser for expressions.
+struct Parser<'l, 's> {
+  lexemes: &'l [(Range<usize>, Lexeme<'s>)],
+  /// The length of the input, in bytes.
+  len: usize,
+  /// The current nesting depth.
+  depth: usize,
+}
+
+impl Parser<'_, '_> {
+  /// Consume the next lexeme if it is one of the operators in `ops`.
+  fn op(&mut self, ops: &[(char, Op)]) -> Option<Op> {
+    let ((_, Lexeme::Punct(c)), rest) = self.lexemes.split_first()? else {
+      return None
+    };
+    let (_, op) = ops.iter().find(|(op, _)| op == c)?;
+    self.lexemes = rest;
+    Some(*op)
+  }
+
+  fn expr(&mut self) -> Result<Expr, SyntaxError> {
+    let mut lhs = self.term()?;
+    while let Some(op) = self.op(&[('+', Op::Add), ('-', Op::Sub)]) {
+      let rhs = self.term()?;
+      lhs = Expr::Binary(Box::new(lhs), op, Box::new(rhs));
+    }
+    Ok(lhs)
+  }
+
+  fn term(&mut self) -> Result<Expr, SyntaxError> {
+    let mut lhs = self.factor()?;
+    while let Some(op) = self.op(&[('*', Op::Mul), ('/', Op::Div), ('%', Op::Rem)]) {
+      let rhs = self.factor()?;
+      lhs = Expr::Binary(Box::new(lhs), op, Box::new(rhs));
+    }
+    Ok(lhs)
+  }
+
+  /// Parse a factor nested inside the current one.
+  fn nested<F>(&mut self, span: &Range<usize>, f: F) -> Result<Expr, SyntaxError>
+  where
+    F: FnOnce(&mut Self) -> Result<Expr, SyntaxError>,
+  {
+    if self.depth >= MAX_DEPTH {
+      return Err(SyntaxError::new(
+        span.clone(),
+        "expression is nested too deeply",
+      ))
+    }
+    self.depth += 1;
+    let result = f(self);
+    self.depth -= 1;
+    result
+  }
+
+  fn factor(&mut self) -> Result<Expr, SyntaxError> {
+    let Some(((span, lexeme), rest)) = self.lexemes.split_first() else {
+      return Err(
+        SyntaxError::new(self.len..self.len, "unexpected end of expression")
+          .expected(["a number", "a value", "`(`"]),
+      )
+    };
+    self.lexemes = rest;
+
+    let expr = match lexeme {
+      Lexeme::Number(n) => Expr::Literal(*n),
+      Lexeme::Name(name) => Expr::Operand(Operand::parse(name).ok_or_else(|| {
+        SyntaxError::new(span.clone(), format!("unknown value `{name}`"))
+          .expected(Operand::EXPECTED)
+      })?),
+      Lexeme::Punct('-') => Expr::Neg(Box::new(self.nested(span, |slf| slf.factor())?)),
+      Lexeme::Punct('(') => {
+        let expr = self.nested(span, |slf| slf.expr())?;
+        match self.lexemes.split_first() {
+          Some(((_, Lexeme::Punct(')')), rest)) => self.lexemes = rest,
+          Some(((span, _), _)) => {
+            return Err(
+              SyntaxError::new(span.clone(), "missing `)`").expected(["an operator", "`)`"]),
+            )
+          },
+          None => {
+            return Err(
+              SyntaxError::new(self.len..self.len, "missing `)`").expected(["an operator", "`)`"]),
+            )
+          },
+        }
+        expr
+      },
+      Lexeme::Punct(c) => {
+        return Err(
+          SyntaxError::new(span.clone(), format!("unexpected `{c}`"))
+            .expected(["a number", "a value", "`(`"]),
+        )
+      },
+    };
+    Ok(expr)
+  }
+}
+
+
+/// An integer arithmetic expression.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) enum Expr {
+  Literal(i64),
+  Operand(Operand),
+  Neg(Box<Expr>),
+  Binary(Box<Expr>, Op, Box<Expr>),
+}
+
+impl Expr {
+  /// Check whether `s` is meant to be an expression, as opposed to
+  /// the name of a token.
+  pub(crate) fn is_expr(s: &str) -> bool {
+    s.contains(['+', '-', '*', '/', '%', '(', ')']) || Operand::parse(s.trim()).is_some()
+  }
+
+  /// Parse an expression from `s`.
+  pub(crate) fn parse(s: &str) -> Result<Self, SyntaxError> {
+    let lexemes = lex(s)?;
+    let mut parser = Parser {
+      lexemes: &lexemes,
+      len: s.len(),
+      depth: 0,
+    };
+    let expr = parser.expr()?;
+    if let Some((span, _)) = parser.lexemes.first() {
+      return Err(
+        SyntaxError::new(span.clone(), "unexpected trailing input").expected(["an operator"]),
+      )
+    }
+    Ok(expr)
+  }
+
+  /// Retrieve all operands referenced by the expression.
+  pub(crate) fn operands(&self) -> Vec<Operand> {
+    fn collect(expr: &Expr, operands: &mut Vec<Operand>) {
+      match expr {
+        Expr::Literal(_) => (),
+        Expr::Operand(operand) => {
+          if !operands.contains(operand) {
+            let () = operands.push(*operand);
+          }
+        },
+        Expr::Neg(expr) => collect(expr, operands),
+        Expr::Binary(lhs, _, rhs) => {
+          let () = collect(lhs, operands);
+          let () = collect(rhs, operands);
+        },
+      }
+    }
+
+    let mut operands = Vec::new();
+    let () = collect(self, &mut operands);
+    operands
+  }
+
+  /// Evaluate the expression, with `value` providing the values of
+  /// operands.
+  pub(crate) fn eval<F>(&self, value: &F) -> Result<i64>
+  where
+    F: Fn(Operand) -> i64,
+  {
+    match self {
+      Self::Literal(n) => Ok(*n),
+      Self::Operand(operand) => Ok(value(*operand)),
+      Self::Neg(expr) => expr
+        .eval(value)?
+        .checked_neg()
+        .context("arithmetic overflow"),
+      Self::Binary(lhs, op, rhs) => op.apply(lhs.eval(value)?, rhs.eval(value)?),
+    }
+  }
+}
+
+/// Find the number at the (one-based) position `n` among all runs of
+/// ASCII digits in `s`.
+pub(crate) fn number(s: &str, n: usize) -> Option<i64> {
+  s.split(|c: char| !c.is_ascii_digit())
+    .filter(|digits| !digits.is_empty())
+    .nth(n - 1)
+    .and_then(|digits| digits.parse().ok())
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+
+  /// Evaluate the expression `s`, with the counter being 7, the size
+  /// 100, and the n-th number of the stem being 10 times n.
+  fn eval(s: &str) -> Result<i64> {
+    let expr = Expr::parse(s).unwrap();
+    expr.eval(&|operand| match operand {
+      Operand::Counter => 7,
+      Operand::Size => 100,
+      Operand::Number(n) => 10 * n as i64,
+    })
+  }
+
+
+  /// Check that operators bind as in arithmetic.
+  #[test]
+  fn precedence() {
+    assert_eq!(eval("1 + 2 * 3").unwrap(), 7);
+    assert_eq!(eval("(1 + 2) * 3").unwrap(), 9);
+    assert_eq!(eval("10 - 4 - 3").unwrap(), 3);
+    assert_eq!(eval("100 / 10 / 5").unwrap(), 2);
+    assert_eq!(eval("7 + 10 % 4 * 2").unwrap(), 11);
+    assert_eq!(eval("counter * 2 + f2").unwrap(), 34);
+    assert_eq!(eval("size / (f1 - 5)").unwrap(), 20);
+  }
+
+  /// Check that negation binds tighter than any binary operator.
+  #[test]
+  fn unary_minus() {
+    assert_eq!(eval("-3").unwrap(), -3);
+    assert_eq!(eval("--3").unwrap(), 3);
+    assert_eq!(eval("-3 * 2").unwrap(), -6);
+    assert_eq!(eval("2 - -3").unwrap(), 5);
+    assert_eq!(eval("-(counter + 1)").unwrap(), -8);
+    assert_eq!(eval("-7 / 2").unwrap(), -3);
+    assert_eq!(eval("-7 % 2").unwrap(), -1);
+  }
+
+  /// Check that dividing by zero is reported as an error.
+  #[test]
+  fn division_by_zero() {
+    assert_eq!(eval("1 / 0").unwrap_err().to_string(), "division by zero");
+    assert_eq!(
+      eval("1 % (counter - 7)").unwrap_err().to_string(),
+      "division by zero"
+    );
+  }
+
+  /// Check that overflows are reported as errors instead of wrapping.
+  #[test]
+  fn overflow() {
+    let max = i64::MAX;
+    let overflow = "arithmetic overflow";
+    assert_eq!(
+      eval(&format!("{max} + 1")).unwrap_err().to_string(),
+      overflow
+    );
+    assert_eq!(
+      eval(&format!("-{max} - 2")).unwrap_err().to_string(),
+      overflow
+    );
+    assert_eq!(
+      eval(&format!("{max} * 2")).unwrap_err().to_string(),
+      overflow
+    );
+    assert_eq!(
+      eval(&format!("(-{max} - 1) / -1")).unwrap_err().to_string(),
+      overflow
+    );
+    assert_eq!(
+      eval(&format!("-(-{max} - 1)")).unwrap_err().to_string(),
+      overflow
+    );
+    assert_eq!(eval(&format!("-{max} - 1")).unwrap(), i64::MIN);
+  }
+
+  /// Check that malformed expressions are rejected, pointing at the
+  /// offending input.
+  #[test]
+  fn malformed() {
+    let err = Expr::parse("1 +").unwrap_err();
+    assert_eq!(err.message, "unexpected end of expression");
+    assert_eq!(err.span, 3..3);
+
+    let err = Expr::parse("(1 + 2").unwrap_err();
+    assert_eq!(err.message, "missing `)`");
+    assert_eq!(err.span, 6..6);
+
+    let err = Expr::parse("(1 2)").unwrap_err();
+    assert_eq!(err.message, "missing `)`");
+    assert_eq!(err.span, 3..4);
+
+    let err = Expr::parse("1 + 2)").unwrap_err();
+    assert_eq!(err.message, "unexpected trailing input");
+    assert_eq!(err.span, 5..6);
+
+    let err = Expr::parse("* 2").unwrap_err();
+    assert_eq!(err.message, "unexpected `*`");
+    assert_eq!(err.span, 0..1);
+
+    let err = Expr::parse("1 $ 2").unwrap_err();
+    assert_eq!(err.message, "unexpected character `$`");
+    assert_eq!(err.span, 2..3);
+
+    let err = Expr::parse("count + 1").unwrap_err();
+    assert_eq!(err.message, "unknown value `count`");
+    assert_eq!(err.span, 0..5);
+
+    let err = Expr::parse("f0 + 1").unwrap_err();
+    assert_eq!(err.message, "unknown value `f0`");
+
+    let err = Expr::parse("99999999999999999999").unwrap_err();
+    assert_eq!(err.message, "invalid number `99999999999999999999`");
+
+    let err = Expr::parse("").unwrap_err();
+    assert_eq!(err.message, "unexpected end of expression");
+  }
+
+  /// Check that excessive nesting is rejected instead of exhausting the
+  /// stack.
+  #[test]
+  fn nesting() {
+    let nested = format!("{}1{}", "(".repeat(MAX_DEPTH), ")".repeat(MAX_DEPTH));
+    assert_eq!(eval(&nested).unwrap(), 1);
+
+    let nested = format!(
+      "{}1{}",
+      "(".repeat(MAX_DEPTH + 1),
+      ")".repeat(MAX_DEPTH + 1)
+    );
+    let err = Expr::parse(&nested).unwrap_err();
+    assert_eq!(err.message, "expression is nested too deeply");
+
+    let err = Expr::parse(&format!("{}1", "-".repeat(MAX_DEPTH + 1))).unwrap_err();
+    assert_eq!(err.message, "expression is nested too deeply");
+  }
+
+  /// Check that the operands of an expression are reported once each.
+  #[test]
+  fn operands() {
+    let expr = Expr::parse("counter + f2 * counter - size").unwrap();
+    assert_eq!(expr.operands(), vec![
+      Operand::Counter,
+      Operand::Number(2),
+      Operand::Size
+    ]);
+  }
+
+  /// Check that expressions are told apart from token names.
+  #[test]
+  fn is_expr() {
+    assert!(Expr::is_expr("counter + 1"));
+    assert!(Expr::is_expr("f1"));
+    assert!(Expr::is_expr(" size "));
+    assert!(!Expr::is_expr("stem"));
+    assert!(!Expr::is_expr("counter:03"));
+  }
+
+  /// Check that numbers get found in file stems by position.
+  #[test]
+  fn stem_numbers() {
+    assert_eq!(number("img_0042-v3", 1), Some(42));
+    assert_eq!(number("img_0042-v3", 2), Some(3));
+    assert_eq!(number("img_0042-v3", 3), None);
+    assert_eq!(number("photo", 1), None);
+  }
+}
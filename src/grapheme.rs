@@ -0,0 +1,231 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Segmentation of text into grapheme clusters, i.e., what users
+//! perceive as single characters.
+//!
+//! The segmentation follows the rules for extended grapheme clusters
+//! of Unicode Standard Annex #29, but classifies characters based on a
+//! condensed set of tables. It covers combining marks of the major
+//! scripts, conjoining Hangul jamo, emoji modifier and ZWJ sequences,
+//! as well as flags formed by pairs of regional indicators.
+
+
+/// The classes of characters relevant for finding cluster boundaries.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Class {
+  /// A carriage return.
+  Cr,
+  /// A line feed.
+  Lf,
+  /// Any other control character, which forms a cluster by itself.
+  Control,
+  /// A character extending the preceding one, such as a combining
+  /// mark, a variation selector, or an emoji modifier.
+  Extend,
+  /// The zero width joiner.
+  Zwj,
+  /// A regional indicator, two of which form a flag.
+  RegionalIndicator,
+  /// A leading Hangul jamo (consonant).
+  L,
+  /// A vowel Hangul jamo.
+  V,
+  /// A trailing Hangul jamo (consonant).
+  T,
+  /// A precomposed Hangul syllable without trailing consonant.
+  Lv,
+  /// A precomposed Hangul syllable with trailing consonant.
+  Lvt,
+  /// A pictographic character, such as an emoji.
+  Pictographic,
+  /// Any other character.
+  Other,
+}
+
+impl Class {
+  /// Classify `c`.
+  fn of(c: char) -> Self {
+    match c {
+      '\r' => Self::Cr,
+      '\n' => Self::Lf,
+      '\u{200d}' => Self::Zwj,
+      c if is_extend(c) => Self::Extend,
+      c if c.is_control() => Self::Control,
+      '\u{1f1e6}'..='\u{1f1ff}' => Self::RegionalIndicator,
+      '\u{1100}'..='\u{115f}' | '\u{a960}'..='\u{a97c}' => Self::L,
+      '\u{1160}'..='\u{11a7}' | '\u{d7b0}'..='\u{d7c6}' => Self::V,
+      '\u{11a8}'..='\u{11ff}' | '\u{d7cb}'..='\u{d7fb}' => Self::T,
+      '\u{ac00}'..='\u{d7a3}' => {
+        if (u32::from(c) - 0xac00) % 28 == 0 {
+          Self::Lv
+        } else {
+          Self::Lvt
+        }
+      },
+      c if is_pictographic(c) => Self::Pictographic,
+      _ => Self::Other,
+    }
+  }
+}
+
+
+/// Check whether `c` extends the character preceding it.
+fn is_extend(c: char) -> bool {
+  let c = u32::from(c);
+  match c {
+    // Indic scripts share the layout of their blocks to a large
+    // degree, including the positions of signs and vowel marks.
+    0x0980..=0x0d7f => matches!(
+      c & 0x7f,
+      0x01..=0x03 | 0x3c | 0x3e..=0x4d | 0x55..=0x57 | 0x62..=0x63
+    ),
+    _ => matches!(
+      c,
+      0x0300..=0x036f
+        | 0x0483..=0x0489
+        | 0x0591..=0x05bd
+        | 0x05bf
+        | 0x05c1..=0x05c2
+        | 0x05c4..=0x05c5
+        | 0x05c7
+        | 0x0610..=0x061a
+        | 0x064b..=0x065f
+        | 0x0670
+        | 0x06d6..=0x06dc
+        | 0x06df..=0x06e4
+        | 0x06e7..=0x06e8
+        | 0x06ea..=0x06ed
+        | 0x0711
+        | 0x0730..=0x074a
+        | 0x07a6..=0x07b0
+        | 0x07eb..=0x07f3
+        | 0x0900..=0x0903
+        | 0x093a..=0x093c
+        | 0x093e..=0x094f
+        | 0x0951..=0x0957
+        | 0x0962..=0x0963
+        | 0x0d81..=0x0d83
+        | 0x0dca
+        | 0x0dcf..=0x0ddf
+        | 0x0df2..=0x0df3
+        | 0x0e31
+        | 0x0e34..=0x0e3a
+        | 0x0e47..=0x0e4e
+        | 0x0eb1
+        | 0x0eb4..=0x0ebc
+        | 0x0ec8..=0x0ecd
+        | 0x0f71..=0x0f84
+        | 0x102b..=0x103e
+        | 0x1056..=0x1059
+        | 0x1ab0..=0x1aff
+        | 0x1dc0..=0x1dff
+        | 0x200c
+        | 0x20d0..=0x20ff
+        | 0x302a..=0x302f
+        | 0x3099..=0x309a
+        | 0xfe00..=0xfe0f
+        | 0xfe20..=0xfe2f
+        | 0xff9e..=0xff9f
+        | 0x1f3fb..=0x1f3ff
+        | 0xe0020..=0xe007f
+        | 0xe0100..=0xe01ef
+    ),
+  }
+}
+
+
+/// Check whether `c` is a pictographic character, such as an emoji.
+fn is_pictographic(c: char) -> bool {
+  matches!(
+    u32::from(c),
+    0x00a9
+      | 0x00ae
+      | 0x203c
+      | 0x2049
+      | 0x2122
+      | 0x2139
+      | 0x2194..=0x21aa
+      | 0x2300..=0x23ff
+      | 0x25aa..=0x25fe
+      | 0x2600..=0x27bf
+      | 0x2934..=0x2935
+      | 0x2b00..=0x2bff
+      | 0x3030
+      | 0x303d
+      | 0x3297
+      | 0x3299
+      | 0x1f000..=0x1faff
+  )
+}
+
+
+/// Check whether there is a cluster boundary between characters of the
+/// classes `prev` and `next`.
+///
+/// `flag` tells whether `prev` is a regional indicator not yet paired
+/// up and `emoji` whether `prev` concludes a pictographic character,
+/// possibly extended.
+fn is_boundary(prev: Class, next: Class, flag: bool, emoji: bool) -> bool {
+  match (prev, next) {
+    (Class::Cr, Class::Lf) => false,
+    (Class::Cr | Class::Lf | Class::Control, _) | (_, Class::Cr | Class::Lf | Class::Control) => {
+      true
+    },
+    (Class::L, Class::L | Class::V | Class::Lv | Class::Lvt)
+    | (Class::Lv | Class::V, Class::V | Class::T)
+    | (Class::Lvt | Class::T, Class::T) => false,
+    (_, Class::Extend | Class::Zwj) => false,
+    (Class::Zwj, Class::Pictographic) => !emoji,
+    (Class::RegionalIndicator, Class::RegionalIndicator) => !flag,
+    _ => true,
+  }
+}
+
+
+/// An iterator over the grapheme clusters of a string.
+///
+/// Created by [`graphemes`].
+#[derive(Clone, Debug)]
+pub struct Graphemes<'s> {
+  /// The part of the string not yet split off.
+  rest: &'s str,
+}
+
+impl<'s> Iterator for Graphemes<'s> {
+  type Item = &'s str;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    let mut chars = self.rest.char_indices();
+    let (_, first) = chars.next()?;
+    let mut prev = Class::of(first);
+    let mut flag = prev == Class::RegionalIndicator;
+    let mut emoji = prev == Class::Pictographic;
+    let mut end = self.rest.len();
+
+    for (idx, c) in chars {
+      let next = Class::of(c);
+      if is_boundary(prev, next, flag, emoji) {
+        end = idx;
+        break
+      }
+      flag = next == Class::RegionalIndicator && !flag;
+      emoji = match next {
+        Class::Pictographic => true,
+        Class::Extend | Class::Zwj => emoji,
+        _ => false,
+      };
+      prev = next;
+    }
+
+    let (cluster, rest) = self.rest.split_at(end);
+    self.rest = rest;
+    Some(cluster)
+  }
+}
+
+
+/// Split `s` into its grapheme clusters.
+pub fn graphemes(s: &str) -> Graphemes<'_> {
+  Graphemes { rest: s }
+}
@@ -0,0 +1,1268 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Persistent journaling of applied renames.
+//!
+//! Each invocation of the renaming logic constitutes a session. All
+//! renames performed as part of a session are recorded in a journal
+//! file, which can later be used to revert them.
+//!
+//! The journal doubles as a write-ahead log: the intent to perform an
+//! operation is recorded before the file system gets touched and the
+//! outcome afterwards. Should the process get interrupted in between,
+//! the session can be recovered deterministically.
+
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Result as FmtResult;
+use std::fs::create_dir_all;
+use std::fs::symlink_metadata;
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::io::Read as _;
+use std::io::Write as _;
+use std::mem::take;
+#[cfg(unix)]
+use std::os::unix::fs::MetadataExt as _;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process;
+use std::sync::Mutex;
+use std::sync::PoisonError;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use tokio::fs::read_dir;
+use tokio::fs::read_to_string;
+use tokio::fs::try_exists;
+use tokio::task::spawn_blocking;
+
+use tracing::warn;
+
+use crate::error::Context as _;
+use crate::error::Error;
+use crate::error::Result;
+use crate::exchange;
+use crate::os::special_file;
+use crate::rename_to;
+use crate::util::digest;
+use crate::util::serde_digest;
+use crate::util::serde_os_strings;
+use crate::util::serde_path;
+use crate::Log;
+
+
+/// The file extension used for journal files.
+const EXTENSION: &str = "jsonl";
+
+
+/// Retrieve the current time as seconds since the Unix epoch.
+fn now() -> u64 {
+  SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map(|duration| duration.as_secs())
+    .unwrap_or_default()
+}
+
+
+/// Retrieve the identity of the file at `path` (not following
+/// symbolic links), if it exists and the platform provides one.
+fn identity(path: &Path) -> Option<u64> {
+  #[cfg(unix)]
+  {
+    symlink_metadata(path).ok().map(|metadata| metadata.ino())
+  }
+  #[cfg(not(unix))]
+  {
+    let _path = path;
+    None
+  }
+}
+
+
+/// Check whether a file exists at `path`, not following symbolic links.
+fn exists(path: &Path) -> bool {
+  symlink_metadata(path).is_ok()
+}
+
+
+fn is_false(value: &bool) -> bool {
+  !value
+}
+
+
+/// Settle the most recent of the `pending` intents matching `f`.
+fn settle<F>(pending: &mut Vec<Intent>, f: F)
+where
+  F: Fn(&Intent) -> bool,
+{
+  if let Some(idx) = pending.iter().rposition(f) {
+    let _intent = pending.remove(idx);
+  }
+}
+
+
+/// Retrieve the default directory in which journals are stored.
+pub fn default_dir() -> Result<PathBuf> {
+  let dir = dirs::data_dir()
+    .context("failed to determine user data directory")?
+    .join("batch-renamer")
+    .join("journal");
+  Ok(dir)
+}
+
+
+/// A single rename as recorded in the journal.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct Entry {
+  /// The path of the file before the rename.
+  #[serde(with = "serde_path")]
+  pub src: PathBuf,
+  /// The path of the file after the rename.
+  #[serde(with = "serde_path")]
+  pub dst: PathBuf,
+  /// The digest of the contents of the file at `dst` after the
+  /// operation, if recorded.
+  #[serde(
+    default,
+    skip_serializing_if = "Option::is_none",
+    with = "serde_digest"
+  )]
+  pub digest: Option<u64>,
+  /// The digest of the contents of the file at `src` after the
+  /// operation, if recorded. Only exchanges leave a file at `src`.
+  #[serde(
+    default,
+    skip_serializing_if = "Option::is_none",
+    with = "serde_digest"
+  )]
+  pub src_digest: Option<u64>,
+  /// A note provided by the user, e.g., explaining why the rename got
+  /// modified or declined.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub note: Option<String>,
+}
+
+
+/// An operation about to be applied, as recorded ahead of touching the
+/// file system.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct Intent {
+  /// The path of the file before the operation.
+  #[serde(with = "serde_path")]
+  pub src: PathBuf,
+  /// The path of the file after the operation.
+  #[serde(with = "serde_path")]
+  pub dst: PathBuf,
+  /// The identity (inode number) of the file at `src` ahead of the
+  /// operation, if available.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub ino: Option<u64>,
+  /// Whether the names of `src` and `dst` are to be exchanged.
+  #[serde(default, skip_serializing_if = "is_false")]
+  pub exchange: bool,
+}
+
+impl Intent {
+  /// Determine whether the operation got applied, based on where the
+  /// file it concerns resides now.
+  fn applied(&self) -> Result<bool> {
+    let Self {
+      src,
+      dst,
+      ino,
+      exchange,
+    } = self;
+
+    match ino {
+      Some(ino) if identity(dst) == Some(*ino) => Ok(true),
+      Some(ino) if identity(src) == Some(*ino) => Ok(false),
+      Some(..) => Err(Error::Invalid(format!(
+        "`{}` is neither at its original path nor at `{}`",
+        src.display(),
+        dst.display()
+      ))),
+      None if *exchange => Err(Error::Invalid(format!(
+        "unable to tell whether `{}` and `{}` got exchanged",
+        src.display(),
+        dst.display()
+      ))),
+      None => match (exists(src), exists(dst)) {
+        (true, false) => Ok(false),
+        (false, true) => Ok(true),
+        _ => Err(Error::Invalid(format!(
+          "unable to tell whether `{}` got renamed to `{}`",
+          src.display(),
+          dst.display()
+        ))),
+      },
+    }
+  }
+}
+
+
+/// A file system supporting snapshots.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileSystem {
+  /// Btrfs, whose snapshots are subvolumes identified by their path.
+  Btrfs,
+  /// ZFS, whose snapshots are identified by their name, as in
+  /// `pool/dataset@snapshot`.
+  Zfs,
+}
+
+impl Display for FileSystem {
+  fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+    match self {
+      Self::Btrfs => f.write_str("Btrfs"),
+      Self::Zfs => f.write_str("ZFS"),
+    }
+  }
+}
+
+
+/// A snapshot of a file system, taken before applying the operations
+/// of a session.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct Snapshot {
+  /// The file system the snapshot got taken of.
+  pub fs: FileSystem,
+  /// The identifier of the snapshot: its path for Btrfs and its name
+  /// for ZFS.
+  pub id: String,
+}
+
+impl Display for Snapshot {
+  fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+    write!(f, "{} snapshot `{}`", self.fs, self.id)
+  }
+}
+
+
+/// The outcome of verifying a file renamed as part of a session.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Verification {
+  /// The file is unchanged since the rename.
+  Intact,
+  /// The contents of the file changed.
+  Modified,
+  /// The file no longer exists.
+  Missing,
+}
+
+
+/// A discrepancy between the file system and the outcome of a session.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Drift {
+  /// A file the session left at the path is no longer present.
+  Missing,
+  /// The path got vacated by the session but is occupied again.
+  Occupied,
+}
+
+
+/// An operation applied as part of a session.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Operation {
+  /// A file got renamed.
+  Rename(Entry),
+  /// The names of two files got exchanged.
+  Exchange(Entry),
+  /// A proposed rename got declined by the user. The file remained at
+  /// `src`.
+  Declined(Entry),
+}
+
+
+/// A record as stored in a journal file.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Record {
+  /// The header of a session.
+  Session {
+    #[serde(with = "serde_os_strings")]
+    command: Vec<OsString>,
+    time: u64,
+  },
+  /// A rename that got applied.
+  Rename(Entry),
+  /// An exchange of the names of two files that got applied.
+  Exchange(Entry),
+  /// A proposed rename that got declined.
+  Declined(Entry),
+  /// An operation about to be applied.
+  Intent(Intent),
+  /// An operation that got announced but not applied.
+  Abandoned {
+    #[serde(with = "serde_path")]
+    src: PathBuf,
+    #[serde(with = "serde_path")]
+    dst: PathBuf,
+  },
+  /// A marker indicating that the session got undone.
+  Undone { time: u64 },
+  /// A snapshot taken before applying operations.
+  Snapshot(Snapshot),
+}
+
+
+/// Append a record to a journal file.
+fn append(file: &mut File, record: &Record) -> Result<()> {
+  let mut line = serde_json::to_vec(record).context("failed to serialize journal record")?;
+  let () = line.push(b'\n');
+  let () = file
+    .write_all(&line)
+    .context("failed to write journal record")?;
+  let () = file.sync_data().context("failed to sync journal")?;
+  Ok(())
+}
+
+
+/// Open the existing journal at `path` for appending records.
+///
+/// The remains of a record that got torn, e.g., because of a crash
+/// while writing it, are cut off first, so that records appended are
+/// not mangled with them.
+fn reopen(path: &Path) -> Result<File> {
+  let mut file = OpenOptions::new()
+    .read(true)
+    .append(true)
+    .open(path)
+    .with_context(|| format!("failed to open journal `{}`", path.display()))?;
+  let mut content = Vec::new();
+  let _count = file
+    .read_to_end(&mut content)
+    .with_context(|| format!("failed to read journal `{}`", path.display()))?;
+  if !content.is_empty() && !content.ends_with(b"\n") {
+    let len = content
+      .iter()
+      .rposition(|b| *b == b'\n')
+      .map_or(0, |pos| pos + 1);
+    let () = file
+      .set_len(len as u64)
+      .with_context(|| format!("failed to truncate journal `{}`", path.display()))?;
+  }
+  Ok(file)
+}
+
+
+/// A journal recording the renames of a single session.
+#[derive(Debug)]
+pub struct Journal {
+  /// The path to the journal file.
+  path: PathBuf,
+  /// The ID of the session.
+  id: String,
+  /// The command used for renaming.
+  command: Vec<OsString>,
+  /// Whether to record digests of the contents of renamed files.
+  checksums: bool,
+  /// The journal file, opened lazily on first use.
+  file: Option<File>,
+}
+
+impl Journal {
+  /// Create a new journal for a session, to be stored in `dir`.
+  ///
+  /// The journal file is only created once the first rename is
+  /// recorded, meaning that sessions without any renames do not leave
+  /// a trace.
+  pub fn new(dir: &Path, command: &[OsString]) -> Self {
+    let time = now();
+    let id = format!("{time}-{}", process::id());
+    let path = dir.join(&id).with_extension(EXTENSION);
+
+    Self {
+      path,
+      id,
+      command: command.to_vec(),
+      checksums: false,
+      file: None,
+    }
+  }
+
+  /// Set whether to record digests of the contents of renamed files,
+  /// allowing for later verification of their integrity.
+  pub fn with_checksums(mut self, checksums: bool) -> Self {
+    self.checksums = checksums;
+    self
+  }
+
+  /// Calculate the digest of the file at `path`, for recording it
+  /// along with a rename, if `journal` records checksums.
+  ///
+  /// The file is read on a blocking thread, without `journal` being
+  /// locked in the meantime.
+  pub async fn digest(journal: &Mutex<Self>, path: &Path) -> Result<Option<u64>> {
+    let checksums = journal
+      .lock()
+      .unwrap_or_else(PoisonError::into_inner)
+      .checksums;
+    // Special files have no contents to speak of.
+    if !checksums || special_file(path).is_some() {
+      return Ok(None)
+    }
+
+    let path = path.to_path_buf();
+    let digest = spawn_blocking(move || digest(&path))
+      .await
+      .context("digest task failed")??;
+    Ok(Some(digest))
+  }
+
+  fn file(&mut self) -> Result<&mut File> {
+    if self.file.is_none() {
+      if let Some(dir) = self.path.parent() {
+        let () = create_dir_all(dir)
+          .with_context(|| format!("failed to create directory `{}`", dir.display()))?;
+      }
+
+      let mut file = OpenOptions::new()
+        .create_new(true)
+        .append(true)
+        .open(&self.path)
+        .with_context(|| format!("failed to create journal `{}`", self.path.display()))?;
+      let header = Record::Session {
+        command: self.command.clone(),
+        time: now(),
+      };
+      let () = append(&mut file, &header)?;
+      self.file = Some(file);
+    }
+
+    // SANITY: The file is guaranteed to be present at this point.
+    Ok(self.file.as_mut().unwrap())
+  }
+
+  /// Record a rename of `src` to `dst`, along with the `digest` of
+  /// the contents of `dst`, as calculated by [`Journal::digest`].
+  pub fn record(&mut self, src: &Path, dst: &Path, digest: Option<u64>) -> Result<()> {
+    self.record_annotated(src, dst, digest, None)
+  }
+
+  /// Record a rename of `src` to `dst`, along with the `digest` of
+  /// the contents of `dst` and a note provided by the user.
+  pub fn record_annotated(
+    &mut self,
+    src: &Path,
+    dst: &Path,
+    digest: Option<u64>,
+    note: Option<&str>,
+  ) -> Result<()> {
+    let record = Record::Rename(Entry {
+      src: src.to_path_buf(),
+      dst: dst.to_path_buf(),
+      digest,
+      src_digest: None,
+      note: note.map(str::to_string),
+    });
+    append(self.file()?, &record)
+  }
+
+  /// Record that the proposed rename of `src` to `dst` got declined,
+  /// along with a note provided by the user.
+  ///
+  /// Declined renames are only recorded for the sake of their notes;
+  /// they play no role in undoing or verifying the session.
+  pub fn record_declined(&mut self, src: &Path, dst: &Path, note: &str) -> Result<()> {
+    let record = Record::Declined(Entry {
+      src: src.to_path_buf(),
+      dst: dst.to_path_buf(),
+      digest: None,
+      src_digest: None,
+      note: Some(note.to_string()),
+    });
+    append(self.file()?, &record)
+  }
+
+  /// Record that `src` is about to be renamed to `dst`.
+  ///
+  /// The intent is settled by recording the rename (or an exchange of
+  /// the two) or by abandoning it.
+  pub fn intend(&mut self, src: &Path, dst: &Path) -> Result<()> {
+    let record = Record::Intent(Intent {
+      src: src.to_path_buf(),
+      dst: dst.to_path_buf(),
+      ino: identity(src),
+      exchange: false,
+    });
+    append(self.file()?, &record)
+  }
+
+  /// Record that the names of `src` and `dst` are about to be
+  /// exchanged.
+  pub fn intend_exchange(&mut self, src: &Path, dst: &Path) -> Result<()> {
+    let record = Record::Intent(Intent {
+      src: src.to_path_buf(),
+      dst: dst.to_path_buf(),
+      ino: identity(src),
+      exchange: true,
+    });
+    append(self.file()?, &record)
+  }
+
+  /// Record that the previously announced operation on `src` and `dst`
+  /// did not get applied.
+  pub fn abandon(&mut self, src: &Path, dst: &Path) -> Result<()> {
+    let record = Record::Abandoned {
+      src: src.to_path_buf(),
+      dst: dst.to_path_buf(),
+    };
+    append(self.file()?, &record)
+  }
+
+  /// Record an exchange of the names of `src` and `dst`, along with
+  /// the digests of the contents of both, as calculated by
+  /// [`Journal::digest`].
+  pub fn record_exchange(
+    &mut self,
+    src: &Path,
+    dst: &Path,
+    digest: Option<u64>,
+    src_digest: Option<u64>,
+  ) -> Result<()> {
+    let record = Record::Exchange(Entry {
+      src: src.to_path_buf(),
+      dst: dst.to_path_buf(),
+      digest,
+      src_digest,
+      note: None,
+    });
+    append(self.file()?, &record)
+  }
+
+  /// Record a snapshot of a file system taken before applying any
+  /// operations, providing a way of rolling back the session as a
+  /// whole.
+  pub fn record_snapshot(&mut self, snapshot: &Snapshot) -> Result<()> {
+    let record = Record::Snapshot(snapshot.clone());
+    append(self.file()?, &record)
+  }
+
+  /// Retrieve the ID of the session being journaled.
+  #[inline]
+  pub fn id(&self) -> &str {
+    &self.id
+  }
+
+  /// Check whether the session left a trace, i.e., whether anything
+  /// got recorded so far.
+  #[inline]
+  pub fn is_recorded(&self) -> bool {
+    self.file.is_some()
+  }
+}
+
+
+impl Log for Mutex<Journal> {
+  fn intend(&self, src: &Path, dst: &Path) -> Result<()> {
+    let mut journal = self.lock().unwrap_or_else(PoisonError::into_inner);
+    journal.intend(src, dst)
+  }
+
+  async fn complete(&self, src: &Path, dst: &Path) -> Result<()> {
+    let digest = Journal::digest(self, dst).await?;
+    let mut journal = self.lock().unwrap_or_else(PoisonError::into_inner);
+    journal.record(src, dst, digest)
+  }
+
+  fn abandon(&self, src: &Path, dst: &Path) -> Result<()> {
+    let mut journal = self.lock().unwrap_or_else(PoisonError::into_inner);
+    journal.abandon(src, dst)
+  }
+}
+
+
+/// A previously journaled session.
+#[derive(Debug)]
+pub struct Session {
+  /// The path to the journal file.
+  path: PathBuf,
+  /// The ID of the session.
+  id: String,
+  /// The command used for renaming.
+  command: Vec<OsString>,
+  /// The time at which the session started, in seconds since the Unix
+  /// epoch.
+  time: u64,
+  /// The operations applied as part of the session, in order.
+  operations: Vec<Operation>,
+  /// The operations announced but never settled, because the session
+  /// got interrupted.
+  pending: Vec<Intent>,
+  /// Whether the session has been undone.
+  undone: bool,
+  /// The snapshots taken before applying operations.
+  snapshots: Vec<Snapshot>,
+}
+
+impl Session {
+  async fn load_path(path: &Path) -> Result<Self> {
+    let id = path
+      .file_stem()
+      .with_context(|| format!("path `{}` does not have file name", path.display()))?
+      .to_string_lossy()
+      .to_string();
+    let content = read_to_string(path)
+      .await
+      .with_context(|| format!("failed to read journal `{}`", path.display()))?;
+
+    let mut lines = content.lines().enumerate();
+    let (command, time) = match lines.next() {
+      Some((_, line)) => match serde_json::from_str(line) {
+        Ok(Record::Session { command, time }) => (command, time),
+        _ => {
+          return Err(Error::Invalid(format!(
+            "journal `{}` lacks a valid session header",
+            path.display()
+          )))
+        },
+      },
+      None => {
+        return Err(Error::Invalid(format!(
+          "journal `{}` is empty",
+          path.display()
+        )))
+      },
+    };
+
+    let mut operations = Vec::new();
+    let mut pending = Vec::new();
+    let mut undone = false;
+    let mut snapshots = Vec::new();
+
+    let mut lines = lines.peekable();
+    while let Some((idx, line)) = lines.next() {
+      let record = match serde_json::from_str(line) {
+        Ok(record) => record,
+        // A malformed last line is the result of a write that got
+        // torn, e.g., by a crash, just the case the journal is meant
+        // to help recover from.
+        Err(err) if lines.peek().is_none() => {
+          warn!(
+            "ignoring truncated line {} of journal `{}`: {err}",
+            idx + 1,
+            path.display()
+          );
+          break
+        },
+        Err(err) => {
+          return Err(err).with_context(|| {
+            format!(
+              "failed to parse line {} of journal `{}`",
+              idx + 1,
+              path.display()
+            )
+          })
+        },
+      };
+
+      match record {
+        Record::Rename(entry) => {
+          // The destination of a rename performed by a command may
+          // differ from the announced one.
+          let () = settle(&mut pending, |intent| {
+            !intent.exchange && intent.src == entry.src
+          });
+          let () = operations.push(Operation::Rename(entry));
+        },
+        Record::Exchange(entry) => {
+          let () = settle(&mut pending, |intent| {
+            intent.exchange
+              && ((intent.src == entry.src && intent.dst == entry.dst)
+                || (intent.src == entry.dst && intent.dst == entry.src))
+          });
+          let () = operations.push(Operation::Exchange(entry));
+        },
+        Record::Declined(entry) => operations.push(Operation::Declined(entry)),
+        Record::Intent(intent) => pending.push(intent),
+        Record::Abandoned { src, dst } => {
+          let () = settle(&mut pending, |intent| {
+            intent.src == src && intent.dst == dst
+          });
+        },
+        Record::Undone { .. } => undone = true,
+        Record::Snapshot(snapshot) => snapshots.push(snapshot),
+        Record::Session { .. } => {
+          return Err(Error::Invalid(format!(
+            "journal `{}` contains unexpected session header in line {}",
+            path.display(),
+            idx + 1
+          )))
+        },
+      }
+    }
+
+    let slf = Self {
+      path: path.to_path_buf(),
+      id,
+      command,
+      time,
+      operations,
+      pending,
+      undone,
+      snapshots,
+    };
+    Ok(slf)
+  }
+
+  /// Load the session with the given ID from `dir`.
+  pub async fn load(dir: &Path, id: &str) -> Result<Self> {
+    let path = dir.join(id).with_extension(EXTENSION);
+    Self::load_path(&path).await
+  }
+
+  /// Load all sessions stored in `dir`, ordered from oldest to newest.
+  ///
+  /// Journals that can't be loaded are skipped with a warning, so that
+  /// a single damaged one does not render all others inaccessible.
+  pub async fn load_all(dir: &Path) -> Result<Vec<Self>> {
+    let mut sessions = Vec::new();
+
+    if !try_exists(dir).await.unwrap_or(false) {
+      return Ok(sessions)
+    }
+
+    let mut entries = read_dir(dir)
+      .await
+      .with_context(|| format!("failed to read directory `{}`", dir.display()))?;
+
+    while let Some(entry) = entries
+      .next_entry()
+      .await
+      .with_context(|| format!("failed to read directory `{}`", dir.display()))?
+    {
+      let path = entry.path();
+      if path.extension() == Some(EXTENSION.as_ref()) {
+        match Self::load_path(&path).await {
+          Ok(session) => sessions.push(session),
+          Err(err) => warn!("skipping journal `{}`: {err:#}", path.display()),
+        }
+      }
+    }
+
+    let () = sessions.sort_by(|x, y| (x.time, &x.id).cmp(&(y.time, &y.id)));
+    Ok(sessions)
+  }
+
+  /// Load the most recent session stored in `dir` that has not been
+  /// undone yet.
+  pub async fn latest(dir: &Path) -> Result<Option<Self>> {
+    let session = Self::load_all(dir)
+      .await?
+      .into_iter()
+      .rev()
+      .find(|session| !session.undone);
+    Ok(session)
+  }
+
+  /// Load the most recent session stored in `dir` that got interrupted
+  /// while applying operations.
+  pub async fn interrupted(dir: &Path) -> Result<Option<Self>> {
+    let session = Self::load_all(dir)
+      .await?
+      .into_iter()
+      .rev()
+      .find(Self::is_interrupted);
+    Ok(session)
+  }
+
+  /// Revert all operations of the session, in reverse order.
+  ///
+  /// Renames that have already been reverted (e.g., as part of an
+  /// earlier, interrupted, undo operation) are skipped. Exchanges are
+  /// indistinguishable from their reversal and always reverted.
+  pub async fn undo(&mut self) -> Result<()> {
+    if self.undone {
+      return Err(Error::Invalid(format!(
+        "session {} has already been undone",
+        self.id
+      )))
+    }
+    if self.is_interrupted() {
+      return Err(Error::Invalid(format!(
+        "session {} got interrupted and needs to be recovered first",
+        self.id
+      )))
+    }
+
+    for operation in self.operations.iter().rev() {
+      let Entry { src, dst, .. } = match operation {
+        Operation::Rename(entry) => entry,
+        Operation::Exchange(Entry { src, dst, .. }) => {
+          let () = exchange(src, dst).await?;
+          continue
+        },
+        Operation::Declined(..) => continue,
+      };
+
+      let src_exists = try_exists(src)
+        .await
+        .with_context(|| format!("failed to check existence of `{}`", src.display()))?;
+      let dst_exists = try_exists(dst)
+        .await
+        .with_context(|| format!("failed to check existence of `{}`", dst.display()))?;
+
+      match (src_exists, dst_exists) {
+        (true, false) => continue,
+        (true, true) => {
+          return Err(Error::Invalid(format!(
+            "failed to restore `{}` from `{}`: file exists",
+            src.display(),
+            dst.display()
+          )))
+        },
+        (false, false) => {
+          return Err(Error::Invalid(format!(
+            "failed to restore `{}`: `{}` does not exist",
+            src.display(),
+            dst.display()
+          )))
+        },
+        (false, true) => (),
+      }
+
+      let () = rename_to(dst, src).await?;
+    }
+
+    let mut file = reopen(&self.path)?;
+    let () = append(&mut file, &Record::Undone { time: now() })?;
+    self.undone = true;
+    Ok(())
+  }
+
+  /// Recover the session after it got interrupted while applying
+  /// operations, reverting it as a whole.
+  ///
+  /// Whether each operation in flight at the time of the interruption
+  /// got applied is determined based on where the file it concerns
+  /// resides, and recorded accordingly. Afterwards, the session is
+  /// undone, restoring all files to their original paths, including
+  /// those left behind at temporary names.
+  pub async fn recover(&mut self) -> Result<()> {
+    if !self.is_interrupted() {
+      return Err(Error::Invalid(format!(
+        "session {} was not interrupted",
+        self.id
+      )))
+    }
+
+    let mut file = reopen(&self.path)?;
+
+    for intent in take(&mut self.pending) {
+      let (record, operation) = if intent.applied()? {
+        let entry = Entry {
+          src: intent.src,
+          dst: intent.dst,
+          digest: None,
+          src_digest: None,
+          note: None,
+        };
+        if intent.exchange {
+          (
+            Record::Exchange(entry.clone()),
+            Some(Operation::Exchange(entry)),
+          )
+        } else {
+          (
+            Record::Rename(entry.clone()),
+            Some(Operation::Rename(entry)),
+          )
+        }
+      } else {
+        let record = Record::Abandoned {
+          src: intent.src,
+          dst: intent.dst,
+        };
+        (record, None)
+      };
+
+      let () = append(&mut file, &record)?;
+      let () = self.operations.extend(operation);
+    }
+
+    self.undo().await
+  }
+
+  /// Verify that the files renamed as part of the session are still
+  /// present with unchanged contents at their final paths.
+  ///
+  /// Only files for which a digest got recorded are verified. Results
+  /// are reported ordered by path.
+  pub async fn verify(&self) -> Result<Vec<(PathBuf, Verification)>> {
+    if self.undone {
+      return Err(Error::Invalid(format!(
+        "session {} has been undone",
+        self.id
+      )))
+    }
+
+    // Determine where each file ended up, as later operations may
+    // have moved it again.
+    let mut expected = BTreeMap::new();
+    for operation in &self.operations {
+      match operation {
+        Operation::Rename(Entry {
+          src, dst, digest, ..
+        }) => {
+          let _prev = expected.remove(src);
+          let _prev = expected.insert(dst.clone(), *digest);
+        },
+        Operation::Exchange(Entry {
+          src,
+          dst,
+          digest,
+          src_digest,
+          ..
+        }) => {
+          let _prev = expected.insert(src.clone(), *src_digest);
+          let _prev = expected.insert(dst.clone(), *digest);
+        },
+        Operation::Declined(..) => (),
+      }
+    }
+
+    let expected = expected
+      .into_iter()
+      .filter_map(|(path, digest)| Some((path, digest?)))
+      .collect::<Vec<_>>();
+    if expected.is_empty() {
+      return Err(Error::Invalid(format!(
+        "session {} did not record any checksums",
+        self.id
+      )))
+    }
+
+    let mut results = Vec::with_capacity(expected.len());
+    for (path, recorded) in expected {
+      let verification = if try_exists(&path)
+        .await
+        .with_context(|| format!("failed to check existence of `{}`", path.display()))?
+      {
+        let file = path.clone();
+        if spawn_blocking(move || digest(&file))
+          .await
+          .context("digest task failed")??
+          == recorded
+        {
+          Verification::Intact
+        } else {
+          Verification::Modified
+        }
+      } else {
+        Verification::Missing
+      };
+      let () = results.push((path, verification));
+    }
+    Ok(results)
+  }
+
+  /// Check that the file system still reflects the outcome of the
+  /// session, with files present at the paths they got renamed to and
+  /// absent from the paths they got renamed from.
+  ///
+  /// Only the presence of files is checked, not their contents.
+  /// Results are reported ordered by path, with `None` indicating that
+  /// a path is as expected.
+  pub fn audit(&self) -> Result<Vec<(PathBuf, Option<Drift>)>> {
+    if self.undone {
+      return Err(Error::Invalid(format!(
+        "session {} has been undone",
+        self.id
+      )))
+    }
+
+    // Determine which paths are expected to be occupied, as later
+    // operations may have moved a file again.
+    let mut expected = BTreeMap::new();
+    for operation in &self.operations {
+      match operation {
+        Operation::Rename(Entry { src, dst, .. }) => {
+          let _prev = expected.insert(src.clone(), false);
+          let _prev = expected.insert(dst.clone(), true);
+        },
+        Operation::Exchange(Entry { src, dst, .. }) => {
+          let _prev = expected.insert(src.clone(), true);
+          let _prev = expected.insert(dst.clone(), true);
+        },
+        Operation::Declined(..) => (),
+      }
+    }
+
+    let results = expected
+      .into_iter()
+      .map(|(path, occupied)| {
+        let drift = match (occupied, exists(&path)) {
+          (true, false) => Some(Drift::Missing),
+          (false, true) => Some(Drift::Occupied),
+          (true, true) | (false, false) => None,
+        };
+        (path, drift)
+      })
+      .collect();
+    Ok(results)
+  }
+
+  /// Retrieve the session's ID.
+  #[inline]
+  pub fn id(&self) -> &str {
+    &self.id
+  }
+
+  /// Retrieve the command used for renaming in this session.
+  #[inline]
+  pub fn command(&self) -> &[OsString] {
+    &self.command
+  }
+
+  /// Retrieve the time the session started, in seconds since the Unix
+  /// epoch.
+  #[inline]
+  pub fn time(&self) -> u64 {
+    self.time
+  }
+
+  /// Retrieve the operations applied as part of the session.
+  #[inline]
+  pub fn operations(&self) -> &[Operation] {
+    &self.operations
+  }
+
+  /// Retrieve the operations announced but never settled, because the
+  /// session got interrupted.
+  #[inline]
+  pub fn pending(&self) -> &[Intent] {
+    &self.pending
+  }
+
+  /// Check whether the session got interrupted while applying
+  /// operations and needs to be recovered.
+  #[inline]
+  pub fn is_interrupted(&self) -> bool {
+    !self.pending.is_empty()
+  }
+
+  /// Check whether the session has been undone.
+  #[inline]
+  pub fn is_undone(&self) -> bool {
+    self.undone
+  }
+
+  /// Retrieve the snapshots of file systems taken before applying the
+  /// operations of the session.
+  #[inline]
+  pub fn snapshots(&self) -> &[Snapshot] {
+    &self.snapshots
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::fs::read_to_string;
+  use std::fs::rename;
+  use std::fs::write;
+
+  use tempfile::tempdir;
+  use tempfile::TempDir;
+
+
+  /// A directory containing a journal along with the files it covers.
+  struct Setup {
+    dir: TempDir,
+    journal: Journal,
+  }
+
+  impl Setup {
+    /// Create the files `files`, each with its name as contents.
+    fn new<const N: usize>(files: [&str; N]) -> Self {
+      let dir = tempdir().unwrap();
+      for file in files {
+        let () = write(dir.path().join(file), file).unwrap();
+      }
+      let journal = Journal::new(&dir.path().join("journal"), &[OsString::from("cmd")]);
+      Self { dir, journal }
+    }
+
+    /// Retrieve the path of the file `name`.
+    fn path(&self, name: &str) -> PathBuf {
+      self.dir.path().join(name)
+    }
+
+    /// Rename `src` to `dst`, journaling the intent and, if `commit` is
+    /// set, the rename itself.
+    fn rename(&mut self, src: &str, dst: &str, commit: bool) {
+      let (src, dst) = (self.path(src), self.path(dst));
+      let () = self.journal.intend(&src, &dst).unwrap();
+      let () = rename(&src, &dst).unwrap();
+      if commit {
+        let () = self.journal.record(&src, &dst, None).unwrap();
+      }
+    }
+
+    /// Load the journaled session from disk.
+    async fn session(&self) -> Session {
+      Session::load(&self.dir.path().join("journal"), self.journal.id())
+        .await
+        .unwrap()
+    }
+
+    /// Retrieve the contents of the file `name`, if it exists.
+    fn contents(&self, name: &str) -> Option<String> {
+      read_to_string(self.path(name)).ok()
+    }
+  }
+
+
+  /// Check that undoing a session restores the original paths of all
+  /// renamed files, once.
+  #[tokio::test]
+  async fn undo() {
+    let mut setup = Setup::new(["a", "b"]);
+    let () = setup.rename("a", "x", true);
+    let () = setup.rename("b", "a", true);
+
+    let mut session = setup.session().await;
+    assert!(!session.is_interrupted());
+    assert_eq!(session.operations().len(), 2);
+    let () = session.undo().await.unwrap();
+    assert_eq!(setup.contents("a").as_deref(), Some("a"));
+    assert_eq!(setup.contents("b").as_deref(), Some("b"));
+    assert_eq!(setup.contents("x"), None);
+
+    let mut session = setup.session().await;
+    assert!(session.is_undone());
+    let err = session.undo().await.unwrap_err();
+    assert!(err.to_string().contains("already been undone"), "{err}");
+    let latest = Session::latest(&setup.path("journal")).await.unwrap();
+    assert!(latest.is_none());
+  }
+
+  /// Check that undoing refuses to overwrite a file that took the
+  /// original path of a renamed one in the meantime.
+  #[tokio::test]
+  async fn undo_occupied() {
+    let mut setup = Setup::new(["a"]);
+    let () = setup.rename("a", "x", true);
+    let () = write(setup.path("a"), "new").unwrap();
+
+    let mut session = setup.session().await;
+    let err = session.undo().await.unwrap_err();
+    assert!(err.to_string().contains("file exists"), "{err}");
+    assert_eq!(setup.contents("a").as_deref(), Some("new"));
+    assert_eq!(setup.contents("x").as_deref(), Some("a"));
+    assert!(!setup.session().await.is_undone());
+  }
+
+  /// Check that a torn last record is ignored when loading a session
+  /// and cut off before appending to its journal.
+  #[tokio::test]
+  async fn torn_record() {
+    let mut setup = Setup::new(["a", "b"]);
+    let () = setup.rename("a", "x", true);
+    let path = setup
+      .path("journal")
+      .join(setup.journal.id())
+      .with_extension(EXTENSION);
+    let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+    let () = file.write_all(br#"{"type":"intent","src":"#).unwrap();
+
+    let mut session = setup.session().await;
+    assert!(!session.is_interrupted());
+    assert_eq!(session.operations().len(), 1);
+    let () = session.undo().await.unwrap();
+    assert_eq!(setup.contents("a").as_deref(), Some("a"));
+
+    let content = read_to_string(&path).unwrap();
+    let last = content.lines().last().unwrap();
+    assert!(
+      matches!(serde_json::from_str(last), Ok(Record::Undone { .. })),
+      "{content}"
+    );
+    assert!(setup.session().await.is_undone());
+  }
+
+  /// Check that an intent without matching commit gets recovered as a
+  /// rename if the file was already moved, and abandoned otherwise.
+  #[tokio::test]
+  async fn recover_uncommitted() {
+    let mut setup = Setup::new(["a", "b", "c"]);
+    let () = setup.rename("a", "x", true);
+    let () = setup.rename("b", "y", false);
+    let (c, z) = (setup.path("c"), setup.path("z"));
+    let () = setup.journal.intend(&c, &z).unwrap();
+
+    let mut session = Session::interrupted(&setup.path("journal"))
+      .await
+      .unwrap()
+      .unwrap();
+    assert_eq!(session.id(), setup.journal.id());
+    assert_eq!(session.pending().len(), 2);
+    let err = session.undo().await.unwrap_err();
+    assert!(err.to_string().contains("needs to be recovered"), "{err}");
+
+    let () = session.recover().await.unwrap();
+    for file in ["a", "b", "c"] {
+      assert_eq!(setup.contents(file).as_deref(), Some(file));
+    }
+    for file in ["x", "y", "z"] {
+      assert_eq!(setup.contents(file), None);
+    }
+
+    let session = setup.session().await;
+    assert!(!session.is_interrupted());
+    assert!(session.is_undone());
+    assert_eq!(session.operations().len(), 2);
+  }
+
+  /// Check that recovery is refused for a file found at neither of its
+  /// paths.
+  #[cfg(unix)]
+  #[tokio::test]
+  async fn recover_missing() {
+    let mut setup = Setup::new(["a"]);
+    let () = setup.rename("a", "x", false);
+    let () = rename(setup.path("x"), setup.path("elsewhere")).unwrap();
+
+    let mut session = setup.session().await;
+    let err = session.recover().await.unwrap_err();
+    assert!(
+      err.to_string().contains("neither at its original path"),
+      "{err}"
+    );
+    assert!(setup.session().await.is_interrupted());
+  }
+
+  /// Check that recovering a session a second time has no effect.
+  #[tokio::test]
+  async fn recover_twice() {
+    let mut setup = Setup::new(["a", "b"]);
+    let () = setup.rename("a", "x", true);
+    let () = setup.rename("b", "y", false);
+
+    let mut session = setup.session().await;
+    let () = session.recover().await.unwrap();
+    let err = session.recover().await.unwrap_err();
+    assert!(err.to_string().contains("was not interrupted"), "{err}");
+
+    let () = write(setup.path("x"), "new").unwrap();
+    let mut session = setup.session().await;
+    let err = session.recover().await.unwrap_err();
+    assert!(err.to_string().contains("was not interrupted"), "{err}");
+    assert!(Session::interrupted(&setup.path("journal"))
+      .await
+      .unwrap()
+      .is_none());
+    assert_eq!(setup.contents("a").as_deref(), Some("a"));
+    assert_eq!(setup.contents("b").as_deref(), Some("b"));
+    assert_eq!(setup.contents("x").as_deref(), Some("new"));
+  }
+}
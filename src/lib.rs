@@ -1,25 +1,146 @@
 // Copyright (C) 2024 Daniel Mueller <deso@posteo.net>
 // SPDX-License-Identifier: GPL-3.0-or-later
 
+//! A library for renaming files using arbitrary commands.
+//!
+//! Library users should stick to the items in the [`prelude`], which
+//! are covered by semantic versioning. Other public items exist to
+//! support the command line utilities and may change at any time.
+
 #![allow(clippy::let_and_return, clippy::let_unit_value)]
 
+#[cfg(feature = "archive")]
+pub mod archive;
+#[cfg(feature = "output")]
+pub mod change;
+pub mod concurrency;
+#[cfg(feature = "config")]
+pub mod config;
+pub mod conflict;
+#[cfg(feature = "template")]
+mod detect;
+pub mod error;
+#[cfg(feature = "template")]
+mod expr;
+pub mod grapheme;
+#[cfg(feature = "journal")]
+pub mod journal;
+pub mod lint;
+#[cfg(feature = "template")]
+pub mod locale;
+#[cfg(feature = "manifest")]
+pub mod manifest;
+#[cfg(feature = "metadata")]
+pub mod metadata;
+mod os;
+#[cfg(feature = "output")]
+pub mod output;
+pub mod plan;
+#[cfg(feature = "policy")]
+pub mod policy;
+pub mod prelude;
+pub mod progress;
+pub mod retry;
+mod sandbox;
+#[cfg(feature = "sanitize")]
+pub mod sanitize;
+#[cfg(feature = "config")]
+pub mod schedule;
+#[cfg(feature = "template")]
+pub mod template;
+#[cfg(feature = "test-util")]
+pub mod test_util;
+#[cfg(any(feature = "config", feature = "policy"))]
+mod toml;
+#[cfg(feature = "traverse")]
+pub mod traverse;
+#[cfg(all(target_os = "linux", feature = "io-uring"))]
+mod uring;
+#[cfg(any(feature = "journal", feature = "manifest", feature = "output"))]
+mod util;
+
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::collections::HashSet;
 use std::ffi::OsStr;
 use std::ffi::OsString;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Result as FmtResult;
+use std::future::Future;
+use std::io;
+use std::io::ErrorKind;
+use std::mem::replace;
 use std::path::Path;
 use std::path::PathBuf;
+use std::process;
 use std::process::Output;
 use std::process::Stdio;
-
-use anyhow::bail;
-use anyhow::Context as _;
-use anyhow::Result;
+use std::str::from_utf8;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
 
 use tempfile::tempdir;
 
 use tokio::fs::canonicalize;
+use tokio::fs::copy;
+use tokio::fs::create_dir;
+use tokio::fs::create_dir_all;
+use tokio::fs::hard_link;
+use tokio::fs::read;
 use tokio::fs::read_dir;
+#[cfg(unix)]
+use tokio::fs::read_link;
+use tokio::fs::remove_file;
+#[cfg(unix)]
+use tokio::fs::symlink;
+use tokio::fs::symlink_metadata;
 use tokio::fs::write;
+use tokio::io::AsyncWriteExt as _;
 use tokio::process::Command;
+use tokio::task::spawn_blocking;
+use tokio::time;
+
+use tracing::debug;
+use tracing::trace;
+use tracing::warn;
+
+use futures::stream;
+use futures::Stream;
+use futures::StreamExt as _;
+
+use crate::concurrency::buffered;
+use crate::concurrency::Concurrency;
+use crate::concurrency::Governor;
+use crate::conflict::displace;
+use crate::conflict::resolve_conflict;
+use crate::conflict::ConflictPolicy;
+use crate::conflict::Displaced;
+use crate::error::Context as _;
+use crate::error::Result;
+use crate::os::from_units;
+use crate::os::rename_exchange;
+use crate::os::rename_noreplace;
+use crate::os::simplify;
+use crate::os::special_file;
+use crate::os::special_kind;
+use crate::os::units;
+use crate::plan::is_relative_file;
+use crate::plan::split_swaps;
+use crate::plan::untangle;
+use crate::plan::Step;
+use crate::progress::Progress;
+use crate::retry::is_transient;
+use crate::retry::retry;
+use crate::retry::RetryPolicy;
+
+pub use crate::error::Error;
+#[doc(hidden)]
+pub use crate::os::os_str_bytes;
+#[doc(hidden)]
+pub use crate::os::os_string_from_bytes;
 
 
 /// Concatenate a command and its arguments into a single string.
@@ -38,6 +159,58 @@ where
     })
 }
 
+/// The placeholder in arguments of a rename command that gets replaced
+/// with the name of the file to rename, as in `mv {} prefix_{}`.
+const PLACEHOLDER: &str = "{}";
+
+
+/// Check whether any of the arguments `args` of a rename command
+/// contains the [`PLACEHOLDER`] for the name of the file.
+fn has_placeholder(args: &[OsString]) -> bool {
+  let placeholder = units(OsStr::new(PLACEHOLDER));
+  args.iter().any(|arg| {
+    units(arg)
+      .windows(placeholder.len())
+      .any(|window| window == &*placeholder)
+  })
+}
+
+
+/// Replace all occurrences of the [`PLACEHOLDER`] in `arg` with `name`.
+fn replace_placeholder(arg: &OsStr, name: &OsStr) -> OsString {
+  let placeholder = units(OsStr::new(PLACEHOLDER));
+  let name = units(name);
+  let mut rest = &*units(arg);
+  let mut replaced = Vec::with_capacity(rest.len());
+  while !rest.is_empty() {
+    if let Some(remainder) = rest.strip_prefix(&*placeholder) {
+      let () = replaced.extend_from_slice(&name);
+      rest = remainder;
+    } else {
+      let () = replaced.push(rest[0]);
+      rest = &rest[1..];
+    }
+  }
+  from_units(replaced)
+}
+
+
+/// Assemble the arguments for invoking a rename command with its own
+/// arguments `args` on the files called `names`.
+///
+/// Occurrences of the [`PLACEHOLDER`] in `args` are replaced with the
+/// name of the file, in which case the command operates on a single
+/// file. Otherwise the names are appended to `args`.
+fn command_args(args: &[OsString], names: &[OsString]) -> Vec<OsString> {
+  match names {
+    [name] if has_placeholder(args) => args
+      .iter()
+      .map(|arg| replace_placeholder(arg, name))
+      .collect(),
+    _ => args.iter().chain(names).cloned().collect(),
+  }
+}
+
 /// Format a command with the given list of arguments as a string.
 #[doc(hidden)]
 pub fn format_command<C, A, S>(command: C, args: A) -> String
@@ -49,7 +222,6 @@ where
   concat_command(command, args).to_string_lossy().to_string()
 }
 
-
 #[doc(hidden)]
 pub fn evaluate<C, A, S>(output: &Output, command: C, args: A) -> Result<()>
 where
@@ -58,120 +230,1915 @@ where
   S: AsRef<OsStr>,
 {
   if !output.status.success() {
-    let code = if let Some(code) = output.status.code() {
-      format!(" ({code})")
-    } else {
-      " (terminated by signal)".to_string()
-    };
+    return Err(Error::Command {
+      command: format_command(command, args),
+      status: output.status.code(),
+      stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+    })
+  }
+  Ok(())
+}
 
-    let stderr = String::from_utf8_lossy(&output.stderr);
-    let stderr = stderr.trim_end();
-    let stderr = if !stderr.is_empty() {
-      format!(": {stderr}")
-    } else {
-      String::new()
-    };
 
-    bail!(
-      "`{}` reported non-zero exit-status{code}{stderr}",
-      format_command(command, args),
-    );
+/// Determine the program to run and its arguments for invoking
+/// `command` with `args` in `dir`, accounting for `opts.sandbox`.
+fn invocation<C, A, S>(
+  command: C,
+  args: A,
+  dir: &Path,
+  opts: &RenameOpts,
+) -> Result<(OsString, Vec<OsString>)>
+where
+  C: AsRef<OsStr>,
+  A: IntoIterator<Item = S>,
+  S: AsRef<OsStr>,
+{
+  if opts.sandbox {
+    let program = OsString::from(sandbox::PROGRAM);
+    // Commands merely printing new names don't need to write anything.
+    let writable = opts.protocol == Protocol::Rename;
+    let args = sandbox::args(command.as_ref(), args, dir, writable)?;
+    Ok((program, args))
+  } else {
+    let program = command.as_ref().to_os_string();
+    let args = args
+      .into_iter()
+      .map(|arg| arg.as_ref().to_os_string())
+      .collect::<Vec<_>>();
+    Ok((program, args))
   }
-  Ok(())
 }
 
 
 /// Run a command with the provided arguments.
-async fn run_in_impl<C, A, S, D>(command: C, args: A, dir: D, stdout: Stdio) -> Result<Output>
+///
+/// If `input` is provided, it is written to the command's stdin. The
+/// command is spawned through `opts.governor` and killed once it has
+/// been running for longer than `opts.timeout`. With `opts.sandbox` set
+/// it runs confined, with write access to `dir` at most.
+async fn run_in_impl<C, A, S, D>(
+  command: C,
+  args: A,
+  dir: D,
+  input: Option<&[u8]>,
+  stdout: Stdio,
+  opts: &RenameOpts,
+) -> Result<Output>
 where
   C: AsRef<OsStr>,
   A: IntoIterator<Item = S> + Clone,
   S: AsRef<OsStr>,
   D: AsRef<Path>,
 {
-  let output = Command::new(command.as_ref())
+  let (program, program_args) = invocation(command.as_ref(), args.clone(), dir.as_ref(), opts)?;
+  let spawn_err = |source| Error::Spawn {
+    command: format_command(&program, &program_args),
+    source,
+  };
+  let stdin = if input.is_some() {
+    Stdio::piped()
+  } else {
+    Stdio::null()
+  };
+  debug!(
+    command = %format_command(&program, &program_args),
+    dir = %dir.as_ref().display(),
+    "running command"
+  );
+  let mut cmd = Command::new(&program);
+  let _cmd = cmd
     .current_dir(dir)
-    .stdin(Stdio::null())
+    .stdin(stdin)
     .stdout(stdout)
-    .args(args.clone())
-    .output()
-    .await
-    .with_context(|| {
-      format!(
-        "failed to run `{}`",
-        format_command(command.as_ref(), args.clone())
-      )
-    })?;
+    .stderr(Stdio::piped())
+    .args(&program_args)
+    .envs(&opts.env)
+    // Ensure that the command does not outlive a timeout or the
+    // cancellation of the operation it is part of.
+    .kill_on_drop(true);
+  let (mut child, _running) = opts.governor.spawn(&mut cmd).await.map_err(spawn_err)?;
+
+  let run = async {
+    if let Some(input) = input {
+      // SANITY: stdin is always piped when input is provided.
+      let mut stdin = child.stdin.take().unwrap();
+      match stdin.write_all(input).await {
+        Ok(()) => (),
+        // The command is free to exit without consuming all input.
+        Err(err) if err.kind() == ErrorKind::BrokenPipe => (),
+        Err(err) => return Err(spawn_err(err)),
+      }
+      // Close stdin, so that the command sees end of input.
+      let () = drop(stdin);
+    }
+    child.wait_with_output().await.map_err(spawn_err)
+  };
 
+  let output = match opts.timeout {
+    Some(duration) => time::timeout(duration, run)
+      .await
+      .map_err(|_elapsed| Error::Timeout {
+        command: format_command(command.as_ref(), args.clone()),
+        timeout: duration,
+      })??,
+    None => run.await?,
+  };
+  trace!(
+    command = %format_command(command.as_ref(), args.clone()),
+    status = ?output.status.code(),
+    stdout = %String::from_utf8_lossy(&output.stdout),
+    stderr = %String::from_utf8_lossy(&output.stderr),
+    "command finished"
+  );
   let () = evaluate(&output, command, args)?;
   Ok(output)
 }
 
-/// Run a command with the provided arguments.
-async fn run_in<C, A, S, D>(command: C, args: A, dir: D) -> Result<()>
+/// Run a command with the provided arguments, as governed by `opts`.
+async fn run_in<C, A, S, D>(command: C, args: A, dir: D, opts: &RenameOpts) -> Result<()>
 where
   C: AsRef<OsStr>,
   A: IntoIterator<Item = S> + Clone,
   S: AsRef<OsStr>,
   D: AsRef<Path>,
 {
-  let _output = run_in_impl(command, args, dir, Stdio::null()).await?;
+  let _output = run_in_impl(command, args, dir, None, Stdio::null(), opts).await?;
   Ok(())
 }
 
 
+/// How to treat files to rename that are symbolic links.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum SymlinkPolicy {
+  /// Rename the file a link refers to, in its directory.
+  #[default]
+  Follow,
+  /// Rename the link itself.
+  NoFollow,
+}
+
+
+/// The way a rename command communicates the new name of a file.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Protocol {
+  /// The command renames the file passed to it as last argument (or in
+  /// place of each `{}` in its arguments).
+  #[default]
+  Rename,
+  /// The command receives the name of the file as last argument (or in
+  /// place of each `{}` in its arguments) and prints the new name to
+  /// stdout.
+  Stdout,
+  /// The command reads the name of the file from stdin and prints the
+  /// new name to stdout, as text transforming tools such as `sed` do.
+  Stdin,
+}
+
+impl FromStr for Protocol {
+  type Err = Error;
+
+  fn from_str(s: &str) -> Result<Self> {
+    match s {
+      "rename" => Ok(Self::Rename),
+      "stdout" => Ok(Self::Stdout),
+      "stdin" => Ok(Self::Stdin),
+      _ => Err(Error::Invalid(format!(
+        "unsupported protocol `{s}`; expected one of `rename`, `stdout`, or `stdin`"
+      ))),
+    }
+  }
+}
+
+impl Display for Protocol {
+  fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+    let s = match self {
+      Self::Rename => "rename",
+      Self::Stdout => "stdout",
+      Self::Stdin => "stdin",
+    };
+    f.write_str(s)
+  }
+}
+
+
+/// Options controlling the renaming of a file.
+///
+/// Options are created via [`Default`] and adjusted from there.
+#[derive(Clone, Debug, Default)]
+#[non_exhaustive]
+pub struct RenameOpts {
+  /// If `true`, don't actually perform the rename but just "simulate"
+  /// it.
+  pub dry_run: bool,
+  /// The policy for retrying the rename on the live data in case of
+  /// transient failures.
+  pub retry: RetryPolicy,
+  /// The policy for dealing with destinations that exist already.
+  pub on_conflict: ConflictPolicy,
+  /// How to treat a file that is a symbolic link.
+  pub symlinks: SymlinkPolicy,
+  /// The way the rename command communicates new names.
+  pub protocol: Protocol,
+  /// The number of files for which new names are determined
+  /// concurrently by batch operations, such as [`rename_many`]. Use
+  /// [`Concurrency::Fixed`] with one for running the rename command
+  /// strictly one file after the other.
+  pub plan_concurrency: Concurrency,
+  /// The number of files renamed concurrently by batch operations
+  /// operating on the live data, such as [`rename_stream`].
+  ///
+  /// Renames touch the actual files and may be subject to rate limits,
+  /// for example on network file systems, so that a lower level than
+  /// for planning may be appropriate.
+  pub apply_concurrency: Concurrency,
+  /// The time after which a rename command gets killed and reported as
+  /// having timed out, or `None` for letting it run indefinitely.
+  pub timeout: Option<Duration>,
+  /// The governor through which rename commands are spawned, reducing
+  /// the number of them running concurrently should a limit on the
+  /// number of processes be hit. It is shared by clones of the options.
+  pub(crate) governor: Arc<Governor>,
+  /// Environment variables set for rename commands, in addition to the
+  /// ones inherited.
+  pub env: BTreeMap<OsString, OsString>,
+  /// The suffix to append to the name of a backup of each file made
+  /// right before it gets renamed on the live data, as by [`backup`],
+  /// or `None` for not making backups.
+  pub backup: Option<OsString>,
+  /// Whether to run rename commands in a sandbox, for commands that are
+  /// not trusted. Commands then see a read-only view of the system,
+  /// lack network access, and may only write to the directory they are
+  /// run in with [`Protocol::Rename`]: a temporary one while
+  /// determining new names and the one containing the file when
+  /// renaming it on the live data. Commands merely printing new names
+  /// may not write at all.
+  ///
+  /// Sandboxing is provided by bubblewrap (`bwrap`), which has to be
+  /// installed.
+  pub sandbox: bool,
+  /// Whether the file being renamed is a directory, which rename
+  /// commands operating on a stand-in then see as an empty directory
+  /// instead of an empty file. [`rename_with`] determines it on its
+  /// own.
+  pub directory: bool,
+}
+
+
+impl RenameOpts {
+  /// Tell rename commands the position of the file being renamed within
+  /// a batch of `total` files, via the `BR_INDEX` (counting from one)
+  /// and `BR_TOTAL` environment variables.
+  pub fn with_position(mut self, index: usize, total: usize) -> Self {
+    let _prev = self.env.insert(
+      OsString::from("BR_INDEX"),
+      OsString::from(index.to_string()),
+    );
+    let _prev = self.env.insert(
+      OsString::from("BR_TOTAL"),
+      OsString::from(total.to_string()),
+    );
+    self
+  }
+
+  /// Tell rename commands the actual location of the file being
+  /// renamed, as they may operate on a stand-in in a temporary
+  /// directory, via the `BR_ORIG_PATH` and `BR_PARENT_DIR` environment
+  /// variables.
+  pub fn with_file(mut self, path: &Path) -> Self {
+    let _prev = self.env.insert(
+      OsString::from("BR_ORIG_PATH"),
+      path.as_os_str().to_os_string(),
+    );
+    if let Some(dir) = path.parent() {
+      let _prev = self.env.insert(
+        OsString::from("BR_PARENT_DIR"),
+        dir.as_os_str().to_os_string(),
+      );
+    }
+    self
+  }
+}
+
+
 /// Rename a file using the provided command.
 ///
 /// The function returns the new name. If `dry_run` is `true`, don't
 /// actually perform the rename but just "simulate" it.
 pub async fn rename(file: &Path, command: &[OsString], dry_run: bool) -> Result<PathBuf> {
+  let opts = RenameOpts {
+    dry_run,
+    ..Default::default()
+  };
+  rename_with(file, command, &opts).await
+}
+
+
+/// Resolve `path` into the canonical, absolute form used for reporting
+/// the paths of renamed files.
+///
+/// With [`SymlinkPolicy::NoFollow`] a symbolic link at `path` is not
+/// followed, i.e., only the directory containing it is canonicalized.
+pub async fn resolve(path: &Path, symlinks: SymlinkPolicy) -> Result<PathBuf> {
+  let error = |source| Error::Canonicalize {
+    path: path.to_path_buf(),
+    source,
+  };
+  match (symlinks, path.file_name()) {
+    (SymlinkPolicy::NoFollow, Some(name)) => {
+      let _metadata = symlink_metadata(path).await.map_err(error)?;
+      let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+      };
+      let dir = canonicalize(dir).await.map(simplify).map_err(error)?;
+      Ok(dir.join(name))
+    },
+    _ => canonicalize(path).await.map(simplify).map_err(error),
+  }
+}
+
+
+/// Find all files (that are not directories) below `dir`, reporting
+/// their paths relative to `dir`. With `empty_dirs` set, empty
+/// directories are reported instead.
+async fn find_files(dir: &Path, empty_dirs: bool) -> Result<Vec<PathBuf>> {
+  let mut files = Vec::new();
+  let mut dirs = vec![PathBuf::new()];
+  while let Some(rel_dir) = dirs.pop() {
+    let mut empty = true;
+    let abs_dir = dir.join(&rel_dir);
+    let mut entries = read_dir(&abs_dir).await.with_context(|| {
+      format!(
+        "failed to read contents of directory `{}`",
+        abs_dir.display()
+      )
+    })?;
+
+    while let Some(entry) = entries
+      .next_entry()
+      .await
+      .with_context(|| format!("failed to read entry of `{}`", abs_dir.display()))?
+    {
+      let path = rel_dir.join(entry.file_name());
+      let file_type = entry
+        .file_type()
+        .await
+        .with_context(|| format!("failed to determine type of `{}`", path.display()))?;
+      empty = false;
+      if file_type.is_dir() {
+        let () = dirs.push(path);
+      } else if !empty_dirs {
+        let () = files.push(path);
+      }
+    }
+
+    if empty_dirs && empty && !rel_dir.as_os_str().is_empty() {
+      let () = files.push(rel_dir);
+    }
+  }
+  Ok(files)
+}
+
+
+/// Determine the paths that files called `names` would be renamed to
+/// by a `command` printing new names, one per line, as per
+/// `opts.protocol`.
+///
+/// All names are passed to a single invocation of the command.
+async fn print_names(
+  names: &[OsString],
+  command: &[OsString],
+  opts: &RenameOpts,
+) -> Result<Vec<PathBuf>> {
+  let (cmd, cmd_args) = command.split_first().context("rename command is missing")?;
+  let mut input = Vec::new();
+  for name in names {
+    let bytes = os_str_bytes(name);
+    if bytes.contains(&b'\n') {
+      return Err(Error::Invalid(format!(
+        "file name `{}` contains a newline and can't be passed to `{}`",
+        name.to_string_lossy(),
+        cmd.to_string_lossy()
+      )))
+    }
+    let () = input.extend_from_slice(&bytes);
+    let () = input.push(b'\n');
+  }
+
+  let output = if opts.protocol == Protocol::Stdin {
+    run_in_impl(cmd, cmd_args, ".", Some(&input), Stdio::piped(), opts).await?
+  } else {
+    let args = command_args(cmd_args, names);
+    run_in_impl(cmd, &args, ".", None, Stdio::piped(), opts).await?
+  };
+
+  let error = |message| Error::Output {
+    command: format_command(cmd, cmd_args),
+    message,
+  };
+  let stdout = output.stdout.strip_suffix(b"\n").unwrap_or(&output.stdout);
+  let lines = stdout
+    .split(|byte| *byte == b'\n')
+    .map(|line| line.strip_suffix(b"\r").unwrap_or(line))
+    .collect::<Vec<_>>();
+  if lines.len() != names.len() {
+    return Err(error(format!(
+      "printed {} line(s) for {} file(s)",
+      lines.len(),
+      names.len()
+    )))
+  }
+
+  names
+    .iter()
+    .zip(lines)
+    .map(|(name, line)| {
+      let invalid = || {
+        error(format!(
+          "printed invalid path `{}` for `{}`",
+          line.escape_ascii(),
+          name.to_string_lossy()
+        ))
+      };
+      let new = os_string_from_bytes(line)
+        .map(PathBuf::from)
+        .map_err(|_| invalid())?;
+      if !is_relative_file(&new) {
+        return Err(invalid())
+      }
+      Ok(new)
+    })
+    .collect()
+}
+
+
+/// Determine the path that a file called `name` would be renamed to by
+/// `command`, relative to the directory containing the file.
+///
+/// With [`Protocol::Rename`] as `opts.protocol` the command operates on
+/// an empty file in a temporary directory, so no file of the given name
+/// needs to exist. With `opts.directory` set, it is an empty directory
+/// instead. Commands may move the file into subdirectories they
+/// create, but should they leave behind no file or several,
+/// [`Error::NoFile`] or [`Error::MultipleFiles`] is reported. With the
+/// other protocols the new name is read from the command's output. A
+/// command running for longer than `opts.timeout` is reported as
+/// [`Error::Timeout`].
+pub async fn propose(name: &OsStr, command: &[OsString], opts: &RenameOpts) -> Result<PathBuf> {
+  if opts.protocol != Protocol::Rename {
+    let mut new = print_names(&[name.to_os_string()], command, opts).await?;
+    // SANITY: `print_names` reports exactly one path per name.
+    let new = new.pop().unwrap();
+    debug!(name = %name.to_string_lossy(), new = %new.display(), "proposed new path");
+    return Ok(new)
+  }
+
   let tmp = tempdir().context("failed to create temporary directory")?;
-  let path = canonicalize(file)
-    .await
-    .with_context(|| format!("failed to canonicalize `{}`", file.display()))?;
+  trace!(dir = %tmp.path().display(), "created temporary directory");
+  let tmp_file = tmp.path().join(name);
+  let result = if opts.directory {
+    create_dir(&tmp_file).await
+  } else {
+    write(&tmp_file, b"").await
+  };
+  let () = result.with_context(|| format!("failed to create `{}`", tmp_file.display()))?;
+
+  let (cmd, cmd_args) = command.split_first().context("rename command is missing")?;
+  let args = command_args(cmd_args, &[name.to_os_string()]);
+  let () = run_in(cmd, &args, tmp.path(), opts).await?;
+
+  let mut files = find_files(tmp.path(), opts.directory).await?;
+  match files.len() {
+    0 => Err(Error::NoFile {
+      name: name.to_os_string(),
+    }),
+    1 => {
+      // SANITY: We just checked that there is exactly one file.
+      let new = files.pop().unwrap();
+      debug!(name = %name.to_string_lossy(), new = %new.display(), "proposed new path");
+      Ok(new)
+    },
+    _ => {
+      let () = files.sort();
+      Err(Error::MultipleFiles {
+        name: name.to_os_string(),
+        files,
+      })
+    },
+  }
+}
+
+
+/// Determine the path that a file called `name` would be renamed to by
+/// applying each of `commands` in turn, as [`propose`] does, with each
+/// command receiving the name produced by the previous one.
+///
+/// Returned are the paths produced by each of the commands, relative
+/// to the directory containing the file; the last one is the final
+/// path.
+pub async fn propose_chain(
+  name: &OsStr,
+  commands: &[Vec<OsString>],
+  opts: &RenameOpts,
+) -> Result<Vec<PathBuf>> {
+  let mut steps = Vec::<PathBuf>::with_capacity(commands.len());
+  for command in commands {
+    let (dir, name) = match steps.last() {
+      Some(path) => {
+        let name = path
+          .file_name()
+          .with_context(|| format!("path `{}` does not have file name", path.display()))?;
+        (path.parent().unwrap_or(Path::new("")), name)
+      },
+      None => (Path::new(""), name),
+    };
+    let new = dir.join(propose(name, command, opts).await?);
+    let () = steps.push(new);
+  }
+  Ok(steps)
+}
+
+
+/// The maximum number of files passed to a single invocation of a
+/// rename command by [`propose_batch`].
+const PROPOSE_BATCH_SIZE: usize = 256;
+
+
+/// Determine the paths that the files called `names[idx]` for each
+/// `idx` in `chunk` would be renamed to by a single invocation of
+/// `command`, operating on files in a temporary directory.
+///
+/// Names in `chunk` have to be unique. Files removed by the command are
+/// not reported.
+async fn propose_chunk(
+  names: &[OsString],
+  chunk: &[usize],
+  command: &[OsString],
+  opts: &RenameOpts,
+) -> Result<Vec<(usize, PathBuf)>> {
+  let (cmd, cmd_args) = command.split_first().context("rename command is missing")?;
+  let tmp = tempdir().context("failed to create temporary directory")?;
+  trace!(dir = %tmp.path().display(), "created temporary directory");
+  for idx in chunk {
+    let tmp_file = tmp.path().join(&names[*idx]);
+    let () = write(&tmp_file, idx.to_string())
+      .await
+      .with_context(|| format!("failed to create `{}`", tmp_file.display()))?;
+  }
+
+  let chunk_names = chunk
+    .iter()
+    .map(|idx| names[*idx].clone())
+    .collect::<Vec<_>>();
+  let args = command_args(cmd_args, &chunk_names);
+  let () = run_in(cmd, &args, tmp.path(), opts).await?;
+
+  let mut proposals = HashMap::<usize, PathBuf>::new();
+  for path in find_files(tmp.path(), false).await? {
+    let tmp_file = tmp.path().join(&path);
+    let content = read(&tmp_file)
+      .await
+      .with_context(|| format!("failed to read `{}`", tmp_file.display()))?;
+    let idx = from_utf8(&content)
+      .ok()
+      .and_then(|idx| idx.parse::<usize>().ok())
+      .filter(|idx| chunk.contains(idx))
+      .ok_or_else(|| Error::Output {
+        command: format_command(cmd, cmd_args),
+        message: format!(
+          "produced `{}`, which is not the result of renaming a single file",
+          path.display()
+        ),
+      })?;
+    if let Some(other) = proposals.remove(&idx) {
+      let mut files = vec![other, path];
+      let () = files.sort();
+      return Err(Error::MultipleFiles {
+        name: names[idx].clone(),
+        files,
+      })
+    }
+    debug!(name = %names[idx].to_string_lossy(), new = %path.display(), "proposed new path");
+    let _prev = proposals.insert(idx, path);
+  }
+  Ok(proposals.into_iter().collect())
+}
+
+
+/// Determine the paths that files called `names` would be renamed to by
+/// `command`, as [`propose`] does, but invoking the command once for
+/// many files.
+///
+/// With [`Protocol::Rename`] the command has to accept multiple files
+/// as arguments. The files it operates on are told apart by their
+/// contents, which it must not change; [`Error::NoFile`] and
+/// [`Error::MultipleFiles`] are reported for files that got removed or
+/// duplicated. With the other
+/// protocols the command has to print one new name per line, in the
+/// order of names received. `opts.timeout` applies to each invocation
+/// of the command. Directories, as per `opts.directory`, can't be told
+/// apart by their contents and are handled one at a time, as by
+/// [`propose`].
+///
+/// Results are reported for each of `names`, in order. Should an
+/// invocation for many files fail as a whole, the command is invoked
+/// for each of them individually, so that only the files it fails for
+/// are reported as failed.
+pub async fn propose_batch(
+  names: &[OsString],
+  command: &[OsString],
+  opts: &RenameOpts,
+) -> Vec<Result<PathBuf>> {
+  if opts.directory && opts.protocol == Protocol::Rename {
+    let mut proposals = Vec::with_capacity(names.len());
+    for name in names {
+      let () = proposals.push(propose(name, command, opts).await);
+    }
+    return proposals
+  }
+
+  let mut proposals = names.iter().map(|_| None).collect::<Vec<_>>();
+  let mut pending = (0..names.len()).collect::<Vec<_>>();
+  // A command referring to the file name via the placeholder can only
+  // operate on a single file at a time.
+  let batch_size = match command.split_first() {
+    Some((_, args)) if has_placeholder(args) => 1,
+    _ => PROPOSE_BATCH_SIZE,
+  };
+
+  while !pending.is_empty() {
+    let chunk = if opts.protocol == Protocol::Rename {
+      // Names have to be unique within a temporary directory, so files
+      // of the same name are deferred to a later invocation.
+      let mut unique = HashSet::new();
+      let (chunk, rest) = pending
+        .into_iter()
+        .partition::<Vec<_>, _>(|idx| unique.len() < batch_size && unique.insert(&names[*idx]));
+      pending = rest;
+      chunk
+    } else {
+      let rest = pending.split_off(pending.len().min(batch_size));
+      replace(&mut pending, rest)
+    };
+
+    let result = if opts.protocol == Protocol::Rename {
+      propose_chunk(names, &chunk, command, opts).await
+    } else {
+      let chunk_names = chunk
+        .iter()
+        .map(|idx| names[*idx].clone())
+        .collect::<Vec<_>>();
+      print_names(&chunk_names, command, opts)
+        .await
+        .map(|new| chunk.iter().copied().zip(new).collect())
+    };
+
+    match result {
+      Ok(new) => {
+        for (idx, path) in new {
+          proposals[idx] = Some(Ok(path));
+        }
+      },
+      Err(err) if chunk.len() == 1 => proposals[chunk[0]] = Some(Err(err)),
+      Err(err) => {
+        debug!(
+          error = %err,
+          count = chunk.len(),
+          "invocation for many files failed; retrying them individually"
+        );
+        for idx in chunk {
+          proposals[idx] = Some(propose(&names[idx], command, opts).await);
+        }
+      },
+    }
+  }
+
+  names
+    .iter()
+    .zip(proposals)
+    .map(|(name, proposal)| proposal.unwrap_or_else(|| Err(Error::NoFile { name: name.clone() })))
+    .collect()
+}
+
+
+/// Rename a file using the provided command and options.
+///
+/// A directory is renamed as a whole, as `mv` would, with the command
+/// operating on an empty stand-in directory while determining the new
+/// name. The function returns the new name. Before the rename is
+/// performed on the live data, a conflict with an existing file is
+/// resolved as per `opts.on_conflict`. Should the rename get skipped as
+/// a result, the original path is returned. If the destination has to
+/// be adjusted, or if the command merely prints new names as per
+/// `opts.protocol`, the file is renamed directly. The same goes for
+/// special files, such as FIFOs and devices, which the command may
+/// otherwise attempt to read.
+pub async fn rename_with(file: &Path, command: &[OsString], opts: &RenameOpts) -> Result<PathBuf> {
+  let path = resolve(file, opts.symlinks).await?;
+  let metadata = symlink_metadata(&path).await.ok();
+  let special = metadata
+    .as_ref()
+    .and_then(|metadata| special_kind(&metadata.file_type()));
+  let opts = &RenameOpts {
+    directory: metadata.is_some_and(|metadata| metadata.is_dir()),
+    ..opts.clone().with_file(&path)
+  };
   let dir = path
     .parent()
     .with_context(|| format!("`{}` does not contain a parent", path.display()))?;
   let file = path
     .file_name()
     .with_context(|| format!("path `{}` does not have file name", path.display()))?;
-  let tmp_file = tmp.path().join(file);
-  let () = write(&tmp_file, b"")
-    .await
-    .with_context(|| format!("failed to create `{}`", tmp_file.display()))?;
 
+  // Perform the rename in a temporary directory first.
+  let new = propose(file, command, opts).await?;
+  let new_path = dir.join(new);
+
+  if !opts.dry_run {
+    let dst = match resolve_conflict(&path, &new_path, opts.on_conflict).await? {
+      Some(dst) => dst,
+      None => return Ok(path),
+    };
+    if let Some(suffix) = &opts.backup {
+      let _backup = backup(&path, suffix).await?;
+    }
+    let (cmd, cmd_args) = command.split_first().context("rename command is missing")?;
+    // An existing file only gets removed once the rename replacing it
+    // succeeded.
+    let displaced = if opts.on_conflict == ConflictPolicy::Overwrite {
+      displace(&path, &dst).await?
+    } else {
+      None
+    };
+
+    let result = if dst != new_path || opts.protocol != Protocol::Rename || special.is_some() {
+      retry(&opts.retry, || rename_to(&path, &dst))
+        .await
+        .map(|()| dst)
+    } else {
+      // Perform the rename on the live data.
+      debug!(src = %path.display(), dst = %new_path.display(), "applying rename via command");
+      let args = command_args(cmd_args, &[file.to_os_string()]);
+      retry(&opts.retry, || run_in(cmd, &args, dir, opts))
+        .await
+        .map(|()| new_path)
+    };
+    if let Some(displaced) = displaced {
+      let () = displaced.settle(result.is_ok()).await;
+    }
+    return result
+  }
+
+  Ok(new_path)
+}
+
+
+/// Assemble the command line that [`rename_with`] runs for renaming
+/// the file at `path` on the live data using `command`, as program
+/// followed by its arguments, wrapped in the sandbox with
+/// `opts.sandbox` set.
+///
+/// The command runs in the directory containing the file, which is
+/// returned along with the command line. `path` has to be resolved
+/// already, as by [`resolve`].
+pub fn command_line(
+  path: &Path,
+  command: &[OsString],
+  opts: &RenameOpts,
+) -> Result<(PathBuf, Vec<OsString>)> {
+  let dir = path
+    .parent()
+    .with_context(|| format!("`{}` does not contain a parent", path.display()))?;
+  let file = path
+    .file_name()
+    .with_context(|| format!("path `{}` does not have file name", path.display()))?;
   let (cmd, cmd_args) = command.split_first().context("rename command is missing")?;
-  // Perform the rename in our temporary directory.
-  let () = run_in(
-    cmd,
-    cmd_args.iter().chain([&file.to_os_string()]),
-    tmp.path(),
-  )
-  .await?;
-
-  let new = read_dir(tmp.path())
+  let args = command_args(cmd_args, &[file.to_os_string()]);
+  let (program, args) = invocation(cmd, args, dir, opts)?;
+  let line = [program].into_iter().chain(args).collect();
+  Ok((dir.to_path_buf(), line))
+}
+
+
+/// Create the missing parent directories of `path`.
+async fn create_parent(path: &Path) -> Result<()> {
+  match path.parent() {
+    Some(dir) if !dir.as_os_str().is_empty() => create_dir_all(dir)
+      .await
+      .with_context(|| format!("failed to create directory `{}`", dir.display())),
+    _ => Ok(()),
+  }
+}
+
+
+/// Rename `src` to `dst` directly, without involving a rename command.
+///
+/// The rename fails if `dst` already exists. Where supported by the
+/// operating system and file system, this guarantee is enforced
+/// atomically. Missing parent directories of `dst` are created.
+///
+/// Paths that seemingly share a directory may still reside on different
+/// mounts, e.g., because of bind mounts or overlay file systems as
+/// commonly used by containers. Renames can't cross mounts and so files
+/// (but not directories) are moved by copying them instead, which is
+/// not atomic.
+pub async fn rename_to(src: &Path, dst: &Path) -> Result<()> {
+  debug!(src = %src.display(), dst = %dst.display(), "renaming directly");
+  let () = create_parent(dst).await?;
+  let (from, to) = (src.to_path_buf(), dst.to_path_buf());
+  let result = spawn_blocking(move || rename_noreplace(&from, &to))
     .await
-    .with_context(|| {
+    .context("rename task failed")?;
+
+  match result {
+    Ok(()) => Ok(()),
+    Err(err) if err.kind() == ErrorKind::AlreadyExists => Err(Error::Exists {
+      src: src.to_path_buf(),
+      dst: dst.to_path_buf(),
+    }),
+    Err(err) if err.kind() == ErrorKind::CrossesDevices => move_across(src, dst, err).await,
+    Err(err) => Err(err).with_context(|| {
       format!(
-        "failed to read contents of directory `{}`",
-        tmp.path().display()
+        "failed to rename `{}` to `{}`",
+        src.display(),
+        dst.display()
       )
-    })?
-    .next_entry()
-    .await
-    .with_context(|| {
+    }),
+  }
+}
+
+/// Move the file `src` to `dst` on a different mount, by copying it to
+/// a temporary file next to `dst`, renaming that one, and removing
+/// `src` eventually.
+///
+/// `err` is the error reported by the rename that was attempted
+/// initially.
+async fn move_across(src: &Path, dst: &Path, err: io::Error) -> Result<()> {
+  let context = || {
+    format!(
+      "failed to move `{}` to `{}` on a different mount",
+      src.display(),
+      dst.display()
+    )
+  };
+
+  let metadata = symlink_metadata(src).await.with_context(context)?;
+  if metadata.is_dir() {
+    return Err(err).with_context(|| {
       format!(
-        "no file found in `{}`; did the rename operation delete instead?",
-        tmp.path().display()
+        "failed to rename directory `{}` to `{}`; they are located on different mounts (e.g., bind mounts or overlay file systems)",
+        src.display(),
+        dst.display()
       )
-    })?
-    .with_context(|| format!("failed to read first file of `{}`", tmp.path().display()))?;
+    })
+  }
 
-  if !dry_run {
-    // Perform the rename on the live data.
-    let () = run_in(cmd, cmd_args.iter().chain([&file.to_os_string()]), dir).await?;
+  if let Some(kind) = special_kind(&metadata.file_type()) {
+    return Err(err).with_context(|| {
+      format!(
+        "failed to rename {kind} `{}` to `{}`; they are located on different mounts and special files can't be copied",
+        src.display(),
+        dst.display()
+      )
+    })
   }
 
-  let new_path = dir.join(new.file_name());
-  Ok(new_path)
+  debug!(src = %src.display(), dst = %dst.display(), "rename crosses mounts; copying instead");
+  let tmp = temp_path(dst, "move")?;
+  let result = if metadata.is_symlink() {
+    copy_symlink(src, &tmp).await
+  } else {
+    copy(src, &tmp).await.map(|_size| ())
+  };
+  if let Err(err) = result {
+    let _result = remove_file(&tmp).await;
+    return Err(err).with_context(context)
+  }
+
+  let (from, to) = (tmp.clone(), dst.to_path_buf());
+  let result = spawn_blocking(move || rename_noreplace(&from, &to))
+    .await
+    .context("rename task failed")?;
+  match result {
+    Ok(()) => (),
+    Err(err) => {
+      let _result = remove_file(&tmp).await;
+      if err.kind() == ErrorKind::AlreadyExists {
+        return Err(Error::Exists {
+          src: src.to_path_buf(),
+          dst: dst.to_path_buf(),
+        })
+      }
+      return Err(err).with_context(context)
+    },
+  }
+
+  if let Err(err) = remove_file(src).await {
+    // Undo the move, so that the file does not end up existing twice.
+    if let Err(err) = remove_file(dst).await {
+      warn!("failed to remove `{}`: {err}", dst.display());
+    }
+    return Err(err).with_context(context)
+  }
+  Ok(())
+}
+
+/// Create a copy of the symbolic link `src` at `dst`.
+#[cfg(unix)]
+async fn copy_symlink(src: &Path, dst: &Path) -> io::Result<()> {
+  let target = read_link(src).await?;
+  symlink(target, dst).await
+}
+
+/// Symbolic links can't be copied faithfully on other systems.
+#[cfg(not(unix))]
+async fn copy_symlink(_src: &Path, _dst: &Path) -> io::Result<()> {
+  Err(io::Error::from(ErrorKind::Unsupported))
+}
+
+
+/// Preserve the file at `path` under its name with `suffix` appended,
+/// before it gets renamed, akin to `mv --backup`.
+///
+/// The backup is a hard link to the file where possible and a copy of
+/// it otherwise. Note that a hard link shares the file's contents and
+/// so only guards against renames, not against modifications in place.
+/// An existing backup is replaced. Returned is the path of the backup.
+///
+/// Directories are not supported: they cannot be hard linked and
+/// copying them would mean duplicating their entire contents.
+pub async fn backup(path: &Path, suffix: &OsStr) -> Result<PathBuf> {
+  if suffix.is_empty() {
+    return Err(Error::Invalid(
+      "backup suffix must not be empty".to_string(),
+    ))
+  }
+  if symlink_metadata(path).await.is_ok_and(|meta| meta.is_dir()) {
+    return Err(Error::Invalid(format!(
+      "cannot back up directory `{}`",
+      path.display()
+    )))
+  }
+
+  let mut name = path
+    .file_name()
+    .with_context(|| format!("path `{}` does not have file name", path.display()))?
+    .to_os_string();
+  let () = name.push(suffix);
+  let backup = path.with_file_name(name);
+
+  let result = match remove_file(&backup).await {
+    Ok(()) => Ok(()),
+    Err(err) if err.kind() == ErrorKind::NotFound => Ok(()),
+    Err(err) => Err(err),
+  };
+  let result = match result {
+    Ok(()) => match hard_link(path, &backup).await {
+      Ok(()) => Ok(()),
+      // Copying special files may block indefinitely.
+      Err(err) if special_file(path).is_some() => Err(err),
+      Err(_err) => copy(path, &backup).await.map(|_size| ()),
+    },
+    Err(err) => Err(err),
+  };
+  let () = result.with_context(|| {
+    format!(
+      "failed to back up `{}` to `{}`",
+      path.display(),
+      backup.display()
+    )
+  })?;
+  Ok(backup)
+}
+
+
+/// Exchange the names of the files `a` and `b`.
+///
+/// Where supported by the operating system and file system, the
+/// exchange happens atomically. Otherwise it is emulated by renaming
+/// `a` to a temporary name, `b` to `a`, and finally the temporary name
+/// to `b`.
+pub async fn exchange(a: &Path, b: &Path) -> Result<()> {
+  let (x, y) = (a.to_path_buf(), b.to_path_buf());
+  let result = spawn_blocking(move || rename_exchange(&x, &y))
+    .await
+    .context("exchange task failed")?;
+
+  match result {
+    Ok(()) => Ok(()),
+    Err(err) if err.kind() == ErrorKind::Unsupported => {
+      debug!("atomic exchange unsupported; exchanging via temporary name");
+      exchange_via_temp(a, b).await
+    },
+    Err(err) => Err(err)
+      .with_context(|| format!("failed to exchange `{}` and `{}`", a.display(), b.display())),
+  }
+}
+
+/// Derive a hidden, temporary path for `path`, located next to it.
+pub(crate) fn temp_path(path: &Path, purpose: &str) -> Result<PathBuf> {
+  let name = path
+    .file_name()
+    .with_context(|| format!("path `{}` does not have file name", path.display()))?;
+  let mut tmp_name = OsString::from(".");
+  let () = tmp_name.push(name);
+  let () = tmp_name.push(format!(".{}.{purpose}", process::id()));
+  Ok(path.with_file_name(tmp_name))
+}
+
+/// Exchange the names of the files `a` and `b` by means of a temporary
+/// name.
+async fn exchange_via_temp(a: &Path, b: &Path) -> Result<()> {
+  let tmp = temp_path(a, "swap")?;
+
+  let () = rename_to(a, &tmp).await?;
+  if let Err(err) = rename_to(b, a).await {
+    // Try restoring the original state. There is nothing we can do
+    // should that fail as well.
+    if let Err(err) = rename_to(&tmp, a).await {
+      warn!("failed to restore `{}`: {err:#}", a.display());
+    }
+    return Err(err)
+  }
+  rename_to(&tmp, b).await
+}
+
+
+/// Rename each source path in `renames` to its destination directly,
+/// without involving a rename command.
+///
+/// Each rename fails if its destination exists. Missing parent
+/// directories of destinations are created. Renames are considered
+/// independent of each other and may be performed in any order. When
+/// built with the `io-uring` feature on Linux, they are submitted to
+/// the kernel in batches, which is considerably faster for large
+/// numbers of files on local file systems. Otherwise, or if io_uring is
+/// unavailable, they are performed one by one.
+///
+/// The outcome of each rename is reported in the order of `renames`.
+pub async fn rename_batch(renames: &[(PathBuf, PathBuf)]) -> Vec<Result<()>> {
+  #[cfg(all(target_os = "linux", feature = "io-uring"))]
+  {
+    // Directories are created upfront; failures surface as failed
+    // renames.
+    let dirs = renames
+      .iter()
+      .filter_map(|(_src, dst)| dst.parent())
+      .collect::<HashSet<_>>();
+    for dir in dirs {
+      let _result = create_dir_all(dir).await;
+    }
+
+    debug!(count = renames.len(), "submitting renames via io_uring");
+    let batch = renames.to_vec();
+    if let Ok(Some(results)) = spawn_blocking(move || uring::rename_batch(&batch)).await {
+      let mut outcomes = Vec::with_capacity(renames.len());
+      for ((src, dst), result) in renames.iter().zip(results) {
+        let outcome = match result {
+          // The file system does not support renaming without
+          // replacement or the rename crosses mounts; let `rename_to`
+          // fall back as appropriate.
+          Err(err)
+            if err.raw_os_error() == Some(libc::EINVAL)
+              || err.kind() == ErrorKind::CrossesDevices =>
+          {
+            rename_to(src, dst).await
+          },
+          result => result.with_context(|| {
+            format!(
+              "failed to rename `{}` to `{}`",
+              src.display(),
+              dst.display()
+            )
+          }),
+        };
+        let () = outcomes.push(outcome);
+      }
+      return outcomes
+    }
+  }
+
+  let mut results = Vec::with_capacity(renames.len());
+  for (src, dst) in renames {
+    let () = results.push(rename_to(src, dst).await);
+  }
+  results
+}
+
+
+/// A log of the individual renames performed as part of a batch, e.g.,
+/// for making them recoverable should the process get interrupted.
+///
+/// Each rename is announced before it is attempted and its outcome
+/// recorded afterwards.
+pub trait Log {
+  /// Record that `src` is about to be renamed to `dst`. A failure to do
+  /// so prevents the rename.
+  fn intend(&self, src: &Path, dst: &Path) -> Result<()>;
+
+  /// Record that `src` got renamed to `dst`.
+  ///
+  /// Recording may involve reading `dst`, e.g., for calculating a
+  /// digest of its contents, which is why it happens asynchronously.
+  fn complete(&self, src: &Path, dst: &Path) -> impl Future<Output = Result<()>>;
+
+  /// Record that `src` did not get renamed to `dst` after all.
+  fn abandon(&self, src: &Path, dst: &Path) -> Result<()>;
+}
+
+impl Log for () {
+  #[inline]
+  fn intend(&self, _src: &Path, _dst: &Path) -> Result<()> {
+    Ok(())
+  }
+
+  #[inline]
+  async fn complete(&self, _src: &Path, _dst: &Path) -> Result<()> {
+    Ok(())
+  }
+
+  #[inline]
+  fn abandon(&self, _src: &Path, _dst: &Path) -> Result<()> {
+    Ok(())
+  }
+}
+
+impl<L> Log for Option<&L>
+where
+  L: Log + ?Sized,
+{
+  fn intend(&self, src: &Path, dst: &Path) -> Result<()> {
+    self.map_or(Ok(()), |log| log.intend(src, dst))
+  }
+
+  async fn complete(&self, src: &Path, dst: &Path) -> Result<()> {
+    match self {
+      Some(log) => log.complete(src, dst).await,
+      None => Ok(()),
+    }
+  }
+
+  fn abandon(&self, src: &Path, dst: &Path) -> Result<()> {
+    self.map_or(Ok(()), |log| log.abandon(src, dst))
+  }
+}
+
+
+/// Record the outcome of renaming `src` to `dst` in `log`.
+async fn settle<L>(log: &L, src: &Path, dst: &Path, result: Result<()>) -> Result<()>
+where
+  L: Log + ?Sized,
+{
+  match result {
+    Ok(()) => log.complete(src, dst).await,
+    Err(err) => {
+      // Should recording fail, the rename stays pending in the log,
+      // with `src` still in place to tell that it did not happen.
+      let _result = log.abandon(src, dst);
+      Err(err)
+    },
+  }
+}
+
+
+/// Rename `src` to `dst` as per [`rename_to`], retrying according to
+/// `policy` and recording the rename in `log`.
+async fn rename_logged<L>(src: &Path, dst: &Path, policy: &RetryPolicy, log: &L) -> Result<()>
+where
+  L: Log + ?Sized,
+{
+  let () = log.intend(src, dst)?;
+  let result = retry(policy, || rename_to(src, dst)).await;
+  settle(log, src, dst, result).await
+}
+
+
+/// Move `src` out of the way to a temporary path next to it, retrying
+/// according to `policy` and recording the rename in `log`.
+async fn park<L>(src: &Path, policy: &RetryPolicy, log: &L) -> Result<PathBuf>
+where
+  L: Log + ?Sized,
+{
+  let tmp = temp_path(src, "cycle")?;
+  let () = rename_logged(src, &tmp, policy, log).await?;
+  Ok(tmp)
+}
+
+
+/// Rename each source path in `renames` to its destination directly,
+/// without involving a rename command, where renames may depend on
+/// each other.
+///
+/// Renames are applied in an order that allows files to take the place
+/// of others being renamed, with cycles of renames broken by moving one
+/// file of each to a temporary name first (see [`plan::untangle`]).
+/// Renames not involving the source or destination of any other are
+/// performed as by [`rename_batch`]. Each fails if its destination
+/// exists and transient failures are retried according to `policy`.
+///
+/// If some of the sources are located inside of directories being
+/// renamed, renames are performed level by level, deepest first, so
+/// that no rename invalidates the source of another.
+///
+/// Each individual rename performed (including those to and from
+/// temporary names) is recorded in `log`, in the order they happen,
+/// e.g., in a way that allows for undoing them. Returned is the
+/// outcome of each rename, in the order of `renames`.
+pub async fn rename_ordered<L>(
+  renames: &[(PathBuf, PathBuf)],
+  policy: &RetryPolicy,
+  log: &L,
+) -> Vec<Result<()>>
+where
+  L: Log + ?Sized,
+{
+  let sources = renames
+    .iter()
+    .map(|(src, _dst)| src.as_path())
+    .collect::<HashSet<_>>();
+  let nested = sources
+    .iter()
+    .any(|src| src.ancestors().skip(1).any(|dir| sources.contains(dir)));
+  if !nested {
+    return rename_level(renames, policy, log).await
+  }
+
+  // Sources at the same depth can't be located inside of each other.
+  let mut levels = BTreeMap::<_, Vec<_>>::new();
+  for (idx, (src, _dst)) in renames.iter().enumerate() {
+    let () = levels
+      .entry(Reverse(src.components().count()))
+      .or_default()
+      .push(idx);
+  }
+
+  let mut results = renames.iter().map(|_| None).collect::<Vec<_>>();
+  for indices in levels.into_values() {
+    let level = indices
+      .iter()
+      .map(|idx| renames[*idx].clone())
+      .collect::<Vec<_>>();
+    for (idx, result) in indices
+      .into_iter()
+      .zip(rename_level(&level, policy, log).await)
+    {
+      results[idx] = Some(result);
+    }
+  }
+  // SANITY: Each rename is part of exactly one level.
+  results.into_iter().map(Option::unwrap).collect()
+}
+
+
+/// Rename each source path in `renames` to its destination directly,
+/// as [`rename_ordered`] does, overwriting the existing files at the
+/// destinations in `overwrite`.
+///
+/// A file about to be overwritten is moved out of the way first (see
+/// [`displace`]) and only removed once the rename taking its place
+/// succeeded, getting restored otherwise. Destinations that are the
+/// source of another rename are moved away instead. Returned is the
+/// outcome of each rename, in the order of `renames`.
+pub async fn rename_replacing<L>(
+  renames: &[(PathBuf, PathBuf)],
+  overwrite: &HashSet<PathBuf>,
+  policy: &RetryPolicy,
+  log: &L,
+) -> Vec<Result<()>>
+where
+  L: Log + ?Sized,
+{
+  let sources = renames
+    .iter()
+    .map(|(src, _dst)| src)
+    .collect::<HashSet<_>>();
+  let mut results = renames.iter().map(|_| None).collect::<Vec<_>>();
+  let mut displaced = renames.iter().map(|_| None).collect::<Vec<_>>();
+  let mut indices = Vec::with_capacity(renames.len());
+  for (idx, (src, dst)) in renames.iter().enumerate() {
+    if overwrite.contains(dst) && !sources.contains(dst) {
+      match displace(src, dst).await {
+        Ok(file) => displaced[idx] = file,
+        Err(err) => {
+          results[idx] = Some(Err(err));
+          continue
+        },
+      }
+    }
+    let () = indices.push(idx);
+  }
+
+  let batch = indices
+    .iter()
+    .map(|idx| renames[*idx].clone())
+    .collect::<Vec<_>>();
+  for (idx, result) in indices
+    .into_iter()
+    .zip(rename_ordered(&batch, policy, log).await)
+  {
+    if let Some(file) = displaced[idx].take() {
+      let () = file.settle(result.is_ok()).await;
+    }
+    results[idx] = Some(result);
+  }
+  // SANITY: Each rename either failed to displace its destination or
+  //         got attempted.
+  results.into_iter().map(Option::unwrap).collect()
+}
+
+
+/// Rename each source path in `renames` to its destination directly,
+/// as [`rename_ordered`] does, for renames none of whose sources are
+/// located inside of another.
+async fn rename_level<L>(
+  renames: &[(PathBuf, PathBuf)],
+  policy: &RetryPolicy,
+  log: &L,
+) -> Vec<Result<()>>
+where
+  L: Log + ?Sized,
+{
+  let sources = renames
+    .iter()
+    .map(|(src, _dst)| src)
+    .collect::<HashSet<_>>();
+  let destinations = renames
+    .iter()
+    .map(|(_src, dst)| dst)
+    .collect::<HashSet<_>>();
+  let (independent, dependent) =
+    renames
+      .iter()
+      .cloned()
+      .enumerate()
+      .partition::<Vec<_>, _>(|(_idx, (src, dst))| {
+        !sources.contains(dst) && !destinations.contains(src)
+      });
+
+  let mut results = renames.iter().map(|_| None).collect::<Vec<_>>();
+
+  // All renames of the batch are announced before any is attempted.
+  let mut intended = Vec::with_capacity(independent.len());
+  for (idx, (src, dst)) in independent {
+    match log.intend(&src, &dst) {
+      Ok(()) => intended.push((idx, (src, dst))),
+      Err(err) => results[idx] = Some(Err(err)),
+    }
+  }
+
+  let (indices, batch) = intended.into_iter().unzip::<_, _, Vec<_>, Vec<_>>();
+  for ((idx, (src, dst)), result) in indices
+    .into_iter()
+    .zip(&batch)
+    .zip(rename_batch(&batch).await)
+  {
+    let result = match result {
+      Err(err) if is_transient(&err) => retry(policy, || rename_to(src, dst)).await,
+      result => result,
+    };
+    results[idx] = Some(settle(log, src, dst, result).await);
+  }
+
+  let (indices, dependent) = dependent.into_iter().unzip::<_, _, Vec<_>, Vec<_>>();
+  let mut parked = HashMap::new();
+  for step in untangle(&dependent) {
+    let (pos, result) = match step {
+      Step::Rename(pos) => {
+        let (src, dst) = &dependent[pos];
+        (pos, rename_logged(src, dst, policy, log).await)
+      },
+      Step::Park(pos) => {
+        let src = &dependent[pos].0;
+        match park(src, policy, log).await {
+          Ok(tmp) => {
+            let _prev = parked.insert(pos, tmp);
+            continue
+          },
+          Err(err) => (pos, Err(err)),
+        }
+      },
+      Step::Unpark(pos) => {
+        let dst = &dependent[pos].1;
+        // A file failing to be parked is reported as failed already.
+        let Some(tmp) = parked.remove(&pos) else {
+          continue
+        };
+        // The error names the temporary path, should the file remain
+        // there.
+        (pos, rename_logged(&tmp, dst, policy, log).await)
+      },
+    };
+    results[indices[pos]] = Some(result);
+  }
+
+  renames
+    .iter()
+    .zip(results)
+    .map(|((src, dst), result)| {
+      // SANITY: Each rename is performed as part of the batch or as
+      //         one of the steps.
+      result.unwrap_or_else(|| {
+        Err(Error::Invalid(format!(
+          "`{}` was not renamed to `{}`",
+          src.display(),
+          dst.display()
+        )))
+      })
+    })
+    .collect()
+}
+
+
+/// Rename all files in `files` using `command`, reporting progress to
+/// `progress`.
+///
+/// New names of all files are determined upfront and concurrently,
+/// before any file gets renamed. The renames are then applied directly,
+/// without running `command` on the actual files, in an order that
+/// allows files to take the place of others being renamed. Conflicts
+/// with existing files are resolved according to `opts.on_conflict` and
+/// transient failures are retried according to `opts.retry`.
+///
+/// `progress` is invoked each time a file has been previewed, renamed,
+/// or failed. Returned is the outcome for each file, in the order of
+/// `files`: its new path, which is the original (resolved) path if the
+/// name does not change or the rename got skipped.
+pub async fn rename_many<F>(
+  files: &[PathBuf],
+  command: &[OsString],
+  opts: &RenameOpts,
+  progress: F,
+) -> Vec<Result<PathBuf>>
+where
+  F: Fn(Progress),
+{
+  let mut state = Progress::new(files.len());
+
+  let preview_opts = RenameOpts {
+    dry_run: true,
+    ..opts.clone()
+  };
+  let preview_opts = &preview_opts;
+  let total = files.len();
+  let previews = stream::iter(files.iter().zip(1..)).map(|(file, index)| async move {
+    let src = resolve(file, preview_opts.symlinks).await?;
+    let opts = preview_opts.clone().with_position(index, total);
+    let dst = rename_with(&src, command, &opts).await?;
+    Ok((src, dst))
+  });
+  let mut previews = buffered(previews, opts.plan_concurrency);
+
+  let mut outcomes = Vec::with_capacity(files.len());
+  while let Some(outcome) = previews.next().await {
+    if outcome.is_ok() {
+      state.previewed += 1;
+    } else {
+      state.failed += 1;
+    }
+    let () = progress(state);
+    let () = outcomes.push(outcome);
+  }
+
+  // The position of each file to rename within `files`, by source. A
+  // file listed multiple times is only renamed for its first listing.
+  let mut positions = HashMap::with_capacity(outcomes.len());
+  let mut results = Vec::with_capacity(outcomes.len());
+  let mut renames = Vec::with_capacity(outcomes.len());
+  for (idx, outcome) in outcomes.into_iter().enumerate() {
+    let result = match outcome {
+      Ok((src, dst)) => {
+        if positions.contains_key(&src) {
+          Some(Err(Error::Invalid(format!(
+            "`{}` is listed more than once",
+            src.display()
+          ))))
+        } else if src == dst || opts.dry_run {
+          Some(Ok(dst))
+        } else {
+          let _prev = positions.insert(src.clone(), idx);
+          let () = renames.push((src, dst));
+          None
+        }
+      },
+      Err(err) => Some(Err(err)),
+    };
+    let () = results.push(result);
+  }
+  if opts.dry_run {
+    // SANITY: No renames are performed on a dry run and all results
+    //         are present.
+    return results.into_iter().map(Option::unwrap).collect()
+  }
+
+  let (swaps, renames) = split_swaps(renames);
+  for (a, b) in swaps {
+    let result = async {
+      if let Some(suffix) = &opts.backup {
+        let _backup = backup(&a, suffix).await?;
+        let _backup = backup(&b, suffix).await?;
+      }
+      retry(&opts.retry, || exchange(&a, &b)).await
+    }
+    .await;
+    let (a_idx, b_idx) = (positions[&a], positions[&b]);
+    match result {
+      Ok(()) => {
+        results[a_idx] = Some(Ok(b));
+        results[b_idx] = Some(Ok(a));
+        state.applied += 2;
+      },
+      Err(err) => {
+        let skipped = Error::Invalid(format!(
+          "`{}` was not renamed because swapping it with `{}` failed",
+          b.display(),
+          a.display()
+        ));
+        results[a_idx] = Some(Err(err));
+        results[b_idx] = Some(Err(skipped));
+        state.failed += 2;
+      },
+    }
+    let () = progress(state);
+  }
+
+  let mut resolved = Vec::with_capacity(renames.len());
+  for (src, dst) in renames {
+    let idx = positions[&src];
+    // A destination that is yet to be renamed is going to be moved
+    // away and does not constitute a conflict.
+    let result = if positions.contains_key(&dst) {
+      Ok(Some(dst))
+    } else {
+      resolve_conflict(&src, &dst, opts.on_conflict).await
+    };
+    match result {
+      Ok(Some(dst)) => resolved.push((idx, src, dst)),
+      Ok(None) => {
+        state.applied += 1;
+        let () = progress(state);
+        results[idx] = Some(Ok(src));
+      },
+      Err(err) => {
+        state.failed += 1;
+        let () = progress(state);
+        results[idx] = Some(Err(err));
+      },
+    }
+  }
+
+  if let Some(suffix) = &opts.backup {
+    let mut backed_up = Vec::with_capacity(resolved.len());
+    for (idx, src, dst) in resolved {
+      match backup(&src, suffix).await {
+        Ok(_backup) => backed_up.push((idx, src, dst)),
+        Err(err) => {
+          state.failed += 1;
+          let () = progress(state);
+          results[idx] = Some(Err(err));
+        },
+      }
+    }
+    resolved = backed_up;
+  }
+
+  let (indices, resolved) = resolved
+    .into_iter()
+    .map(|(idx, src, dst)| (idx, (src, dst)))
+    .unzip::<_, _, Vec<_>, Vec<_>>();
+  let overwrite = if opts.on_conflict == ConflictPolicy::Overwrite {
+    resolved.iter().map(|(_src, dst)| dst.clone()).collect()
+  } else {
+    HashSet::new()
+  };
+  let outcomes = rename_replacing(&resolved, &overwrite, &opts.retry, &()).await;
+  for ((idx, (_src, dst)), result) in indices.into_iter().zip(resolved).zip(outcomes) {
+    if result.is_ok() {
+      state.applied += 1;
+    } else {
+      state.failed += 1;
+    }
+    let () = progress(state);
+    results[idx] = Some(result.map(|()| dst));
+  }
+
+  results
+    .into_iter()
+    .zip(files)
+    .map(|(result, file)| {
+      // SANITY: Each file is renamed as part of a swap or individually.
+      result.unwrap_or_else(|| {
+        Err(Error::Invalid(format!(
+          "`{}` was not renamed",
+          file.display()
+        )))
+      })
+    })
+    .collect()
+}
+
+
+/// The outcome of renaming a single file, as reported by
+/// [`rename_stream`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RenameOutcome {
+  /// The (resolved) path of the file before the rename.
+  pub src: PathBuf,
+  /// The new path of the file, which is `src` if the name does not
+  /// change or the rename got skipped.
+  pub dst: PathBuf,
+}
+
+
+/// Rename all files in `files` using `command`, reporting the outcome
+/// of each rename as soon as it is available.
+///
+/// Files are renamed individually as per [`rename_with`], with
+/// `opts.apply_concurrency` governing how many are processed
+/// concurrently.
+/// Outcomes are produced in the order of `files`, so that failures can
+/// be attributed to the file they occurred for. Unlike with
+/// [`rename_many`], renames are not ordered with respect to each other,
+/// i.e., a file may not take the place of another one being renamed.
+pub fn rename_stream<'input>(
+  files: &'input [PathBuf],
+  command: &'input [OsString],
+  opts: &'input RenameOpts,
+) -> impl Stream<Item = Result<RenameOutcome>> + 'input {
+  let total = files.len();
+  let renames = stream::iter(files.iter().zip(1..)).map(move |(file, index)| async move {
+    let src = resolve(file, opts.symlinks).await?;
+    let opts = opts.clone().with_position(index, total);
+    let dst = rename_with(&src, command, &opts).await?;
+    Ok(RenameOutcome { src, dst })
+  });
+  buffered(renames, opts.apply_concurrency)
+}
+
+
+/// Revert the renames in `done`, in reverse order, recording each in
+/// `log`.
+async fn roll_back<L>(done: &[(&Path, &Path)], policy: &RetryPolicy, log: &L) -> Result<()>
+where
+  L: Log + ?Sized,
+{
+  for (src, dst) in done.iter().rev() {
+    let () = rename_logged(dst, src, policy, log).await?;
+  }
+  Ok(())
+}
+
+
+/// A transaction of renames, prepared but not yet committed.
+///
+/// Preparing a transaction moves each source to a temporary name next
+/// to it. Committing it moves these files on to their destinations,
+/// while aborting it moves them back. In between, the sources are gone
+/// but the destinations not yet visible, which allows for interleaving
+/// the commit of another system, such as a database referring to the
+/// files, with the renames.
+///
+/// Existing files overwritten by the transaction are moved to temporary
+/// names as part of preparing it as well. They are only removed once
+/// the transaction got committed and restored should it be aborted.
+///
+/// A transaction that is dropped without being committed or aborted
+/// leaves its files at their temporary names. With a [`Log`], these
+/// renames are recorded and can be recovered from.
+#[derive(Debug)]
+#[must_use = "files stay at temporary names unless the transaction is committed or aborted"]
+pub struct Transaction<'log, L>
+where
+  L: ?Sized,
+{
+  /// The renames making up the transaction.
+  renames: Vec<(PathBuf, PathBuf)>,
+  /// The temporary path of the file of each rename.
+  staged: Vec<PathBuf>,
+  /// The existing files to be overwritten by renames.
+  displaced: Vec<Displaced>,
+  /// The policy for retrying transient failures.
+  policy: RetryPolicy,
+  /// The log recording each individual step.
+  log: &'log L,
+}
+
+impl<'log, L> Transaction<'log, L>
+where
+  L: Log + ?Sized,
+{
+  /// Prepare a transaction of the renames in `renames`, by moving each
+  /// source to a temporary name next to it.
+  ///
+  /// Each step is retried according to `policy` and recorded in `log`
+  /// (including those of a roll back). Should one fail, the steps
+  /// performed so far are reverted and the returned error names the
+  /// rename that caused the abort.
+  pub async fn prepare(
+    renames: &[(PathBuf, PathBuf)],
+    policy: &RetryPolicy,
+    log: &'log L,
+  ) -> Result<Self> {
+    Self::prepare_replacing(renames, &HashSet::new(), policy, log).await
+  }
+
+  /// Prepare a transaction of the renames in `renames`, as per
+  /// [`Transaction::prepare`], overwriting the existing files at the
+  /// destinations in `overwrite`.
+  ///
+  /// The files to overwrite are moved out of the way after all sources
+  /// got staged (see [`displace`]).
+  pub async fn prepare_replacing(
+    renames: &[(PathBuf, PathBuf)],
+    overwrite: &HashSet<PathBuf>,
+    policy: &RetryPolicy,
+    log: &'log L,
+  ) -> Result<Self> {
+    let mut sources = HashSet::with_capacity(renames.len());
+    let mut destinations = HashSet::with_capacity(renames.len());
+    for (src, dst) in renames {
+      if !sources.insert(src) {
+        return Err(Error::Invalid(format!(
+          "`{}` is to be renamed more than once",
+          src.display()
+        )))
+      }
+      if !destinations.insert(dst) {
+        return Err(Error::Invalid(format!(
+          "multiple files would be renamed to `{}`",
+          dst.display()
+        )))
+      }
+    }
+
+    let staged = renames
+      .iter()
+      .map(|(src, _dst)| temp_path(src, "stage"))
+      .collect::<Result<Vec<_>>>()?;
+
+    let mut done = Vec::with_capacity(renames.len());
+    for ((src, dst), tmp) in renames.iter().zip(&staged) {
+      if let Err(err) = rename_logged(src, tmp, policy, log).await {
+        let rollback = roll_back(&done, policy, log).await.err().map(Box::new);
+        return Err(Error::Transaction {
+          src: src.clone(),
+          dst: dst.clone(),
+          source: Box::new(err),
+          rollback,
+        })
+      }
+      let () = done.push((src.as_path(), tmp.as_path()));
+    }
+
+    let mut displaced = Vec::new();
+    for (src, dst) in renames.iter().filter(|(_src, dst)| overwrite.contains(dst)) {
+      match displace(src, dst).await {
+        Ok(file) => displaced.extend(file),
+        Err(err) => {
+          let () = restore(displaced).await;
+          let rollback = roll_back(&done, policy, log).await.err().map(Box::new);
+          return Err(Error::Transaction {
+            src: src.clone(),
+            dst: dst.clone(),
+            source: Box::new(err),
+            rollback,
+          })
+        },
+      }
+    }
+
+    let slf = Self {
+      renames: renames.to_vec(),
+      staged,
+      displaced,
+      policy: *policy,
+      log,
+    };
+    Ok(slf)
+  }
+
+  /// Retrieve the renames making up the transaction.
+  #[inline]
+  pub fn renames(&self) -> &[(PathBuf, PathBuf)] {
+    &self.renames
+  }
+
+  /// Commit the transaction, by moving each file on to its
+  /// destination.
+  ///
+  /// Should a rename fail, all renames of the transaction are
+  /// reverted, leaving the file system in its original state, and the
+  /// returned error names the rename that caused the abort.
+  pub async fn commit(self) -> Result<()> {
+    let mut done = self
+      .renames
+      .iter()
+      .zip(&self.staged)
+      .map(|((src, _dst), tmp)| (src.as_path(), tmp.as_path()))
+      .collect::<Vec<_>>();
+
+    for ((src, dst), tmp) in self.renames.iter().zip(&self.staged) {
+      if let Err(err) = rename_logged(tmp, dst, &self.policy, self.log).await {
+        let rollback = roll_back(&done, &self.policy, self.log)
+          .await
+          .err()
+          .map(Box::new);
+        // Overwritten files can only be restored once the renames
+        // taking their place got reverted.
+        let () = restore(self.displaced).await;
+        return Err(Error::Transaction {
+          src: src.clone(),
+          dst: dst.clone(),
+          source: Box::new(err),
+          rollback,
+        })
+      }
+      let () = done.push((tmp.as_path(), dst.as_path()));
+    }
+
+    for file in self.displaced {
+      let () = file.settle(true).await;
+    }
+    Ok(())
+  }
+
+  /// Abort the transaction, by moving each file back to its original
+  /// path.
+  pub async fn abort(self) -> Result<()> {
+    let done = self
+      .renames
+      .iter()
+      .zip(&self.staged)
+      .map(|((src, _dst), tmp)| (src.as_path(), tmp.as_path()))
+      .collect::<Vec<_>>();
+    let result = roll_back(&done, &self.policy, self.log).await;
+    let () = restore(self.displaced).await;
+    result
+  }
+}
+
+
+/// Restore the files in `displaced`, after the renames overwriting them
+/// did not happen.
+async fn restore(displaced: Vec<Displaced>) {
+  for file in displaced {
+    let () = file.settle(false).await;
+  }
+}
+
+
+/// Apply all renames in `renames` as a single transaction, without
+/// involving a rename command.
+///
+/// Renames are staged by first moving each source to a temporary name
+/// next to it and only then to its destination. As a result, renames
+/// may depend on each other arbitrarily, including chains and cycles.
+/// Each step is retried according to `policy`. Should one fail, all
+/// renames performed so far are reverted, leaving the file system in
+/// its original state, and the returned error names the rename that
+/// caused the abort.
+///
+/// Use [`Transaction`] for performing other work in between staging
+/// and applying the renames.
+pub async fn rename_transaction(
+  renames: &[(PathBuf, PathBuf)],
+  policy: &RetryPolicy,
+) -> Result<()> {
+  rename_transaction_logged(renames, policy, &()).await
+}
+
+
+/// Apply all renames in `renames` as a single transaction, as per
+/// [`rename_transaction`], recording each individual step (including
+/// those of a roll back) in `log`.
+pub async fn rename_transaction_logged<L>(
+  renames: &[(PathBuf, PathBuf)],
+  policy: &RetryPolicy,
+  log: &L,
+) -> Result<()>
+where
+  L: Log + ?Sized,
+{
+  rename_transaction_replacing(renames, &HashSet::new(), policy, log).await
+}
+
+
+/// Apply all renames in `renames` as a single transaction, as per
+/// [`rename_transaction_logged`], overwriting the existing files at the
+/// destinations in `overwrite`.
+///
+/// Overwritten files are only removed once all renames got applied and
+/// restored should the transaction be aborted.
+pub async fn rename_transaction_replacing<L>(
+  renames: &[(PathBuf, PathBuf)],
+  overwrite: &HashSet<PathBuf>,
+  policy: &RetryPolicy,
+  log: &L,
+) -> Result<()>
+where
+  L: Log + ?Sized,
+{
+  Transaction::prepare_replacing(renames, overwrite, policy, log)
+    .await?
+    .commit()
+    .await
 }
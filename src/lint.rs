@@ -0,0 +1,324 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Checks flagging planned renames that may warrant a second look.
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Result as FmtResult;
+use std::path::Path;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::sync::PoisonError;
+
+use crate::error::Error;
+use crate::error::Result;
+
+
+/// A kind of warning about a planned rename.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Warning {
+  /// Multiple files are to be renamed to the same path.
+  Collision,
+  /// The new file name retains little of the old one.
+  BigChange,
+  /// The extension of the file changes.
+  ExtensionChange,
+  /// The file becomes hidden or stops being hidden.
+  HiddenChange,
+  /// The file is to be moved to a different file system, which
+  /// requires copying it.
+  CrossDevice,
+}
+
+impl Warning {
+  /// All warnings, in the order their checks are run.
+  pub const ALL: [Self; 5] = [
+    Self::Collision,
+    Self::BigChange,
+    Self::ExtensionChange,
+    Self::HiddenChange,
+    Self::CrossDevice,
+  ];
+}
+
+impl FromStr for Warning {
+  type Err = Error;
+
+  fn from_str(s: &str) -> Result<Self> {
+    match s {
+      "collision" => Ok(Self::Collision),
+      "big-change" => Ok(Self::BigChange),
+      "extension-change" => Ok(Self::ExtensionChange),
+      "hidden-change" => Ok(Self::HiddenChange),
+      "cross-device" => Ok(Self::CrossDevice),
+      _ => Err(Error::Invalid(format!(
+        "unsupported warning `{s}`; expected one of `collision`, `big-change`, `extension-change`, `hidden-change`, or `cross-device`"
+      ))),
+    }
+  }
+}
+
+impl Display for Warning {
+  fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+    let s = match self {
+      Self::Collision => "collision",
+      Self::BigChange => "big-change",
+      Self::ExtensionChange => "extension-change",
+      Self::HiddenChange => "hidden-change",
+      Self::CrossDevice => "cross-device",
+    };
+    f.write_str(s)
+  }
+}
+
+
+/// The severity of a warning.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Severity {
+  /// The warning is not reported.
+  Allow,
+  /// The warning is reported, but the rename may proceed.
+  #[default]
+  Warn,
+  /// The warning is treated as an error, preventing the rename.
+  Deny,
+}
+
+impl Display for Severity {
+  fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+    let s = match self {
+      Self::Allow => "allow",
+      Self::Warn => "warning",
+      Self::Deny => "error",
+    };
+    f.write_str(s)
+  }
+}
+
+
+/// A warning reported about a planned rename.
+#[derive(Clone, Debug)]
+pub struct Diagnostic {
+  /// The kind of warning.
+  pub warning: Warning,
+  /// The severity the warning is reported with.
+  pub severity: Severity,
+  /// The path of the file to rename.
+  pub src: PathBuf,
+  /// The planned new path of the file.
+  pub dst: PathBuf,
+  /// A description of the problem.
+  pub message: String,
+}
+
+impl Display for Diagnostic {
+  fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+    write!(f, "{}: {} [{}]", self.severity, self.message, self.warning)
+  }
+}
+
+
+/// Calculate the number of single character insertions, deletions,
+/// and substitutions needed to turn `a` into `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+  let b = b.chars().collect::<Vec<_>>();
+  let mut row = (0..=b.len()).collect::<Vec<_>>();
+  for (i, ca) in a.chars().enumerate() {
+    let mut diagonal = row[0];
+    row[0] = i + 1;
+    for (j, cb) in b.iter().enumerate() {
+      let above = row[j + 1];
+      row[j + 1] = if ca == *cb {
+        diagonal
+      } else {
+        1 + diagonal.min(above).min(row[j])
+      };
+      diagonal = above;
+    }
+  }
+  row[b.len()]
+}
+
+
+/// Check whether the file name `new` retains less than a quarter of
+/// the file name `old`.
+fn is_big_change(old: &str, new: &str) -> bool {
+  let len = old.chars().count().max(new.chars().count());
+  len > 0 && edit_distance(old, new) * 4 > len * 3
+}
+
+
+/// Check whether `path` names a hidden file.
+fn is_hidden(path: &Path) -> bool {
+  path
+    .file_name()
+    .is_some_and(|name| name.as_encoded_bytes().starts_with(b"."))
+}
+
+
+/// Retrieve the device of the file system containing `path`, or of the
+/// closest existing ancestor if `path` does not exist.
+#[cfg(unix)]
+async fn device(path: &Path) -> Option<u64> {
+  use std::os::unix::fs::MetadataExt as _;
+
+  for path in path.ancestors() {
+    if let Ok(metadata) = tokio::fs::metadata(path).await {
+      return Some(metadata.dev())
+    }
+  }
+  None
+}
+
+
+/// Check whether `dst` is on a different file system than `src`.
+#[cfg(unix)]
+async fn is_cross_device(src: &Path, dst: &Path) -> bool {
+  match (device(src).await, device(dst).await) {
+    (Some(src), Some(dst)) => src != dst,
+    _ => false,
+  }
+}
+
+/// Devices can't be compared on other systems.
+#[cfg(not(unix))]
+async fn is_cross_device(_src: &Path, _dst: &Path) -> bool {
+  false
+}
+
+
+/// A set of checks to run on planned renames, along with the severity
+/// of each warning.
+///
+/// Collisions are detected among all renames checked by the same
+/// object.
+#[derive(Debug, Default)]
+pub struct Lints {
+  /// Severities deviating from the default.
+  severities: HashMap<Warning, Severity>,
+  /// The destinations of the renames checked so far.
+  destinations: Mutex<HashSet<PathBuf>>,
+}
+
+impl Lints {
+  /// Set the severity with which `warning` is reported.
+  pub fn set(mut self, warning: Warning, severity: Severity) -> Self {
+    let _prev = self.severities.insert(warning, severity);
+    self
+  }
+
+  /// Retrieve the severity with which `warning` is reported.
+  pub fn severity(&self, warning: Warning) -> Severity {
+    self.severities.get(&warning).copied().unwrap_or_default()
+  }
+
+  /// Check whether any warning is reported as an error.
+  pub fn denies_any(&self) -> bool {
+    Warning::ALL
+      .iter()
+      .any(|warning| self.severity(*warning) == Severity::Deny)
+  }
+
+  /// Check the rename of `src` to `dst`, returning the warnings that
+  /// are not allowed.
+  pub async fn check(&self, src: &Path, dst: &Path) -> Vec<Diagnostic> {
+    if src == dst {
+      return Vec::new()
+    }
+
+    let mut diagnostics = Vec::new();
+    let mut report = |warning, message| {
+      let severity = self.severity(warning);
+      if severity != Severity::Allow {
+        let () = diagnostics.push(Diagnostic {
+          warning,
+          severity,
+          src: src.to_path_buf(),
+          dst: dst.to_path_buf(),
+          message,
+        });
+      }
+    };
+
+    let collision = !self
+      .destinations
+      .lock()
+      .unwrap_or_else(PoisonError::into_inner)
+      .insert(dst.to_path_buf());
+    if collision {
+      let () = report(
+        Warning::Collision,
+        format!(
+          "`{}` would be renamed to `{}`, as would another file",
+          src.display(),
+          dst.display()
+        ),
+      );
+    }
+
+    let old = src
+      .file_name()
+      .map(|name| name.to_string_lossy())
+      .unwrap_or_default();
+    let new = dst
+      .file_name()
+      .map(|name| name.to_string_lossy())
+      .unwrap_or_default();
+    if is_big_change(&old, &new) {
+      let () = report(
+        Warning::BigChange,
+        format!("`{old}` would be renamed to the very different `{new}`"),
+      );
+    }
+
+    let old_ext = src
+      .extension()
+      .map(|ext| ext.to_string_lossy().to_lowercase());
+    let new_ext = dst
+      .extension()
+      .map(|ext| ext.to_string_lossy().to_lowercase());
+    let change = match (&old_ext, &new_ext) {
+      (Some(old_ext), Some(new_ext)) if old_ext != new_ext => Some(format!(
+        "change its extension from `{old_ext}` to `{new_ext}`"
+      )),
+      (Some(old_ext), None) => Some(format!("lose its extension `{old_ext}`")),
+      (None, Some(new_ext)) => Some(format!("gain the extension `{new_ext}`")),
+      _ => None,
+    };
+    if let Some(change) = change {
+      let () = report(
+        Warning::ExtensionChange,
+        format!("`{old}` would {change} as `{new}`"),
+      );
+    }
+
+    if is_hidden(src) != is_hidden(dst) {
+      let what = if is_hidden(dst) {
+        "become hidden"
+      } else {
+        "no longer be hidden"
+      };
+      let () = report(
+        Warning::HiddenChange,
+        format!("`{old}` would {what} as `{new}`"),
+      );
+    }
+
+    if is_cross_device(src, dst).await {
+      let () = report(
+        Warning::CrossDevice,
+        format!(
+          "`{}` would be moved to a different file system at `{}`",
+          src.display(),
+          dst.display()
+        ),
+      );
+    }
+
+    diagnostics
+  }
+}
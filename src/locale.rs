@@ -0,0 +1,172 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Locale dependent formatting of dates, interpretation of dates and
+//! numbers, and mapping of case.
+
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Result as FmtResult;
+use std::str::FromStr;
+
+use chrono::DateTime;
+use chrono::NaiveDate;
+
+use crate::error::Context as _;
+use crate::error::Error;
+use crate::error::Result;
+use crate::grapheme::graphemes;
+
+
+/// The locales whose names of months are recognized in dates in
+/// addition to those of the locale in use.
+const MONTH_LOCALES: [chrono::Locale; 7] = [
+  chrono::Locale::en_US,
+  chrono::Locale::de_DE,
+  chrono::Locale::fr_FR,
+  chrono::Locale::es_ES,
+  chrono::Locale::it_IT,
+  chrono::Locale::nl_NL,
+  chrono::Locale::pt_PT,
+];
+
+
+/// A locale, as used for the names of months and days and for mapping
+/// the case of text.
+///
+/// The default is the POSIX locale, which does not depend on the
+/// environment.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Locale(chrono::Locale);
+
+impl Locale {
+  /// Check whether the locale uses the Turkic mapping of the letter
+  /// `i`, with `I` and `ı` as well as `İ` and `i` forming pairs.
+  fn is_turkic(&self) -> bool {
+    let name = self.0.to_string();
+    name.starts_with("tr_") || name.starts_with("az_")
+  }
+
+  /// Convert `s` to upper case.
+  pub fn to_uppercase(&self, s: &str) -> String {
+    if self.is_turkic() {
+      s.chars()
+        .map(|c| {
+          if c == 'i' {
+            'İ'.to_string()
+          } else {
+            c.to_uppercase().to_string()
+          }
+        })
+        .collect()
+    } else {
+      s.to_uppercase()
+    }
+  }
+
+  /// Convert `s` to lower case.
+  pub fn to_lowercase(&self, s: &str) -> String {
+    if self.is_turkic() {
+      graphemes(s)
+        .flat_map(|grapheme| {
+          // A decomposed `İ` keeps its dot as combining mark, which
+          // becomes part of the lower case `i`.
+          let (grapheme, dotted) = match grapheme.strip_prefix("I\u{307}") {
+            Some(marks) => (marks, true),
+            None => (grapheme, false),
+          };
+          dotted.then_some('i').into_iter().chain(grapheme.chars())
+        })
+        .map(|c| match c {
+          'I' => 'ı'.to_string(),
+          'İ' => 'i'.to_string(),
+          c => c.to_lowercase().to_string(),
+        })
+        .collect()
+    } else {
+      s.to_lowercase()
+    }
+  }
+
+  /// Retrieve the character separating the integer and fractional
+  /// parts of numbers.
+  pub(crate) fn decimal_point(&self) -> char {
+    // `chrono` does not expose the locale's decimal point, but uses it
+    // for fractional seconds.
+    // SANITY: The time is valid.
+    let time = DateTime::from_timestamp_millis(500).unwrap();
+    time
+      .format_localized("%.3f", self.0)
+      .to_string()
+      .chars()
+      .next()
+      .unwrap_or('.')
+  }
+
+  /// Check whether dates written in numbers put the day before the
+  /// month, as in `14/07/2023`, as opposed to `07/14/2023`.
+  pub(crate) fn is_day_first(&self) -> bool {
+    // SANITY: The date is valid.
+    let date = NaiveDate::from_ymd_opt(2001, 2, 3).unwrap();
+    let date = date.format_localized("%x", self.0).to_string();
+    match (date.find('3'), date.find('2')) {
+      (Some(day), Some(month)) => day < month,
+      _ => false,
+    }
+  }
+
+  /// Retrieve the lower case full and abbreviated names of the twelve
+  /// months, along with their numbers.
+  ///
+  /// Names in the locale come first, followed by those of a few widely
+  /// used languages.
+  pub(crate) fn months(&self) -> Vec<(String, u32)> {
+    let mut months = Vec::new();
+    for locale in [self.0].into_iter().chain(MONTH_LOCALES) {
+      for month in 1..=12 {
+        // SANITY: All months of the year 2000 have a first day.
+        let date = NaiveDate::from_ymd_opt(2000, month, 1).unwrap();
+        for format in ["%B", "%b"] {
+          let name = date.format_localized(format, locale).to_string();
+          // Some abbreviations end in a dot, as in `févr.`.
+          let name = self.to_lowercase(name.trim_end_matches('.'));
+          if !name.is_empty() {
+            let () = months.push((name, month));
+          }
+        }
+      }
+    }
+    months
+  }
+
+  /// Retrieve the locale for usage with `chrono`.
+  #[inline]
+  pub(crate) fn chrono(&self) -> chrono::Locale {
+    self.0
+  }
+}
+
+impl FromStr for Locale {
+  type Err = Error;
+
+  /// Parse a locale from its name, such as `de_DE` or `de-DE`.
+  ///
+  /// An encoding suffix, as in `de_DE.UTF-8`, is ignored.
+  fn from_str(s: &str) -> Result<Self> {
+    let name = s.split_once('.').map(|(name, _encoding)| name).unwrap_or(s);
+    let name = name.replace('-', "_");
+    let locale = match name.as_str() {
+      "C" => chrono::Locale::POSIX,
+      name => chrono::Locale::try_from(name)
+        .ok()
+        .with_context(|| format!("unsupported locale `{s}`"))?,
+    };
+    Ok(Self(locale))
+  }
+}
+
+impl Display for Locale {
+  fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+    self.0.fmt(f)
+  }
+}
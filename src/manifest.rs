@@ -0,0 +1,306 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Manifests recording the authoritative names of files.
+//!
+//! A manifest is a text file with one line per file, holding a digest
+//! of the file's contents and its path relative to the directory
+//! containing the manifest, separated by a tab. Files are identified
+//! by their contents, so that the manifest and the directory can be
+//! reconciled after either got changed.
+
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::hash::DefaultHasher;
+use std::hash::Hash as _;
+use std::hash::Hasher as _;
+use std::io::ErrorKind;
+use std::path::Component;
+use std::path::Path;
+use std::path::PathBuf;
+use std::str::from_utf8;
+use std::str::FromStr;
+
+use tokio::fs::metadata;
+use tokio::fs::read;
+use tokio::fs::write;
+use tokio::task::spawn_blocking;
+
+use crate::error::Context as _;
+use crate::error::Error;
+use crate::error::Result;
+use crate::os::os_str_bytes;
+use crate::os::os_string_from_bytes;
+use crate::os::special_file;
+use crate::plan::is_relative_file;
+use crate::traverse::traverse;
+use crate::traverse::TraverseOpts;
+use crate::util::digest;
+
+
+/// The direction in which to synchronize a manifest and the directory
+/// it describes.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Direction {
+  /// Only report differences.
+  #[default]
+  Check,
+  /// Rename files to match the manifest.
+  Files,
+  /// Update the manifest from the directory.
+  Manifest,
+  /// Rename files to match the manifest, then update the manifest from
+  /// the directory.
+  Both,
+}
+
+impl FromStr for Direction {
+  type Err = Error;
+
+  fn from_str(s: &str) -> Result<Self> {
+    match s {
+      "check" => Ok(Self::Check),
+      "files" => Ok(Self::Files),
+      "manifest" => Ok(Self::Manifest),
+      "both" => Ok(Self::Both),
+      _ => Err(Error::Invalid(format!(
+        "unsupported sync direction `{s}`; expected one of `check`, `files`, `manifest`, or `both`"
+      ))),
+    }
+  }
+}
+
+
+/// How files are identified when matching them up.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Identity {
+  /// Files are identified by their contents.
+  #[default]
+  Content,
+  /// Files are identified by their size and modification time, which
+  /// is much quicker to determine but less reliable.
+  Metadata,
+}
+
+impl FromStr for Identity {
+  type Err = Error;
+
+  fn from_str(s: &str) -> Result<Self> {
+    match s {
+      "content" => Ok(Self::Content),
+      "metadata" => Ok(Self::Metadata),
+      _ => Err(Error::Invalid(format!(
+        "unsupported identity `{s}`; expected one of `content` or `metadata`"
+      ))),
+    }
+  }
+}
+
+
+/// Serialize a relative path, using `/` as separator on all systems.
+fn path_bytes(path: &Path) -> Result<Vec<u8>> {
+  let mut bytes = Vec::new();
+  for component in path.components() {
+    let component = match component {
+      Component::Normal(name) => os_str_bytes(name),
+      _ => {
+        return Err(Error::Invalid(format!(
+          "path `{}` is not a plain relative path",
+          path.display()
+        )))
+      },
+    };
+    if component.contains(&b'\t') || component.contains(&b'\n') {
+      return Err(Error::Invalid(format!(
+        "path `{}` contains a tab or newline and can't be recorded",
+        path.display()
+      )))
+    }
+    if !bytes.is_empty() {
+      let () = bytes.push(b'/');
+    }
+    let () = bytes.extend_from_slice(&component);
+  }
+  Ok(bytes)
+}
+
+
+/// A difference between a manifest and the directory it describes.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Drift {
+  /// The file at `path` is recorded as `recorded`.
+  Moved { path: PathBuf, recorded: PathBuf },
+  /// The contents of the file at the path changed.
+  Modified(PathBuf),
+  /// The file at the path is not recorded.
+  Untracked(PathBuf),
+  /// No file with the recorded contents exists.
+  Missing(PathBuf),
+}
+
+
+/// A mapping of (relative) file paths to digests of their contents.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Manifest {
+  files: BTreeMap<PathBuf, u64>,
+}
+
+impl Manifest {
+  /// Load the manifest at `path`.
+  ///
+  /// A missing manifest is treated as empty.
+  pub async fn load(path: &Path) -> Result<Self> {
+    let content = match read(path).await {
+      Ok(content) => content,
+      Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Self::default()),
+      Err(err) => {
+        return Err(err).with_context(|| format!("failed to read manifest `{}`", path.display()))
+      },
+    };
+
+    let mut files = BTreeMap::new();
+    for (idx, line) in content.split(|byte| *byte == b'\n').enumerate() {
+      let parse = || {
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        if line.is_empty() || line.starts_with(b"#") {
+          return Ok(None)
+        }
+        let tab = line
+          .iter()
+          .position(|byte| *byte == b'\t')
+          .context("missing tab separating digest and path")?;
+        let (digest, file) = (&line[..tab], &line[tab + 1..]);
+        let digest = from_utf8(digest)
+          .ok()
+          .and_then(|digest| u64::from_str_radix(digest, 16).ok())
+          .context("invalid digest")?;
+        let file = file
+          .split(|byte| *byte == b'/')
+          .map(os_string_from_bytes)
+          .collect::<Result<PathBuf>>()?;
+        if !is_relative_file(&file) {
+          return Err(Error::Invalid(format!("invalid path `{}`", file.display())))
+        }
+        Ok(Some((file, digest)))
+      };
+
+      let entry = parse()
+        .with_context(|| format!("invalid line {} in manifest `{}`", idx + 1, path.display()))?;
+      if let Some((file, digest)) = entry {
+        if files.insert(file.clone(), digest).is_some() {
+          return Err(Error::Invalid(format!(
+            "manifest `{}` lists `{}` more than once",
+            path.display(),
+            file.display()
+          )))
+        }
+      }
+    }
+    Ok(Self { files })
+  }
+
+  /// Record all files below `dir`, with the exception of `exclude`.
+  pub async fn scan(dir: &Path, exclude: &Path) -> Result<Self> {
+    Self::scan_by(dir, Some(exclude), Identity::Content).await
+  }
+
+  /// Record all files below `dir`, with the exception of `exclude`,
+  /// identifying them as per `identity`.
+  ///
+  /// Manifests identifying files by metadata are only meaningful for
+  /// comparing them with each other and should not be saved.
+  pub async fn scan_by(dir: &Path, exclude: Option<&Path>, identity: Identity) -> Result<Self> {
+    let paths = traverse(&[dir.to_path_buf()], &TraverseOpts::default()).await?;
+
+    let mut files = BTreeMap::new();
+    for path in paths {
+      // Special files have no contents to identify them by.
+      if Some(path.as_path()) == exclude || special_file(&path).is_some() {
+        continue
+      }
+      let file = path
+        .strip_prefix(dir)
+        .with_context(|| format!("`{}` is not inside `{}`", path.display(), dir.display()))?
+        .to_path_buf();
+      let digest = match identity {
+        Identity::Content => spawn_blocking(move || digest(&path))
+          .await
+          .context("digest task failed")??,
+        Identity::Metadata => {
+          let meta = metadata(&path)
+            .await
+            .with_context(|| format!("failed to retrieve metadata of `{}`", path.display()))?;
+          let mut hasher = DefaultHasher::new();
+          let () = meta.len().hash(&mut hasher);
+          let () = meta.modified().ok().hash(&mut hasher);
+          hasher.finish()
+        },
+      };
+      let _prev = files.insert(file, digest);
+    }
+    Ok(Self { files })
+  }
+
+  /// Write the manifest to `path`.
+  pub async fn save(&self, path: &Path) -> Result<()> {
+    let mut content = Vec::new();
+    for (file, digest) in &self.files {
+      let () = content.extend_from_slice(format!("{digest:016x}\t").as_bytes());
+      let () = content.extend_from_slice(&path_bytes(file)?);
+      let () = content.push(b'\n');
+    }
+    write(path, content)
+      .await
+      .with_context(|| format!("failed to write manifest `{}`", path.display()))
+  }
+
+  /// Determine how the files in `actual` differ from the ones recorded
+  /// in the manifest.
+  ///
+  /// Files are matched by their contents. Should several files share
+  /// the same contents, they are matched in the order of their paths.
+  pub fn drift(&self, actual: &Self) -> Vec<Drift> {
+    let mut recorded = BTreeMap::<_, Vec<_>>::new();
+    let mut present = BTreeMap::<_, Vec<_>>::new();
+    for (file, digest) in &self.files {
+      if actual.files.get(file) != Some(digest) {
+        let () = recorded.entry(*digest).or_default().push(file);
+      }
+    }
+    for (file, digest) in &actual.files {
+      if self.files.get(file) != Some(digest) {
+        let () = present.entry(*digest).or_default().push(file);
+      }
+    }
+
+    let mut drift = Vec::new();
+    let mut untracked = BTreeSet::new();
+    let mut missing = BTreeSet::new();
+    for (digest, files) in present {
+      let mut recorded = recorded.remove(&digest).unwrap_or_default().into_iter();
+      for file in files {
+        match recorded.next() {
+          Some(recorded) => drift.push(Drift::Moved {
+            path: file.clone(),
+            recorded: recorded.clone(),
+          }),
+          None => {
+            let _inserted = untracked.insert(file);
+          },
+        }
+      }
+      let () = missing.extend(recorded);
+    }
+    let () = missing.extend(recorded.into_values().flatten());
+
+    for file in untracked {
+      if missing.remove(file) {
+        let () = drift.push(Drift::Modified(file.clone()));
+      } else {
+        let () = drift.push(Drift::Untracked(file.clone()));
+      }
+    }
+    let () = drift.extend(missing.into_iter().cloned().map(Drift::Missing));
+    drift
+  }
+}
@@ -0,0 +1,291 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Extraction of metadata embedded in media files.
+//!
+//! Supported are EXIF data as found in images and ID3 tags as found in
+//! audio files. Their fields are made available to templates as tokens
+//! of the form `{exif.<field>}` and `{id3.<field>}`.
+
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Result as FmtResult;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::is_separator;
+use std::path::Path;
+
+use chrono::format::Item;
+use chrono::format::StrftimeItems;
+use chrono::NaiveDate;
+use chrono::NaiveDateTime;
+
+use exif::In;
+use exif::Reader;
+use exif::Tag as ExifTag;
+use exif::Value;
+
+use id3::no_tag_ok;
+use id3::Tag as Id3Tag;
+use id3::TagLike as _;
+
+use tokio::task::spawn_blocking;
+
+use crate::error::Context as _;
+use crate::error::Error;
+use crate::error::Result;
+use crate::locale::Locale;
+use crate::os::special_file;
+
+
+/// The default format used for the `exif.date` token.
+const DEFAULT_DATE_FORMAT: &str = "%Y-%m-%d";
+
+
+/// EXIF data of an image.
+#[derive(Clone, Debug, Default)]
+#[non_exhaustive]
+pub struct Exif {
+  /// The time at which the image was taken.
+  pub date: Option<NaiveDateTime>,
+  /// The model of the camera used for taking the image.
+  pub model: Option<String>,
+}
+
+impl Exif {
+  /// Read the EXIF data of the file at `path`.
+  ///
+  /// Files not containing EXIF data, including special files such as
+  /// FIFOs, result in an empty object.
+  ///
+  /// This function blocks.
+  pub fn read(path: &Path) -> Result<Self> {
+    // Opening special files may block indefinitely.
+    if special_file(path).is_some() {
+      return Ok(Self::default())
+    }
+
+    let file = File::open(path).with_context(|| format!("failed to open `{}`", path.display()))?;
+    let exif = match Reader::new().read_from_container(&mut BufReader::new(file)) {
+      Ok(exif) => exif,
+      Err(exif::Error::Io(err)) => {
+        return Err(err).with_context(|| format!("failed to read `{}`", path.display()))
+      },
+      Err(_) => return Ok(Self::default()),
+    };
+
+    let ascii = |tag| match exif.get_field(tag, In::PRIMARY).map(|field| &field.value) {
+      Some(Value::Ascii(values)) => values.first().cloned(),
+      _ => None,
+    };
+
+    let date =
+      ascii(ExifTag::DateTimeOriginal)
+        .or_else(|| ascii(ExifTag::DateTime))
+        .and_then(|date| exif::DateTime::from_ascii(&date).ok())
+        .and_then(|date| {
+          NaiveDate::from_ymd_opt(date.year.into(), date.month.into(), date.day.into())?
+            .and_hms_opt(date.hour.into(), date.minute.into(), date.second.into())
+        });
+    let model = ascii(ExifTag::Model)
+      .map(|model| String::from_utf8_lossy(&model).trim().to_string())
+      .filter(|model| !model.is_empty());
+
+    let slf = Self { date, model };
+    Ok(slf)
+  }
+}
+
+
+/// ID3 tag of an audio file.
+#[derive(Clone, Debug, Default)]
+#[non_exhaustive]
+pub struct Id3 {
+  /// The title of the track.
+  pub title: Option<String>,
+  /// The artist of the track.
+  pub artist: Option<String>,
+  /// The number of the track on its album.
+  pub track: Option<u32>,
+}
+
+impl Id3 {
+  /// Read the ID3 tag of the file at `path`.
+  ///
+  /// Files not containing an ID3 tag, including special files such as
+  /// FIFOs, result in an empty object.
+  ///
+  /// This function blocks.
+  pub fn read(path: &Path) -> Result<Self> {
+    if special_file(path).is_some() {
+      return Ok(Self::default())
+    }
+
+    let tag = no_tag_ok(Id3Tag::read_from_path(path))
+      .with_context(|| format!("failed to read ID3 tag of `{}`", path.display()))?;
+    let Some(tag) = tag else {
+      return Ok(Self::default())
+    };
+
+    let slf = Self {
+      title: tag.title().map(str::to_string),
+      artist: tag.artist().map(str::to_string),
+      track: tag.track(),
+    };
+    Ok(slf)
+  }
+}
+
+
+/// A metadata field usable as template token.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) enum Field {
+  ExifDate { format: String },
+  ExifModel,
+  Id3Title,
+  Id3Artist,
+  Id3Track { width: usize, zero: bool },
+}
+
+impl Field {
+  /// Check whether a token called `name` refers to a metadata field.
+  pub(crate) fn is_field(name: &str) -> bool {
+    name.starts_with("exif.") || name.starts_with("id3.")
+  }
+
+  /// Parse a field from its token name and optional specification.
+  pub(crate) fn parse(name: &str, spec: Option<&str>) -> Result<Self> {
+    let field = match (name, spec) {
+      ("exif.date", spec) => {
+        let format = spec.unwrap_or(DEFAULT_DATE_FORMAT);
+        if StrftimeItems::new(format).any(|item| item == Item::Error) {
+          return Err(Error::Invalid(format!("invalid time format `{format}`")))
+        }
+        Self::ExifDate {
+          format: format.to_string(),
+        }
+      },
+      ("exif.model", None) => Self::ExifModel,
+      ("id3.title", None) => Self::Id3Title,
+      ("id3.artist", None) => Self::Id3Artist,
+      ("id3.track", None) => Self::Id3Track {
+        width: 0,
+        zero: false,
+      },
+      ("id3.track", Some(spec)) => Self::Id3Track {
+        width: spec
+          .parse()
+          .with_context(|| format!("invalid track width `{spec}`"))?,
+        zero: spec.starts_with('0'),
+      },
+      ("exif.model" | "id3.title" | "id3.artist", Some(_)) => {
+        return Err(Error::Invalid(format!(
+          "template token `{name}` does not accept a specification"
+        )))
+      },
+      _ => return Err(Error::Invalid(format!("unknown template token `{name}`"))),
+    };
+    Ok(field)
+  }
+}
+
+impl Display for Field {
+  fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+    match self {
+      Self::ExifDate { format } => write!(f, "exif.date:{format}"),
+      Self::ExifModel => f.write_str("exif.model"),
+      Self::Id3Title => f.write_str("id3.title"),
+      Self::Id3Artist => f.write_str("id3.artist"),
+      Self::Id3Track { width: 0, .. } => f.write_str("id3.track"),
+      Self::Id3Track { width, zero } => {
+        write!(f, "id3.track:{}{width}", if *zero { "0" } else { "" })
+      },
+    }
+  }
+}
+
+
+/// Replace characters that can't be part of a file name.
+fn sanitize(value: &str) -> String {
+  value
+    .chars()
+    .map(|c| if is_separator(c) || c == '\0' { '_' } else { c })
+    .collect()
+}
+
+
+/// A provider of the metadata of a single file, extracting them lazily
+/// as fields are requested.
+#[derive(Debug)]
+pub(crate) struct Provider<'p> {
+  path: &'p Path,
+  exif: Option<Exif>,
+  id3: Option<Id3>,
+}
+
+impl<'p> Provider<'p> {
+  pub(crate) fn new(path: &'p Path) -> Self {
+    Self {
+      path,
+      exif: None,
+      id3: None,
+    }
+  }
+
+  async fn exif(&mut self) -> Result<&Exif> {
+    if self.exif.is_none() {
+      let path = self.path.to_path_buf();
+      self.exif = Some(
+        spawn_blocking(move || Exif::read(&path))
+          .await
+          .context("EXIF task failed")??,
+      );
+    }
+    // SANITY: We just made sure that EXIF data are present.
+    Ok(self.exif.as_ref().unwrap())
+  }
+
+  async fn id3(&mut self) -> Result<&Id3> {
+    if self.id3.is_none() {
+      let path = self.path.to_path_buf();
+      self.id3 = Some(
+        spawn_blocking(move || Id3::read(&path))
+          .await
+          .context("ID3 task failed")??,
+      );
+    }
+    // SANITY: We just made sure that the ID3 tag is present.
+    Ok(self.id3.as_ref().unwrap())
+  }
+
+  /// Render `field` for the file, formatting times according to
+  /// `locale`.
+  pub(crate) async fn render(&mut self, field: &Field, locale: &Locale) -> Result<String> {
+    let path = self.path;
+    let missing = || format!("`{}` lacks metadata for `{{{field}}}`", path.display());
+
+    let value = match field {
+      Field::ExifDate { format } => {
+        let date = self.exif().await?.date.with_context(missing)?;
+        // EXIF times lack a time zone, but only times with one can be
+        // formatted according to a locale.
+        date
+          .and_utc()
+          .format_localized(format, locale.chrono())
+          .to_string()
+      },
+      Field::ExifModel => self.exif().await?.model.clone().with_context(missing)?,
+      Field::Id3Title => self.id3().await?.title.clone().with_context(missing)?,
+      Field::Id3Artist => self.id3().await?.artist.clone().with_context(missing)?,
+      Field::Id3Track { width, zero } => {
+        let track = self.id3().await?.track.with_context(missing)?;
+        if *zero {
+          format!("{track:0width$}")
+        } else {
+          format!("{track:width$}")
+        }
+      },
+    };
+    Ok(sanitize(&value))
+  }
+}
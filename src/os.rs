@@ -0,0 +1,391 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Abstractions over platform specific functionality.
+
+use std::borrow::Cow;
+use std::ffi::OsStr;
+use std::ffi::OsString;
+use std::fs::metadata;
+use std::fs::rename;
+use std::fs::symlink_metadata;
+use std::io;
+use std::io::ErrorKind;
+use std::path::Path;
+use std::path::PathBuf;
+
+use crate::error::Result;
+
+#[cfg(unix)]
+pub use unix::*;
+#[cfg(windows)]
+pub use windows::*;
+
+
+/// Rename `src` to `dst`, failing if `dst` exists, by checking for its
+/// existence first.
+///
+/// The check is inherently racy and only used where the operating
+/// system provides no means for enforcing it atomically.
+fn rename_checked(src: &Path, dst: &Path) -> io::Result<()> {
+  match symlink_metadata(dst) {
+    Ok(_) => Err(io::Error::from(ErrorKind::AlreadyExists)),
+    Err(err) if err.kind() == ErrorKind::NotFound => rename(src, dst),
+    Err(err) => Err(err),
+  }
+}
+
+
+/// Describe the kind of special file at `path`, such as a FIFO or a
+/// device, if it is one, following symbolic links.
+///
+/// Special files have no contents to speak of and reading them may
+/// block indefinitely, so they are only ever renamed.
+pub(crate) fn special_file(path: &Path) -> Option<&'static str> {
+  metadata(path)
+    .ok()
+    .and_then(|metadata| special_kind(&metadata.file_type()))
+}
+
+
+#[cfg(unix)]
+mod unix {
+  use super::*;
+
+  use std::ffi::CString;
+  use std::fs::FileType;
+  #[cfg(feature = "traverse")]
+  use std::mem::MaybeUninit;
+  use std::os::unix::ffi::OsStrExt as _;
+  use std::os::unix::ffi::OsStringExt as _;
+  use std::os::unix::fs::FileTypeExt as _;
+  #[cfg(feature = "traverse")]
+  use std::ptr;
+
+  #[cfg(feature = "traverse")]
+  use libc::c_char;
+  #[cfg(feature = "traverse")]
+  use libc::c_int;
+  #[cfg(feature = "traverse")]
+  use libc::size_t;
+
+
+  /// The unit an `OsStr` is made up of on this platform.
+  pub(crate) type Unit = u8;
+
+
+  /// Retrieve the units making up `s`.
+  pub(crate) fn units(s: &OsStr) -> Cow<'_, [Unit]> {
+    Cow::Borrowed(s.as_bytes())
+  }
+
+  /// Create an `OsString` from the units making it up.
+  pub(crate) fn from_units(units: Vec<Unit>) -> OsString {
+    OsString::from_vec(units)
+  }
+
+  /// Encode `s` as bytes, losslessly.
+  ///
+  /// On Unix systems an `OsStr` is an arbitrary sequence of bytes
+  /// already.
+  pub fn os_str_bytes(s: &OsStr) -> Cow<'_, [u8]> {
+    Cow::Borrowed(s.as_bytes())
+  }
+
+  /// Decode an `OsString` from bytes as produced by [`os_str_bytes`].
+  pub fn os_string_from_bytes(bytes: &[u8]) -> Result<OsString> {
+    Ok(OsStr::from_bytes(bytes).to_os_string())
+  }
+
+  /// Convert a path as returned by `canonicalize` into the form users
+  /// are accustomed to.
+  pub(crate) fn simplify(path: PathBuf) -> PathBuf {
+    path
+  }
+
+  /// Describe the kind of special file of type `file_type`, if it is
+  /// one.
+  pub(crate) fn special_kind(file_type: &FileType) -> Option<&'static str> {
+    if file_type.is_fifo() {
+      Some("FIFO")
+    } else if file_type.is_socket() {
+      Some("socket")
+    } else if file_type.is_block_device() {
+      Some("block device")
+    } else if file_type.is_char_device() {
+      Some("character device")
+    } else {
+      None
+    }
+  }
+
+  /// Convert a path into a C string suitable for passing to the
+  /// operating system.
+  #[cfg_attr(not(any(target_os = "linux", target_os = "macos")), allow(dead_code))]
+  pub(crate) fn c_path(path: &Path) -> io::Result<CString> {
+    CString::new(path.as_os_str().as_bytes()).map_err(io::Error::from)
+  }
+
+  /// Rename `src` to `dst`, atomically failing if `dst` exists.
+  #[cfg(target_os = "linux")]
+  pub(crate) fn rename_noreplace(src: &Path, dst: &Path) -> io::Result<()> {
+    let (c_src, c_dst) = (c_path(src)?, c_path(dst)?);
+    // SAFETY: Both paths are valid NUL terminated strings.
+    let rc = unsafe {
+      libc::syscall(
+        libc::SYS_renameat2,
+        libc::AT_FDCWD,
+        c_src.as_ptr(),
+        libc::AT_FDCWD,
+        c_dst.as_ptr(),
+        libc::RENAME_NOREPLACE,
+      )
+    };
+    if rc == 0 {
+      return Ok(())
+    }
+
+    let err = io::Error::last_os_error();
+    match err.raw_os_error() {
+      // The kernel or the file system does not support the flag.
+      Some(libc::EINVAL | libc::ENOSYS) => rename_checked(src, dst),
+      _ => Err(err),
+    }
+  }
+
+  /// Rename `src` to `dst`, atomically failing if `dst` exists.
+  #[cfg(target_os = "macos")]
+  pub(crate) fn rename_noreplace(src: &Path, dst: &Path) -> io::Result<()> {
+    let (c_src, c_dst) = (c_path(src)?, c_path(dst)?);
+    // SAFETY: Both paths are valid NUL terminated strings.
+    let rc = unsafe { libc::renamex_np(c_src.as_ptr(), c_dst.as_ptr(), libc::RENAME_EXCL) };
+    if rc == 0 {
+      return Ok(())
+    }
+
+    let err = io::Error::last_os_error();
+    match err.raw_os_error() {
+      // The file system does not support the flag.
+      Some(libc::ENOTSUP) => rename_checked(src, dst),
+      _ => Err(err),
+    }
+  }
+
+  /// Rename `src` to `dst`, failing if `dst` exists.
+  #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+  pub(crate) fn rename_noreplace(src: &Path, dst: &Path) -> io::Result<()> {
+    rename_checked(src, dst)
+  }
+
+  /// Atomically exchange the names of `a` and `b`.
+  ///
+  /// An error of kind [`ErrorKind::Unsupported`] is reported if the
+  /// kernel or file system lacks support.
+  #[cfg(target_os = "linux")]
+  pub(crate) fn rename_exchange(a: &Path, b: &Path) -> io::Result<()> {
+    let (c_a, c_b) = (c_path(a)?, c_path(b)?);
+    // SAFETY: Both paths are valid NUL terminated strings.
+    let rc = unsafe {
+      libc::syscall(
+        libc::SYS_renameat2,
+        libc::AT_FDCWD,
+        c_a.as_ptr(),
+        libc::AT_FDCWD,
+        c_b.as_ptr(),
+        libc::RENAME_EXCHANGE,
+      )
+    };
+    if rc == 0 {
+      return Ok(())
+    }
+
+    let err = io::Error::last_os_error();
+    match err.raw_os_error() {
+      Some(libc::EINVAL | libc::ENOSYS) => Err(io::Error::from(ErrorKind::Unsupported)),
+      _ => Err(err),
+    }
+  }
+
+  /// Atomically exchange the names of `a` and `b`.
+  ///
+  /// An error of kind [`ErrorKind::Unsupported`] is reported if the
+  /// file system lacks support.
+  #[cfg(target_os = "macos")]
+  pub(crate) fn rename_exchange(a: &Path, b: &Path) -> io::Result<()> {
+    let (c_a, c_b) = (c_path(a)?, c_path(b)?);
+    // SAFETY: Both paths are valid NUL terminated strings.
+    let rc = unsafe { libc::renamex_np(c_a.as_ptr(), c_b.as_ptr(), libc::RENAME_SWAP) };
+    if rc == 0 {
+      return Ok(())
+    }
+
+    let err = io::Error::last_os_error();
+    match err.raw_os_error() {
+      Some(libc::ENOTSUP) => Err(io::Error::from(ErrorKind::Unsupported)),
+      _ => Err(err),
+    }
+  }
+
+  /// Atomically exchange the names of `a` and `b`.
+  ///
+  /// This operation is unsupported on this platform.
+  #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+  pub(crate) fn rename_exchange(_a: &Path, _b: &Path) -> io::Result<()> {
+    Err(io::Error::from(ErrorKind::Unsupported))
+  }
+
+  /// Look up the database entry called `name` using the reentrant
+  /// function `get` (e.g., `getpwnam_r`), retrieving the ID it carries
+  /// via `id`.
+  #[cfg(feature = "traverse")]
+  fn lookup<T>(
+    name: &str,
+    get: unsafe extern "C" fn(*const c_char, *mut T, *mut c_char, size_t, *mut *mut T) -> c_int,
+    id: fn(&T) -> u32,
+  ) -> io::Result<Option<u32>> {
+    let c_name = CString::new(name).map_err(io::Error::from)?;
+    let mut buffer = vec![0 as c_char; 1024];
+    loop {
+      let mut entry = MaybeUninit::<T>::uninit();
+      let mut result = ptr::null_mut();
+      // SAFETY: The name is a valid NUL terminated string and all other
+      //         pointers reference memory of the sizes indicated.
+      let rc = unsafe {
+        get(
+          c_name.as_ptr(),
+          entry.as_mut_ptr(),
+          buffer.as_mut_ptr(),
+          buffer.len(),
+          &mut result,
+        )
+      };
+      match rc {
+        0 if result.is_null() => return Ok(None),
+        // SAFETY: A non-NULL result indicates that the entry got
+        //         initialized.
+        0 => return Ok(Some(id(unsafe { entry.assume_init_ref() }))),
+        libc::ERANGE => buffer.resize(buffer.len() * 2, 0),
+        rc => return Err(io::Error::from_raw_os_error(rc)),
+      }
+    }
+  }
+
+  /// Look up the ID of the user called `name`.
+  #[cfg(feature = "traverse")]
+  pub(crate) fn user_id(name: &str) -> io::Result<Option<u32>> {
+    lookup(name, libc::getpwnam_r, |passwd| passwd.pw_uid)
+  }
+
+  /// Look up the ID of the group called `name`.
+  #[cfg(feature = "traverse")]
+  pub(crate) fn group_id(name: &str) -> io::Result<Option<u32>> {
+    lookup(name, libc::getgrnam_r, |group| group.gr_gid)
+  }
+}
+
+
+#[cfg(windows)]
+mod windows {
+  use super::*;
+
+  use std::char::decode_utf16;
+  use std::fs::FileType;
+  use std::os::windows::ffi::OsStrExt as _;
+  use std::os::windows::ffi::OsStringExt as _;
+
+  use crate::error::Context as _;
+
+
+  /// The unit an `OsStr` is made up of on this platform.
+  pub(crate) type Unit = u16;
+
+
+  /// Retrieve the units making up `s`.
+  pub(crate) fn units(s: &OsStr) -> Cow<'_, [Unit]> {
+    Cow::Owned(s.encode_wide().collect())
+  }
+
+  /// Create an `OsString` from the units making it up.
+  pub(crate) fn from_units(units: Vec<Unit>) -> OsString {
+    OsString::from_wide(&units)
+  }
+
+  /// Encode `s` as bytes, losslessly.
+  ///
+  /// On Windows an `OsStr` is a potentially ill-formed sequence of
+  /// UTF-16 code units. It is encoded as WTF-8, which is identical to
+  /// UTF-8 for well-formed input and encodes unpaired surrogates just
+  /// like any other code point.
+  pub fn os_str_bytes(s: &OsStr) -> Cow<'_, [u8]> {
+    if let Some(s) = s.to_str() {
+      return Cow::Owned(s.as_bytes().to_vec())
+    }
+
+    let mut bytes = Vec::new();
+    for result in decode_utf16(s.encode_wide()) {
+      match result {
+        Ok(c) => {
+          let mut buffer = [0; 4];
+          let () = bytes.extend_from_slice(c.encode_utf8(&mut buffer).as_bytes());
+        },
+        Err(err) => {
+          let surrogate = err.unpaired_surrogate();
+          let () = bytes.extend_from_slice(&[
+            0xe0 | (surrogate >> 12) as u8,
+            0x80 | ((surrogate >> 6) & 0x3f) as u8,
+            0x80 | (surrogate & 0x3f) as u8,
+          ]);
+        },
+      }
+    }
+    Cow::Owned(bytes)
+  }
+
+  /// Decode an `OsString` from bytes as produced by [`os_str_bytes`].
+  ///
+  /// Only well-formed UTF-8 is supported.
+  pub fn os_string_from_bytes(bytes: &[u8]) -> Result<OsString> {
+    let s = std::str::from_utf8(bytes)
+      .with_context(|| format!("`{}` is not valid UTF-8", bytes.escape_ascii()))?;
+    Ok(OsString::from(s))
+  }
+
+  /// Convert a path as returned by `canonicalize` into the form users
+  /// are accustomed to.
+  ///
+  /// `canonicalize` reports paths in their verbatim form (e.g.,
+  /// `\\?\C:\foo`). The verbatim prefix is removed for paths starting
+  /// with a drive letter.
+  pub(crate) fn simplify(path: PathBuf) -> PathBuf {
+    let simplified = path
+      .to_str()
+      .and_then(|s| s.strip_prefix(r"\\?\"))
+      .filter(|rest| {
+        let bytes = rest.as_bytes();
+        bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':'
+      })
+      .map(PathBuf::from);
+
+    simplified.unwrap_or(path)
+  }
+
+  /// Describe the kind of special file of type `file_type`, if it is
+  /// one.
+  ///
+  /// Files on this platform are never considered special.
+  pub(crate) fn special_kind(_file_type: &FileType) -> Option<&'static str> {
+    None
+  }
+
+  /// Rename `src` to `dst`, failing if `dst` exists.
+  pub(crate) fn rename_noreplace(src: &Path, dst: &Path) -> io::Result<()> {
+    rename_checked(src, dst)
+  }
+
+  /// Atomically exchange the names of `a` and `b`.
+  ///
+  /// This operation is unsupported on this platform.
+  pub(crate) fn rename_exchange(_a: &Path, _b: &Path) -> io::Result<()> {
+    Err(io::Error::from(ErrorKind::Unsupported))
+  }
+}
@@ -0,0 +1,258 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Machine readable reporting of renames.
+
+use std::ffi::OsString;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Result as FmtResult;
+use std::io::Write;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use serde::Serialize;
+
+use crate::change::Change;
+use crate::change::Changes;
+use crate::error::Context as _;
+use crate::error::Error;
+use crate::error::Result;
+use crate::os::os_str_bytes;
+use crate::util::serde_os_strings;
+use crate::util::serde_path;
+
+
+/// The format in which to report renames.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Format {
+  /// Output meant for consumption by humans.
+  #[default]
+  Human,
+  /// One JSON object per line for each file.
+  Json,
+  /// The old and new path of each renamed file, each terminated by a
+  /// NUL character.
+  Null,
+}
+
+impl FromStr for Format {
+  type Err = Error;
+
+  fn from_str(s: &str) -> Result<Self> {
+    match s {
+      "human" => Ok(Self::Human),
+      "json" => Ok(Self::Json),
+      "null" => Ok(Self::Null),
+      _ => Err(Error::Invalid(format!(
+        "unsupported output format `{s}`; expected one of `human`, `json`, or `null`"
+      ))),
+    }
+  }
+}
+
+
+/// The outcome of processing a file.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+  /// The file would be renamed, but the rename was not applied (as
+  /// part of a dry-run).
+  Planned,
+  /// The file was renamed.
+  Renamed,
+  /// The rename command did not change the file's name.
+  Unchanged,
+  /// The user declined renaming the file.
+  Declined,
+  /// The file was left alone, because its new path exists already.
+  Skipped,
+  /// The file was left alone, because its new path is taken by a file
+  /// whose rename got declined.
+  Blocked,
+  /// The file was removed, because a file with identical contents
+  /// exists at its new path already.
+  Discarded,
+  /// Renaming the file failed.
+  Failed,
+}
+
+impl Status {
+  /// Check whether the status represents a (potential) change of name.
+  pub fn is_rename(&self) -> bool {
+    matches!(self, Self::Planned | Self::Renamed)
+  }
+}
+
+
+/// The number of files of a batch per outcome, for summarizing it.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize)]
+pub struct Summary {
+  /// The number of files that were (or would be) renamed.
+  pub renamed: usize,
+  /// The number of files whose name would not change.
+  pub unchanged: usize,
+  /// The number of files that were left alone, because the user
+  /// declined renaming them or their new path is taken.
+  pub skipped: usize,
+  /// The number of files for which processing failed.
+  pub failed: usize,
+  /// The number of files that were (or would be) renamed, per kind of
+  /// change made to their names.
+  pub changes: Changes,
+}
+
+impl Summary {
+  /// Account for a file processed with the given outcome and, for
+  /// renames, the kind of change made to its name.
+  pub fn record(&mut self, status: Status, change: Option<Change>) {
+    let count = match status {
+      Status::Planned | Status::Renamed => &mut self.renamed,
+      Status::Unchanged => &mut self.unchanged,
+      Status::Declined | Status::Skipped | Status::Blocked | Status::Discarded => &mut self.skipped,
+      Status::Failed => &mut self.failed,
+    };
+    *count += 1;
+    if let Some(change) = change {
+      let () = self.changes.record(change);
+    }
+  }
+
+  /// Write the summary to `writer`, as a JSON object on a line of its
+  /// own, noting whether renames were merely planned as per `dry_run`.
+  pub fn write<W>(&self, dry_run: bool, mut writer: W) -> Result<()>
+  where
+    W: Write,
+  {
+    #[derive(Serialize)]
+    struct Record<'s> {
+      summary: &'s Summary,
+      dry_run: bool,
+    }
+
+    let record = Record {
+      summary: self,
+      dry_run,
+    };
+    let mut line = serde_json::to_vec(&record).context("failed to serialize summary")?;
+    let () = line.push(b'\n');
+    let () = writer.write_all(&line).context("failed to write summary")?;
+    Ok(())
+  }
+}
+
+impl Display for Summary {
+  fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+    let () = write!(f, "{} renamed", self.renamed)?;
+    if self.renamed > 0 {
+      let () = write!(f, " ({})", self.changes)?;
+    }
+    write!(
+      f,
+      ", {} unchanged, {} skipped, {} failed",
+      self.unchanged, self.skipped, self.failed
+    )
+  }
+}
+
+
+/// A report about the processing of a single file.
+#[derive(Clone, Debug, Serialize)]
+pub struct Report {
+  /// The path of the file before the rename.
+  #[serde(serialize_with = "serde_path::serialize")]
+  pub src: PathBuf,
+  /// The (proposed) path of the file after the rename.
+  #[serde(serialize_with = "serde_path::serialize")]
+  pub dst: PathBuf,
+  /// The outcome of processing the file.
+  pub status: Status,
+  /// The kind of change made to the file's name, for (potential)
+  /// renames.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub change: Option<Change>,
+  /// The error that occurred, if any.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub error: Option<String>,
+}
+
+impl Report {
+  /// Write the report to `writer` in the given format.
+  ///
+  /// Nothing is written for [`Format::Human`], which is up to the
+  /// caller to provide. In [`Format::Null`] only reports about renames
+  /// are written.
+  pub fn write<W>(&self, format: Format, mut writer: W) -> Result<()>
+  where
+    W: Write,
+  {
+    match format {
+      Format::Human => (),
+      Format::Json => {
+        let mut line = serde_json::to_vec(self).context("failed to serialize report")?;
+        let () = line.push(b'\n');
+        let () = writer.write_all(&line).context("failed to write report")?;
+      },
+      Format::Null if self.status.is_rename() => {
+        let mut pair = Vec::new();
+        let () = pair.extend_from_slice(&os_str_bytes(self.src.as_os_str()));
+        let () = pair.push(b'\0');
+        let () = pair.extend_from_slice(&os_str_bytes(self.dst.as_os_str()));
+        let () = pair.push(b'\0');
+        let () = writer.write_all(&pair).context("failed to write report")?;
+      },
+      Format::Null => (),
+    }
+    Ok(())
+  }
+}
+
+
+/// A record of a completed batch of renames.
+#[derive(Clone, Debug, Serialize)]
+pub struct Batch {
+  /// The number of files of the batch per outcome.
+  pub summary: Summary,
+  /// Reports about the files that got renamed.
+  pub renames: Vec<Report>,
+}
+
+
+/// A record of an applied rename, as written to an audit log.
+#[derive(Clone, Debug, Serialize)]
+pub struct Audit {
+  /// The sequence number of the record, increasing monotonically over
+  /// the records of a log.
+  pub seq: u64,
+  /// The time at which the rename got applied, in RFC 3339 format.
+  pub time: String,
+  /// The path of the file before the rename.
+  #[serde(serialize_with = "serde_path::serialize")]
+  pub src: PathBuf,
+  /// The path of the file after the rename.
+  #[serde(serialize_with = "serde_path::serialize")]
+  pub dst: PathBuf,
+  /// The command used for renaming.
+  #[serde(serialize_with = "serde_os_strings::serialize")]
+  pub command: Vec<OsString>,
+  /// The exit status of the rename command, if the new path got
+  /// determined by one.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub status: Option<i32>,
+}
+
+impl Audit {
+  /// Write the record to `writer`, as a JSON object on a line of its
+  /// own.
+  pub fn write<W>(&self, mut writer: W) -> Result<()>
+  where
+    W: Write,
+  {
+    let mut line = serde_json::to_vec(self).context("failed to serialize audit record")?;
+    let () = line.push(b'\n');
+    let () = writer
+      .write_all(&line)
+      .context("failed to write audit record")?;
+    Ok(())
+  }
+}
@@ -0,0 +1,552 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Analysis of planned renames.
+
+use std::collections::hash_map::Entry;
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::path::Component;
+use std::path::Path;
+use std::path::PathBuf;
+
+
+/// A rename, comprising the source and destination path.
+pub type Rename = (PathBuf, PathBuf);
+
+
+/// Separate the renames in `renames` that would not change the path of
+/// a file from all others.
+///
+/// Both sets of renames are reported in their original order.
+pub fn split_unchanged(renames: Vec<Rename>) -> (Vec<Rename>, Vec<Rename>) {
+  renames.into_iter().partition(|(src, dst)| src == dst)
+}
+
+
+/// Separate the renames in `renames` that are blocked by files staying
+/// in place, as listed in `kept`, from all others.
+///
+/// A rename is blocked if its destination is one of the files in `kept`
+/// or the source of another blocked rename, as would be the case for
+/// renames depending on declined ones. Each blocked rename is reported
+/// along with the file blocking it, i.e., the one staying in place at
+/// the end of the chain. The remaining renames are reported in their
+/// original order.
+pub fn split_blocked(
+  renames: Vec<Rename>,
+  kept: &HashSet<PathBuf>,
+) -> (Vec<(Rename, PathBuf)>, Vec<Rename>) {
+  // The blocked renames, by source, along with the files blocking them.
+  let mut blockers = HashMap::<&Path, &Path>::new();
+  loop {
+    let mut changed = false;
+    for (src, dst) in &renames {
+      if blockers.contains_key(src.as_path()) {
+        continue
+      }
+      let blocker = if kept.contains(dst) {
+        Some(dst.as_path())
+      } else {
+        blockers.get(dst.as_path()).copied()
+      };
+      if let Some(blocker) = blocker {
+        let _prev = blockers.insert(src, blocker);
+        changed = true;
+      }
+    }
+    if !changed {
+      break
+    }
+  }
+
+  let blockers = blockers
+    .into_iter()
+    .map(|(src, blocker)| (src.to_path_buf(), blocker.to_path_buf()))
+    .collect::<HashMap<_, _>>();
+  let mut blocked = Vec::with_capacity(blockers.len());
+  let mut rest = Vec::with_capacity(renames.len() - blockers.len());
+  for (src, dst) in renames {
+    match blockers.get(&src) {
+      Some(blocker) => blocked.push(((src, dst), blocker.clone())),
+      None => rest.push((src, dst)),
+    }
+  }
+  (blocked, rest)
+}
+
+
+/// Separate the swaps contained in `renames` from all other renames.
+///
+/// A swap is a pair of renames `a -> b` and `b -> a`, which cannot be
+/// applied one after the other without one clobbering the file of the
+/// other. Each swap is reported once, as the pair of paths whose names
+/// are to be exchanged. The remaining renames are reported in their
+/// original order.
+pub fn split_swaps(renames: Vec<Rename>) -> (Vec<Rename>, Vec<Rename>) {
+  let sources = renames
+    .iter()
+    .enumerate()
+    .map(|(idx, (src, _dst))| (src, idx))
+    .collect::<HashMap<_, _>>();
+
+  // For each rename, whether it is the first or second half of a swap.
+  let mut halves = vec![None; renames.len()];
+  for (idx, (src, dst)) in renames.iter().enumerate() {
+    if let Some(&other) = sources.get(dst) {
+      if other > idx && renames[other].1 == *src {
+        halves[idx] = Some(true);
+        halves[other] = Some(false);
+      }
+    }
+  }
+
+  let mut swaps = Vec::new();
+  let mut rest = Vec::new();
+  for (rename, half) in renames.into_iter().zip(halves) {
+    match half {
+      None => rest.push(rename),
+      Some(true) => swaps.push(rename),
+      Some(false) => (),
+    }
+  }
+  (swaps, rest)
+}
+
+
+/// Order `renames` so that a rename moving a file out of the way comes
+/// before the ones whose destination is that file.
+///
+/// Renames forming cycles can't be ordered this way and are reported
+/// last, in their original order.
+pub fn order_chains(renames: Vec<Rename>) -> Vec<Rename> {
+  let mut ordered = Vec::with_capacity(renames.len());
+  let mut pending = renames;
+  loop {
+    let sources = pending
+      .iter()
+      .map(|(src, _dst)| src.clone())
+      .collect::<HashSet<_>>();
+    let (ready, blocked) = pending
+      .into_iter()
+      .partition::<Vec<_>, _>(|(src, dst)| src == dst || !sources.contains(dst));
+    if ready.is_empty() {
+      let () = ordered.extend(blocked);
+      break ordered
+    }
+    let () = ordered.extend(ready);
+    pending = blocked;
+  }
+}
+
+
+/// A step in applying renames that may depend on each other, as
+/// determined by [`untangle`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Step {
+  /// Perform the rename with the given index.
+  Rename(usize),
+  /// Move the source of the rename with the given index out of the way,
+  /// to a temporary path, to break a cycle.
+  Park(usize),
+  /// Move the file parked for the rename with the given index to its
+  /// destination.
+  Unpark(usize),
+}
+
+
+/// Determine the steps for applying `renames` one after the other,
+/// without any rename clobbering the file of another.
+///
+/// As with [`order_chains`], a rename moving a file out of the way
+/// comes before the ones whose destination is that file. Each cycle of
+/// renames, such as `a -> b`, `b -> c`, and `c -> a`, is broken by
+/// parking the file of one of its renames at a temporary path, applying
+/// the then ordered rest of the cycle, and finally moving the parked
+/// file to its destination.
+pub fn untangle(renames: &[Rename]) -> Vec<Step> {
+  let mut steps = Vec::with_capacity(renames.len());
+  // The indices of pending renames, along with whether their file has
+  // been parked.
+  let mut pending = (0..renames.len())
+    .map(|idx| (idx, false))
+    .collect::<Vec<_>>();
+
+  loop {
+    let sources = pending
+      .iter()
+      .filter(|(_idx, parked)| !parked)
+      .map(|(idx, _parked)| &renames[*idx].0)
+      .collect::<HashSet<_>>();
+    let (ready, mut blocked) = pending
+      .into_iter()
+      .partition::<Vec<_>, _>(|(idx, _parked)| {
+        let (src, dst) = &renames[*idx];
+        src == dst || !sources.contains(dst)
+      });
+
+    if !ready.is_empty() {
+      let () = steps.extend(ready.into_iter().map(|(idx, parked)| {
+        if parked {
+          Step::Unpark(idx)
+        } else {
+          Step::Rename(idx)
+        }
+      }));
+    } else if blocked.is_empty() {
+      break steps
+    } else {
+      // The destination of each blocked rename is the source of another
+      // one, so following them eventually leads into a cycle.
+      let by_src = blocked
+        .iter()
+        .enumerate()
+        .filter(|(_pos, (_idx, parked))| !parked)
+        .map(|(pos, (idx, _parked))| (&renames[*idx].0, pos))
+        .collect::<HashMap<_, _>>();
+      let mut visited = HashSet::new();
+      let mut pos = 0;
+      while visited.insert(pos) {
+        match by_src.get(&renames[blocked[pos].0].1) {
+          Some(next) => pos = *next,
+          None => break,
+        }
+      }
+
+      blocked[pos].1 = true;
+      let () = steps.push(Step::Park(blocked[pos].0));
+    }
+    pending = blocked;
+  }
+}
+
+
+/// Find destinations whose names differ only in case.
+///
+/// Such names are distinct on case-sensitive file systems, but refer to
+/// the same file on case-insensitive ones, as commonly used on macOS
+/// and Windows. Each collision is reported as the pair of colliding
+/// paths, in order of first occurrence. Identical paths are not
+/// considered colliding.
+pub fn case_collisions<'p, I>(destinations: I) -> Vec<(PathBuf, PathBuf)>
+where
+  I: IntoIterator<Item = &'p Path>,
+{
+  let mut folded = HashMap::<_, &Path>::new();
+  let mut collisions = Vec::new();
+
+  for dst in destinations {
+    let Some(name) = dst.file_name() else {
+      continue
+    };
+    let key = (dst.parent(), name.to_string_lossy().to_lowercase());
+    match folded.entry(key) {
+      Entry::Vacant(vacancy) => {
+        let _dst = vacancy.insert(dst);
+      },
+      Entry::Occupied(occupied) => {
+        if *occupied.get() != dst {
+          let () = collisions.push((occupied.get().to_path_buf(), dst.to_path_buf()));
+        }
+      },
+    }
+  }
+  collisions
+}
+
+
+/// The length, in characters, from which on a file name is considered
+/// excessively long by [`analyze`].
+pub const LONG_NAME: usize = 200;
+
+
+/// Statistics about the names files would be renamed to, surfacing
+/// outliers that may be caused by a misbehaving rename command.
+#[derive(Clone, Debug, Default)]
+#[non_exhaustive]
+pub struct Analysis {
+  /// The number of new file names per length, in characters.
+  pub lengths: BTreeMap<usize, usize>,
+  /// The destinations whose file names have an empty stem, as in
+  /// `.jpg`, while their original names don't.
+  pub empty_stems: Vec<PathBuf>,
+  /// The destinations whose file names are at least [`LONG_NAME`]
+  /// characters long.
+  pub long_names: Vec<PathBuf>,
+  /// Groups of destinations in the same directory whose file names
+  /// share a stem, as in `a.jpg` and `a.png`.
+  pub duplicate_stems: Vec<Vec<PathBuf>>,
+}
+
+
+/// Retrieve the stem of the file name of `path`, i.e., the name without
+/// its last extension. Unlike [`Path::file_stem`], the stem of `.jpg`
+/// is empty.
+fn stem(path: &Path) -> Option<String> {
+  let name = path.file_name()?.to_string_lossy();
+  let stem = match name.rsplit_once('.') {
+    Some((stem, _extension)) => stem,
+    None => &name,
+  };
+  Some(stem.to_string())
+}
+
+
+/// Analyze the names of the destinations of those of `renames` that
+/// change the path of a file.
+///
+/// Duplicate stems are reported in order of first occurrence.
+pub fn analyze(renames: &[Rename]) -> Analysis {
+  let mut analysis = Analysis::default();
+  let mut stems = HashMap::<(Option<&Path>, String), usize>::new();
+  let mut groups = Vec::<Vec<PathBuf>>::new();
+
+  for (src, dst) in renames.iter().filter(|(src, dst)| src != dst) {
+    let Some(name) = dst.file_name() else {
+      continue
+    };
+    let length = name.to_string_lossy().chars().count();
+    *analysis.lengths.entry(length).or_default() += 1;
+    if length >= LONG_NAME {
+      let () = analysis.long_names.push(dst.clone());
+    }
+
+    let dst_stem = stem(dst).unwrap_or_default();
+    if dst_stem.is_empty() && stem(src).is_some_and(|stem| !stem.is_empty()) {
+      let () = analysis.empty_stems.push(dst.clone());
+    }
+    match stems.entry((dst.parent(), dst_stem)) {
+      Entry::Vacant(vacancy) => {
+        let _idx = vacancy.insert(groups.len());
+        let () = groups.push(vec![dst.clone()]);
+      },
+      Entry::Occupied(occupied) => {
+        let () = groups[*occupied.get()].push(dst.clone());
+      },
+    }
+  }
+
+  analysis.duplicate_stems = groups.into_iter().filter(|group| group.len() > 1).collect();
+  analysis
+}
+
+
+/// Lexically normalize `path`, removing `.` components and resolving
+/// `..` components against their preceding component.
+///
+/// Symbolic links are not taken into account.
+pub fn normalize(path: &Path) -> PathBuf {
+  let mut normalized = PathBuf::new();
+  for component in path.components() {
+    match component {
+      Component::CurDir => (),
+      Component::ParentDir => match normalized.components().next_back() {
+        Some(Component::Normal(_)) => {
+          let _popped = normalized.pop();
+        },
+        // The parent of the root is the root itself.
+        Some(Component::RootDir | Component::Prefix(_)) => (),
+        Some(Component::CurDir | Component::ParentDir) | None => {
+          let () = normalized.push(component);
+        },
+      },
+      component => normalized.push(component),
+    }
+  }
+  normalized
+}
+
+
+/// Check whether `path` refers to a location inside of the directory
+/// `root` (or any of its subdirectories), after normalization.
+pub fn is_within(path: &Path, root: &Path) -> bool {
+  normalize(path).starts_with(normalize(root))
+}
+
+
+/// Check whether `path` is a relative path naming a file, such as the
+/// new path of a file relative to the directory containing it.
+pub fn is_relative_file(path: &Path) -> bool {
+  path.file_name().is_some()
+    && path
+      .components()
+      .all(|component| matches!(component, Component::Normal(_) | Component::ParentDir))
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+
+  /// Create renames from pairs of paths.
+  fn renames<const N: usize>(pairs: [(&str, &str); N]) -> Vec<Rename> {
+    pairs
+      .into_iter()
+      .map(|(src, dst)| (PathBuf::from(src), PathBuf::from(dst)))
+      .collect()
+  }
+
+
+  /// Check that renames blocked by kept files are separated, including
+  /// those depending on blocked ones transitively.
+  #[test]
+  fn split_blocked_chains() {
+    let kept = HashSet::from([PathBuf::from("k")]);
+    let (blocked, rest) = split_blocked(
+      renames([("a", "b"), ("b", "k"), ("c", "d"), ("x", "a")]),
+      &kept,
+    );
+    assert_eq!(blocked, vec![
+      ((PathBuf::from("a"), PathBuf::from("b")), PathBuf::from("k")),
+      ((PathBuf::from("b"), PathBuf::from("k")), PathBuf::from("k")),
+      ((PathBuf::from("x"), PathBuf::from("a")), PathBuf::from("k")),
+    ]);
+    assert_eq!(rest, renames([("c", "d")]));
+  }
+
+  /// Check that nothing is blocked if no file stays in place.
+  #[test]
+  fn split_blocked_nothing_kept() {
+    let (blocked, rest) = split_blocked(renames([("a", "b"), ("b", "c")]), &HashSet::new());
+    assert_eq!(blocked, Vec::new());
+    assert_eq!(rest, renames([("a", "b"), ("b", "c")]));
+  }
+
+  /// Check that swaps are reported once and separated from all other
+  /// renames, including longer cycles.
+  #[test]
+  fn split_swaps_pairs() {
+    let (swaps, rest) = split_swaps(renames([
+      ("a", "b"),
+      ("c", "d"),
+      ("b", "a"),
+      ("x", "y"),
+      ("y", "z"),
+      ("z", "x"),
+    ]));
+    assert_eq!(swaps, renames([("a", "b")]));
+    assert_eq!(
+      rest,
+      renames([("c", "d"), ("x", "y"), ("y", "z"), ("z", "x")])
+    );
+  }
+
+  /// Check that renames moving files out of the way come first, with
+  /// cycles reported last.
+  #[test]
+  fn order_chains_with_cycle() {
+    let ordered = order_chains(renames([
+      ("a", "b"),
+      ("b", "c"),
+      ("x", "y"),
+      ("y", "x"),
+      ("s", "s"),
+    ]));
+    assert_eq!(
+      ordered,
+      renames([("b", "c"), ("s", "s"), ("a", "b"), ("x", "y"), ("y", "x")])
+    );
+  }
+
+  /// Check the steps for a chain of renames, without any cycle.
+  #[test]
+  fn untangle_chain() {
+    let steps = untangle(&renames([("a", "b"), ("b", "c"), ("c", "d")]));
+    assert_eq!(steps, vec![
+      Step::Rename(2),
+      Step::Rename(1),
+      Step::Rename(0)
+    ]);
+  }
+
+  /// Check that a swap is broken by parking one of its files.
+  #[test]
+  fn untangle_two_cycle() {
+    let steps = untangle(&renames([("a", "b"), ("b", "a")]));
+    assert_eq!(steps, vec![Step::Park(0), Step::Rename(1), Step::Unpark(0)]);
+  }
+
+  /// Check that a cycle of three renames is broken by parking a single
+  /// file.
+  #[test]
+  fn untangle_three_cycle() {
+    let steps = untangle(&renames([("a", "b"), ("b", "c"), ("c", "a")]));
+    assert_eq!(steps, vec![
+      Step::Park(0),
+      Step::Rename(2),
+      Step::Rename(1),
+      Step::Unpark(0),
+    ]);
+  }
+
+  /// Check that a cycle reached via a chain of renames gets broken
+  /// inside of the cycle, not on the chain leading to it. Ruling out
+  /// destinations claimed twice, as `a` is here, is up to the caller.
+  #[test]
+  fn untangle_chain_into_cycle() {
+    let steps = untangle(&renames([("d", "a"), ("a", "b"), ("b", "a")]));
+    assert_eq!(steps, vec![
+      Step::Park(1),
+      Step::Rename(0),
+      Step::Rename(2),
+      Step::Unpark(1),
+    ]);
+  }
+
+  /// Check that renames not changing the path are performed right away
+  /// and do not get mistaken for cycles.
+  #[test]
+  fn untangle_self_renames() {
+    let steps = untangle(&renames([("a", "a"), ("b", "c"), ("c", "b")]));
+    assert_eq!(steps, vec![
+      Step::Rename(0),
+      Step::Park(1),
+      Step::Rename(2),
+      Step::Unpark(1),
+    ]);
+
+    let steps = untangle(&renames([("a", "a"), ("b", "b")]));
+    assert_eq!(steps, vec![Step::Rename(0), Step::Rename(1)]);
+  }
+
+  /// Check that separate cycles are broken one after the other, each by
+  /// parking a single file.
+  #[test]
+  fn untangle_separate_cycles() {
+    let steps = untangle(&renames([("a", "b"), ("b", "a"), ("c", "d"), ("d", "c")]));
+    assert_eq!(steps, vec![
+      Step::Park(0),
+      Step::Rename(1),
+      Step::Unpark(0),
+      Step::Park(2),
+      Step::Rename(3),
+      Step::Unpark(2),
+    ]);
+  }
+
+  /// Check that untangling nothing results in no steps.
+  #[test]
+  fn untangle_empty() {
+    assert_eq!(untangle(&[]), Vec::new());
+  }
+
+  /// Check that names differing only in case are reported as colliding,
+  /// but only within the same directory.
+  #[test]
+  fn case_collisions_same_directory() {
+    let paths = [
+      Path::new("dir/File.txt"),
+      Path::new("dir/file.TXT"),
+      Path::new("other/file.txt"),
+      Path::new("dir/File.txt"),
+      Path::new("dir/unrelated"),
+    ];
+    let collisions = case_collisions(paths);
+    assert_eq!(collisions, vec![(
+      PathBuf::from("dir/File.txt"),
+      PathBuf::from("dir/file.TXT")
+    )]);
+  }
+}
@@ -0,0 +1,320 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Naming policies codifying conventions for file names.
+//!
+//! A policy is read from a file in a subset of TOML. Keys at the top
+//! level apply to all files below the directory containing the policy
+//! file. Each table applies to the files matching its name, a glob
+//! pattern matched against the file name or, if it contains a path
+//! separator, against the path relative to said directory. Supported
+//! keys are:
+//! - `max-length`: the maximum number of characters of a file name
+//! - `allowed-characters`: the characters a file name may consist of,
+//!   with ranges written as in `a-z`
+//! - `pattern`: a glob pattern that file names have to match
+//! - `severity`: `warn` or `deny`, for reporting violations of the
+//!   rules in the same table (defaults to `deny`)
+
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Result as FmtResult;
+use std::path::Path;
+use std::path::PathBuf;
+
+use globset::Glob;
+use globset::GlobBuilder;
+use globset::GlobMatcher;
+
+use tokio::fs::read_to_string;
+
+use crate::error::Context as _;
+use crate::error::Error;
+use crate::error::Result;
+use crate::lint::Severity;
+use crate::toml::parse_header;
+use crate::toml::parse_pair;
+use crate::toml::strip_comment;
+use crate::toml::Value;
+
+
+/// The name of the policy file looked for by default.
+pub const DEFAULT_FILE: &str = "naming-policy.toml";
+
+
+/// A set of characters, as written in `a-z0-9_`.
+#[derive(Clone, Debug)]
+struct Characters {
+  /// The inclusive ranges of characters in the set.
+  ranges: Vec<(char, char)>,
+  /// The set as written by the user.
+  spec: String,
+}
+
+impl Characters {
+  fn parse(spec: &str) -> Result<Self> {
+    let chars = spec.chars().collect::<Vec<_>>();
+    let mut ranges = Vec::new();
+    let mut idx = 0;
+    while idx < chars.len() {
+      match chars.get(idx..idx + 3) {
+        Some([first, '-', last]) => {
+          if first > last {
+            return Err(Error::Invalid(format!(
+              "invalid character range `{first}-{last}`"
+            )))
+          }
+          let () = ranges.push((*first, *last));
+          idx += 3;
+        },
+        _ => {
+          let () = ranges.push((chars[idx], chars[idx]));
+          idx += 1;
+        },
+      }
+    }
+
+    let slf = Self {
+      ranges,
+      spec: spec.to_string(),
+    };
+    Ok(slf)
+  }
+
+  fn contains(&self, c: char) -> bool {
+    self
+      .ranges
+      .iter()
+      .any(|(first, last)| (*first..=*last).contains(&c))
+  }
+}
+
+
+/// The rules applying to a set of files.
+#[derive(Clone, Debug)]
+struct Rules {
+  /// The files the rules apply to, or `None` if they apply to all.
+  scope: Option<(String, GlobMatcher)>,
+  max_length: Option<usize>,
+  allowed: Option<Characters>,
+  pattern: Option<(String, GlobMatcher)>,
+  severity: Severity,
+}
+
+impl Rules {
+  fn new(scope: Option<&str>) -> Result<Self> {
+    let scope = scope
+      .map(|scope| {
+        let glob = if scope.contains('/') {
+          GlobBuilder::new(scope).literal_separator(true).build()
+        } else {
+          Glob::new(scope)
+        }
+        .with_context(|| format!("invalid glob pattern `{scope}`"))?;
+        Result::<_>::Ok((scope.to_string(), glob.compile_matcher()))
+      })
+      .transpose()?;
+
+    let slf = Self {
+      scope,
+      max_length: None,
+      allowed: None,
+      pattern: None,
+      severity: Severity::Deny,
+    };
+    Ok(slf)
+  }
+
+  fn set(&mut self, key: &str, value: Value) -> Result<()> {
+    match (key, value) {
+      ("max-length", Value::Integer(length)) => self.max_length = Some(length),
+      ("allowed-characters", Value::String(spec)) => {
+        self.allowed = Some(Characters::parse(&spec)?);
+      },
+      ("pattern", Value::String(pattern)) => {
+        let glob =
+          Glob::new(&pattern).with_context(|| format!("invalid glob pattern `{pattern}`"))?;
+        self.pattern = Some((pattern, glob.compile_matcher()));
+      },
+      ("severity", Value::String(severity)) => {
+        self.severity = match severity.as_str() {
+          "warn" => Severity::Warn,
+          "deny" => Severity::Deny,
+          _ => {
+            return Err(Error::Invalid(format!(
+              "unsupported severity `{severity}`; expected `warn` or `deny`"
+            )))
+          },
+        };
+      },
+      ("max-length", _) => {
+        return Err(Error::Invalid(format!(
+          "invalid value for `{key}`; expected an integer"
+        )))
+      },
+      ("allowed-characters" | "pattern" | "severity", _) => {
+        return Err(Error::Invalid(format!(
+          "invalid value for `{key}`; expected a string"
+        )))
+      },
+      (key, _) => return Err(Error::Invalid(format!("unsupported key `{key}`"))),
+    }
+    Ok(())
+  }
+
+  fn applies(&self, relative: &Path) -> bool {
+    match &self.scope {
+      None => true,
+      Some((scope, glob)) if scope.contains('/') => glob.is_match(relative),
+      Some((_, glob)) => relative.file_name().is_some_and(|name| glob.is_match(name)),
+    }
+  }
+
+  fn check(&self, name: &str, violations: &mut Vec<String>) {
+    if let Some(max) = self.max_length {
+      let length = name.chars().count();
+      if length > max {
+        let () = violations.push(format!(
+          "name is {length} characters long, exceeding the maximum of {max}"
+        ));
+      }
+    }
+
+    if let Some(allowed) = &self.allowed {
+      if let Some(c) = name.chars().find(|c| !allowed.contains(*c)) {
+        let () = violations.push(format!(
+          "name contains `{c}`, which is not among the allowed characters `{}`",
+          allowed.spec
+        ));
+      }
+    }
+
+    if let Some((pattern, glob)) = &self.pattern {
+      if !glob.is_match(name) {
+        let () = violations.push(format!("name does not match the pattern `{pattern}`"));
+      }
+    }
+  }
+}
+
+
+/// Parse a line of a policy file, adding a table to `rules` or
+/// setting a rule of the last one.
+fn parse_line(line: &str, rules: &mut Vec<Rules>) -> Result<()> {
+  let line = line.trim();
+  if line.is_empty() || line.starts_with('#') {
+    return Ok(())
+  }
+
+  if let Some(header) = line.strip_prefix('[') {
+    let (header, rest) = header.rsplit_once(']').context("unterminated table name")?;
+    let _rest = strip_comment(rest)?;
+    let scope = parse_header(header)?;
+    let () = rules.push(Rules::new(Some(&scope))?);
+  } else {
+    let (key, value) = parse_pair(line)?;
+    let rules = rules.last_mut().context("no table to set the rule for")?;
+    let () = rules.set(&key, value)?;
+  }
+  Ok(())
+}
+
+
+/// A violation of a naming policy.
+#[derive(Clone, Debug)]
+pub struct Violation {
+  /// The path violating the policy.
+  pub path: PathBuf,
+  /// The severity of the violation.
+  pub severity: Severity,
+  /// A description of the violation.
+  pub message: String,
+}
+
+impl Display for Violation {
+  fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+    write!(
+      f,
+      "{}: `{}` violates the naming policy: {}",
+      self.severity,
+      self.path.display(),
+      self.message
+    )
+  }
+}
+
+
+/// A naming policy.
+#[derive(Clone, Debug)]
+pub struct Policy {
+  /// The directory containing the policy file, below which it applies.
+  dir: PathBuf,
+  /// The rules, in the order they appear in the file.
+  rules: Vec<Rules>,
+}
+
+impl Policy {
+  /// Parse the policy in `content`, applying to files below `dir`.
+  pub fn parse(content: &str, dir: &Path) -> Result<Self> {
+    let mut rules = vec![Rules::new(None)?];
+    for (idx, line) in content.lines().enumerate() {
+      let () = parse_line(line, &mut rules)
+        .with_context(|| format!("invalid policy in line {}", idx + 1))?;
+    }
+
+    let slf = Self {
+      dir: dir.to_path_buf(),
+      rules,
+    };
+    Ok(slf)
+  }
+
+  /// Load the policy from the file at `path`.
+  pub async fn load(path: &Path) -> Result<Self> {
+    let content = read_to_string(path)
+      .await
+      .with_context(|| format!("failed to read naming policy `{}`", path.display()))?;
+    let path = crate::resolve(path, crate::SymlinkPolicy::Follow).await?;
+    // SANITY: A canonical path to a file always has a parent.
+    let dir = path.parent().unwrap();
+    Self::parse(&content, dir)
+      .with_context(|| format!("failed to parse naming policy `{}`", path.display()))
+  }
+
+  /// Check whether any violation is reported as an error.
+  pub fn denies_any(&self) -> bool {
+    self
+      .rules
+      .iter()
+      .any(|rules| rules.severity == Severity::Deny)
+  }
+
+  /// Check the (absolute) path `path` against the policy.
+  ///
+  /// Paths outside of the directory containing the policy file are not
+  /// subject to it.
+  pub fn check(&self, path: &Path) -> Vec<Violation> {
+    let Ok(relative) = path.strip_prefix(&self.dir) else {
+      return Vec::new()
+    };
+    let name = path
+      .file_name()
+      .map(|name| name.to_string_lossy())
+      .unwrap_or_default();
+
+    let mut violations = Vec::new();
+    for rules in self.rules.iter().filter(|rules| rules.applies(relative)) {
+      let mut messages = Vec::new();
+      let () = rules.check(&name, &mut messages);
+      let () = violations.extend(messages.into_iter().map(|message| Violation {
+        path: path.to_path_buf(),
+        severity: rules.severity,
+        message: match &rules.scope {
+          Some((scope, _)) => format!("{message} (for `{scope}`)"),
+          None => message,
+        },
+      }));
+    }
+    violations
+  }
+}
@@ -0,0 +1,28 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! The stable interface of the crate.
+//!
+//! Items exported here only change in a backwards incompatible manner
+//! with a new major version. Everything else that is public, including
+//! the remaining contents of modules re-exported from, may evolve in
+//! minor releases as the needs of the command line utilities change.
+
+pub use crate::concurrency::Concurrency;
+pub use crate::conflict::ConflictPolicy;
+pub use crate::error::Error;
+pub use crate::error::Result;
+pub use crate::plan::Rename;
+pub use crate::progress::Progress;
+pub use crate::propose;
+pub use crate::rename;
+pub use crate::rename_batch;
+pub use crate::rename_many;
+pub use crate::rename_transaction;
+pub use crate::rename_with;
+pub use crate::resolve;
+pub use crate::retry::RetryPolicy;
+pub use crate::Protocol;
+pub use crate::RenameOpts;
+pub use crate::SymlinkPolicy;
+pub use crate::Transaction;
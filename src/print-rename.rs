@@ -3,19 +3,40 @@
 
 #![allow(clippy::let_and_return, clippy::let_unit_value)]
 
+#[path = "batch-rename/shell.rs"]
+mod shell;
+
 use std::env::args_os;
 use std::ffi::OsString;
+use std::io::stderr;
 use std::io::stdout;
 use std::io::Write as _;
-use std::os::unix::ffi::OsStrExt as _;
 use std::path::PathBuf;
 
+use anyhow::ensure;
 use anyhow::Result;
 
 use clap::error::ErrorKind;
+use clap::ArgAction;
+use clap::CommandFactory as _;
 use clap::Parser;
+use clap_complete::generate;
+use clap_complete::Shell;
+
+use batch_renamer::change::classify;
+use batch_renamer::os_str_bytes;
+use batch_renamer::output::Format;
+use batch_renamer::output::Report;
+use batch_renamer::output::Status;
+use batch_renamer::rename_with;
+use batch_renamer::resolve;
+use batch_renamer::Protocol;
+use batch_renamer::RenameOpts;
+use batch_renamer::SymlinkPolicy;
+
+use tracing_subscriber::filter::LevelFilter;
 
-use batch_renamer::rename;
+use crate::shell::quote_shell;
 
 
 #[derive(Debug, Parser)]
@@ -24,11 +45,80 @@ struct Args {
   /// Do not actually perform the rename.
   #[clap(short = 'n', long = "dry-run")]
   dry_run: bool,
+  /// Rename a symbolic link itself instead of the file it refers to.
+  #[clap(short = 'P', long)]
+  no_dereference: bool,
+  /// The way the command communicates the new name: `rename` (the
+  /// command renames the file), `stdout` (the command receives the name
+  /// as argument and prints the new one), or `stdin` (the command reads
+  /// the name from stdin and prints the new one).
+  #[clap(long, value_name = "PROTOCOL", default_value = "rename")]
+  protocol: Protocol,
+  /// Print the new path as a shell variable assignment to the variable
+  /// with the given name, suitable for consumption by `eval`.
+  ///
+  /// This mode is meant for usage from shell widgets that want to
+  /// update the command line buffer with the new name.
+  #[clap(long, value_name = "VAR")]
+  shell_var: Option<String>,
+  /// The format in which to report the rename: `human`, `json`, or
+  /// `null` (old and new path, each terminated by NUL).
+  #[clap(
+    long,
+    value_name = "FORMAT",
+    default_value = "human",
+    conflicts_with = "shell_var"
+  )]
+  output: Format,
+  /// Print a script for completing arguments in the given shell
+  /// (`bash`, `zsh`, `fish`, `elvish`, or `powershell`) instead of
+  /// renaming.
+  #[clap(long, value_name = "SHELL", exclusive = true)]
+  completions: Option<Shell>,
+  /// Report what is going on in more detail on stderr, such as the
+  /// command run and the directory it is run in.
+  ///
+  /// Provide twice for including the output of the command as well.
+  #[clap(short, long, action = ArgAction::Count, conflicts_with = "quiet")]
+  verbose: u8,
+  /// Only report errors on stderr, omitting diagnostic warnings.
+  #[clap(long)]
+  quiet: bool,
   /// The command (and arguments) to use for renaming the file.
   #[clap(required = true)]
   command: Vec<OsString>,
   /// The file to rename.
-  file: PathBuf,
+  #[clap(required = true)]
+  file: Option<PathBuf>,
+}
+
+
+/// Check whether `name` is a valid shell variable name.
+fn is_shell_identifier(name: &str) -> bool {
+  let mut chars = name.chars();
+  chars
+    .next()
+    .map(|c| c.is_ascii_alphabetic() || c == '_')
+    .unwrap_or(false)
+    && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+
+/// Install a subscriber printing diagnostics to stderr, in the detail
+/// requested via `verbose` and `quiet`.
+fn init_tracing(verbose: u8, quiet: bool) {
+  let level = match (quiet, verbose) {
+    (true, _) => LevelFilter::ERROR,
+    (false, 0) => LevelFilter::WARN,
+    (false, 1) => LevelFilter::DEBUG,
+    (false, _) => LevelFilter::TRACE,
+  };
+  let () = tracing_subscriber::fmt()
+    .with_max_level(level)
+    .with_writer(stderr)
+    .without_time()
+    .with_target(false)
+    .init();
 }
 
 
@@ -45,7 +135,81 @@ async fn main() -> Result<()> {
     },
   };
 
-  let new_path = rename(&args.file, &args.command, args.dry_run).await?;
-  let () = stdout().write_all(new_path.as_os_str().as_bytes())?;
+  let () = init_tracing(args.verbose, args.quiet);
+
+  if let Some(shell) = args.completions {
+    let () = generate(
+      shell,
+      &mut Args::command(),
+      "print-rename",
+      &mut stdout().lock(),
+    );
+    return Ok(())
+  }
+  // SANITY: clap ensures that a file is provided unless completions
+  //         got requested.
+  let file = args.file.unwrap();
+
+  if let Some(var) = &args.shell_var {
+    ensure!(
+      is_shell_identifier(var),
+      "`{var}` is not a valid shell variable name"
+    );
+  }
+
+  let symlinks = if args.no_dereference {
+    SymlinkPolicy::NoFollow
+  } else {
+    SymlinkPolicy::Follow
+  };
+  let mut opts = RenameOpts::default();
+  opts.dry_run = args.dry_run;
+  opts.symlinks = symlinks;
+  opts.protocol = args.protocol;
+
+  if args.output != Format::Human {
+    // Resolve the source path before it may be gone as a result of
+    // the rename.
+    let src = resolve(&file, symlinks)
+      .await
+      .unwrap_or_else(|_| file.clone());
+    let result = rename_with(&file, &args.command, &opts).await;
+    let report = match &result {
+      Ok(dst) => Report {
+        src: src.clone(),
+        dst: dst.clone(),
+        status: if *dst == src {
+          Status::Unchanged
+        } else if args.dry_run {
+          Status::Planned
+        } else {
+          Status::Renamed
+        },
+        change: classify(&src, dst),
+        error: None,
+      },
+      Err(err) => Report {
+        dst: src.clone(),
+        src,
+        status: Status::Failed,
+        change: None,
+        error: Some(format!("{err:#}")),
+      },
+    };
+    let () = report.write(args.output, stdout().lock())?;
+    let _path = result?;
+    return Ok(())
+  }
+
+  let new_path = rename_with(&file, &args.command, &opts).await?;
+  let mut stdout = stdout().lock();
+  if let Some(var) = &args.shell_var {
+    let () = stdout.write_all(var.as_bytes())?;
+    let () = stdout.write_all(b"=")?;
+    let () = stdout.write_all(&os_str_bytes(&quote_shell(&new_path)))?;
+    let () = stdout.write_all(b"\n")?;
+  } else {
+    let () = stdout.write_all(&os_str_bytes(new_path.as_os_str()))?;
+  }
   Ok(())
 }
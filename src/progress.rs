@@ -0,0 +1,91 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Facilities for tracking the progress of a batch.
+
+use std::time::Duration;
+use std::time::Instant;
+
+
+/// The weight given to the most recent observation when updating the
+/// moving average. Larger values make the estimate adapt more quickly
+/// to changes in the mix of fast and slow files.
+const ALPHA: f64 = 0.1;
+
+
+/// The number of files of a batch in the various stages of renaming.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[non_exhaustive]
+pub struct Progress {
+  /// The total number of files in the batch.
+  pub total: usize,
+  /// The number of files whose new name has been determined.
+  pub previewed: usize,
+  /// The number of files that have been renamed.
+  pub applied: usize,
+  /// The number of files that could not be previewed or renamed.
+  pub failed: usize,
+}
+
+impl Progress {
+  /// Create a `Progress` object for a batch of `total` files, none of
+  /// which have been processed yet.
+  pub fn new(total: usize) -> Self {
+    Self {
+      total,
+      ..Default::default()
+    }
+  }
+}
+
+
+/// An estimator for the time remaining until a batch completes.
+///
+/// The estimator tracks an exponentially weighted moving average of
+/// the intervals between completions of items. Because it considers
+/// completion intervals and not per-item durations, the estimate
+/// remains meaningful when items are processed concurrently.
+#[derive(Clone, Debug)]
+pub struct Eta {
+  /// The time of the most recent completion (or of the creation of the
+  /// object, if nothing has completed yet).
+  last: Instant,
+  /// The moving average of completion intervals, in seconds.
+  average: Option<f64>,
+}
+
+impl Eta {
+  /// Create a new estimator, starting the clock.
+  pub fn new() -> Self {
+    Self {
+      last: Instant::now(),
+      average: None,
+    }
+  }
+
+  /// Record the completion of an item.
+  pub fn tick(&mut self) {
+    let now = Instant::now();
+    let interval = now.duration_since(self.last).as_secs_f64();
+    self.last = now;
+    self.average = Some(match self.average {
+      Some(average) => ALPHA * interval + (1.0 - ALPHA) * average,
+      None => interval,
+    });
+  }
+
+  /// Estimate the time it takes for `remaining` more items to complete.
+  ///
+  /// `None` is returned if no estimate can be provided yet.
+  pub fn estimate(&self, remaining: usize) -> Option<Duration> {
+    self
+      .average
+      .map(|average| Duration::from_secs_f64(average * remaining as f64))
+  }
+}
+
+impl Default for Eta {
+  fn default() -> Self {
+    Self::new()
+  }
+}
@@ -0,0 +1,144 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Retrying of operations failing with transient errors.
+
+use std::error::Error as StdError;
+use std::future::Future;
+use std::io;
+use std::io::ErrorKind;
+use std::iter::successors;
+use std::time::Duration;
+
+use tokio::time::sleep;
+
+use crate::error::Result;
+
+
+/// `errno` values considered transient, e.g., because they are known
+/// to be reported sporadically by network file systems.
+#[cfg(unix)]
+const TRANSIENT_ERRNOS: [i32; 5] = [
+  libc::EBUSY,
+  libc::ESTALE,
+  libc::EAGAIN,
+  libc::EINTR,
+  libc::ETIMEDOUT,
+];
+
+/// System error codes considered transient, e.g., because they are
+/// known to be reported sporadically by network shares.
+#[cfg(windows)]
+const TRANSIENT_ERRNOS: [i32; 5] = [
+  // ERROR_SHARING_VIOLATION
+  32,  // ERROR_LOCK_VIOLATION
+  33,  // ERROR_NETNAME_DELETED
+  64,  // ERROR_SEM_TIMEOUT
+  121, // ERROR_BUSY
+  170,
+];
+
+/// Error kinds considered transient, for errors not originating from
+/// the operating system.
+const TRANSIENT_KINDS: [ErrorKind; 3] = [
+  ErrorKind::WouldBlock,
+  ErrorKind::Interrupted,
+  ErrorKind::TimedOut,
+];
+
+
+/// A policy for retrying operations that failed with a transient
+/// error.
+#[derive(Clone, Copy, Debug)]
+#[non_exhaustive]
+pub struct RetryPolicy {
+  /// The maximum number of retries. Zero disables retrying.
+  pub retries: u32,
+  /// The delay before the first retry. The delay doubles with each
+  /// subsequent retry.
+  pub backoff: Duration,
+}
+
+impl RetryPolicy {
+  /// Create a policy retrying up to `retries` times, with `backoff`
+  /// as the delay before the first retry.
+  pub fn new(retries: u32, backoff: Duration) -> Self {
+    Self { retries, backoff }
+  }
+}
+
+impl Default for RetryPolicy {
+  fn default() -> Self {
+    Self::new(0, Duration::from_millis(100))
+  }
+}
+
+
+/// Check whether an I/O error is transient.
+fn is_transient_io(err: &io::Error) -> bool {
+  match err.raw_os_error() {
+    Some(errno) => TRANSIENT_ERRNOS.contains(&errno),
+    None => TRANSIENT_KINDS.contains(&err.kind()),
+  }
+}
+
+
+/// Retrieve the operating system's description of an error code, e.g.,
+/// "Device or resource busy" for `EBUSY`.
+fn describe(errno: i32) -> String {
+  let description = io::Error::from_raw_os_error(errno).to_string();
+  match description.find(" (os error") {
+    Some(idx) => description[..idx].to_string(),
+    None => description,
+  }
+}
+
+
+/// Check whether an error is transient and the failed operation may
+/// succeed when retried.
+///
+/// An error is considered transient if any error in its chain is an
+/// I/O error of a transient kind. Because rename commands report
+/// failures only textually, an error is also considered transient if
+/// its message mentions the description of a transient `errno` value
+/// (e.g., "Device or resource busy").
+pub fn is_transient(err: &(dyn StdError + 'static)) -> bool {
+  let chain = || successors(Some(err), |cause| (*cause).source());
+  let io = chain()
+    .filter_map(|cause| cause.downcast_ref::<io::Error>())
+    .any(is_transient_io);
+
+  io || {
+    let message = chain()
+      .map(|cause| cause.to_string())
+      .collect::<Vec<_>>()
+      .join(": ");
+    TRANSIENT_ERRNOS
+      .iter()
+      .any(|errno| message.contains(&describe(*errno)))
+  }
+}
+
+
+/// Run the operation `f`, retrying it according to `policy` for as long
+/// as it fails with a transient error.
+pub async fn retry<F, Fut, T>(policy: &RetryPolicy, mut f: F) -> Result<T>
+where
+  F: FnMut() -> Fut,
+  Fut: Future<Output = Result<T>>,
+{
+  let mut delay = policy.backoff;
+  let mut attempt = 0;
+
+  loop {
+    match f().await {
+      Ok(value) => break Ok(value),
+      Err(err) if attempt < policy.retries && is_transient(&err) => {
+        let () = sleep(delay).await;
+        delay = delay.saturating_mul(2);
+        attempt += 1;
+      },
+      Err(err) => break Err(err),
+    }
+  }
+}
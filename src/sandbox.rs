@@ -0,0 +1,65 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Confinement of rename commands by means of bubblewrap.
+
+use std::ffi::OsStr;
+use std::ffi::OsString;
+use std::path::absolute;
+use std::path::Path;
+
+use crate::error::Context as _;
+use crate::error::Result;
+
+
+/// The program used for running rename commands in a sandbox.
+pub(crate) const PROGRAM: &str = "bwrap";
+
+
+/// Assemble the arguments to [`PROGRAM`] for running `command` with
+/// `args` from within `dir`, confined to a read-only view of the
+/// system without network access and, if `writable` is set, with write
+/// access to `dir` only.
+pub(crate) fn args<C, A, S>(
+  command: C,
+  args: A,
+  dir: &Path,
+  writable: bool,
+) -> Result<Vec<OsString>>
+where
+  C: AsRef<OsStr>,
+  A: IntoIterator<Item = S>,
+  S: AsRef<OsStr>,
+{
+  let dir = absolute(dir)
+    .with_context(|| format!("failed to make `{}` absolute", dir.display()))?
+    .into_os_string();
+  let sandbox = [
+    OsStr::new("--ro-bind"),
+    OsStr::new("/"),
+    OsStr::new("/"),
+    OsStr::new("--dev"),
+    OsStr::new("/dev"),
+    OsStr::new("--proc"),
+    OsStr::new("/proc"),
+    OsStr::new(if writable { "--bind" } else { "--ro-bind" }),
+    &dir,
+    &dir,
+    OsStr::new("--chdir"),
+    &dir,
+    // Namespaces isolate the command from other processes and the
+    // network.
+    OsStr::new("--unshare-all"),
+    OsStr::new("--die-with-parent"),
+    // Prevent the command from injecting input into the terminal.
+    OsStr::new("--new-session"),
+    OsStr::new("--"),
+    command.as_ref(),
+  ];
+  let args = sandbox
+    .into_iter()
+    .map(OsStr::to_os_string)
+    .chain(args.into_iter().map(|arg| arg.as_ref().to_os_string()))
+    .collect();
+  Ok(args)
+}
@@ -0,0 +1,918 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Built-in cleanup of file names.
+//!
+//! Sanitizing a file name replaces runs of whitespace with a single
+//! underscore and removes control characters as well as characters
+//! that have special meaning to shells (such as `$`, `*`, or quotes),
+//! along with leading dashes, which make for names that get confused
+//! with options, and trailing dots, which Windows drops silently.
+//! Names that Windows reserves for devices, such as `con` or `nul.txt`,
+//! get an underscore appended to their stem. Optionally, names are
+//! lowercased.
+//!
+//! Names are furthermore brought into a Unicode normalization form,
+//! either composed (NFC) or decomposed (NFD). Normalization covers
+//! Latin letters with diacritics, which make up the vast majority of
+//! names in need of it; other characters are left alone.
+
+use std::ffi::OsStr;
+use std::ffi::OsString;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Result as FmtResult;
+use std::path::Path;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use crate::error::Context as _;
+use crate::error::Error;
+use crate::error::Result;
+use crate::grapheme::graphemes;
+use crate::resolve;
+use crate::SymlinkPolicy;
+
+
+/// Characters removed from file names, because they have special
+/// meaning to shells or are not permitted on common file systems.
+const HOSTILE: [char; 22] = [
+  '!', '"', '#', '$', '&', '\'', '(', ')', '*', ':', ';', '<', '>', '?', '[', '\\', ']', '`', '{',
+  '|', '}', '~',
+];
+
+/// Names reserved for devices on Windows, regardless of case and
+/// extension.
+const RESERVED: [&str; 22] = [
+  "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+  "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+
+/// The Unicode normalization form to bring file names into.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Normalization {
+  /// Canonical composition, as commonly used on Linux and Windows.
+  #[default]
+  Nfc,
+  /// Canonical decomposition, as used by macOS.
+  Nfd,
+}
+
+impl FromStr for Normalization {
+  type Err = Error;
+
+  fn from_str(s: &str) -> Result<Self> {
+    match s {
+      "nfc" => Ok(Self::Nfc),
+      "nfd" => Ok(Self::Nfd),
+      _ => Err(Error::Invalid(format!(
+        "unsupported normalization form `{s}`; expected one of `nfc` or `nfd`"
+      ))),
+    }
+  }
+}
+
+impl Display for Normalization {
+  fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+    let s = match self {
+      Self::Nfc => "nfc",
+      Self::Nfd => "nfd",
+    };
+    f.write_str(s)
+  }
+}
+
+
+/// Retrieve the canonical combining class of `c`.
+///
+/// Characters other than combining diacritical marks are treated as
+/// starters.
+fn combining_class(c: char) -> u8 {
+  match u32::from(c) {
+    cp @ 0x300..=0x36f => COMBINING_CLASSES[(cp - 0x300) as usize],
+    _ => 0,
+  }
+}
+
+
+/// Append the full canonical decomposition of `c` to `out`.
+fn decompose(c: char, out: &mut Vec<char>) {
+  match DECOMPOSITIONS.binary_search_by_key(&c, |(composed, _base, _mark)| *composed) {
+    Ok(idx) => {
+      let (_composed, base, mark) = DECOMPOSITIONS[idx];
+      let () = decompose(base, out);
+      let () = out.push(mark);
+    },
+    Err(..) => out.push(c),
+  }
+}
+
+
+/// Find the character composed of `base` and `mark`, if any.
+fn compose(base: char, mark: char) -> Option<char> {
+  DECOMPOSITIONS
+    .iter()
+    .find(|(_composed, b, m)| *b == base && *m == mark)
+    .map(|(composed, _base, _mark)| *composed)
+}
+
+
+/// Bring `s` into canonical decomposition (NFD), as a sequence of
+/// characters.
+fn nfd(s: &str) -> Vec<char> {
+  let mut chars = Vec::with_capacity(s.len());
+  for c in s.chars() {
+    let () = decompose(c, &mut chars);
+  }
+
+  // Marks following a starter are ordered by their combining class.
+  let mut start = 0;
+  while start < chars.len() {
+    let len = chars[start..]
+      .iter()
+      .take_while(|c| combining_class(**c) != 0)
+      .count();
+    let () = chars[start..start + len].sort_by_key(|c| combining_class(*c));
+    start += len.max(1);
+  }
+  chars
+}
+
+
+/// Bring `s` into canonical composition (NFC).
+fn nfc(s: &str) -> String {
+  let mut composed = Vec::with_capacity(s.len());
+  // The index of the last starter and the combining class of the
+  // character most recently added.
+  let mut starter = None;
+  let mut last_class = 0;
+
+  for c in nfd(s) {
+    let class = combining_class(c);
+    if let Some(idx) = starter {
+      // A mark can only be combined with the starter if no mark of the
+      // same or a higher class sits in between.
+      let adjacent = idx + 1 == composed.len();
+      if adjacent || (last_class != 0 && last_class < class) {
+        if let Some(c) = compose(composed[idx], c) {
+          composed[idx] = c;
+          continue
+        }
+      }
+    }
+
+    if class == 0 {
+      starter = Some(composed.len());
+    }
+    last_class = class;
+    let () = composed.push(c);
+  }
+  composed.into_iter().collect()
+}
+
+
+/// Check whether `name` is reserved for a device on Windows, i.e.,
+/// whether the part before its first dot is a device name.
+fn is_reserved(name: &str) -> bool {
+  let stem = name.split('.').next().unwrap_or_default();
+  RESERVED
+    .iter()
+    .any(|reserved| reserved.eq_ignore_ascii_case(stem))
+}
+
+
+/// A built-in transformation cleaning up file names.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Sanitizer {
+  lowercase: bool,
+  normalization: Normalization,
+  symlinks: SymlinkPolicy,
+}
+
+impl Sanitizer {
+  /// Set whether to lowercase names.
+  pub fn with_lowercase(mut self, lowercase: bool) -> Self {
+    self.lowercase = lowercase;
+    self
+  }
+
+  /// Set the Unicode normalization form to bring names into.
+  pub fn with_normalization(mut self, normalization: Normalization) -> Self {
+    self.normalization = normalization;
+    self
+  }
+
+  /// Set how to treat files that are symbolic links.
+  pub fn with_symlink_policy(mut self, symlinks: SymlinkPolicy) -> Self {
+    self.symlinks = symlinks;
+    self
+  }
+
+  /// Sanitize the file name `name`.
+  ///
+  /// Bytes not forming valid UTF-8 are removed. Characters are
+  /// treated as grapheme clusters, so that combining marks and other
+  /// extending characters share the fate of the character they are
+  /// attached to. Should nothing usable remain, `name` is returned
+  /// unchanged.
+  pub fn sanitize(&self, name: &OsStr) -> OsString {
+    let lossy = name.to_string_lossy();
+    let lowered;
+    let name_ref = if self.lowercase {
+      lowered = lossy.to_lowercase();
+      lowered.as_str()
+    } else {
+      &lossy
+    };
+    let normalized = match self.normalization {
+      Normalization::Nfc => nfc(name_ref),
+      Normalization::Nfd => nfd(name_ref).into_iter().collect(),
+    };
+
+    let mut sanitized = String::with_capacity(normalized.len());
+    let trimmed = normalized
+      .trim_start()
+      .trim_end_matches(|c: char| c == '.' || c.is_whitespace());
+    for grapheme in graphemes(trimmed) {
+      // SANITY: Grapheme clusters are never empty.
+      let c = grapheme.chars().next().unwrap();
+      if c.is_whitespace() {
+        if !sanitized.ends_with('_') {
+          let () = sanitized.push('_');
+        }
+      } else if !c.is_control() && !HOSTILE.contains(&c) && c != char::REPLACEMENT_CHARACTER {
+        let () = sanitized.push_str(grapheme);
+      }
+    }
+
+    match sanitized.trim_start_matches('-').trim_end_matches('.') {
+      "" => name.to_os_string(),
+      sanitized if is_reserved(sanitized) => {
+        let (stem, ext) = sanitized.split_at(sanitized.find('.').unwrap_or(sanitized.len()));
+        OsString::from(format!("{stem}_{ext}"))
+      },
+      sanitized => OsString::from(sanitized),
+    }
+  }
+
+  /// Determine the new path of the file at `path`.
+  ///
+  /// The file itself is not renamed.
+  pub async fn plan(&self, path: &Path) -> Result<PathBuf> {
+    let path = resolve(path, self.symlinks).await?;
+    let name = path
+      .file_name()
+      .with_context(|| format!("path `{}` does not have file name", path.display()))?;
+    Ok(path.with_file_name(self.sanitize(name)))
+  }
+}
+
+
+/// The canonical decompositions of Latin letters with diacritics, as
+/// pairs of a base character and a combining mark, ordered by the
+/// composed character.
+///
+/// Generated from the Unicode Character Database, covering the Latin-1
+/// Supplement, Latin Extended-A and -B, and Latin Extended Additional
+/// blocks.
+const DECOMPOSITIONS: [(char, char, char); 497] = [
+  ('\u{00c0}', 'A', '\u{0300}'),
+  ('\u{00c1}', 'A', '\u{0301}'),
+  ('\u{00c2}', 'A', '\u{0302}'),
+  ('\u{00c3}', 'A', '\u{0303}'),
+  ('\u{00c4}', 'A', '\u{0308}'),
+  ('\u{00c5}', 'A', '\u{030a}'),
+  ('\u{00c7}', 'C', '\u{0327}'),
+  ('\u{00c8}', 'E', '\u{0300}'),
+  ('\u{00c9}', 'E', '\u{0301}'),
+  ('\u{00ca}', 'E', '\u{0302}'),
+  ('\u{00cb}', 'E', '\u{0308}'),
+  ('\u{00cc}', 'I', '\u{0300}'),
+  ('\u{00cd}', 'I', '\u{0301}'),
+  ('\u{00ce}', 'I', '\u{0302}'),
+  ('\u{00cf}', 'I', '\u{0308}'),
+  ('\u{00d1}', 'N', '\u{0303}'),
+  ('\u{00d2}', 'O', '\u{0300}'),
+  ('\u{00d3}', 'O', '\u{0301}'),
+  ('\u{00d4}', 'O', '\u{0302}'),
+  ('\u{00d5}', 'O', '\u{0303}'),
+  ('\u{00d6}', 'O', '\u{0308}'),
+  ('\u{00d9}', 'U', '\u{0300}'),
+  ('\u{00da}', 'U', '\u{0301}'),
+  ('\u{00db}', 'U', '\u{0302}'),
+  ('\u{00dc}', 'U', '\u{0308}'),
+  ('\u{00dd}', 'Y', '\u{0301}'),
+  ('\u{00e0}', 'a', '\u{0300}'),
+  ('\u{00e1}', 'a', '\u{0301}'),
+  ('\u{00e2}', 'a', '\u{0302}'),
+  ('\u{00e3}', 'a', '\u{0303}'),
+  ('\u{00e4}', 'a', '\u{0308}'),
+  ('\u{00e5}', 'a', '\u{030a}'),
+  ('\u{00e7}', 'c', '\u{0327}'),
+  ('\u{00e8}', 'e', '\u{0300}'),
+  ('\u{00e9}', 'e', '\u{0301}'),
+  ('\u{00ea}', 'e', '\u{0302}'),
+  ('\u{00eb}', 'e', '\u{0308}'),
+  ('\u{00ec}', 'i', '\u{0300}'),
+  ('\u{00ed}', 'i', '\u{0301}'),
+  ('\u{00ee}', 'i', '\u{0302}'),
+  ('\u{00ef}', 'i', '\u{0308}'),
+  ('\u{00f1}', 'n', '\u{0303}'),
+  ('\u{00f2}', 'o', '\u{0300}'),
+  ('\u{00f3}', 'o', '\u{0301}'),
+  ('\u{00f4}', 'o', '\u{0302}'),
+  ('\u{00f5}', 'o', '\u{0303}'),
+  ('\u{00f6}', 'o', '\u{0308}'),
+  ('\u{00f9}', 'u', '\u{0300}'),
+  ('\u{00fa}', 'u', '\u{0301}'),
+  ('\u{00fb}', 'u', '\u{0302}'),
+  ('\u{00fc}', 'u', '\u{0308}'),
+  ('\u{00fd}', 'y', '\u{0301}'),
+  ('\u{00ff}', 'y', '\u{0308}'),
+  ('\u{0100}', 'A', '\u{0304}'),
+  ('\u{0101}', 'a', '\u{0304}'),
+  ('\u{0102}', 'A', '\u{0306}'),
+  ('\u{0103}', 'a', '\u{0306}'),
+  ('\u{0104}', 'A', '\u{0328}'),
+  ('\u{0105}', 'a', '\u{0328}'),
+  ('\u{0106}', 'C', '\u{0301}'),
+  ('\u{0107}', 'c', '\u{0301}'),
+  ('\u{0108}', 'C', '\u{0302}'),
+  ('\u{0109}', 'c', '\u{0302}'),
+  ('\u{010a}', 'C', '\u{0307}'),
+  ('\u{010b}', 'c', '\u{0307}'),
+  ('\u{010c}', 'C', '\u{030c}'),
+  ('\u{010d}', 'c', '\u{030c}'),
+  ('\u{010e}', 'D', '\u{030c}'),
+  ('\u{010f}', 'd', '\u{030c}'),
+  ('\u{0112}', 'E', '\u{0304}'),
+  ('\u{0113}', 'e', '\u{0304}'),
+  ('\u{0114}', 'E', '\u{0306}'),
+  ('\u{0115}', 'e', '\u{0306}'),
+  ('\u{0116}', 'E', '\u{0307}'),
+  ('\u{0117}', 'e', '\u{0307}'),
+  ('\u{0118}', 'E', '\u{0328}'),
+  ('\u{0119}', 'e', '\u{0328}'),
+  ('\u{011a}', 'E', '\u{030c}'),
+  ('\u{011b}', 'e', '\u{030c}'),
+  ('\u{011c}', 'G', '\u{0302}'),
+  ('\u{011d}', 'g', '\u{0302}'),
+  ('\u{011e}', 'G', '\u{0306}'),
+  ('\u{011f}', 'g', '\u{0306}'),
+  ('\u{0120}', 'G', '\u{0307}'),
+  ('\u{0121}', 'g', '\u{0307}'),
+  ('\u{0122}', 'G', '\u{0327}'),
+  ('\u{0123}', 'g', '\u{0327}'),
+  ('\u{0124}', 'H', '\u{0302}'),
+  ('\u{0125}', 'h', '\u{0302}'),
+  ('\u{0128}', 'I', '\u{0303}'),
+  ('\u{0129}', 'i', '\u{0303}'),
+  ('\u{012a}', 'I', '\u{0304}'),
+  ('\u{012b}', 'i', '\u{0304}'),
+  ('\u{012c}', 'I', '\u{0306}'),
+  ('\u{012d}', 'i', '\u{0306}'),
+  ('\u{012e}', 'I', '\u{0328}'),
+  ('\u{012f}', 'i', '\u{0328}'),
+  ('\u{0130}', 'I', '\u{0307}'),
+  ('\u{0134}', 'J', '\u{0302}'),
+  ('\u{0135}', 'j', '\u{0302}'),
+  ('\u{0136}', 'K', '\u{0327}'),
+  ('\u{0137}', 'k', '\u{0327}'),
+  ('\u{0139}', 'L', '\u{0301}'),
+  ('\u{013a}', 'l', '\u{0301}'),
+  ('\u{013b}', 'L', '\u{0327}'),
+  ('\u{013c}', 'l', '\u{0327}'),
+  ('\u{013d}', 'L', '\u{030c}'),
+  ('\u{013e}', 'l', '\u{030c}'),
+  ('\u{0143}', 'N', '\u{0301}'),
+  ('\u{0144}', 'n', '\u{0301}'),
+  ('\u{0145}', 'N', '\u{0327}'),
+  ('\u{0146}', 'n', '\u{0327}'),
+  ('\u{0147}', 'N', '\u{030c}'),
+  ('\u{0148}', 'n', '\u{030c}'),
+  ('\u{014c}', 'O', '\u{0304}'),
+  ('\u{014d}', 'o', '\u{0304}'),
+  ('\u{014e}', 'O', '\u{0306}'),
+  ('\u{014f}', 'o', '\u{0306}'),
+  ('\u{0150}', 'O', '\u{030b}'),
+  ('\u{0151}', 'o', '\u{030b}'),
+  ('\u{0154}', 'R', '\u{0301}'),
+  ('\u{0155}', 'r', '\u{0301}'),
+  ('\u{0156}', 'R', '\u{0327}'),
+  ('\u{0157}', 'r', '\u{0327}'),
+  ('\u{0158}', 'R', '\u{030c}'),
+  ('\u{0159}', 'r', '\u{030c}'),
+  ('\u{015a}', 'S', '\u{0301}'),
+  ('\u{015b}', 's', '\u{0301}'),
+  ('\u{015c}', 'S', '\u{0302}'),
+  ('\u{015d}', 's', '\u{0302}'),
+  ('\u{015e}', 'S', '\u{0327}'),
+  ('\u{015f}', 's', '\u{0327}'),
+  ('\u{0160}', 'S', '\u{030c}'),
+  ('\u{0161}', 's', '\u{030c}'),
+  ('\u{0162}', 'T', '\u{0327}'),
+  ('\u{0163}', 't', '\u{0327}'),
+  ('\u{0164}', 'T', '\u{030c}'),
+  ('\u{0165}', 't', '\u{030c}'),
+  ('\u{0168}', 'U', '\u{0303}'),
+  ('\u{0169}', 'u', '\u{0303}'),
+  ('\u{016a}', 'U', '\u{0304}'),
+  ('\u{016b}', 'u', '\u{0304}'),
+  ('\u{016c}', 'U', '\u{0306}'),
+  ('\u{016d}', 'u', '\u{0306}'),
+  ('\u{016e}', 'U', '\u{030a}'),
+  ('\u{016f}', 'u', '\u{030a}'),
+  ('\u{0170}', 'U', '\u{030b}'),
+  ('\u{0171}', 'u', '\u{030b}'),
+  ('\u{0172}', 'U', '\u{0328}'),
+  ('\u{0173}', 'u', '\u{0328}'),
+  ('\u{0174}', 'W', '\u{0302}'),
+  ('\u{0175}', 'w', '\u{0302}'),
+  ('\u{0176}', 'Y', '\u{0302}'),
+  ('\u{0177}', 'y', '\u{0302}'),
+  ('\u{0178}', 'Y', '\u{0308}'),
+  ('\u{0179}', 'Z', '\u{0301}'),
+  ('\u{017a}', 'z', '\u{0301}'),
+  ('\u{017b}', 'Z', '\u{0307}'),
+  ('\u{017c}', 'z', '\u{0307}'),
+  ('\u{017d}', 'Z', '\u{030c}'),
+  ('\u{017e}', 'z', '\u{030c}'),
+  ('\u{01a0}', 'O', '\u{031b}'),
+  ('\u{01a1}', 'o', '\u{031b}'),
+  ('\u{01af}', 'U', '\u{031b}'),
+  ('\u{01b0}', 'u', '\u{031b}'),
+  ('\u{01cd}', 'A', '\u{030c}'),
+  ('\u{01ce}', 'a', '\u{030c}'),
+  ('\u{01cf}', 'I', '\u{030c}'),
+  ('\u{01d0}', 'i', '\u{030c}'),
+  ('\u{01d1}', 'O', '\u{030c}'),
+  ('\u{01d2}', 'o', '\u{030c}'),
+  ('\u{01d3}', 'U', '\u{030c}'),
+  ('\u{01d4}', 'u', '\u{030c}'),
+  ('\u{01d5}', '\u{00dc}', '\u{0304}'),
+  ('\u{01d6}', '\u{00fc}', '\u{0304}'),
+  ('\u{01d7}', '\u{00dc}', '\u{0301}'),
+  ('\u{01d8}', '\u{00fc}', '\u{0301}'),
+  ('\u{01d9}', '\u{00dc}', '\u{030c}'),
+  ('\u{01da}', '\u{00fc}', '\u{030c}'),
+  ('\u{01db}', '\u{00dc}', '\u{0300}'),
+  ('\u{01dc}', '\u{00fc}', '\u{0300}'),
+  ('\u{01de}', '\u{00c4}', '\u{0304}'),
+  ('\u{01df}', '\u{00e4}', '\u{0304}'),
+  ('\u{01e0}', '\u{0226}', '\u{0304}'),
+  ('\u{01e1}', '\u{0227}', '\u{0304}'),
+  ('\u{01e2}', '\u{00c6}', '\u{0304}'),
+  ('\u{01e3}', '\u{00e6}', '\u{0304}'),
+  ('\u{01e6}', 'G', '\u{030c}'),
+  ('\u{01e7}', 'g', '\u{030c}'),
+  ('\u{01e8}', 'K', '\u{030c}'),
+  ('\u{01e9}', 'k', '\u{030c}'),
+  ('\u{01ea}', 'O', '\u{0328}'),
+  ('\u{01eb}', 'o', '\u{0328}'),
+  ('\u{01ec}', '\u{01ea}', '\u{0304}'),
+  ('\u{01ed}', '\u{01eb}', '\u{0304}'),
+  ('\u{01ee}', '\u{01b7}', '\u{030c}'),
+  ('\u{01ef}', '\u{0292}', '\u{030c}'),
+  ('\u{01f0}', 'j', '\u{030c}'),
+  ('\u{01f4}', 'G', '\u{0301}'),
+  ('\u{01f5}', 'g', '\u{0301}'),
+  ('\u{01f8}', 'N', '\u{0300}'),
+  ('\u{01f9}', 'n', '\u{0300}'),
+  ('\u{01fa}', '\u{00c5}', '\u{0301}'),
+  ('\u{01fb}', '\u{00e5}', '\u{0301}'),
+  ('\u{01fc}', '\u{00c6}', '\u{0301}'),
+  ('\u{01fd}', '\u{00e6}', '\u{0301}'),
+  ('\u{01fe}', '\u{00d8}', '\u{0301}'),
+  ('\u{01ff}', '\u{00f8}', '\u{0301}'),
+  ('\u{0200}', 'A', '\u{030f}'),
+  ('\u{0201}', 'a', '\u{030f}'),
+  ('\u{0202}', 'A', '\u{0311}'),
+  ('\u{0203}', 'a', '\u{0311}'),
+  ('\u{0204}', 'E', '\u{030f}'),
+  ('\u{0205}', 'e', '\u{030f}'),
+  ('\u{0206}', 'E', '\u{0311}'),
+  ('\u{0207}', 'e', '\u{0311}'),
+  ('\u{0208}', 'I', '\u{030f}'),
+  ('\u{0209}', 'i', '\u{030f}'),
+  ('\u{020a}', 'I', '\u{0311}'),
+  ('\u{020b}', 'i', '\u{0311}'),
+  ('\u{020c}', 'O', '\u{030f}'),
+  ('\u{020d}', 'o', '\u{030f}'),
+  ('\u{020e}', 'O', '\u{0311}'),
+  ('\u{020f}', 'o', '\u{0311}'),
+  ('\u{0210}', 'R', '\u{030f}'),
+  ('\u{0211}', 'r', '\u{030f}'),
+  ('\u{0212}', 'R', '\u{0311}'),
+  ('\u{0213}', 'r', '\u{0311}'),
+  ('\u{0214}', 'U', '\u{030f}'),
+  ('\u{0215}', 'u', '\u{030f}'),
+  ('\u{0216}', 'U', '\u{0311}'),
+  ('\u{0217}', 'u', '\u{0311}'),
+  ('\u{0218}', 'S', '\u{0326}'),
+  ('\u{0219}', 's', '\u{0326}'),
+  ('\u{021a}', 'T', '\u{0326}'),
+  ('\u{021b}', 't', '\u{0326}'),
+  ('\u{021e}', 'H', '\u{030c}'),
+  ('\u{021f}', 'h', '\u{030c}'),
+  ('\u{0226}', 'A', '\u{0307}'),
+  ('\u{0227}', 'a', '\u{0307}'),
+  ('\u{0228}', 'E', '\u{0327}'),
+  ('\u{0229}', 'e', '\u{0327}'),
+  ('\u{022a}', '\u{00d6}', '\u{0304}'),
+  ('\u{022b}', '\u{00f6}', '\u{0304}'),
+  ('\u{022c}', '\u{00d5}', '\u{0304}'),
+  ('\u{022d}', '\u{00f5}', '\u{0304}'),
+  ('\u{022e}', 'O', '\u{0307}'),
+  ('\u{022f}', 'o', '\u{0307}'),
+  ('\u{0230}', '\u{022e}', '\u{0304}'),
+  ('\u{0231}', '\u{022f}', '\u{0304}'),
+  ('\u{0232}', 'Y', '\u{0304}'),
+  ('\u{0233}', 'y', '\u{0304}'),
+  ('\u{1e00}', 'A', '\u{0325}'),
+  ('\u{1e01}', 'a', '\u{0325}'),
+  ('\u{1e02}', 'B', '\u{0307}'),
+  ('\u{1e03}', 'b', '\u{0307}'),
+  ('\u{1e04}', 'B', '\u{0323}'),
+  ('\u{1e05}', 'b', '\u{0323}'),
+  ('\u{1e06}', 'B', '\u{0331}'),
+  ('\u{1e07}', 'b', '\u{0331}'),
+  ('\u{1e08}', '\u{00c7}', '\u{0301}'),
+  ('\u{1e09}', '\u{00e7}', '\u{0301}'),
+  ('\u{1e0a}', 'D', '\u{0307}'),
+  ('\u{1e0b}', 'd', '\u{0307}'),
+  ('\u{1e0c}', 'D', '\u{0323}'),
+  ('\u{1e0d}', 'd', '\u{0323}'),
+  ('\u{1e0e}', 'D', '\u{0331}'),
+  ('\u{1e0f}', 'd', '\u{0331}'),
+  ('\u{1e10}', 'D', '\u{0327}'),
+  ('\u{1e11}', 'd', '\u{0327}'),
+  ('\u{1e12}', 'D', '\u{032d}'),
+  ('\u{1e13}', 'd', '\u{032d}'),
+  ('\u{1e14}', '\u{0112}', '\u{0300}'),
+  ('\u{1e15}', '\u{0113}', '\u{0300}'),
+  ('\u{1e16}', '\u{0112}', '\u{0301}'),
+  ('\u{1e17}', '\u{0113}', '\u{0301}'),
+  ('\u{1e18}', 'E', '\u{032d}'),
+  ('\u{1e19}', 'e', '\u{032d}'),
+  ('\u{1e1a}', 'E', '\u{0330}'),
+  ('\u{1e1b}', 'e', '\u{0330}'),
+  ('\u{1e1c}', '\u{0228}', '\u{0306}'),
+  ('\u{1e1d}', '\u{0229}', '\u{0306}'),
+  ('\u{1e1e}', 'F', '\u{0307}'),
+  ('\u{1e1f}', 'f', '\u{0307}'),
+  ('\u{1e20}', 'G', '\u{0304}'),
+  ('\u{1e21}', 'g', '\u{0304}'),
+  ('\u{1e22}', 'H', '\u{0307}'),
+  ('\u{1e23}', 'h', '\u{0307}'),
+  ('\u{1e24}', 'H', '\u{0323}'),
+  ('\u{1e25}', 'h', '\u{0323}'),
+  ('\u{1e26}', 'H', '\u{0308}'),
+  ('\u{1e27}', 'h', '\u{0308}'),
+  ('\u{1e28}', 'H', '\u{0327}'),
+  ('\u{1e29}', 'h', '\u{0327}'),
+  ('\u{1e2a}', 'H', '\u{032e}'),
+  ('\u{1e2b}', 'h', '\u{032e}'),
+  ('\u{1e2c}', 'I', '\u{0330}'),
+  ('\u{1e2d}', 'i', '\u{0330}'),
+  ('\u{1e2e}', '\u{00cf}', '\u{0301}'),
+  ('\u{1e2f}', '\u{00ef}', '\u{0301}'),
+  ('\u{1e30}', 'K', '\u{0301}'),
+  ('\u{1e31}', 'k', '\u{0301}'),
+  ('\u{1e32}', 'K', '\u{0323}'),
+  ('\u{1e33}', 'k', '\u{0323}'),
+  ('\u{1e34}', 'K', '\u{0331}'),
+  ('\u{1e35}', 'k', '\u{0331}'),
+  ('\u{1e36}', 'L', '\u{0323}'),
+  ('\u{1e37}', 'l', '\u{0323}'),
+  ('\u{1e38}', '\u{1e36}', '\u{0304}'),
+  ('\u{1e39}', '\u{1e37}', '\u{0304}'),
+  ('\u{1e3a}', 'L', '\u{0331}'),
+  ('\u{1e3b}', 'l', '\u{0331}'),
+  ('\u{1e3c}', 'L', '\u{032d}'),
+  ('\u{1e3d}', 'l', '\u{032d}'),
+  ('\u{1e3e}', 'M', '\u{0301}'),
+  ('\u{1e3f}', 'm', '\u{0301}'),
+  ('\u{1e40}', 'M', '\u{0307}'),
+  ('\u{1e41}', 'm', '\u{0307}'),
+  ('\u{1e42}', 'M', '\u{0323}'),
+  ('\u{1e43}', 'm', '\u{0323}'),
+  ('\u{1e44}', 'N', '\u{0307}'),
+  ('\u{1e45}', 'n', '\u{0307}'),
+  ('\u{1e46}', 'N', '\u{0323}'),
+  ('\u{1e47}', 'n', '\u{0323}'),
+  ('\u{1e48}', 'N', '\u{0331}'),
+  ('\u{1e49}', 'n', '\u{0331}'),
+  ('\u{1e4a}', 'N', '\u{032d}'),
+  ('\u{1e4b}', 'n', '\u{032d}'),
+  ('\u{1e4c}', '\u{00d5}', '\u{0301}'),
+  ('\u{1e4d}', '\u{00f5}', '\u{0301}'),
+  ('\u{1e4e}', '\u{00d5}', '\u{0308}'),
+  ('\u{1e4f}', '\u{00f5}', '\u{0308}'),
+  ('\u{1e50}', '\u{014c}', '\u{0300}'),
+  ('\u{1e51}', '\u{014d}', '\u{0300}'),
+  ('\u{1e52}', '\u{014c}', '\u{0301}'),
+  ('\u{1e53}', '\u{014d}', '\u{0301}'),
+  ('\u{1e54}', 'P', '\u{0301}'),
+  ('\u{1e55}', 'p', '\u{0301}'),
+  ('\u{1e56}', 'P', '\u{0307}'),
+  ('\u{1e57}', 'p', '\u{0307}'),
+  ('\u{1e58}', 'R', '\u{0307}'),
+  ('\u{1e59}', 'r', '\u{0307}'),
+  ('\u{1e5a}', 'R', '\u{0323}'),
+  ('\u{1e5b}', 'r', '\u{0323}'),
+  ('\u{1e5c}', '\u{1e5a}', '\u{0304}'),
+  ('\u{1e5d}', '\u{1e5b}', '\u{0304}'),
+  ('\u{1e5e}', 'R', '\u{0331}'),
+  ('\u{1e5f}', 'r', '\u{0331}'),
+  ('\u{1e60}', 'S', '\u{0307}'),
+  ('\u{1e61}', 's', '\u{0307}'),
+  ('\u{1e62}', 'S', '\u{0323}'),
+  ('\u{1e63}', 's', '\u{0323}'),
+  ('\u{1e64}', '\u{015a}', '\u{0307}'),
+  ('\u{1e65}', '\u{015b}', '\u{0307}'),
+  ('\u{1e66}', '\u{0160}', '\u{0307}'),
+  ('\u{1e67}', '\u{0161}', '\u{0307}'),
+  ('\u{1e68}', '\u{1e62}', '\u{0307}'),
+  ('\u{1e69}', '\u{1e63}', '\u{0307}'),
+  ('\u{1e6a}', 'T', '\u{0307}'),
+  ('\u{1e6b}', 't', '\u{0307}'),
+  ('\u{1e6c}', 'T', '\u{0323}'),
+  ('\u{1e6d}', 't', '\u{0323}'),
+  ('\u{1e6e}', 'T', '\u{0331}'),
+  ('\u{1e6f}', 't', '\u{0331}'),
+  ('\u{1e70}', 'T', '\u{032d}'),
+  ('\u{1e71}', 't', '\u{032d}'),
+  ('\u{1e72}', 'U', '\u{0324}'),
+  ('\u{1e73}', 'u', '\u{0324}'),
+  ('\u{1e74}', 'U', '\u{0330}'),
+  ('\u{1e75}', 'u', '\u{0330}'),
+  ('\u{1e76}', 'U', '\u{032d}'),
+  ('\u{1e77}', 'u', '\u{032d}'),
+  ('\u{1e78}', '\u{0168}', '\u{0301}'),
+  ('\u{1e79}', '\u{0169}', '\u{0301}'),
+  ('\u{1e7a}', '\u{016a}', '\u{0308}'),
+  ('\u{1e7b}', '\u{016b}', '\u{0308}'),
+  ('\u{1e7c}', 'V', '\u{0303}'),
+  ('\u{1e7d}', 'v', '\u{0303}'),
+  ('\u{1e7e}', 'V', '\u{0323}'),
+  ('\u{1e7f}', 'v', '\u{0323}'),
+  ('\u{1e80}', 'W', '\u{0300}'),
+  ('\u{1e81}', 'w', '\u{0300}'),
+  ('\u{1e82}', 'W', '\u{0301}'),
+  ('\u{1e83}', 'w', '\u{0301}'),
+  ('\u{1e84}', 'W', '\u{0308}'),
+  ('\u{1e85}', 'w', '\u{0308}'),
+  ('\u{1e86}', 'W', '\u{0307}'),
+  ('\u{1e87}', 'w', '\u{0307}'),
+  ('\u{1e88}', 'W', '\u{0323}'),
+  ('\u{1e89}', 'w', '\u{0323}'),
+  ('\u{1e8a}', 'X', '\u{0307}'),
+  ('\u{1e8b}', 'x', '\u{0307}'),
+  ('\u{1e8c}', 'X', '\u{0308}'),
+  ('\u{1e8d}', 'x', '\u{0308}'),
+  ('\u{1e8e}', 'Y', '\u{0307}'),
+  ('\u{1e8f}', 'y', '\u{0307}'),
+  ('\u{1e90}', 'Z', '\u{0302}'),
+  ('\u{1e91}', 'z', '\u{0302}'),
+  ('\u{1e92}', 'Z', '\u{0323}'),
+  ('\u{1e93}', 'z', '\u{0323}'),
+  ('\u{1e94}', 'Z', '\u{0331}'),
+  ('\u{1e95}', 'z', '\u{0331}'),
+  ('\u{1e96}', 'h', '\u{0331}'),
+  ('\u{1e97}', 't', '\u{0308}'),
+  ('\u{1e98}', 'w', '\u{030a}'),
+  ('\u{1e99}', 'y', '\u{030a}'),
+  ('\u{1e9b}', '\u{017f}', '\u{0307}'),
+  ('\u{1ea0}', 'A', '\u{0323}'),
+  ('\u{1ea1}', 'a', '\u{0323}'),
+  ('\u{1ea2}', 'A', '\u{0309}'),
+  ('\u{1ea3}', 'a', '\u{0309}'),
+  ('\u{1ea4}', '\u{00c2}', '\u{0301}'),
+  ('\u{1ea5}', '\u{00e2}', '\u{0301}'),
+  ('\u{1ea6}', '\u{00c2}', '\u{0300}'),
+  ('\u{1ea7}', '\u{00e2}', '\u{0300}'),
+  ('\u{1ea8}', '\u{00c2}', '\u{0309}'),
+  ('\u{1ea9}', '\u{00e2}', '\u{0309}'),
+  ('\u{1eaa}', '\u{00c2}', '\u{0303}'),
+  ('\u{1eab}', '\u{00e2}', '\u{0303}'),
+  ('\u{1eac}', '\u{1ea0}', '\u{0302}'),
+  ('\u{1ead}', '\u{1ea1}', '\u{0302}'),
+  ('\u{1eae}', '\u{0102}', '\u{0301}'),
+  ('\u{1eaf}', '\u{0103}', '\u{0301}'),
+  ('\u{1eb0}', '\u{0102}', '\u{0300}'),
+  ('\u{1eb1}', '\u{0103}', '\u{0300}'),
+  ('\u{1eb2}', '\u{0102}', '\u{0309}'),
+  ('\u{1eb3}', '\u{0103}', '\u{0309}'),
+  ('\u{1eb4}', '\u{0102}', '\u{0303}'),
+  ('\u{1eb5}', '\u{0103}', '\u{0303}'),
+  ('\u{1eb6}', '\u{1ea0}', '\u{0306}'),
+  ('\u{1eb7}', '\u{1ea1}', '\u{0306}'),
+  ('\u{1eb8}', 'E', '\u{0323}'),
+  ('\u{1eb9}', 'e', '\u{0323}'),
+  ('\u{1eba}', 'E', '\u{0309}'),
+  ('\u{1ebb}', 'e', '\u{0309}'),
+  ('\u{1ebc}', 'E', '\u{0303}'),
+  ('\u{1ebd}', 'e', '\u{0303}'),
+  ('\u{1ebe}', '\u{00ca}', '\u{0301}'),
+  ('\u{1ebf}', '\u{00ea}', '\u{0301}'),
+  ('\u{1ec0}', '\u{00ca}', '\u{0300}'),
+  ('\u{1ec1}', '\u{00ea}', '\u{0300}'),
+  ('\u{1ec2}', '\u{00ca}', '\u{0309}'),
+  ('\u{1ec3}', '\u{00ea}', '\u{0309}'),
+  ('\u{1ec4}', '\u{00ca}', '\u{0303}'),
+  ('\u{1ec5}', '\u{00ea}', '\u{0303}'),
+  ('\u{1ec6}', '\u{1eb8}', '\u{0302}'),
+  ('\u{1ec7}', '\u{1eb9}', '\u{0302}'),
+  ('\u{1ec8}', 'I', '\u{0309}'),
+  ('\u{1ec9}', 'i', '\u{0309}'),
+  ('\u{1eca}', 'I', '\u{0323}'),
+  ('\u{1ecb}', 'i', '\u{0323}'),
+  ('\u{1ecc}', 'O', '\u{0323}'),
+  ('\u{1ecd}', 'o', '\u{0323}'),
+  ('\u{1ece}', 'O', '\u{0309}'),
+  ('\u{1ecf}', 'o', '\u{0309}'),
+  ('\u{1ed0}', '\u{00d4}', '\u{0301}'),
+  ('\u{1ed1}', '\u{00f4}', '\u{0301}'),
+  ('\u{1ed2}', '\u{00d4}', '\u{0300}'),
+  ('\u{1ed3}', '\u{00f4}', '\u{0300}'),
+  ('\u{1ed4}', '\u{00d4}', '\u{0309}'),
+  ('\u{1ed5}', '\u{00f4}', '\u{0309}'),
+  ('\u{1ed6}', '\u{00d4}', '\u{0303}'),
+  ('\u{1ed7}', '\u{00f4}', '\u{0303}'),
+  ('\u{1ed8}', '\u{1ecc}', '\u{0302}'),
+  ('\u{1ed9}', '\u{1ecd}', '\u{0302}'),
+  ('\u{1eda}', '\u{01a0}', '\u{0301}'),
+  ('\u{1edb}', '\u{01a1}', '\u{0301}'),
+  ('\u{1edc}', '\u{01a0}', '\u{0300}'),
+  ('\u{1edd}', '\u{01a1}', '\u{0300}'),
+  ('\u{1ede}', '\u{01a0}', '\u{0309}'),
+  ('\u{1edf}', '\u{01a1}', '\u{0309}'),
+  ('\u{1ee0}', '\u{01a0}', '\u{0303}'),
+  ('\u{1ee1}', '\u{01a1}', '\u{0303}'),
+  ('\u{1ee2}', '\u{01a0}', '\u{0323}'),
+  ('\u{1ee3}', '\u{01a1}', '\u{0323}'),
+  ('\u{1ee4}', 'U', '\u{0323}'),
+  ('\u{1ee5}', 'u', '\u{0323}'),
+  ('\u{1ee6}', 'U', '\u{0309}'),
+  ('\u{1ee7}', 'u', '\u{0309}'),
+  ('\u{1ee8}', '\u{01af}', '\u{0301}'),
+  ('\u{1ee9}', '\u{01b0}', '\u{0301}'),
+  ('\u{1eea}', '\u{01af}', '\u{0300}'),
+  ('\u{1eeb}', '\u{01b0}', '\u{0300}'),
+  ('\u{1eec}', '\u{01af}', '\u{0309}'),
+  ('\u{1eed}', '\u{01b0}', '\u{0309}'),
+  ('\u{1eee}', '\u{01af}', '\u{0303}'),
+  ('\u{1eef}', '\u{01b0}', '\u{0303}'),
+  ('\u{1ef0}', '\u{01af}', '\u{0323}'),
+  ('\u{1ef1}', '\u{01b0}', '\u{0323}'),
+  ('\u{1ef2}', 'Y', '\u{0300}'),
+  ('\u{1ef3}', 'y', '\u{0300}'),
+  ('\u{1ef4}', 'Y', '\u{0323}'),
+  ('\u{1ef5}', 'y', '\u{0323}'),
+  ('\u{1ef6}', 'Y', '\u{0309}'),
+  ('\u{1ef7}', 'y', '\u{0309}'),
+  ('\u{1ef8}', 'Y', '\u{0303}'),
+  ('\u{1ef9}', 'y', '\u{0303}'),
+];
+
+/// The canonical combining classes of the combining diacritical marks
+/// (U+0300 to U+036F).
+const COMBINING_CLASSES: [u8; 112] = [
+  230, 230, 230, 230, 230, 230, 230, 230, 230, 230, 230, 230, 230, 230, 230, 230, 230, 230, 230,
+  230, 230, 232, 220, 220, 220, 220, 232, 216, 220, 220, 220, 220, 220, 202, 202, 220, 220, 220,
+  220, 202, 202, 220, 220, 220, 220, 220, 220, 220, 220, 220, 220, 220, 1, 1, 1, 1, 1, 220, 220,
+  220, 220, 230, 230, 230, 230, 230, 230, 230, 230, 240, 230, 220, 220, 220, 230, 230, 230, 220,
+  220, 0, 230, 230, 230, 220, 220, 220, 220, 230, 232, 220, 220, 230, 233, 234, 234, 233, 234, 234,
+  233, 230, 230, 230, 230, 230, 230, 230, 230, 230, 230, 230, 230, 230,
+];
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::fs::create_dir;
+  use std::fs::write;
+
+  use tempfile::tempdir;
+
+
+  /// Sanitize `name` with the default settings.
+  fn sanitize(name: &str) -> String {
+    let sanitized = Sanitizer::default().sanitize(OsStr::new(name));
+    sanitized.into_string().unwrap()
+  }
+
+
+  /// Check that whitespace, control characters, and hostile characters
+  /// get replaced or removed.
+  #[test]
+  fn reserved_characters() {
+    assert_eq!(sanitize("my  file\t.txt"), "my_file_.txt");
+    assert_eq!(sanitize("  padded.txt  "), "padded.txt");
+    assert_eq!(sanitize("a$b*c?d\"e'f`g.txt"), "abcdefg.txt");
+    assert_eq!(sanitize("a\\b:c|d<e>f.txt"), "abcdef.txt");
+    assert_eq!(sanitize("(1) [draft] {x}!.txt"), "1_draft_x.txt");
+    assert_eq!(sanitize("bell\u{7}\u{1b}.txt"), "bell.txt");
+    assert_eq!(sanitize("--rf.txt"), "rf.txt");
+    assert_eq!(sanitize("a-b.txt"), "a-b.txt");
+  }
+
+  /// Check that trailing dots and whitespace get removed, while leading
+  /// dots are kept.
+  #[test]
+  fn trailing_dots_and_spaces() {
+    assert_eq!(sanitize("name."), "name");
+    assert_eq!(sanitize("name. . "), "name");
+    assert_eq!(sanitize("name.txt..."), "name.txt");
+    assert_eq!(sanitize("name.txt.!"), "name.txt");
+    assert_eq!(sanitize(".profile"), ".profile");
+    assert_eq!(sanitize("..hidden"), "..hidden");
+  }
+
+  /// Check that names reserved for devices on Windows get adjusted.
+  #[test]
+  fn windows_reserved_names() {
+    assert_eq!(sanitize("con"), "con_");
+    assert_eq!(sanitize("NUL.txt"), "NUL_.txt");
+    assert_eq!(sanitize("Lpt1.tar.gz"), "Lpt1_.tar.gz");
+    assert_eq!(sanitize("aux."), "aux_");
+    assert_eq!(sanitize("-prn"), "prn_");
+    assert_eq!(sanitize("com9 "), "com9_");
+    assert_eq!(sanitize("console"), "console");
+    assert_eq!(sanitize("com10"), "com10");
+    assert_eq!(sanitize("my con.txt"), "my_con.txt");
+  }
+
+  /// Check that names without anything usable are left unchanged.
+  #[test]
+  fn empty_output() {
+    for name in ["", "$$$", "---", ".", "..", "...", " . ", "\u{1}", "-."] {
+      assert_eq!(sanitize(name), name);
+    }
+  }
+
+  /// Check that names get brought into the requested normalization
+  /// form.
+  #[test]
+  fn normalization() {
+    let decomposed = "Cafe\u{301} Mu\u{308}ller.txt";
+    let composed = "Caf\u{e9}_M\u{fc}ller.txt";
+    assert_eq!(sanitize(decomposed), composed);
+    assert_eq!(sanitize(composed), composed);
+
+    let sanitizer = Sanitizer::default().with_normalization(Normalization::Nfd);
+    let expected = "Cafe\u{301}_Mu\u{308}ller.txt";
+    assert_eq!(sanitizer.sanitize(OsStr::new(composed)), expected);
+    assert_eq!(sanitizer.sanitize(OsStr::new(decomposed)), expected);
+
+    // Marks get reordered by combining class and composed with the
+    // starter where possible.
+    assert_eq!(nfc("a\u{323}\u{302}"), "\u{1ead}");
+    assert_eq!(nfc("a\u{302}\u{323}"), "\u{1ead}");
+    assert_eq!(nfd("\u{1ead}"), vec!['a', '\u{323}', '\u{302}']);
+    assert_eq!(nfc("\u{3b1}\u{301}"), "\u{3b1}\u{301}");
+  }
+
+  /// Check that combining marks share the fate of the character they
+  /// are attached to.
+  #[test]
+  fn combining_marks() {
+    assert_eq!(sanitize("a$\u{301}b.txt"), "ab.txt");
+    assert_eq!(sanitize("a \u{301}b.txt"), "a_b.txt");
+  }
+
+  /// Check that names get lowercased if requested.
+  #[test]
+  fn lowercase() {
+    let sanitizer = Sanitizer::default().with_lowercase(true);
+    let sanitized = sanitizer.sanitize(OsStr::new("\u{c4}rger IM Büro.TXT"));
+    assert_eq!(sanitized, "\u{e4}rger_im_b\u{fc}ro.txt");
+  }
+
+  /// Check that bytes not forming valid UTF-8 get removed.
+  #[cfg(unix)]
+  #[test]
+  fn invalid_utf8() {
+    use std::os::unix::ffi::OsStrExt as _;
+
+    let name = OsStr::from_bytes(b"a\xffb.txt");
+    assert_eq!(Sanitizer::default().sanitize(name), "ab.txt");
+  }
+
+  /// Check that only the file name part of a path gets sanitized.
+  #[tokio::test]
+  async fn plan() {
+    let dir = tempdir().unwrap();
+    let subdir = dir.path().join("some dir");
+    let () = create_dir(&subdir).unwrap();
+    let () = write(subdir.join("my file$.txt"), "").unwrap();
+
+    let path = Sanitizer::default()
+      .plan(&subdir.join("my file$.txt"))
+      .await
+      .unwrap();
+    assert_eq!(path, subdir.join("my_file.txt"));
+  }
+}
@@ -0,0 +1,191 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Schedules for running renames periodically.
+//!
+//! Schedules are expressed in the style of cron, i.e., as five fields
+//! separated by whitespace: minute, hour, day of month, month, and day
+//! of week (with both 0 and 7 denoting Sunday). Each field is either
+//! `*`, a number, a range such as `1-5`, or a list thereof, as in
+//! `1,15`, optionally followed by a step, as in `*/15`. The macros
+//! `@hourly`, `@daily`, `@weekly`, `@monthly`, and `@yearly` are
+//! supported as well.
+
+use std::collections::BTreeSet;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Result as FmtResult;
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+
+use crate::error::Context as _;
+use crate::error::Error;
+use crate::error::Result;
+
+
+/// The names of the days of the week, starting with Sunday, as used by
+/// systemd.
+const WEEKDAYS: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+
+
+/// Parse a single field of a cron expression, with `range` being the
+/// range of valid values, into the set of values it matches.
+fn parse_field(field: &str, range: RangeInclusive<u32>) -> Result<BTreeSet<u32>> {
+  let number = |s: &str| -> Result<u32> {
+    let value = s
+      .parse::<u32>()
+      .with_context(|| format!("`{s}` is not a number"))?;
+    if !range.contains(&value) {
+      return Err(Error::Invalid(format!(
+        "`{value}` is out of range {}-{}",
+        range.start(),
+        range.end()
+      )))
+    }
+    Ok(value)
+  };
+
+  let mut values = BTreeSet::new();
+  for item in field.split(',') {
+    let (item, step) = match item.split_once('/') {
+      Some((item, step)) => {
+        let step = step
+          .parse::<u32>()
+          .ok()
+          .filter(|step| *step > 0)
+          .with_context(|| format!("`{step}` is not a valid step"))?;
+        (item, step)
+      },
+      None => (item, 1),
+    };
+    let (start, end) = match item {
+      "*" => (*range.start(), *range.end()),
+      item => match item.split_once('-') {
+        Some((start, end)) => (number(start)?, number(end)?),
+        // A single value with a step extends to the end of the range,
+        // as in `5/15`.
+        None if step > 1 => (number(item)?, *range.end()),
+        None => {
+          let value = number(item)?;
+          (value, value)
+        },
+      },
+    };
+    if start > end {
+      return Err(Error::Invalid(format!("range `{item}` is empty")))
+    }
+    let () = values.extend((start..=end).step_by(step as usize));
+  }
+  Ok(values)
+}
+
+
+/// Render the set of `values` as a component of a systemd calendar
+/// event, with `all` being the set of all possible values.
+fn render_field<F>(values: &BTreeSet<u32>, all: RangeInclusive<u32>, render: F) -> String
+where
+  F: Fn(u32) -> String,
+{
+  if all.clone().all(|value| values.contains(&value)) {
+    return "*".to_string()
+  }
+
+  // Collapse consecutive values into ranges.
+  let mut runs = Vec::<(u32, u32)>::new();
+  for value in values {
+    match runs.last_mut() {
+      Some((_, end)) if *end + 1 == *value => *end = *value,
+      _ => runs.push((*value, *value)),
+    }
+  }
+  runs
+    .into_iter()
+    .map(|(start, end)| {
+      if start == end {
+        render(start)
+      } else {
+        format!("{}..{}", render(start), render(end))
+      }
+    })
+    .collect::<Vec<_>>()
+    .join(",")
+}
+
+
+/// A schedule for running renames periodically.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Schedule {
+  /// The expression the schedule got parsed from.
+  expression: String,
+  /// The schedule as a systemd calendar event.
+  calendar: String,
+}
+
+impl Schedule {
+  /// Retrieve the schedule as a calendar event, as used by the
+  /// `OnCalendar` setting of systemd timers.
+  pub fn calendar(&self) -> &str {
+    &self.calendar
+  }
+}
+
+impl FromStr for Schedule {
+  type Err = Error;
+
+  fn from_str(s: &str) -> Result<Self> {
+    let expression = s.trim();
+    let calendar = match expression {
+      "@hourly" => "hourly".to_string(),
+      "@daily" | "@midnight" => "daily".to_string(),
+      "@weekly" => "weekly".to_string(),
+      "@monthly" => "monthly".to_string(),
+      "@yearly" | "@annually" => "yearly".to_string(),
+      _ => {
+        let fields = expression.split_whitespace().collect::<Vec<_>>();
+        let [minute, hour, day, month, weekday] = fields.as_slice() else {
+          return Err(Error::Invalid(format!("invalid schedule `{expression}`; expected five fields: minute, hour, day of month, month, and day of week")))
+        };
+        let parse = |field: &str, name: &str, range| {
+          parse_field(field, range)
+            .with_context(|| format!("invalid {name} `{field}` in schedule `{expression}`"))
+        };
+        let minute = parse(minute, "minute", 0..=59)?;
+        let hour = parse(hour, "hour", 0..=23)?;
+        let day = parse(day, "day of month", 1..=31)?;
+        let month = parse(month, "month", 1..=12)?;
+        let weekday = parse(weekday, "day of week", 0..=7)?
+          .into_iter()
+          .map(|day| day % 7)
+          .collect::<BTreeSet<_>>();
+
+        let weekday = render_field(&weekday, 0..=6, |day| WEEKDAYS[day as usize].to_string());
+        let day = render_field(&day, 1..=31, |day| format!("{day:02}"));
+        // cron runs when either the day of month or the day of week
+        // matches, systemd only when both do.
+        if weekday != "*" && day != "*" {
+          return Err(Error::Invalid(format!("invalid schedule `{expression}`; restricting both day of month and day of week is not supported")))
+        }
+        let month = render_field(&month, 1..=12, |month| format!("{month:02}"));
+        let hour = render_field(&hour, 0..=23, |hour| format!("{hour:02}"));
+        let minute = render_field(&minute, 0..=59, |minute| format!("{minute:02}"));
+        let date = format!("*-{month}-{day} {hour}:{minute}:00");
+        if weekday == "*" {
+          date
+        } else {
+          format!("{weekday} {date}")
+        }
+      },
+    };
+
+    Ok(Self {
+      expression: expression.to_string(),
+      calendar,
+    })
+  }
+}
+
+impl Display for Schedule {
+  fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+    f.write_str(&self.expression)
+  }
+}
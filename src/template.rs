@@ -0,0 +1,1081 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Template based renaming of files.
+//!
+//! A template is a string containing tokens enclosed in curly braces,
+//! which are replaced with information about the file being renamed.
+//! Literal braces are expressed as `{{` and `}}`. The following tokens
+//! are supported:
+//! - `{stem}`: the file name without extension
+//! - `{ext}`: the extension including the leading dot, if any
+//! - `{name}`: the full file name
+//! - `{counter}`: a counter incremented for each file of a batch; a
+//!   minimum width may be provided, with a leading zero requesting zero
+//!   padding (e.g., `{counter:03}`)
+//! - `{mtime}`: the file's modification time; a `strftime` style format
+//!   may be provided (e.g., `{mtime:%Y-%m-%d}`), with `%Y-%m-%d` being
+//!   the default
+//! - `{size}`: the file's size in bytes
+//! - `{f<N>}`: the `N`th number contained in the file stem, counting
+//!   from one (e.g., `{f2}` is `7` for `IMG_2023_07.jpg`)
+//!
+//! Integer arithmetic using `+`, `-`, `*`, `/`, `%`, and parentheses is
+//! supported on `{counter}`, `{size}`, and `{f<N>}`, as in
+//! `{counter + 100}` or `{f2 * 2:03}`. As for `{counter}`, a minimum
+//! width may be provided for the result.
+//!
+//! With the `metadata` feature enabled, the following tokens are
+//! available in addition, failing for files lacking the respective
+//! information:
+//! - `{exif.date}`: the time an image was taken; a `strftime` style
+//!   format may be provided, as for `{mtime}`
+//! - `{exif.model}`: the model of the camera that took an image
+//! - `{id3.title}`, `{id3.artist}`: the title and artist of a track
+//! - `{id3.track}`: the track number, optionally with a width, as for
+//!   `{counter}`
+//!
+//! Rendered names may contain path separators, in which case files are
+//! moved to the resulting path relative to their current directory.
+//!
+//! The case of any token's value may be changed by appending `|upper`
+//! or `|lower`, as in `{stem|lower}`. Appending `|date` replaces the
+//! value with the first date it contains, formatted as `YYYY-MM-DD`;
+//! dates may be written as in `2023-07-14`, `14.07.2023`, or
+//! `14. März 2023`, with month names in a few widely used languages
+//! being recognized. Similarly, `|number` replaces the value with the
+//! first number it contains, without grouping of digits and with `.`
+//! as decimal separator, as in `1234.5` for `1.234,5`. Case mapping,
+//! the interpretation of ambiguous dates and numbers (such as
+//! `03/04/2023` and `1,234`), as well as the names of months and days
+//! in time formats depend on the template's locale, which defaults to
+//! the POSIX locale.
+//!
+//! Templates parsed with [`Template::parse_with_functions`] may call
+//! user-defined functions, as in `{shortdate()}` or `{tag(raw, 03)}`.
+//! A function is a template snippet, in which `$1`, `$2`, ... refer to
+//! the arguments of a call and `$$` denotes a literal `$`. Calls get
+//! replaced with the function's snippet when parsing the template.
+
+use std::collections::BTreeMap;
+use std::collections::HashSet;
+use std::error::Error as StdError;
+use std::ffi::OsString;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Result as FmtResult;
+use std::fs::Metadata;
+use std::io::ErrorKind;
+use std::ops::Range;
+use std::path::is_separator;
+use std::path::Path;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use chrono::format::Item;
+use chrono::format::StrftimeItems;
+use chrono::DateTime;
+use chrono::Local;
+
+use tokio::fs::metadata;
+use tokio::fs::read_dir;
+
+use crate::detect::find_date;
+use crate::detect::find_number;
+use crate::error::Context as _;
+use crate::error::Error;
+use crate::error::Result;
+use crate::expr::number;
+use crate::expr::Expr;
+use crate::expr::Operand;
+use crate::expr::SyntaxError;
+use crate::locale::Locale;
+#[cfg(feature = "metadata")]
+use crate::metadata::Field;
+#[cfg(feature = "metadata")]
+use crate::metadata::Provider;
+use crate::plan::is_relative_file;
+use crate::plan::normalize;
+use crate::resolve;
+use crate::SymlinkPolicy;
+
+
+/// The default format used for the `mtime` token.
+const DEFAULT_TIME_FORMAT: &str = "%Y-%m-%d";
+
+
+/// An error encountered while parsing a template.
+///
+/// The error's `Display` representation spans multiple lines, pointing
+/// at the problem's location in the template.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ParseError {
+  /// The template.
+  template: String,
+  /// The byte range of the offending part of the template.
+  span: Range<usize>,
+  /// A description of the problem.
+  message: String,
+  /// Descriptions of the input that would have been accepted instead.
+  expected: Vec<String>,
+}
+
+impl ParseError {
+  /// Retrieve the byte range of the offending part of the template.
+  ///
+  /// The range is empty if input is missing at the end of the
+  /// template or of a token.
+  pub fn span(&self) -> Range<usize> {
+    self.span.clone()
+  }
+
+  /// Retrieve the description of the problem.
+  pub fn message(&self) -> &str {
+    &self.message
+  }
+
+  /// Retrieve descriptions of the input that would have been accepted
+  /// at the location of the problem, if known.
+  pub fn expected(&self) -> &[String] {
+    &self.expected
+  }
+}
+
+impl Display for ParseError {
+  fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+    let () = write!(f, "invalid template: {}", self.message)?;
+    let () = match self.expected.as_slice() {
+      [] => (),
+      [expected] => write!(f, "; expected {expected}")?,
+      [first, second] => write!(f, "; expected {first} or {second}")?,
+      [expected @ .., last] => write!(f, "; expected one of {}, or {last}", expected.join(", "))?,
+    };
+
+    // SANITY: Spans always lie on character boundaries within the
+    //         template.
+    let column = self.template[..self.span.start].chars().count();
+    let width = self.template[self.span.clone()].chars().count().max(1);
+    write!(
+      f,
+      "\n  {}\n  {}{}",
+      self.template,
+      " ".repeat(column),
+      "^".repeat(width)
+    )
+  }
+}
+
+impl StdError for ParseError {}
+
+
+/// A token of a template.
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum Token {
+  Stem,
+  Ext,
+  Name,
+  Counter {
+    width: usize,
+    zero: bool,
+  },
+  Mtime {
+    format: String,
+  },
+  Size,
+  Expr {
+    source: String,
+    expr: Expr,
+    width: usize,
+    zero: bool,
+  },
+  #[cfg(feature = "metadata")]
+  Metadata(Field),
+}
+
+impl Token {
+  /// The descriptions of all tokens.
+  #[cfg(not(feature = "metadata"))]
+  const EXPECTED: &[&str] = &[
+    "`stem`",
+    "`ext`",
+    "`name`",
+    "`counter`",
+    "`mtime`",
+    "`size`",
+    "`f<N>`",
+    "an expression",
+  ];
+  #[cfg(feature = "metadata")]
+  const EXPECTED: &[&str] = &[
+    "`stem`",
+    "`ext`",
+    "`name`",
+    "`counter`",
+    "`mtime`",
+    "`size`",
+    "`f<N>`",
+    "an expression",
+    "`exif.date`",
+    "`exif.model`",
+    "`id3.title`",
+    "`id3.artist`",
+    "`id3.track`",
+  ];
+
+  /// Parse a token from its content, i.e., its name and optional
+  /// specification separated by a colon.
+  ///
+  /// Spans of errors are relative to `content`.
+  fn parse(content: &str) -> Result<Self, SyntaxError> {
+    let (name, spec) = match content.split_once(':') {
+      Some((name, spec)) => (name, Some(spec)),
+      None => (content, None),
+    };
+    let name_start = name.len() - name.trim_start().len();
+    let name = name.trim();
+    let name_span = name_start..name_start + name.len();
+    let spec_span = content.len() - spec.map(str::len).unwrap_or(0)..content.len();
+
+    let width = |spec: &str, what: &str| {
+      spec.parse::<usize>().map_err(|_| {
+        SyntaxError::new(spec_span.clone(), format!("invalid {what} width `{spec}`"))
+          .expected(["a width, such as `3` or `03`"])
+      })
+    };
+
+    let token = match (name, spec) {
+      ("stem", None) => Self::Stem,
+      ("ext", None) => Self::Ext,
+      ("name", None) => Self::Name,
+      ("counter", None) => Self::Counter {
+        width: 0,
+        zero: false,
+      },
+      ("counter", Some(spec)) => Self::Counter {
+        width: width(spec, "counter")?,
+        zero: spec.starts_with('0'),
+      },
+      ("mtime", spec) => {
+        let format = spec.unwrap_or(DEFAULT_TIME_FORMAT);
+        if StrftimeItems::new(format).any(|item| item == Item::Error) {
+          return Err(
+            SyntaxError::new(spec_span, format!("invalid time format `{format}`"))
+              .expected(["a strftime style format, such as `%Y-%m-%d`"]),
+          )
+        }
+        Self::Mtime {
+          format: format.to_string(),
+        }
+      },
+      ("size", None) => Self::Size,
+      ("stem" | "ext" | "name" | "size", Some(_)) => {
+        return Err(SyntaxError::new(
+          spec_span,
+          format!("template token `{name}` does not accept a specification"),
+        ))
+      },
+      (name, spec) if Expr::is_expr(name) => {
+        let (width, zero) = match spec {
+          Some(spec) => (width(spec, "expression")?, spec.starts_with('0')),
+          None => (0, false),
+        };
+        Self::Expr {
+          source: name.to_string(),
+          expr: Expr::parse(name).map_err(|err| err.shift(name_start))?,
+          width,
+          zero,
+        }
+      },
+      #[cfg(feature = "metadata")]
+      (name, spec) if Field::is_field(name) => Self::Metadata(
+        Field::parse(name, spec)
+          .map_err(|err| SyntaxError::new(0..content.len(), format!("{err:#}")))?,
+      ),
+      _ => {
+        return Err(
+          SyntaxError::new(name_span, format!("unknown template token `{name}`"))
+            .expected(Self::EXPECTED.iter().copied()),
+        )
+      },
+    };
+    Ok(token)
+  }
+}
+
+impl Display for Token {
+  fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+    match self {
+      Self::Stem => f.write_str("stem"),
+      Self::Ext => f.write_str("ext"),
+      Self::Name => f.write_str("name"),
+      Self::Counter { width: 0, .. } => f.write_str("counter"),
+      Self::Counter { width, zero } => {
+        write!(f, "counter:{}{width}", if *zero { "0" } else { "" })
+      },
+      Self::Mtime { format } => write!(f, "mtime:{format}"),
+      Self::Size => f.write_str("size"),
+      Self::Expr {
+        source, width: 0, ..
+      } => f.write_str(source),
+      Self::Expr {
+        source,
+        width,
+        zero,
+        ..
+      } => write!(f, "{source}:{}{width}", if *zero { "0" } else { "" }),
+      #[cfg(feature = "metadata")]
+      Self::Metadata(field) => field.fmt(f),
+    }
+  }
+}
+
+
+/// A filter applied to the value of a token.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Filter {
+  Upper,
+  Lower,
+  Date,
+  Number,
+}
+
+impl Filter {
+  /// Parse a filter from its name.
+  fn parse(name: &str) -> Option<Self> {
+    match name {
+      "upper" => Some(Self::Upper),
+      "lower" => Some(Self::Lower),
+      "date" => Some(Self::Date),
+      "number" => Some(Self::Number),
+      _ => None,
+    }
+  }
+
+  /// Apply the filter to `s`, according to `locale`.
+  fn apply(&self, s: &str, locale: &Locale) -> Result<String> {
+    match self {
+      Self::Upper => Ok(locale.to_uppercase(s)),
+      Self::Lower => Ok(locale.to_lowercase(s)),
+      Self::Date => find_date(s, locale)
+        .map(|date| date.format("%Y-%m-%d").to_string())
+        .with_context(|| format!("`{s}` does not contain a date")),
+      Self::Number => {
+        find_number(s, locale).with_context(|| format!("`{s}` does not contain a number"))
+      },
+    }
+  }
+}
+
+impl Display for Filter {
+  fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+    match self {
+      Self::Upper => f.write_str("upper"),
+      Self::Lower => f.write_str("lower"),
+      Self::Date => f.write_str("date"),
+      Self::Number => f.write_str("number"),
+    }
+  }
+}
+
+
+/// Parse a call of a user-defined function, such as `shortdate()` or
+/// `tag(raw, 03)`, into the function's name and arguments.
+fn parse_call(content: &str) -> Option<(&str, Vec<&str>)> {
+  let (name, args) = content.trim().strip_suffix(')')?.split_once('(')?;
+  let name = name.trim_end();
+  if !is_function_name(name) {
+    return None
+  }
+
+  let args = if args.trim().is_empty() {
+    Vec::new()
+  } else {
+    args.split(',').map(str::trim).collect()
+  };
+  Some((name, args))
+}
+
+
+/// Check whether `name` is a valid name of a user-defined function.
+fn is_function_name(name: &str) -> bool {
+  name.starts_with(|c: char| c.is_ascii_alphabetic())
+    && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+
+/// Substitute the arguments `args` for the parameters `$1`, `$2`, ...
+/// in `body`, the snippet of the function called `name`.
+fn substitute(name: &str, body: &str, args: &[&str]) -> Result<String, String> {
+  let mut expanded = String::with_capacity(body.len());
+  let mut used = 0;
+  let mut rest = body;
+  while let Some(idx) = rest.find('$') {
+    let () = expanded.push_str(&rest[..idx]);
+    rest = &rest[idx + 1..];
+    if let Some(remainder) = rest.strip_prefix('$') {
+      let () = expanded.push('$');
+      rest = remainder;
+      continue
+    }
+
+    let digits = rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    let param = rest[..digits]
+      .parse::<usize>()
+      .ok()
+      .filter(|param| *param > 0)
+      .ok_or_else(|| {
+        format!("function `{name}` contains invalid parameter; expected `$1`, `$2`, ..., or `$$`")
+      })?;
+    let arg = args.get(param - 1).ok_or_else(|| {
+      format!(
+        "function `{name}` called with {} argument(s) refers to `${param}`",
+        args.len()
+      )
+    })?;
+    let () = expanded.push_str(arg);
+    used = used.max(param);
+    rest = &rest[digits..];
+  }
+  let () = expanded.push_str(rest);
+
+  if args.len() > used {
+    return Err(format!(
+      "function `{name}` takes {used} argument(s) but got called with {}",
+      args.len()
+    ))
+  }
+  Ok(expanded)
+}
+
+
+/// Retrieve the metadata of the file at `path`, caching them in
+/// `cache`.
+async fn cached_metadata<'c>(cache: &'c mut Option<Metadata>, path: &Path) -> Result<&'c Metadata> {
+  if cache.is_none() {
+    let meta = metadata(path)
+      .await
+      .with_context(|| format!("failed to retrieve metadata of `{}`", path.display()))?;
+    *cache = Some(meta);
+  }
+  // SANITY: We just made sure that metadata are present.
+  Ok(cache.as_ref().unwrap())
+}
+
+
+/// A part of a template.
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum Part {
+  Literal(String),
+  Token(Token, Option<Filter>),
+}
+
+
+/// An element of a pattern matching names produced by a template.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Pattern<'t> {
+  Literal(&'t str),
+  Counter,
+  Any,
+}
+
+/// Match `name` against `pattern`, reporting the value of the first
+/// counter on success.
+fn match_pattern(pattern: &[Pattern<'_>], name: &str) -> Option<Option<u64>> {
+  match pattern.split_first() {
+    None => name.is_empty().then_some(None),
+    Some((Pattern::Literal(literal), rest)) => match_pattern(rest, name.strip_prefix(literal)?),
+    Some((Pattern::Counter, rest)) => {
+      let digits = name.len() - name.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+      (1..=digits).rev().find_map(|len| {
+        let counter = name[..len].parse().ok()?;
+        let _counter = match_pattern(rest, &name[len..])?;
+        Some(Some(counter))
+      })
+    },
+    Some((Pattern::Any, rest)) => name
+      .char_indices()
+      .map(|(idx, _)| idx)
+      .chain([name.len()])
+      .find_map(|idx| match_pattern(rest, &name[idx..])),
+  }
+}
+
+
+/// A template describing the new name of a file.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Template {
+  parts: Vec<Part>,
+  locale: Locale,
+  symlinks: SymlinkPolicy,
+}
+
+impl Template {
+  /// Parse the parts of the template `s`, expanding calls of
+  /// `functions`. `stack` contains the names of the functions being
+  /// expanded.
+  ///
+  /// Spans of errors are relative to `s`.
+  fn parse_parts(
+    s: &str,
+    functions: &BTreeMap<String, String>,
+    stack: &mut Vec<String>,
+  ) -> Result<Vec<Part>, SyntaxError> {
+    let mut parts = Vec::new();
+    let mut literal = String::new();
+    let mut chars = s.chars();
+
+    while let Some(c) = chars.next() {
+      let start = s.len() - chars.as_str().len() - c.len_utf8();
+      match c {
+        '{' if chars.as_str().starts_with('{') => {
+          let _brace = chars.next();
+          let () = literal.push('{');
+        },
+        '}' if chars.as_str().starts_with('}') => {
+          let _brace = chars.next();
+          let () = literal.push('}');
+        },
+        '{' => {
+          let rest = chars.as_str();
+          let end = rest.find('}').ok_or_else(|| {
+            SyntaxError::new(start..s.len(), "unterminated token").expected(["`}`"])
+          })?;
+          let (content, filter) = match rest[..end].rsplit_once('|') {
+            Some((content, filter)) => match Filter::parse(filter.trim()) {
+              Some(filter) => (content, Some(filter)),
+              None => (&rest[..end], None),
+            },
+            None => (&rest[..end], None),
+          };
+          chars = rest[end + 1..].chars();
+
+          if !literal.is_empty() {
+            let () = parts.push(Part::Literal(literal.split_off(0)));
+          }
+
+          if let Some((name, args)) = parse_call(content) {
+            let span = start..start + end + 2;
+            let body = functions.get(name).ok_or_else(|| {
+              SyntaxError::new(span.clone(), format!("unknown function `{name}`"))
+                .expected(functions.keys().map(|name| format!("`{name}`")))
+            })?;
+            if stack.iter().any(|caller| caller == name) {
+              return Err(SyntaxError::new(
+                span,
+                format!("function `{name}` calls itself"),
+              ))
+            }
+            if let Some(filter) = filter {
+              return Err(SyntaxError::new(
+                span,
+                format!("filter `{filter}` cannot be applied to the result of function `{name}`"),
+              ))
+            }
+
+            let body =
+              substitute(name, body, &args).map_err(|err| SyntaxError::new(span.clone(), err))?;
+            let () = stack.push(name.to_string());
+            let called = Self::parse_parts(&body, functions, stack).map_err(|err| {
+              SyntaxError::new(span, format!("invalid function `{name}`: {}", err.message))
+                .expected(err.expected)
+            })?;
+            let _name = stack.pop();
+            let () = parts.extend(called);
+          } else {
+            let token = Token::parse(content).map_err(|err| err.shift(start + 1))?;
+            let () = parts.push(Part::Token(token, filter));
+          }
+        },
+        '}' => {
+          return Err(
+            SyntaxError::new(start..start + 1, "unmatched `}`")
+              .expected(["`}}` for a literal brace"]),
+          )
+        },
+        c => literal.push(c),
+      }
+    }
+
+    if !literal.is_empty() {
+      let () = parts.push(Part::Literal(literal));
+    }
+    Ok(parts)
+  }
+
+  /// Parse a template from `s`.
+  ///
+  /// Parsing never panics, no matter the input. Errors describe the
+  /// problem along with its location in `s`.
+  pub fn parse(s: &str) -> Result<Self, ParseError> {
+    Self::parse_with_functions(s, &BTreeMap::new())
+  }
+
+  /// Parse a template from `s`, which may call the user-defined
+  /// `functions`, mapping function names to the template snippets they
+  /// expand to.
+  ///
+  /// Calls are expanded as part of parsing, i.e., the resulting
+  /// template no longer refers to any function.
+  pub fn parse_with_functions(
+    s: &str,
+    functions: &BTreeMap<String, String>,
+  ) -> Result<Self, ParseError> {
+    let parts = Self::parse_parts(s, functions, &mut Vec::new()).map_err(|err| ParseError {
+      template: s.to_string(),
+      span: err.span,
+      message: err.message,
+      expected: err.expected,
+    })?;
+
+    let slf = Self {
+      parts,
+      locale: Locale::default(),
+      symlinks: SymlinkPolicy::default(),
+    };
+    Ok(slf)
+  }
+
+  /// Render the new file name for the file at `path`, with `counter`
+  /// being the value of the batch-wide counter for the file.
+  ///
+  /// The result is a path relative to the directory containing the
+  /// file.
+  pub async fn render(&self, path: &Path, counter: u64) -> Result<OsString> {
+    let file = path
+      .file_name()
+      .with_context(|| format!("path `{}` does not have file name", path.display()))?;
+    let file = Path::new(file);
+    let mut meta = None;
+    #[cfg(feature = "metadata")]
+    let mut provider = Provider::new(path);
+    let mut name = OsString::new();
+
+    for part in &self.parts {
+      let (token, filter) = match part {
+        Part::Literal(literal) => {
+          let () = name.push(literal);
+          continue
+        },
+        Part::Token(token, filter) => (token, filter),
+      };
+
+      let value = match token {
+        Token::Stem => file.file_stem().unwrap_or_default().to_os_string(),
+        Token::Ext => match file.extension() {
+          Some(ext) => {
+            let mut value = OsString::from(".");
+            let () = value.push(ext);
+            value
+          },
+          None => OsString::new(),
+        },
+        Token::Name => file.as_os_str().to_os_string(),
+        Token::Counter { width, zero } => {
+          if *zero {
+            OsString::from(format!("{counter:0width$}"))
+          } else {
+            OsString::from(format!("{counter:width$}"))
+          }
+        },
+        Token::Mtime { format } => {
+          let mtime = cached_metadata(&mut meta, path)
+            .await?
+            .modified()
+            .with_context(|| {
+              format!(
+                "failed to retrieve modification time of `{}`",
+                path.display()
+              )
+            })?;
+          let mtime = DateTime::<Local>::from(mtime);
+          OsString::from(
+            mtime
+              .format_localized(format, self.locale.chrono())
+              .to_string(),
+          )
+        },
+        Token::Size => {
+          let size = cached_metadata(&mut meta, path).await?.len();
+          OsString::from(size.to_string())
+        },
+        Token::Expr {
+          source,
+          expr,
+          width,
+          zero,
+        } => {
+          let mut values = Vec::new();
+          for operand in expr.operands() {
+            let value = match operand {
+              Operand::Counter => i64::try_from(counter).ok(),
+              Operand::Size => i64::try_from(cached_metadata(&mut meta, path).await?.len()).ok(),
+              Operand::Number(n) => {
+                let stem = file.file_stem().unwrap_or_default().to_string_lossy();
+                let value = number(&stem, n)
+                  .with_context(|| format!("`{}` lacks number #{n}", path.display()))?;
+                Some(value)
+              },
+            };
+            let value = value.with_context(|| {
+              format!("value in `{source}` is too large for `{}`", path.display())
+            })?;
+            let () = values.push((operand, value));
+          }
+
+          let value = expr
+            .eval(&|operand| {
+              // SANITY: All operands got evaluated above.
+              values.iter().find(|(op, _)| *op == operand).unwrap().1
+            })
+            .with_context(|| format!("failed to evaluate `{source}` for `{}`", path.display()))?;
+          if *zero {
+            OsString::from(format!("{value:0width$}"))
+          } else {
+            OsString::from(format!("{value:width$}"))
+          }
+        },
+        #[cfg(feature = "metadata")]
+        Token::Metadata(field) => OsString::from(provider.render(field, &self.locale).await?),
+      };
+
+      let value = match filter {
+        Some(filter) => {
+          let value = value.to_str().with_context(|| {
+            format!(
+              "cannot apply filter `{filter}` to non-UTF-8 value `{}` for `{}`",
+              value.to_string_lossy(),
+              path.display()
+            )
+          })?;
+          let value = filter.apply(value, &self.locale).with_context(|| {
+            format!("failed to apply filter `{filter}` for `{}`", path.display())
+          })?;
+          OsString::from(value)
+        },
+        None => value,
+      };
+      let () = name.push(value);
+    }
+
+    if name.is_empty() {
+      return Err(Error::Invalid(format!(
+        "template `{self}` produced empty file name for `{}`",
+        path.display()
+      )))
+    }
+    let rel = Path::new(&name);
+    if !is_relative_file(rel) {
+      return Err(Error::Invalid(format!(
+        "template `{self}` produced invalid path `{}` for `{}`",
+        name.to_string_lossy(),
+        path.display()
+      )))
+    }
+    Ok(name)
+  }
+
+  /// Set the locale used for mapping case, for interpreting dates and
+  /// numbers, and for the names of months and days in time formats.
+  pub fn with_locale(mut self, locale: Locale) -> Self {
+    self.locale = locale;
+    self
+  }
+
+  /// Set how to treat files that are symbolic links.
+  pub fn with_symlink_policy(mut self, symlinks: SymlinkPolicy) -> Self {
+    self.symlinks = symlinks;
+    self
+  }
+
+  /// Build a pattern matching the file names (i.e., the last path
+  /// components) produced by the template.
+  fn name_pattern(&self) -> Vec<Pattern<'_>> {
+    let mut pattern = Vec::new();
+    for part in self.parts.iter().rev() {
+      match part {
+        Part::Literal(literal) => match literal.rfind(is_separator) {
+          Some(idx) => {
+            // Separators are ASCII characters.
+            let () = pattern.push(Pattern::Literal(&literal[idx + 1..]));
+            break
+          },
+          None => pattern.push(Pattern::Literal(literal)),
+        },
+        Part::Token(Token::Counter { .. }, _) => pattern.push(Pattern::Counter),
+        Part::Token(..) => pattern.push(Pattern::Any),
+      }
+    }
+    let () = pattern.reverse();
+    pattern
+  }
+
+  /// Determine the highest counter value among existing files whose
+  /// names match the template, for continuing their numbering when
+  /// renaming the files at `paths`.
+  ///
+  /// Considered are all files in the directories the files at `paths`
+  /// would be moved to, except for these files themselves. Zero is
+  /// reported if no file matches.
+  pub async fn last_counter(&self, paths: &[PathBuf]) -> Result<u64> {
+    let pattern = self.name_pattern();
+    if !pattern.contains(&Pattern::Counter) {
+      return Err(Error::Invalid(format!(
+        "template `{self}` does not contain a `{{counter}}` token in its file name"
+      )))
+    }
+
+    let mut sources = HashSet::with_capacity(paths.len());
+    let mut dirs = HashSet::new();
+    for (path, counter) in paths.iter().zip(1..) {
+      let path = resolve(path, self.symlinks).await?;
+      let dst = self.plan(&path, counter).await?;
+      if let Some(dir) = dst.parent() {
+        let _inserted = dirs.insert(dir.to_path_buf());
+      }
+      let _inserted = sources.insert(path);
+    }
+
+    let mut last = 0;
+    for dir in dirs {
+      let context = || format!("failed to read directory `{}`", dir.display());
+      let mut entries = match read_dir(&dir).await {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == ErrorKind::NotFound => continue,
+        Err(err) => return Err(err).with_context(context),
+      };
+
+      while let Some(entry) = entries.next_entry().await.with_context(context)? {
+        if sources.contains(&entry.path()) {
+          continue
+        }
+        if let Some(name) = entry.file_name().to_str() {
+          if let Some(Some(counter)) = match_pattern(&pattern, name) {
+            last = last.max(counter);
+          }
+        }
+      }
+    }
+    Ok(last)
+  }
+
+  /// Determine the new path of the file at `path`, with `counter` being
+  /// the value of the batch-wide counter for the file.
+  ///
+  /// The file itself is not renamed.
+  pub async fn plan(&self, path: &Path, counter: u64) -> Result<PathBuf> {
+    let path = resolve(path, self.symlinks).await?;
+    let name = self.render(&path, counter).await?;
+    Ok(normalize(&path.with_file_name(name)))
+  }
+}
+
+impl FromStr for Template {
+  type Err = ParseError;
+
+  fn from_str(s: &str) -> Result<Self, ParseError> {
+    Self::parse(s)
+  }
+}
+
+impl Display for Template {
+  fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+    for part in &self.parts {
+      match part {
+        Part::Literal(literal) => f.write_str(&literal.replace('{', "{{").replace('}', "}}"))?,
+        Part::Token(token, None) => write!(f, "{{{token}}}")?,
+        Part::Token(token, Some(filter)) => write!(f, "{{{token}|{filter}}}")?,
+      }
+    }
+    Ok(())
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::fs::write;
+
+  use tempfile::tempdir;
+
+
+  /// Render `template` for the file at `path`, with the counter being
+  /// `counter`.
+  async fn render(template: &str, path: &str, counter: u64) -> String {
+    let template = Template::parse(template).unwrap();
+    let name = template.render(Path::new(path), counter).await.unwrap();
+    name.into_string().unwrap()
+  }
+
+
+  /// Check that the parts of a file name get substituted for the
+  /// respective tokens.
+  #[tokio::test]
+  async fn name_tokens() {
+    assert_eq!(render("{stem}", "dir/a.tar.gz", 1).await, "a.tar");
+    assert_eq!(render("{ext}", "dir/a.tar.gz", 1).await, ".gz");
+    assert_eq!(render("{name}", "dir/a.tar.gz", 1).await, "a.tar.gz");
+    assert_eq!(render("x{ext}", "dir/a", 1).await, "x");
+    assert_eq!(render("{stem}{ext}", ".profile", 1).await, ".profile");
+    assert_eq!(render("{ stem }-2{ext}", "a.txt", 1).await, "a-2.txt");
+    assert_eq!(render("{stem|upper}", "a.txt", 1).await, "A");
+    assert_eq!(render("{f2}-{f1}", "IMG_2023_07.jpg", 1).await, "7-2023");
+  }
+
+  /// Check that templates get displayed as they would be written.
+  #[test]
+  fn display() {
+    for template in [
+      "{stem}-{counter:03}{ext}",
+      "{counter:4}_{name|lower}",
+      "{mtime:%Y}/{f2 * 2:03}",
+      "{{{stem}}}",
+    ] {
+      assert_eq!(Template::parse(template).unwrap().to_string(), template);
+    }
+  }
+
+  /// Check that the counter gets rendered with the requested width.
+  #[tokio::test]
+  async fn counter_width() {
+    assert_eq!(render("{counter}", "a.txt", 7).await, "7");
+    assert_eq!(render("{counter:3}", "a.txt", 7).await, "  7");
+    assert_eq!(render("{counter:03}", "a.txt", 7).await, "007");
+    assert_eq!(render("{counter:02}", "a.txt", 1234).await, "1234");
+    assert_eq!(render("{counter + 1:04}", "a.txt", 7).await, "0008");
+    assert_eq!(render("{f1:03}", "IMG_7.jpg", 1).await, "007");
+    assert_eq!(render("{counter - 10:04}", "a.txt", 7).await, "-003");
+  }
+
+  /// Check that arithmetic on the counter allows for arbitrary start
+  /// values and steps.
+  #[tokio::test]
+  async fn counter_start_and_step() {
+    let mut names = Vec::new();
+    for counter in 1..=3 {
+      let () = names.push(render("{(counter - 1) * 5 + 100}", "a.txt", counter).await);
+    }
+    assert_eq!(names, vec!["100", "105", "110"]);
+  }
+
+  /// Check that numbering continues after the highest counter among
+  /// existing files.
+  #[tokio::test]
+  async fn last_counter() {
+    let dir = tempdir().unwrap();
+    for file in ["a.txt", "b.txt", "img-003.jpg", "img-017.png", "img-x.jpg"] {
+      let () = write(dir.path().join(file), "").unwrap();
+    }
+    let paths = [dir.path().join("a.txt"), dir.path().join("b.txt")];
+
+    let template = Template::parse("img-{counter:03}.{ext}").unwrap();
+    assert_eq!(template.last_counter(&paths).await.unwrap(), 17);
+
+    let template = Template::parse("other-{counter}{ext}").unwrap();
+    assert_eq!(template.last_counter(&paths).await.unwrap(), 0);
+
+    let template = Template::parse("img-{stem}{ext}").unwrap();
+    assert!(template.last_counter(&paths).await.is_err());
+  }
+
+  /// Check that the `mtime` token gets formatted as requested.
+  #[tokio::test]
+  async fn mtime() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("a.txt");
+    let () = write(&path, "").unwrap();
+    let path = path.to_str().unwrap();
+
+    let date = render("{mtime}", path, 1).await;
+    assert_eq!(date.len(), "YYYY-MM-DD".len(), "{date}");
+    assert!(date.starts_with(&render("{mtime:%Y}-", path, 1).await));
+    assert_eq!(render("{size}", path, 1).await, "0");
+
+    let err = Template::parse("{mtime:%Q}").unwrap_err();
+    assert_eq!(err.message(), "invalid time format `%Q`");
+    assert_eq!(err.span(), 7..9);
+  }
+
+  /// Check that unknown tokens are rejected, pointing at their name.
+  #[test]
+  fn unknown_token() {
+    let err = Template::parse("a-{ foo }").unwrap_err();
+    assert_eq!(err.message(), "unknown template token `foo`");
+    assert_eq!(err.span(), 4..7);
+    assert_eq!(err.expected().first().map(String::as_str), Some("`stem`"));
+
+    let err = Template::parse("{stem:3}").unwrap_err();
+    assert_eq!(
+      err.message(),
+      "template token `stem` does not accept a specification"
+    );
+    assert_eq!(err.span(), 6..7);
+
+    let err = Template::parse("{counter:x}").unwrap_err();
+    assert_eq!(err.message(), "invalid counter width `x`");
+    assert_eq!(err.span(), 9..10);
+
+    let err = Template::parse("{shortdate()}").unwrap_err();
+    assert_eq!(err.message(), "unknown function `shortdate`");
+  }
+
+  /// Check that doubled braces denote literal ones, while single ones
+  /// have to be balanced.
+  #[tokio::test]
+  async fn escaping() {
+    assert_eq!(render("{{stem}}", "a.txt", 1).await, "{stem}");
+    assert_eq!(render("{{{stem}}}", "a.txt", 1).await, "{a}");
+    assert_eq!(render("a}}b{{c", "a.txt", 1).await, "a}b{c");
+
+    let err = Template::parse("a{stem").unwrap_err();
+    assert_eq!(err.message(), "unterminated token");
+    assert_eq!(err.span(), 1..6);
+
+    let err = Template::parse("a}b").unwrap_err();
+    assert_eq!(err.message(), "unmatched `}`");
+    assert_eq!(err.span(), 1..2);
+  }
+
+  /// Check that calls of user-defined functions get expanded.
+  #[tokio::test]
+  async fn functions() {
+    let functions = BTreeMap::from([
+      ("tag".to_string(), "$1-{counter:$2}".to_string()),
+      ("cost".to_string(), "$$$1".to_string()),
+      ("loop".to_string(), "{loop()}".to_string()),
+    ]);
+    let template = Template::parse_with_functions("{tag(raw, 03)}{cost(5)}", &functions).unwrap();
+    let name = template.render(Path::new("a.txt"), 2).await.unwrap();
+    assert_eq!(name, "raw-002$5");
+
+    let err = Template::parse_with_functions("{loop()}", &functions).unwrap_err();
+    assert_eq!(
+      err.message(),
+      "invalid function `loop`: function `loop` calls itself"
+    );
+    let err = Template::parse_with_functions("{tag(raw)}", &functions).unwrap_err();
+    assert_eq!(
+      err.message(),
+      "function `tag` called with 1 argument(s) refers to `$2`"
+    );
+  }
+
+  /// Check that rendering fails for names that are empty or absolute,
+  /// while relative paths are accepted.
+  #[tokio::test]
+  async fn invalid_names() {
+    let template = Template::parse("{ext}").unwrap();
+    assert!(template.render(Path::new("a"), 1).await.is_err());
+
+    let template = Template::parse("/{name}").unwrap();
+    assert!(template.render(Path::new("a"), 1).await.is_err());
+
+    let template = Template::parse("{name}/..").unwrap();
+    assert!(template.render(Path::new("a"), 1).await.is_err());
+
+    let template = Template::parse("../sub/{name}").unwrap();
+    let name = template.render(Path::new("a"), 1).await.unwrap();
+    assert_eq!(name, "../sub/a");
+  }
+}
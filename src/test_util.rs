@@ -0,0 +1,305 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Support for end-to-end tests of the renaming logic and the command
+//! line utilities built on top of it.
+//!
+//! A [`Fixture`] is a temporary directory populated with files to
+//! rename, while a [`FakeCommand`] is a rename command with scripted
+//! behavior, speaking any of the supported [`Protocol`]s.
+
+use std::ffi::OsStr;
+use std::ffi::OsString;
+use std::fs::create_dir_all;
+use std::fs::read_dir;
+#[cfg(unix)]
+use std::fs::set_permissions;
+use std::fs::write;
+#[cfg(unix)]
+use std::fs::Permissions;
+#[cfg(unix)]
+use std::os::unix::ffi::OsStrExt as _;
+#[cfg(unix)]
+use std::os::unix::fs::symlink;
+#[cfg(unix)]
+use std::os::unix::fs::PermissionsExt as _;
+use std::path::Path;
+use std::path::PathBuf;
+
+use tempfile::tempdir;
+use tempfile::TempDir;
+
+use crate::error::Context as _;
+use crate::error::Result;
+#[cfg(unix)]
+use crate::Protocol;
+
+
+/// An entry to create as part of a [`Fixture`].
+#[derive(Clone, Debug)]
+enum Entry {
+  /// A regular file with the given contents.
+  File(PathBuf, Vec<u8>),
+  /// A directory.
+  Dir(PathBuf),
+  /// A symbolic link to the given target.
+  #[cfg(unix)]
+  Symlink(PathBuf, PathBuf),
+}
+
+
+/// A builder for [`Fixture`] objects.
+#[derive(Clone, Debug, Default)]
+pub struct FixtureBuilder {
+  /// The entries to create, in order.
+  entries: Vec<Entry>,
+}
+
+impl FixtureBuilder {
+  /// Add an empty file at `path`, relative to the fixture's directory.
+  ///
+  /// Missing parent directories are created as well.
+  pub fn with_file(self, path: impl Into<PathBuf>) -> Self {
+    self.with_contents(path, Vec::new())
+  }
+
+  /// Add a file with the given contents at `path`, relative to the
+  /// fixture's directory.
+  pub fn with_contents(mut self, path: impl Into<PathBuf>, contents: impl Into<Vec<u8>>) -> Self {
+    let () = self.entries.push(Entry::File(path.into(), contents.into()));
+    self
+  }
+
+  /// Add a directory at `path`, relative to the fixture's directory.
+  pub fn with_dir(mut self, path: impl Into<PathBuf>) -> Self {
+    let () = self.entries.push(Entry::Dir(path.into()));
+    self
+  }
+
+  /// Add a symbolic link at `path`, relative to the fixture's
+  /// directory, pointing to `target`.
+  #[cfg(unix)]
+  pub fn with_symlink(mut self, path: impl Into<PathBuf>, target: impl Into<PathBuf>) -> Self {
+    let () = self
+      .entries
+      .push(Entry::Symlink(path.into(), target.into()));
+    self
+  }
+
+  /// Create the fixture in a new temporary directory.
+  pub fn build(self) -> Result<Fixture> {
+    let dir = tempdir().context("failed to create temporary directory")?;
+    for entry in self.entries {
+      let path = match &entry {
+        Entry::File(path, ..) | Entry::Dir(path) => path,
+        #[cfg(unix)]
+        Entry::Symlink(path, ..) => path,
+      };
+      let path = dir.path().join(path);
+      if let Some(parent) = path.parent() {
+        let () = create_dir_all(parent)
+          .with_context(|| format!("failed to create directory `{}`", parent.display()))?;
+      }
+
+      let () = match entry {
+        Entry::File(_, contents) => {
+          write(&path, contents).with_context(|| format!("failed to write `{}`", path.display()))?
+        },
+        Entry::Dir(..) => create_dir_all(&path)
+          .with_context(|| format!("failed to create directory `{}`", path.display()))?,
+        #[cfg(unix)]
+        Entry::Symlink(_, target) => symlink(target, &path)
+          .with_context(|| format!("failed to create symbolic link `{}`", path.display()))?,
+      };
+    }
+    Ok(Fixture { dir })
+  }
+}
+
+
+/// A temporary directory populated with files, removed once dropped.
+#[derive(Debug)]
+pub struct Fixture {
+  /// The directory containing the files.
+  dir: TempDir,
+}
+
+impl Fixture {
+  /// Create a builder for a fixture.
+  pub fn builder() -> FixtureBuilder {
+    FixtureBuilder::default()
+  }
+
+  /// Retrieve the path to the fixture's directory.
+  pub fn path(&self) -> &Path {
+    self.dir.path()
+  }
+
+  /// List all entries other than directories below the fixture's
+  /// directory, relative to it and sorted, for comparing the outcome
+  /// of renames against expectations.
+  pub fn files(&self) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let mut dirs = vec![self.dir.path().to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+      let entries =
+        read_dir(&dir).with_context(|| format!("failed to read directory `{}`", dir.display()))?;
+      for entry in entries {
+        let entry =
+          entry.with_context(|| format!("failed to read directory `{}`", dir.display()))?;
+        let path = entry.path();
+        let file_type = entry
+          .file_type()
+          .with_context(|| format!("failed to inspect `{}`", path.display()))?;
+        if file_type.is_dir() {
+          let () = dirs.push(path);
+        } else {
+          // SANITY: All entries are located below the fixture's
+          //         directory.
+          let () = files.push(path.strip_prefix(self.dir.path()).unwrap().to_path_buf());
+        }
+      }
+    }
+    let () = files.sort();
+    Ok(files)
+  }
+}
+
+
+/// Single-quote `s` for use in a shell script.
+#[cfg(unix)]
+fn quote(s: &OsStr) -> Vec<u8> {
+  let mut quoted = b"'".to_vec();
+  for b in s.as_bytes() {
+    if *b == b'\'' {
+      let () = quoted.extend_from_slice(b"'\\''");
+    } else {
+      let () = quoted.push(*b);
+    }
+  }
+  let () = quoted.push(b'\'');
+  quoted
+}
+
+
+/// A builder for rename commands with scripted behavior.
+///
+/// The command maps file names as configured and leaves all others
+/// unchanged. Like real rename commands, it handles any number of files
+/// per invocation, passed as arguments or, with [`Protocol::Stdin`], as
+/// lines on stdin, so that it can be used for batched previews. It is
+/// implemented as a shell script and only available on Unix systems.
+#[cfg(unix)]
+#[derive(Clone, Debug, Default)]
+pub struct FakeCommand {
+  /// The protocol to speak.
+  protocol: Protocol,
+  /// The file names to map, along with the new names.
+  renames: Vec<(OsString, OsString)>,
+  /// The file names for which to fail.
+  failures: Vec<OsString>,
+}
+
+#[cfg(unix)]
+impl FakeCommand {
+  /// Speak the given protocol.
+  pub fn with_protocol(mut self, protocol: Protocol) -> Self {
+    self.protocol = protocol;
+    self
+  }
+
+  /// Rename files called `from` to `to`.
+  pub fn with_rename(mut self, from: impl AsRef<OsStr>, to: impl AsRef<OsStr>) -> Self {
+    let () = self
+      .renames
+      .push((from.as_ref().to_os_string(), to.as_ref().to_os_string()));
+    self
+  }
+
+  /// Fail with a non-zero exit status for files called `name`,
+  /// including for all other files passed to the same invocation.
+  pub fn with_failure(mut self, name: impl AsRef<OsStr>) -> Self {
+    let () = self.failures.push(name.as_ref().to_os_string());
+    self
+  }
+
+  /// Generate the script implementing the command.
+  fn script(&self) -> Vec<u8> {
+    let mut script =
+      b"#!/bin/sh\nset -e\nrename() {\nname=$(basename -- \"$1\")\ncase $name in\n".to_vec();
+    for name in &self.failures {
+      let () = script.extend_from_slice(&quote(name));
+      let () = script.extend_from_slice(b") echo \"failed to rename $name\" >&2; exit 1;;\n");
+    }
+    for (from, to) in &self.renames {
+      let () = script.extend_from_slice(&quote(from));
+      let () = script.extend_from_slice(b") new=");
+      let () = script.extend_from_slice(&quote(to));
+      let () = script.extend_from_slice(b";;\n");
+    }
+    let () = script.extend_from_slice(b"*) new=$name;;\nesac\n");
+    let () = match self.protocol {
+      Protocol::Rename => script.extend_from_slice(
+        b"[ \"$new\" = \"$name\" ] || mv -- \"$1\" \"$(dirname -- \"$1\")/$new\"\n",
+      ),
+      Protocol::Stdout | Protocol::Stdin => script.extend_from_slice(b"printf '%s\\n' \"$new\"\n"),
+    };
+    let () = script.extend_from_slice(b"}\n");
+    let () = match self.protocol {
+      // Files are passed as arguments.
+      Protocol::Rename | Protocol::Stdout => {
+        script.extend_from_slice(b"for path; do rename \"$path\"; done\n")
+      },
+      // Files are passed as lines on stdin.
+      Protocol::Stdin => {
+        script.extend_from_slice(b"while IFS= read -r path; do rename \"$path\"; done\n")
+      },
+    };
+    script
+  }
+
+  /// Write the command to a new temporary directory.
+  pub fn build(self) -> Result<FakeScript> {
+    let dir = tempdir().context("failed to create temporary directory")?;
+    let path = dir.path().join("rename");
+    let () = write(&path, self.script())
+      .with_context(|| format!("failed to write `{}`", path.display()))?;
+    let () = set_permissions(&path, Permissions::from_mode(0o755))
+      .with_context(|| format!("failed to make `{}` executable", path.display()))?;
+
+    let script = FakeScript {
+      _dir: dir,
+      path,
+      protocol: self.protocol,
+    };
+    Ok(script)
+  }
+}
+
+
+/// A rename command created by a [`FakeCommand`], removed once
+/// dropped.
+#[cfg(unix)]
+#[derive(Debug)]
+pub struct FakeScript {
+  /// The directory containing the script, kept around for as long as
+  /// the script is in use.
+  _dir: TempDir,
+  /// The path to the script.
+  path: PathBuf,
+  /// The protocol the script speaks.
+  protocol: Protocol,
+}
+
+#[cfg(unix)]
+impl FakeScript {
+  /// Retrieve the path to the script, to be used as rename command.
+  pub fn path(&self) -> &Path {
+    &self.path
+  }
+
+  /// Retrieve the protocol the script speaks.
+  pub fn protocol(&self) -> Protocol {
+    self.protocol
+  }
+}
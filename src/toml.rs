@@ -0,0 +1,144 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Parsing of the subset of TOML used by our configuration files.
+//!
+//! Supported are `key = value` pairs, one per line, with strings,
+//! integers, and single line arrays of strings as values, table
+//! headers, and comments.
+
+use crate::error::Context as _;
+use crate::error::Error;
+use crate::error::Result;
+
+
+/// A value in a configuration file.
+#[derive(Clone, Debug)]
+pub(crate) enum Value {
+  String(String),
+  Integer(usize),
+  Array(Vec<String>),
+}
+
+
+/// Parse a quoted string at the start of `s`, returning it along with
+/// the remainder of `s`.
+fn parse_string(s: &str) -> Result<(String, &str)> {
+  let mut chars = s.char_indices();
+  let quote = match chars.next() {
+    Some((_, quote @ ('"' | '\''))) => quote,
+    _ => return Err(Error::Invalid("expected a quoted string".to_string())),
+  };
+
+  let mut string = String::new();
+  while let Some((idx, c)) = chars.next() {
+    match c {
+      c if c == quote => return Ok((string, &s[idx + 1..])),
+      '\\' if quote == '"' => match chars.next() {
+        Some((_, '"')) => string.push('"'),
+        Some((_, '\\')) => string.push('\\'),
+        Some((_, 't')) => string.push('\t'),
+        Some((_, 'n')) => string.push('\n'),
+        Some((_, c)) => {
+          return Err(Error::Invalid(format!(
+            "unsupported escape sequence `\\{c}`"
+          )))
+        },
+        None => break,
+      },
+      c => string.push(c),
+    }
+  }
+  Err(Error::Invalid("unterminated string".to_string()))
+}
+
+
+/// Parse an array of quoted strings at the start of `s`, such as
+/// `["a", "b"]`, returning it along with the remainder of `s`.
+fn parse_array(s: &str) -> Result<(Vec<String>, &str)> {
+  let mut rest = s
+    .strip_prefix('[')
+    .context("expected an array")?
+    .trim_start();
+  let mut strings = Vec::new();
+
+  loop {
+    if let Some(rest) = rest.strip_prefix(']') {
+      return Ok((strings, rest))
+    }
+
+    let (string, remainder) = parse_string(rest)?;
+    let () = strings.push(string);
+    let remainder = remainder.trim_start();
+    rest = match remainder.strip_prefix(',') {
+      Some(remainder) => remainder.trim_start(),
+      None if remainder.starts_with(']') => remainder,
+      None if remainder.is_empty() => return Err(Error::Invalid("unterminated array".to_string())),
+      None => return Err(Error::Invalid("expected `,` or `]` in array".to_string())),
+    };
+  }
+}
+
+
+/// Remove a trailing comment and surrounding white space from `s`.
+pub(crate) fn strip_comment(s: &str) -> Result<&str> {
+  let s = s.trim();
+  if s.is_empty() || s.starts_with('#') {
+    Ok("")
+  } else {
+    Err(Error::Invalid(format!("unexpected `{s}`")))
+  }
+}
+
+
+/// Parse a table header, such as `["*.jpg"]`, without the brackets.
+pub(crate) fn parse_header(s: &str) -> Result<String> {
+  let s = s.trim();
+  if s.starts_with(['"', '\'']) {
+    let (name, rest) = parse_string(s)?;
+    if !rest.trim().is_empty() {
+      return Err(Error::Invalid(format!("unexpected `{rest}` in table name")))
+    }
+    Ok(name)
+  } else {
+    if s.is_empty() {
+      return Err(Error::Invalid("empty table name".to_string()))
+    }
+    Ok(s.to_string())
+  }
+}
+
+
+/// Parse a `key = value` pair.
+pub(crate) fn parse_pair(line: &str) -> Result<(String, Value)> {
+  let (key, value) = line
+    .split_once('=')
+    .context("expected `key = value` or `[table]`")?;
+  let key = key.trim();
+  if key.is_empty()
+    || !key
+      .chars()
+      .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+  {
+    return Err(Error::Invalid(format!("invalid key `{key}`")))
+  }
+
+  let value = value.trim_start();
+  let value = if value.starts_with(['"', '\'']) {
+    let (string, rest) = parse_string(value)?;
+    let _rest = strip_comment(rest)?;
+    Value::String(string)
+  } else if value.starts_with('[') {
+    let (strings, rest) = parse_array(value)?;
+    let _rest = strip_comment(rest)?;
+    Value::Array(strings)
+  } else {
+    let end = value.find('#').unwrap_or(value.len());
+    let number = value[..end].trim();
+    let number = number
+      .parse()
+      .with_context(|| format!("invalid value `{number}`; expected a string, integer, or array"))?;
+    Value::Integer(number)
+  };
+  Ok((key.to_string(), value))
+}
@@ -0,0 +1,341 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Recursive selection of files to rename.
+
+use std::cmp::Reverse;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::Duration;
+use std::time::SystemTime;
+
+use globset::Glob;
+use globset::GlobBuilder;
+use globset::GlobSet;
+use globset::GlobSetBuilder;
+
+use tokio::fs::read_dir;
+use tokio::fs::symlink_metadata;
+
+use crate::error::Context as _;
+use crate::error::Error;
+use crate::error::Result;
+#[cfg(unix)]
+use crate::os;
+
+
+/// A set of glob patterns, matched either against a file's name or,
+/// if the pattern contains a path separator, against its path relative
+/// to the traversal root.
+#[derive(Clone, Debug)]
+struct Patterns {
+  /// Patterns to match against file names.
+  names: GlobSet,
+  /// Patterns to match against relative paths.
+  paths: GlobSet,
+}
+
+impl Patterns {
+  fn new<P>(patterns: &[P]) -> Result<Self>
+  where
+    P: AsRef<str>,
+  {
+    let mut names = GlobSetBuilder::new();
+    let mut paths = GlobSetBuilder::new();
+
+    for pattern in patterns {
+      let pattern = pattern.as_ref();
+      if pattern.contains('/') {
+        let glob = GlobBuilder::new(pattern)
+          .literal_separator(true)
+          .build()
+          .with_context(|| format!("invalid glob pattern `{pattern}`"))?;
+        let _paths = paths.add(glob);
+      } else {
+        let glob =
+          Glob::new(pattern).with_context(|| format!("invalid glob pattern `{pattern}`"))?;
+        let _names = names.add(glob);
+      }
+    }
+
+    let slf = Self {
+      names: names.build().context("failed to build glob set")?,
+      paths: paths.build().context("failed to build glob set")?,
+    };
+    Ok(slf)
+  }
+
+  fn is_empty(&self) -> bool {
+    self.names.is_empty() && self.paths.is_empty()
+  }
+
+  fn matches(&self, root: &Path, path: &Path) -> bool {
+    let name_match = path
+      .file_name()
+      .map(|name| self.names.is_match(name))
+      .unwrap_or(false);
+    let path_match = || {
+      path
+        .strip_prefix(root)
+        .map(|relative| self.paths.is_match(relative))
+        .unwrap_or(false)
+    };
+
+    name_match || path_match()
+  }
+}
+
+
+/// A filter deciding which files to include during traversal.
+#[derive(Clone, Debug)]
+pub struct Filter {
+  /// Patterns at least one of which a file has to match. If empty,
+  /// all files are included.
+  include: Patterns,
+  /// Patterns excluding files and directories.
+  exclude: Patterns,
+}
+
+impl Filter {
+  /// Create a new filter from lists of include and exclude glob
+  /// patterns.
+  pub fn new<I, E>(include: &[I], exclude: &[E]) -> Result<Self>
+  where
+    I: AsRef<str>,
+    E: AsRef<str>,
+  {
+    let slf = Self {
+      include: Patterns::new(include)?,
+      exclude: Patterns::new(exclude)?,
+    };
+    Ok(slf)
+  }
+
+  /// Check whether a directory should be descended into.
+  fn descend(&self, root: &Path, dir: &Path) -> bool {
+    !self.exclude.matches(root, dir)
+  }
+
+  /// Check whether a file is to be included.
+  fn includes(&self, root: &Path, file: &Path) -> bool {
+    (self.include.is_empty() || self.include.matches(root, file))
+      && !self.exclude.matches(root, file)
+  }
+}
+
+impl Default for Filter {
+  fn default() -> Self {
+    // SANITY: Creating a filter from empty pattern lists cannot fail.
+    Self::new::<&str, &str>(&[], &[]).unwrap()
+  }
+}
+
+
+/// Parse `age`, a number followed by one of the units `s`, `m`, `h`,
+/// `d`, and `w`, for seconds, minutes, hours, days, and weeks,
+/// respectively, as in `30d`.
+pub fn age(age: &str) -> Result<Duration> {
+  let split = age.find(|c: char| !c.is_ascii_digit()).unwrap_or(age.len());
+  let (count, unit) = age.split_at(split);
+  let count = count.parse::<u64>().with_context(|| {
+    format!("invalid age `{age}`; expected a number followed by a unit, as in `30d`")
+  })?;
+  let secs = match unit {
+    "s" => 1,
+    "m" => 60,
+    "h" => 60 * 60,
+    "d" => 24 * 60 * 60,
+    "w" => 7 * 24 * 60 * 60,
+    _ => {
+      return Err(Error::Invalid(format!(
+        "invalid unit in age `{age}`; expected one of `s`, `m`, `h`, `d`, or `w`"
+      )))
+    },
+  };
+  count
+    .checked_mul(secs)
+    .map(Duration::from_secs)
+    .with_context(|| format!("age `{age}` is out of range"))
+}
+
+
+/// Resolve `user`, the name or numeric ID of a user, to the user's ID.
+#[cfg(unix)]
+pub fn user_id(user: &str) -> Result<u32> {
+  os::user_id(user)
+    .with_context(|| format!("failed to look up user `{user}`"))?
+    .or_else(|| user.parse().ok())
+    .with_context(|| format!("user `{user}` does not exist"))
+}
+
+
+/// Resolve `group`, the name or numeric ID of a group, to the group's
+/// ID.
+#[cfg(unix)]
+pub fn group_id(group: &str) -> Result<u32> {
+  os::group_id(group)
+    .with_context(|| format!("failed to look up group `{group}`"))?
+    .or_else(|| group.parse().ok())
+    .with_context(|| format!("group `{group}` does not exist"))
+}
+
+
+/// Options controlling traversal.
+#[derive(Clone, Debug, Default)]
+#[non_exhaustive]
+pub struct TraverseOpts {
+  /// The filter to apply to encountered files.
+  pub filter: Filter,
+  /// The maximum depth to descend to. Files directly inside of one of
+  /// the provided directories are at depth 1.
+  pub max_depth: Option<usize>,
+  /// Whether to report the directories encountered as well, subject to
+  /// the filter just like files. Directories are reported after all
+  /// files and directories inside of them.
+  pub dirs: bool,
+  /// The ID of the user encountered files and directories have to be
+  /// owned by to be reported.
+  #[cfg(unix)]
+  pub owner: Option<u32>,
+  /// The ID of the group encountered files and directories have to
+  /// belong to to be reported.
+  #[cfg(unix)]
+  pub group: Option<u32>,
+  /// The time before which encountered files have to have been
+  /// modified last to be reported. Directories are not subject to it.
+  pub modified_before: Option<SystemTime>,
+}
+
+
+/// Check whether `path` is owned by the user and group required by
+/// `opts`, if any.
+async fn owned(path: &Path, opts: &TraverseOpts) -> Result<bool> {
+  #[cfg(unix)]
+  {
+    use std::os::unix::fs::MetadataExt as _;
+
+    if opts.owner.is_none() && opts.group.is_none() {
+      return Ok(true)
+    }
+
+    let metadata = symlink_metadata(path)
+      .await
+      .with_context(|| format!("failed to retrieve metadata for `{}`", path.display()))?;
+    Ok(
+      opts.owner.is_none_or(|uid| metadata.uid() == uid)
+        && opts.group.is_none_or(|gid| metadata.gid() == gid),
+    )
+  }
+
+  #[cfg(not(unix))]
+  {
+    let _ = (path, opts);
+    Ok(true)
+  }
+}
+
+
+/// Check whether `path` was last modified before the time required by
+/// `opts`, if any.
+async fn aged(path: &Path, opts: &TraverseOpts) -> Result<bool> {
+  let Some(before) = opts.modified_before else {
+    return Ok(true)
+  };
+
+  let metadata = symlink_metadata(path)
+    .await
+    .with_context(|| format!("failed to retrieve metadata for `{}`", path.display()))?;
+  let modified = metadata.modified().with_context(|| {
+    format!(
+      "failed to inquire modification time of `{}`",
+      path.display()
+    )
+  })?;
+  Ok(modified < before)
+}
+
+
+/// Recursively collect all files below the directory `root`.
+async fn traverse_dir(root: &Path, opts: &TraverseOpts, files: &mut Vec<PathBuf>) -> Result<()> {
+  let mut stack = vec![(root.to_path_buf(), 0)];
+
+  while let Some((dir, depth)) = stack.pop() {
+    let mut entries = Vec::new();
+    let mut dir_entries = read_dir(&dir)
+      .await
+      .with_context(|| format!("failed to read directory `{}`", dir.display()))?;
+
+    while let Some(entry) = dir_entries
+      .next_entry()
+      .await
+      .with_context(|| format!("failed to read directory `{}`", dir.display()))?
+    {
+      let file_type = entry
+        .file_type()
+        .await
+        .with_context(|| format!("failed to inquire type of `{}`", entry.path().display()))?;
+      let () = entries.push((entry.path(), file_type));
+    }
+
+    // Sort entries to make the order in which files are reported
+    // deterministic.
+    let () = entries.sort_by(|(x, _), (y, _)| x.cmp(y));
+
+    let depth = depth + 1;
+    let descend = opts.max_depth.map(|max| depth < max).unwrap_or(true);
+    let mut dirs = Vec::new();
+    for (path, file_type) in entries {
+      if file_type.is_dir() {
+        if opts.dirs && opts.filter.includes(root, &path) && owned(&path, opts).await? {
+          let () = files.push(path.clone());
+        }
+        if descend && opts.filter.descend(root, &path) {
+          let () = dirs.push((path, depth));
+        }
+      } else if opts.filter.includes(root, &path)
+        && owned(&path, opts).await?
+        && aged(&path, opts).await?
+      {
+        let () = files.push(path);
+      }
+    }
+
+    // Push in reverse, so that directories are visited in sorted
+    // order.
+    let () = stack.extend(dirs.into_iter().rev());
+  }
+  Ok(())
+}
+
+
+/// Collect the files to rename from a list of paths.
+///
+/// Directories are traversed recursively, with all files inside of
+/// them being subject to `opts`. Other paths are reported as-is.
+///
+/// If directories are reported as well, paths are ordered deepest
+/// first, so that renaming them in order never invalidates the path of
+/// one yet to be renamed. The provided directories themselves are never
+/// reported.
+pub async fn traverse(paths: &[PathBuf], opts: &TraverseOpts) -> Result<Vec<PathBuf>> {
+  let mut files = Vec::new();
+
+  for path in paths {
+    let metadata = symlink_metadata(path)
+      .await
+      .with_context(|| format!("failed to retrieve metadata for `{}`", path.display()))?;
+
+    if metadata.is_dir() {
+      let () = traverse_dir(path, opts, &mut files).await?;
+    } else {
+      let () = files.push(path.clone());
+    }
+  }
+
+  if opts.dirs {
+    // The sort is stable, preserving the order of paths at the same
+    // depth.
+    let () = files.sort_by_key(|path| Reverse(path.components().count()));
+  }
+  Ok(files)
+}
@@ -0,0 +1,104 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Batched renaming of files using io_uring.
+
+use std::io;
+use std::path::PathBuf;
+
+use io_uring::opcode::RenameAt;
+use io_uring::types::Fd;
+use io_uring::IoUring;
+
+use crate::os::c_path;
+
+
+/// The number of submission queue entries of the ring, which also is
+/// the maximum number of renames submitted at once.
+const ENTRIES: u32 = 256;
+
+
+/// Submit the renames of `chunk` to `ring` and wait for their
+/// completion, storing the outcome of each in `results`.
+fn rename_chunk(
+  ring: &mut IoUring,
+  chunk: &[(PathBuf, PathBuf)],
+  results: &mut [io::Result<()>],
+) -> io::Result<()> {
+  let mut paths = Vec::with_capacity(chunk.len());
+  for ((src, dst), result) in chunk.iter().zip(results.iter_mut()) {
+    match c_path(src).and_then(|src| Ok((src, c_path(dst)?))) {
+      Ok(c_paths) => {
+        let () = paths.push(Some(c_paths));
+      },
+      Err(err) => {
+        let () = paths.push(None);
+        *result = Err(err);
+      },
+    }
+  }
+
+  let mut submitted = 0;
+  for (idx, c_paths) in paths.iter().enumerate() {
+    let Some((src, dst)) = c_paths else { continue };
+    let entry = RenameAt::new(
+      Fd(libc::AT_FDCWD),
+      src.as_ptr(),
+      Fd(libc::AT_FDCWD),
+      dst.as_ptr(),
+    )
+    .flags(libc::RENAME_NOREPLACE)
+    .build()
+    .user_data(idx as u64);
+
+    // SAFETY: The paths referenced by the entry stay alive until we
+    //         have reaped all completions below.
+    let () = unsafe { ring.submission().push(&entry) }
+      .map_err(|_| io::Error::other("io_uring submission queue is full"))?;
+    submitted += 1;
+  }
+
+  let mut completed = 0;
+  while completed < submitted {
+    let _count = ring.submit_and_wait(submitted - completed)?;
+    for cqe in ring.completion() {
+      if cqe.result() < 0 {
+        results[cqe.user_data() as usize] = Err(io::Error::from_raw_os_error(-cqe.result()));
+      }
+      completed += 1;
+    }
+  }
+  Ok(())
+}
+
+
+/// Rename each source path in `renames` to its destination, failing
+/// the individual rename if the destination exists.
+///
+/// Renames are submitted to the kernel in batches and may be performed
+/// in any order. `None` is returned if io_uring is unavailable, in
+/// which case no rename has been attempted.
+///
+/// This function blocks.
+pub(crate) fn rename_batch(renames: &[(PathBuf, PathBuf)]) -> Option<Vec<io::Result<()>>> {
+  let mut ring = IoUring::new(ENTRIES).ok()?;
+  let mut results = renames.iter().map(|_| Ok(())).collect::<Vec<_>>();
+
+  let mut start = 0;
+  for chunk in renames.chunks(ENTRIES as usize) {
+    let end = start + chunk.len();
+    if let Err(err) = rename_chunk(&mut ring, chunk, &mut results[start..end]) {
+      // We can no longer tell the outcome of renames that were in
+      // flight, so report all outstanding ones as failed.
+      for result in &mut results[start..] {
+        *result = Err(io::Error::new(
+          err.kind(),
+          format!("failed to operate io_uring: {err}"),
+        ));
+      }
+      break
+    }
+    start = end;
+  }
+  Some(results)
+}
@@ -0,0 +1,214 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+// Deserialization is only needed for the journal.
+#![cfg_attr(not(feature = "journal"), allow(dead_code, unused_imports))]
+
+#[cfg(any(feature = "journal", feature = "output"))]
+use std::ffi::OsStr;
+#[cfg(any(feature = "journal", feature = "output"))]
+use std::ffi::OsString;
+#[cfg(any(feature = "journal", feature = "manifest"))]
+use std::fs::File;
+#[cfg(any(feature = "journal", feature = "manifest"))]
+use std::io::Read as _;
+#[cfg(any(feature = "journal", feature = "manifest"))]
+use std::path::Path;
+
+#[cfg(any(feature = "journal", feature = "output"))]
+use serde::Deserialize;
+#[cfg(any(feature = "journal", feature = "output"))]
+use serde::Serialize;
+#[cfg(any(feature = "journal", feature = "output"))]
+use serde::Serializer;
+
+#[cfg(any(feature = "journal", feature = "manifest"))]
+use crate::error::Context as _;
+#[cfg(any(feature = "journal", feature = "manifest"))]
+use crate::error::Error;
+use crate::error::Result;
+#[cfg(any(feature = "journal", feature = "output"))]
+use crate::os::from_units;
+#[cfg(any(feature = "journal", feature = "manifest"))]
+use crate::os::special_file;
+#[cfg(any(feature = "journal", feature = "output"))]
+use crate::os::units;
+#[cfg(any(feature = "journal", feature = "output"))]
+use crate::os::Unit;
+
+
+/// Calculate the digest of the contents of the file at `path`.
+///
+/// The digest is a 64 bit FNV-1a hash. It is not meant to withstand
+/// tampering, but merely to tell apart files and to detect accidental
+/// changes to them.
+///
+/// This function blocks.
+#[cfg(any(feature = "journal", feature = "manifest"))]
+pub(crate) fn digest(path: &Path) -> Result<u64> {
+  const OFFSET: u64 = 0xcbf29ce484222325;
+  const PRIME: u64 = 0x100000001b3;
+
+  // Opening special files may block indefinitely.
+  if let Some(kind) = special_file(path) {
+    return Err(Error::Invalid(format!(
+      "`{}` is a {kind} and has no contents to digest",
+      path.display()
+    )))
+  }
+
+  let mut file =
+    File::open(path).with_context(|| format!("failed to open `{}`", path.display()))?;
+  let mut buffer = vec![0; 64 * 1024];
+  let mut hash = OFFSET;
+  loop {
+    let count = file
+      .read(&mut buffer)
+      .with_context(|| format!("failed to read `{}`", path.display()))?;
+    if count == 0 {
+      break Ok(hash)
+    }
+    for byte in &buffer[..count] {
+      hash = (hash ^ u64::from(*byte)).wrapping_mul(PRIME);
+    }
+  }
+}
+
+
+/// The serialized representation of an `OsStr`.
+///
+/// Strings are serialized as such whenever possible and fall back to a
+/// sequence of raw platform units (bytes on Unix systems, UTF-16 code
+/// units on Windows) otherwise.
+#[cfg(any(feature = "journal", feature = "output"))]
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Repr {
+  Str(String),
+  Units(Vec<Unit>),
+}
+
+#[cfg(any(feature = "journal", feature = "output"))]
+impl From<Repr> for OsString {
+  fn from(repr: Repr) -> Self {
+    match repr {
+      Repr::Str(s) => OsString::from(s),
+      Repr::Units(units) => from_units(units),
+    }
+  }
+}
+
+
+/// A wrapper around an `OsStr` that serializes it losslessly.
+#[cfg(any(feature = "journal", feature = "output"))]
+struct Lossless<'s>(&'s OsStr);
+
+#[cfg(any(feature = "journal", feature = "output"))]
+impl Serialize for Lossless<'_> {
+  fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+  where
+    S: Serializer,
+  {
+    if let Some(s) = self.0.to_str() {
+      serializer.serialize_str(s)
+    } else {
+      serializer.collect_seq(units(self.0).iter())
+    }
+  }
+}
+
+
+/// (De-)serialization support for paths that may not be valid UTF-8.
+#[cfg(any(feature = "journal", feature = "output"))]
+pub(crate) mod serde_path {
+  use std::ffi::OsString;
+  use std::path::Path;
+  use std::path::PathBuf;
+
+  use serde::Deserialize as _;
+  use serde::Deserializer;
+  use serde::Serialize as _;
+  use serde::Serializer;
+
+  use super::Lossless;
+  use super::Repr;
+
+
+  pub(crate) fn serialize<S>(path: &Path, serializer: S) -> Result<S::Ok, S::Error>
+  where
+    S: Serializer,
+  {
+    Lossless(path.as_os_str()).serialize(serializer)
+  }
+
+  pub(crate) fn deserialize<'de, D>(deserializer: D) -> Result<PathBuf, D::Error>
+  where
+    D: Deserializer<'de>,
+  {
+    let path = OsString::from(Repr::deserialize(deserializer)?);
+    Ok(PathBuf::from(path))
+  }
+}
+
+
+/// (De-)serialization support for lists of `OsString` objects, such as
+/// command lines.
+#[cfg(any(feature = "journal", feature = "output"))]
+pub(crate) mod serde_os_strings {
+  use std::ffi::OsString;
+
+  use serde::Deserialize as _;
+  use serde::Deserializer;
+  use serde::Serializer;
+
+  use super::Lossless;
+  use super::Repr;
+
+
+  pub(crate) fn serialize<S>(strings: &[OsString], serializer: S) -> Result<S::Ok, S::Error>
+  where
+    S: Serializer,
+  {
+    serializer.collect_seq(strings.iter().map(|s| Lossless(s)))
+  }
+
+  pub(crate) fn deserialize<'de, D>(deserializer: D) -> Result<Vec<OsString>, D::Error>
+  where
+    D: Deserializer<'de>,
+  {
+    let strings = Vec::<Repr>::deserialize(deserializer)?;
+    Ok(strings.into_iter().map(OsString::from).collect())
+  }
+}
+
+
+/// (De-)serialization support for optional file digests, represented
+/// as hexadecimal strings so as to not lose precision with consumers
+/// treating numbers as floating point values.
+#[cfg(feature = "journal")]
+pub(crate) mod serde_digest {
+  use serde::de::Error as _;
+  use serde::Deserialize as _;
+  use serde::Deserializer;
+  use serde::Serializer;
+
+
+  pub(crate) fn serialize<S>(digest: &Option<u64>, serializer: S) -> Result<S::Ok, S::Error>
+  where
+    S: Serializer,
+  {
+    match digest {
+      Some(digest) => serializer.serialize_str(&format!("{digest:016x}")),
+      None => serializer.serialize_none(),
+    }
+  }
+
+  pub(crate) fn deserialize<'de, D>(deserializer: D) -> Result<Option<u64>, D::Error>
+  where
+    D: Deserializer<'de>,
+  {
+    Option::<String>::deserialize(deserializer)?
+      .map(|digest| u64::from_str_radix(&digest, 16).map_err(D::Error::custom))
+      .transpose()
+  }
+}
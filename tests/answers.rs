@@ -0,0 +1,177 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! End-to-end tests of the interactive flow of `batch-rename`, driven
+//! by answers scripted via --answers-from.
+
+#![cfg(unix)]
+
+use std::ffi::OsStr;
+use std::fs::write;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Command;
+use std::process::Output;
+use std::process::Stdio;
+
+use batch_renamer::test_util::FakeCommand;
+use batch_renamer::test_util::FakeScript;
+use batch_renamer::test_util::Fixture;
+
+use tempfile::tempdir;
+
+
+/// Create a rename command changing the extension of the files used by
+/// the tests from `.txt` to `.md`.
+fn command() -> FakeScript {
+  FakeCommand::default()
+    .with_rename("a.txt", "a.md")
+    .with_rename("b.txt", "b.md")
+    .with_rename("c.txt", "c.md")
+    .build()
+    .unwrap()
+}
+
+
+/// Run `batch-rename` with the given arguments in `dir`, answering
+/// prompts as per `answers`.
+fn batch_rename<A>(dir: &Path, answers: &str, args: &[A]) -> Output
+where
+  A: AsRef<OsStr>,
+{
+  // The user's configuration and journal are left alone.
+  let home = tempdir().unwrap();
+  let path = home.path().join("answers");
+  let () = write(&path, answers).unwrap();
+
+  Command::new(env!("CARGO_BIN_EXE_batch-rename"))
+    .current_dir(dir)
+    .env("HOME", home.path())
+    .env("XDG_CONFIG_HOME", home.path().join("config"))
+    .env("XDG_DATA_HOME", home.path().join("data"))
+    .env("XDG_STATE_HOME", home.path().join("state"))
+    .arg("--answers-from")
+    .arg(&path)
+    .args(args)
+    .stdin(Stdio::null())
+    .output()
+    .unwrap()
+}
+
+
+/// Run `batch-rename` on the files `a.txt`, `b.txt`, and `c.txt`,
+/// answering prompts as per `answers`, and report the files present
+/// afterwards.
+fn rename_files(answers: &str) -> (Output, Vec<PathBuf>) {
+  let fixture = Fixture::builder()
+    .with_file("a.txt")
+    .with_file("b.txt")
+    .with_file("c.txt")
+    .build()
+    .unwrap();
+  let script = command();
+  let args = [
+    script.path().as_os_str(),
+    OsStr::new("--"),
+    OsStr::new("a.txt"),
+    OsStr::new("b.txt"),
+    OsStr::new("c.txt"),
+  ];
+  let output = batch_rename(fixture.path(), answers, &args);
+  (output, fixture.files().unwrap())
+}
+
+
+/// Check that renames get applied or skipped as answered.
+#[test]
+fn accept_and_decline() {
+  let (output, files) = rename_files("y\nn\n\n");
+  assert!(output.status.success(), "{output:?}");
+  assert_eq!(files, vec![
+    PathBuf::from("a.md"),
+    PathBuf::from("b.txt"),
+    PathBuf::from("c.md"),
+  ]);
+}
+
+
+/// Check that quitting leaves the remaining files alone.
+#[test]
+fn quit() {
+  let (output, files) = rename_files("y\nq\n");
+  assert!(output.status.success(), "{output:?}");
+  assert_eq!(files, vec![
+    PathBuf::from("a.md"),
+    PathBuf::from("b.txt"),
+    PathBuf::from("c.txt"),
+  ]);
+}
+
+
+/// Check that running out of answers counts as an interruption.
+#[test]
+fn out_of_answers() {
+  let (_output, files) = rename_files("y\n");
+  assert_eq!(files, vec![
+    PathBuf::from("a.md"),
+    PathBuf::from("b.txt"),
+    PathBuf::from("c.txt"),
+  ]);
+}
+
+
+/// Check that paths can be edited, with the replacement provided on
+/// the same line as the key or on the next one.
+#[test]
+fn edit() {
+  let (output, files) = rename_files("e first.txt\ne\nsecond.txt\nn\n");
+  assert!(output.status.success(), "{output:?}");
+  assert_eq!(files, vec![
+    PathBuf::from("c.txt"),
+    PathBuf::from("first.txt"),
+    PathBuf::from("second.txt"),
+  ]);
+}
+
+
+/// Check that answers are used for renaming the members of archives as
+/// well.
+#[cfg(feature = "archive")]
+#[test]
+fn archive() {
+  let fixture = Fixture::builder()
+    .with_file("a.txt")
+    .with_file("b.txt")
+    .with_file("c.txt")
+    .build()
+    .unwrap();
+  let status = Command::new("tar")
+    .current_dir(fixture.path())
+    .args(["-cf", "archive.tar", "a.txt", "b.txt", "c.txt"])
+    .status()
+    .unwrap();
+  assert!(status.success());
+
+  let script = command();
+  let args = [
+    OsStr::new("--archive"),
+    OsStr::new("archive.tar"),
+    script.path().as_os_str(),
+  ];
+  let output = batch_rename(fixture.path(), "y\nn\ne c.bak\n", &args);
+  assert!(output.status.success(), "{output:?}");
+
+  let output = Command::new("tar")
+    .current_dir(fixture.path())
+    .args(["-tf", "archive.tar"])
+    .output()
+    .unwrap();
+  assert!(output.status.success());
+  let mut members = String::from_utf8(output.stdout)
+    .unwrap()
+    .lines()
+    .map(str::to_string)
+    .collect::<Vec<_>>();
+  let () = members.sort();
+  assert_eq!(members, vec!["a.md", "b.txt", "c.bak"]);
+}
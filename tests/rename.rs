@@ -0,0 +1,251 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! End-to-end tests of the renaming logic, using the fixtures and fake
+//! rename commands provided by the `test-util` feature.
+
+use std::ffi::OsStr;
+#[cfg(unix)]
+use std::ffi::OsString;
+#[cfg(unix)]
+use std::io::Write as _;
+use std::path::PathBuf;
+#[cfg(unix)]
+use std::process::Command;
+#[cfg(unix)]
+use std::process::Stdio;
+
+use batch_renamer::backup;
+#[cfg(unix)]
+use batch_renamer::propose_batch;
+#[cfg(unix)]
+use batch_renamer::rename_many;
+#[cfg(unix)]
+use batch_renamer::test_util::FakeCommand;
+use batch_renamer::test_util::Fixture;
+#[cfg(unix)]
+use batch_renamer::Protocol;
+#[cfg(unix)]
+use batch_renamer::RenameOpts;
+
+
+/// Check that a fixture contains the files it was built with.
+#[test]
+fn fixture_files() {
+  let fixture = Fixture::builder()
+    .with_file("a.txt")
+    .with_contents("dir/b.txt", "b")
+    .with_dir("empty")
+    .build()
+    .unwrap();
+
+  let files = fixture.files().unwrap();
+  assert_eq!(files, vec![
+    PathBuf::from("a.txt"),
+    PathBuf::from("dir/b.txt")
+  ]);
+  assert!(fixture.path().join("empty").is_dir());
+}
+
+
+/// Run a fake rename command on the given files, passed as arguments
+/// or on stdin as per its protocol, returning its stdout.
+#[cfg(unix)]
+fn run(protocol: Protocol, names: &[&str]) -> (bool, String) {
+  let script = FakeCommand::default()
+    .with_protocol(protocol)
+    .with_rename("a", "x")
+    .with_rename("c", "z")
+    .with_failure("fail")
+    .build()
+    .unwrap();
+
+  let mut command = Command::new(script.path());
+  let () = match protocol {
+    Protocol::Rename | Protocol::Stdout => {
+      let _command = command.args(names);
+    },
+    Protocol::Stdin => {
+      let _command = command.stdin(Stdio::piped());
+    },
+  };
+  let mut child = command
+    .stdout(Stdio::piped())
+    .stderr(Stdio::null())
+    .spawn()
+    .unwrap();
+  if let Some(mut stdin) = child.stdin.take() {
+    for name in names {
+      let () = writeln!(stdin, "{name}").unwrap();
+    }
+  }
+  let output = child.wait_with_output().unwrap();
+  (
+    output.status.success(),
+    String::from_utf8(output.stdout).unwrap(),
+  )
+}
+
+
+/// Check that a fake command maps all files passed to a single
+/// invocation.
+#[cfg(unix)]
+#[test]
+fn fake_command_many_files() {
+  let (success, stdout) = run(Protocol::Stdout, &["a", "b", "c"]);
+  assert!(success);
+  assert_eq!(stdout, "x\nb\nz\n");
+
+  let (success, stdout) = run(Protocol::Stdin, &["a", "b", "c"]);
+  assert!(success);
+  assert_eq!(stdout, "x\nb\nz\n");
+
+  let (success, _stdout) = run(Protocol::Stdout, &["a", "fail", "c"]);
+  assert!(!success);
+
+  let (success, _stdout) = run(Protocol::Stdin, &["a", "fail", "c"]);
+  assert!(!success);
+}
+
+
+/// Check that new names get determined for batches of files, with
+/// failures reported for the affected files only.
+#[cfg(unix)]
+#[tokio::test]
+async fn propose_many() {
+  for protocol in [Protocol::Rename, Protocol::Stdout, Protocol::Stdin] {
+    let script = FakeCommand::default()
+      .with_protocol(protocol)
+      .with_rename("a.txt", "a.md")
+      .with_rename("c.txt", "c.md")
+      .with_failure("d.txt")
+      .build()
+      .unwrap();
+    let mut opts = RenameOpts::default();
+    opts.protocol = script.protocol();
+    let command = [script.path().as_os_str().to_os_string()];
+
+    let names = ["a.txt", "b.txt", "c.txt"].map(OsString::from);
+    let proposals = propose_batch(&names, &command, &opts)
+      .await
+      .into_iter()
+      .collect::<Result<Vec<_>, _>>()
+      .unwrap();
+    assert_eq!(
+      proposals,
+      vec![
+        PathBuf::from("a.md"),
+        PathBuf::from("b.txt"),
+        PathBuf::from("c.md")
+      ],
+      "{protocol}"
+    );
+
+    let names = ["a.txt", "d.txt", "c.txt"].map(OsString::from);
+    let proposals = propose_batch(&names, &command, &opts).await;
+    assert_eq!(proposals[0].as_ref().unwrap(), &PathBuf::from("a.md"));
+    assert!(proposals[1].is_err(), "{protocol}");
+    assert_eq!(proposals[2].as_ref().unwrap(), &PathBuf::from("c.md"));
+  }
+}
+
+
+/// Check that files in a fixture get renamed as the fake command
+/// dictates.
+#[cfg(unix)]
+#[tokio::test]
+async fn rename_fixture() {
+  for protocol in [Protocol::Rename, Protocol::Stdout, Protocol::Stdin] {
+    let fixture = Fixture::builder()
+      .with_file("a.txt")
+      .with_file("b.txt")
+      .with_file("dir/c.txt")
+      .with_file("d.txt")
+      .build()
+      .unwrap();
+    let script = FakeCommand::default()
+      .with_protocol(protocol)
+      .with_rename("a.txt", "a.md")
+      .with_rename("c.txt", "c.md")
+      .with_failure("d.txt")
+      .build()
+      .unwrap();
+    let mut opts = RenameOpts::default();
+    opts.protocol = script.protocol();
+    let command = [script.path().as_os_str().to_os_string()];
+
+    let files = ["a.txt", "b.txt", "dir/c.txt", "d.txt"].map(|file| fixture.path().join(file));
+    let outcomes = rename_many(&files, &command, &opts, |_progress| ()).await;
+    assert_eq!(outcomes.len(), 4);
+    assert!(outcomes[..3].iter().all(Result::is_ok), "{protocol}");
+    assert!(outcomes[3].is_err(), "{protocol}");
+
+    let files = fixture.files().unwrap();
+    assert_eq!(
+      files,
+      vec![
+        PathBuf::from("a.md"),
+        PathBuf::from("b.txt"),
+        PathBuf::from("d.txt"),
+        PathBuf::from("dir/c.md"),
+      ],
+      "{protocol}"
+    );
+  }
+}
+
+
+/// Check that a file listed multiple times is only renamed once, with
+/// the additional listings reported as failures.
+#[cfg(unix)]
+#[tokio::test]
+async fn rename_duplicate() {
+  let fixture = Fixture::builder()
+    .with_file("a.txt")
+    .with_file("b.txt")
+    .build()
+    .unwrap();
+  let script = FakeCommand::default()
+    .with_rename("a.txt", "a.md")
+    .build()
+    .unwrap();
+  let mut opts = RenameOpts::default();
+  opts.protocol = script.protocol();
+  let command = [script.path().as_os_str().to_os_string()];
+
+  let files = ["a.txt", "b.txt", "./a.txt"].map(|file| fixture.path().join(file));
+  let outcomes = rename_many(&files, &command, &opts, |_progress| ()).await;
+  assert_eq!(outcomes.len(), 3);
+  assert!(outcomes[0].as_ref().unwrap().ends_with("a.md"));
+  assert!(outcomes[1].as_ref().unwrap().ends_with("b.txt"));
+  let err = outcomes[2].as_ref().unwrap_err();
+  assert!(err.to_string().contains("listed more than once"), "{err}");
+
+  let files = fixture.files().unwrap();
+  assert_eq!(files, vec![PathBuf::from("a.md"), PathBuf::from("b.txt")]);
+}
+
+
+/// Check that directories are not backed up.
+#[tokio::test]
+async fn backup_directory() {
+  let fixture = Fixture::builder()
+    .with_file("a.txt")
+    .with_dir("dir")
+    .build()
+    .unwrap();
+
+  let path = backup(&fixture.path().join("a.txt"), OsStr::new("~"))
+    .await
+    .unwrap();
+  assert_eq!(path, fixture.path().join("a.txt~"));
+
+  let err = backup(&fixture.path().join("dir"), OsStr::new("~"))
+    .await
+    .unwrap_err();
+  assert!(
+    err.to_string().contains("cannot back up directory"),
+    "{err}"
+  );
+  assert!(!fixture.path().join("dir~").exists());
+}